@@ -1,16 +1,18 @@
 locals {
   additional_tags = {
-
+{%- for key, value in eks_tags %}
+    "{{ key }}" = "{{ value }}"
+{%- endfor %}
   }
 }
 
 locals {
-  tags_eks = {
+  tags_eks = merge(local.additional_tags, {
     ClusterId = var.kubernetes_cluster_id,
     ClusterName = var.kubernetes_cluster_name,
     Region = var.region
     {% if resource_expiration_in_seconds is defined %}ttl = var.resource_expiration_in_seconds{% endif %}
-  }
+  })
 }
 
 resource "aws_cloudwatch_log_group" "eks_cloudwatch_log_group" {
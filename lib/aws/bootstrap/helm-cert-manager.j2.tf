@@ -150,4 +150,25 @@ resource "helm_release" "cert_manager_config" {
     value = "{{ cloudflare_email }}"
   }
 {% endif %}
+{% if external_dns_provider == "aws" %}
+  set {
+    name = "provider.route53.region"
+    value = "{{ aws_region }}"
+  }
+
+  set {
+    name = "provider.route53.hostedZoneId"
+    value = "{{ route53_hosted_zone_id }}"
+  }
+
+  set {
+    name = "provider.route53.accessKeyId"
+    value = "{{ aws_access_key }}"
+  }
+
+  set {
+    name = "provider.route53.secretAccessKey"
+    value = "{{ aws_secret_key }}"
+  }
+{% endif %}
 }
@@ -0,0 +1,45 @@
+resource "azurerm_resource_group" "kubernetes_cluster" {
+  name = "qovery-{{ aks_cluster_id }}"
+  location = "{{ azure_region }}"
+}
+
+{% set default_node_pool = aks_worker_nodes | first %}
+resource "azurerm_kubernetes_cluster" "kubernetes_cluster" {
+  name = "qovery-{{ aks_cluster_id }}"
+  location = azurerm_resource_group.kubernetes_cluster.location
+  resource_group_name = azurerm_resource_group.kubernetes_cluster.name
+  dns_prefix = "qovery-{{ aks_cluster_id }}"
+  kubernetes_version = "{{ aks_version }}"
+
+  # the first node group is the default node pool, the others are added separately
+  default_node_pool {
+    name = "default"
+    vm_size = "{{ default_node_pool.instance_type }}"
+    enable_auto_scaling = true
+    node_count = {{ default_node_pool.desired_size }}
+    min_count = {{ default_node_pool.min_size }}
+    max_count = {{ default_node_pool.max_size }}
+  }
+
+  identity {
+    type = "SystemAssigned"
+  }
+
+  tags = {
+    cluster_id = "{{ aks_cluster_id }}"
+    organization_id = "{{ organization_id }}"
+  }
+}
+
+{% for aks_worker_node in aks_worker_nodes | slice(start=1) %}
+resource "azurerm_kubernetes_cluster_node_pool" "app_node_pool_{{ loop.index }}" {
+  # lowercase alphanumeric, 12 characters at most
+  name = "pool{{ loop.index }}"
+  kubernetes_cluster_id = azurerm_kubernetes_cluster.kubernetes_cluster.id
+  vm_size = "{{ aks_worker_node.instance_type }}"
+  enable_auto_scaling = true
+  node_count = {{ aks_worker_node.desired_size }}
+  min_count = {{ aks_worker_node.min_size }}
+  max_count = {{ aks_worker_node.max_size }}
+}
+{% endfor %}
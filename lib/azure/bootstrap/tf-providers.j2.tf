@@ -0,0 +1,29 @@
+provider "aws" {
+  alias = "tfstates"
+  access_key = "{{ aws_access_key_tfstates_account }}"
+  secret_key = "{{ aws_secret_key_tfstates_account }}"
+  region = "{{ aws_region_tfstates_account }}"
+}
+
+provider "azurerm" {
+  features {}
+  subscription_id = "{{ azure_subscription_id }}"
+  tenant_id = "{{ azure_tenant_id }}"
+  client_id = "{{ azure_client_id }}"
+  client_secret = "{{ azure_client_secret }}"
+}
+
+terraform {
+  required_providers {
+    aws = {
+      source = "hashicorp/aws"
+      version    = "~> 3.8.0"
+    }
+
+    azurerm = {
+      source = "hashicorp/azurerm"
+      version = "~> 2.46.0"
+    }
+  }
+  required_version = ">= 0.13"
+}
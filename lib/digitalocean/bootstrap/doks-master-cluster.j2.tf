@@ -3,14 +3,19 @@ resource "digitalocean_kubernetes_cluster" "kubernetes_cluster" {
   region = var.region
   version = var.doks_version
   vpc_uuid = digitalocean_vpc.qovery_vpc.id
+  tags = [{% for tag in doks_tags %}"{{ tag }}"{% if not loop.last %}, {% endif %}{% endfor %}]
 
   node_pool {
-    tags = [digitalocean_tag.cluster_tag.id]
+    tags = [digitalocean_tag.cluster_tag.id{% for tag in doks_tags %}, "{{ tag }}"{% endfor %}]
     name = "qovery-${var.kubernetes_cluster_id}"
     size = "{{ doks_worker_nodes[0].instance_type }}"
+{%- if doks_worker_nodes[0].auto_scale %}
     # use Digital Ocean built-in cluster autoscaler
     auto_scale = true
     min_nodes  = "{{ doks_worker_nodes[0].min_size }}"
     max_nodes  = "{{ doks_worker_nodes[0].max_size }}"
+{%- else %}
+    node_count = "{{ doks_worker_nodes[0].desired_size }}"
+{%- endif %}
   }
 }
\ No newline at end of file
@@ -8,10 +8,14 @@ resource "digitalocean_kubernetes_node_pool" "app_node_pool_{{ loop.index }}" {
 
   name = "qovery-{{kubernetes_cluster_id}}-{{ loop.index }}"
   size = "{{ doks_worker_node.instance_type }}"
-  tags = [digitalocean_tag.cluster_tag.id]
+  tags = [digitalocean_tag.cluster_tag.id{% for tag in doks_tags %}, "{{ tag }}"{% endfor %}]
+{%- if doks_worker_node.auto_scale %}
   auto_scale = true
   min_nodes  = "{{ doks_worker_node.min_size }}"
   max_nodes  = "{{ doks_worker_node.max_size }}"
+{%- else %}
+  node_count = "{{ doks_worker_node.desired_size }}"
+{%- endif %}
 
   depends_on = [
     digitalocean_kubernetes_cluster.kubernetes_cluster,
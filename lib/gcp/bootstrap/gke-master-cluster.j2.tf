@@ -0,0 +1,35 @@
+resource "google_container_cluster" "kubernetes_cluster" {
+  name = "qovery-{{ gke_cluster_id }}"
+  location = "{{ gcp_region }}"
+  min_master_version = "{{ gke_version }}"
+
+  # node pools are managed separately
+  remove_default_node_pool = true
+  initial_node_count = 1
+
+  resource_labels = {
+    cluster_id = "{{ gke_cluster_id }}"
+    organization_id = "{{ organization_id }}"
+  }
+}
+
+{% for gke_worker_node in gke_worker_nodes %}
+resource "google_container_node_pool" "app_node_pool_{{ loop.index }}" {
+  name = "qovery-{{ gke_cluster_id }}-{{ loop.index }}"
+  location = "{{ gcp_region }}"
+  cluster = google_container_cluster.kubernetes_cluster.name
+  initial_node_count = {{ gke_worker_node.desired_size }}
+
+  autoscaling {
+    min_node_count = {{ gke_worker_node.min_size }}
+    max_node_count = {{ gke_worker_node.max_size }}
+  }
+
+  node_config {
+    machine_type = "{{ gke_worker_node.instance_type }}"
+    oauth_scopes = [
+      "https://www.googleapis.com/auth/cloud-platform",
+    ]
+  }
+}
+{% endfor %}
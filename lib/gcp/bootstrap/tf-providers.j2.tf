@@ -0,0 +1,27 @@
+provider "aws" {
+  alias = "tfstates"
+  access_key = "{{ aws_access_key_tfstates_account }}"
+  secret_key = "{{ aws_secret_key_tfstates_account }}"
+  region = "{{ aws_region_tfstates_account }}"
+}
+
+provider "google" {
+  credentials = file("{{ gcp_credentials_file_path }}")
+  project = "{{ gcp_project_id }}"
+  region = "{{ gcp_region }}"
+}
+
+terraform {
+  required_providers {
+    aws = {
+      source = "hashicorp/aws"
+      version    = "~> 3.8.0"
+    }
+
+    google = {
+      source = "hashicorp/google"
+      version = "~> 3.51.0"
+    }
+  }
+  required_version = ">= 0.13"
+}
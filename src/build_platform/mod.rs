@@ -1,10 +1,12 @@
 use serde::{Deserialize, Serialize};
 
-use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
+use crate::build_platform::registry::ImageReference;
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope, SimpleError};
 use crate::git::Credentials;
 use crate::models::{Context, Listen};
 
 pub mod local_docker;
+pub mod registry;
 
 pub trait BuildPlatform: Listen {
     fn context(&self) -> &Context;
@@ -64,12 +66,167 @@ pub struct Image {
     pub registry_secret: Option<String>,
     // complete registry URL where the image has been pushed
     pub registry_url: Option<String>,
+    // credentials to pull the image from a private registry: Optional
+    pub registry_credentials: Option<RegistryCredentials>,
+    // immutable digest of the pushed image (e.g. sha256:...): Optional
+    pub digest: Option<String>,
+    // the tags and digests of a multi-architecture build, one per architecture: Optional
+    pub architecture_variants: Vec<ImageArchitectureVariant>,
+    // registries to pull the image from without registry URL, in order of preference (e.g. a mirror first)
+    pub registry_candidates: Vec<String>,
+}
+
+/// the cpu architecture of the nodes an image runs on
+#[derive(Clone, Copy, Eq, PartialEq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Architecture {
+    Amd64,
+    Arm64,
+}
+
+impl Default for Architecture {
+    fn default() -> Self {
+        Architecture::Amd64
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct ImageArchitectureVariant {
+    pub architecture: Architecture,
+    pub tag: String,
+    pub digest: Option<String>,
 }
 
 impl Image {
     pub fn name_with_tag(&self) -> String {
         format!("{}:{}", self.name, self.tag)
     }
+
+    /// the image pinned by its digest (e.g. `registry/name@sha256:...`), if the digest is known
+    pub fn name_with_digest(&self) -> Option<String> {
+        let digest = self.digest.as_ref()?;
+
+        let name = match &self.registry_url {
+            Some(registry_url) => registry_url_without_tag(registry_url.as_str()),
+            None => self.name.as_str(),
+        };
+
+        Some(format!("{}@{}", name, digest))
+    }
+
+    /// the image to run on nodes of the given architecture. A single variant is run whatever the architecture,
+    /// without a matching variant the image is run with its own tag (e.g. a multi-architecture manifest)
+    pub fn for_architecture(&self, architecture: Architecture) -> Image {
+        let variant = match self.architecture_variants.iter().find(|v| v.architecture == architecture) {
+            Some(variant) => variant,
+            None if self.architecture_variants.len() == 1 => &self.architecture_variants[0],
+            None => return self.clone(),
+        };
+
+        Image {
+            tag: variant.tag.clone(),
+            registry_url: self
+                .registry_url
+                .as_ref()
+                .map(|registry_url| format!("{}:{}", registry_url_without_tag(registry_url.as_str()), variant.tag)),
+            digest: variant.digest.clone(),
+            architecture_variants: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// whether the registry has the manifest of the image, the digest is checked when known instead of the tag
+    pub fn exists(&self, credentials: Option<&RegistryCredentials>) -> Result<bool, SimpleError> {
+        let mut image_reference = match &self.registry_url {
+            Some(registry_url) => ImageReference::parse(registry_url.as_str()),
+            None => ImageReference::parse(self.name_with_tag().as_str()),
+        };
+
+        if let Some(digest) = &self.digest {
+            image_reference.reference = digest.clone();
+        }
+
+        registry::manifest_exists(&image_reference, credentials)
+    }
+
+    /// the image pulled from the given registry (e.g. `mirror.example.com/team`)
+    pub fn in_registry(&self, registry: &str) -> Image {
+        Image {
+            registry_url: Some(format!("{}/{}", registry.trim_end_matches('/'), self.name_with_tag())),
+            registry_candidates: vec![],
+            ..self.clone()
+        }
+    }
+
+    /// the image in the first registry candidate having its manifest, an unreachable candidate is skipped
+    pub fn in_first_reachable_registry(&self, credentials: Option<&RegistryCredentials>) -> Option<Image> {
+        for registry in &self.registry_candidates {
+            let image = self.in_registry(registry.as_str());
+
+            match image.exists(credentials) {
+                Ok(true) => return Some(image),
+                Ok(false) => warn!("image {} not found in the registry {}", self.name_with_tag(), registry),
+                Err(err) => warn!(
+                    "registry {} is not reachable for the image {}: {:?}",
+                    registry,
+                    self.name_with_tag(),
+                    err.message
+                ),
+            }
+        }
+
+        None
+    }
+}
+
+/// the registry url ends with the (mutable) tag of the image
+fn registry_url_without_tag(registry_url: &str) -> &str {
+    match registry_url.rfind(':') {
+        Some(idx) if !registry_url[idx..].contains('/') => &registry_url[..idx],
+        _ => registry_url,
+    }
+}
+
+#[derive(Clone, Eq, PartialEq, Hash)]
+pub struct RegistryCredentials {
+    pub server: String,
+    pub login: String,
+    pub password: String,
+}
+
+impl RegistryCredentials {
+    /// from the base64 `AWS:password` authorization token returned by ECR
+    pub fn ecr(server: &str, authorization_token: &str) -> Option<RegistryCredentials> {
+        let decoded_token = base64::decode(authorization_token).ok()?;
+        let token = std::str::from_utf8(decoded_token.as_slice()).ok()?;
+        let mut s_token = token.splitn(2, ':');
+
+        Some(RegistryCredentials {
+            server: server.to_string(),
+            login: s_token.next()?.to_string(),
+            password: s_token.next()?.to_string(),
+        })
+    }
+
+    /// DOCR accepts the API token as login and password
+    pub fn docr(api_token: &str) -> RegistryCredentials {
+        RegistryCredentials {
+            server: "registry.digitalocean.com".to_string(),
+            login: api_token.to_string(),
+            password: api_token.to_string(),
+        }
+    }
+}
+
+// the password must never end up in the logs
+impl std::fmt::Debug for RegistryCredentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RegistryCredentials")
+            .field("server", &self.server)
+            .field("login", &self.login)
+            .field("password", &"********")
+            .finish()
+    }
 }
 
 pub struct BuildResult {
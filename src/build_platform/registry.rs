@@ -0,0 +1,336 @@
+use std::collections::BTreeMap;
+
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{ACCEPT, WWW_AUTHENTICATE};
+use reqwest::StatusCode;
+
+use crate::build_platform::RegistryCredentials;
+use crate::error::{SimpleError, SimpleErrorKind};
+
+/// the registry of the images without registry host (e.g. `nginx:1.19`)
+pub const DOCKER_HUB_REGISTRY: &str = "registry-1.docker.io";
+
+// single and multi arch manifests, in the docker and OCI formats
+const MANIFEST_MEDIA_TYPES: &str = "application/vnd.docker.distribution.manifest.v2+json, \
+application/vnd.docker.distribution.manifest.list.v2+json, \
+application/vnd.oci.image.manifest.v1+json, \
+application/vnd.oci.image.index.v1+json";
+
+/// an image split the way a registry v2 API addresses it
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageReference {
+    pub registry: String,
+    pub repository: String,
+    /// the tag or the digest (e.g. `sha256:...`) of the manifest
+    pub reference: String,
+}
+
+impl ImageReference {
+    /// parse `[registry/]repository[:tag|@digest]`, the tag is `latest` by default
+    pub fn parse(image: &str) -> ImageReference {
+        let (name, reference) = match image.rfind('@') {
+            Some(idx) => (&image[..idx], &image[idx + 1..]),
+            None => match image.rfind(':') {
+                Some(idx) if !image[idx..].contains('/') => (&image[..idx], &image[idx + 1..]),
+                _ => (image, "latest"),
+            },
+        };
+
+        let (registry, repository) = match name.find('/') {
+            Some(idx) if is_registry_host(&name[..idx]) => (&name[..idx], name[idx + 1..].to_string()),
+            Some(_) => (DOCKER_HUB_REGISTRY, name.to_string()),
+            None => (DOCKER_HUB_REGISTRY, format!("library/{}", name)),
+        };
+
+        ImageReference {
+            registry: registry.to_string(),
+            repository,
+            reference: reference.to_string(),
+        }
+    }
+
+    pub fn manifest_url(&self) -> String {
+        // like docker, the local registries are reached over plain http
+        let scheme = match self.registry.starts_with("localhost") || self.registry.starts_with("127.0.0.1") {
+            true => "http",
+            false => "https",
+        };
+
+        format!("{}://{}/v2/{}/manifests/{}", scheme, self.registry, self.repository, self.reference)
+    }
+}
+
+fn is_registry_host(component: &str) -> bool {
+    component.contains('.') || component.contains(':') || component == "localhost"
+}
+
+/// check the manifest with a HEAD request, a token is requested when the registry asks for one (Docker Hub, GCR...)
+pub fn manifest_exists(
+    image_reference: &ImageReference,
+    credentials: Option<&RegistryCredentials>,
+) -> Result<bool, SimpleError> {
+    let client = Client::new();
+    let url = image_reference.manifest_url();
+
+    let head = || with_basic_auth(client.head(url.as_str()).header(ACCEPT, MANIFEST_MEDIA_TYPES), credentials);
+    let response = send(head(), url.as_str())?;
+
+    let response = match (response.status(), bearer_challenge(&response)) {
+        (StatusCode::UNAUTHORIZED, Some(challenge)) => {
+            let token = request_token(&client, &challenge, credentials)?;
+            send(head().bearer_auth(token), url.as_str())?
+        }
+        _ => response,
+    };
+
+    match response.status() {
+        StatusCode::OK => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        status => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("Receive status code {} from the registry for {}", status, url)),
+        )),
+    }
+}
+
+fn with_basic_auth(request: RequestBuilder, credentials: Option<&RegistryCredentials>) -> RequestBuilder {
+    match credentials {
+        Some(credentials) => request.basic_auth(credentials.login.as_str(), Some(credentials.password.as_str())),
+        None => request,
+    }
+}
+
+fn send(request: RequestBuilder, url: &str) -> Result<Response, SimpleError> {
+    request.send().map_err(|err| {
+        SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("Unable to get a response from the registry for {}: {}", url, err)),
+        )
+    })
+}
+
+fn bearer_challenge(response: &Response) -> Option<BTreeMap<String, String>> {
+    let header = response.headers().get(WWW_AUTHENTICATE)?.to_str().ok()?;
+    parse_bearer_challenge(header)
+}
+
+/// the parameters of `Bearer realm="..",service="..",scope=".."`
+fn parse_bearer_challenge(header: &str) -> Option<BTreeMap<String, String>> {
+    let mut params = header.trim().strip_prefix("Bearer ")?.trim();
+    let mut challenge = BTreeMap::new();
+
+    while !params.is_empty() {
+        let idx = params.find('=')?;
+        let key = params[..idx].trim().to_string();
+        let rest = &params[idx + 1..];
+
+        // a quoted value may contain commas, e.g. `scope="repository:app:pull,push"`
+        let (value, rest) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => {
+                let end = rest.find(',').unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+
+        challenge.insert(key, value.to_string());
+        params = rest.trim_start_matches(',').trim();
+    }
+
+    match challenge.contains_key("realm") {
+        true => Some(challenge),
+        false => None,
+    }
+}
+
+fn request_token(
+    client: &Client,
+    challenge: &BTreeMap<String, String>,
+    credentials: Option<&RegistryCredentials>,
+) -> Result<String, SimpleError> {
+    let realm = challenge["realm"].as_str();
+    let query = challenge
+        .iter()
+        .filter(|(key, _)| key.as_str() == "service" || key.as_str() == "scope")
+        .collect::<Vec<_>>();
+
+    let response = send(with_basic_auth(client.get(realm).query(&query), credentials), realm)?;
+    if response.status() != StatusCode::OK {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("Receive status code {} from {} while requesting a token", response.status(), realm)),
+        ));
+    }
+
+    let body = response.text().unwrap_or_default();
+    let token = serde_json::from_str::<serde_json::Value>(body.as_str())
+        .ok()
+        .and_then(|json| {
+            json.get("token")
+                .or_else(|| json.get("access_token"))
+                .and_then(|token| token.as_str())
+                .map(|token| token.to_string())
+        });
+
+    match token {
+        Some(token) => Ok(token),
+        None => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("No token in the response of {}", realm)),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    use crate::build_platform::registry::{parse_bearer_challenge, ImageReference};
+    use crate::build_platform::{Image, RegistryCredentials};
+
+    /// a registry answering each request with the status code of its path, 404 for the unknown paths
+    fn mock_registry(routes: Vec<(&'static str, u16)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = match stream {
+                    Ok(stream) => stream,
+                    Err(_) => break,
+                };
+
+                let mut buffer = [0; 4096];
+                let size = stream.read(&mut buffer).unwrap_or(0);
+                let request = String::from_utf8_lossy(&buffer[..size]).to_string();
+                let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+                let status = match routes.iter().find(|(route, _)| *route == path) {
+                    Some((_, status)) => *status,
+                    None => 404,
+                };
+
+                let _ = write!(stream, "HTTP/1.1 {} Mock\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+            }
+        });
+
+        address
+    }
+
+    fn image(registry_url: &str) -> Image {
+        Image {
+            application_id: "application-id".to_string(),
+            name: "app".to_string(),
+            tag: "tag".to_string(),
+            commit_id: "commit-id".to_string(),
+            registry_name: None,
+            registry_secret: None,
+            registry_url: Some(registry_url.to_string()),
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
+        }
+    }
+
+    #[test]
+    fn test_parse_image_reference() {
+        let reference = ImageReference::parse("nginx:1.19");
+        assert_eq!(reference.registry, "registry-1.docker.io");
+        assert_eq!(reference.repository, "library/nginx");
+        assert_eq!(reference.reference, "1.19");
+
+        let reference = ImageReference::parse("registry.digitalocean.com/qovery/app:v1");
+        assert_eq!(reference.registry, "registry.digitalocean.com");
+        assert_eq!(reference.repository, "qovery/app");
+        assert_eq!(reference.reference, "v1");
+
+        let reference = ImageReference::parse("123456789.dkr.ecr.eu-west-3.amazonaws.com/app@sha256:abc");
+        assert_eq!(reference.registry, "123456789.dkr.ecr.eu-west-3.amazonaws.com");
+        assert_eq!(reference.repository, "app");
+        assert_eq!(reference.reference, "sha256:abc");
+        assert_eq!(
+            reference.manifest_url(),
+            "https://123456789.dkr.ecr.eu-west-3.amazonaws.com/v2/app/manifests/sha256:abc"
+        );
+
+        let reference = ImageReference::parse("localhost:5000/team/app");
+        assert_eq!(reference.registry, "localhost:5000");
+        assert_eq!(reference.repository, "team/app");
+        assert_eq!(reference.reference, "latest");
+        assert_eq!(reference.manifest_url(), "http://localhost:5000/v2/team/app/manifests/latest");
+    }
+
+    #[test]
+    fn test_parse_bearer_challenge() {
+        let challenge = parse_bearer_challenge(
+            r#"Bearer realm="https://auth.docker.io/token",service="registry.docker.io",scope="repository:library/nginx:pull,push""#,
+        )
+        .unwrap();
+
+        assert_eq!(challenge["realm"], "https://auth.docker.io/token");
+        assert_eq!(challenge["service"], "registry.docker.io");
+        assert_eq!(challenge["scope"], "repository:library/nginx:pull,push");
+
+        assert_eq!(parse_bearer_challenge(r#"Basic realm="https://registry.example.com""#), None);
+    }
+
+    #[test]
+    fn test_image_exists() {
+        let address = mock_registry(vec![("/v2/app/manifests/present", 200), ("/v2/app/manifests/forbidden", 403)]);
+
+        let present = image(format!("{}/app:present", address).as_str());
+        assert!(present.exists(None).unwrap());
+
+        let missing = image(format!("{}/app:missing", address).as_str());
+        assert!(!missing.exists(None).unwrap());
+
+        // the digest has priority over the mutable tag
+        let mut pinned = image(format!("{}/app:present", address).as_str());
+        pinned.digest = Some("sha256:abc".to_string());
+        assert!(!pinned.exists(None).unwrap());
+
+        let forbidden = image(format!("{}/app:forbidden", address).as_str());
+        assert!(forbidden.exists(None).is_err());
+    }
+
+    #[test]
+    fn test_first_reachable_registry() {
+        let failing = mock_registry(vec![("/v2/app/manifests/tag", 500)]);
+        let mirror = mock_registry(vec![("/v2/team/app/manifests/tag", 200)]);
+
+        let mut candidates = image("unused");
+        candidates.registry_url = None;
+        candidates.registry_candidates = vec![failing, format!("{}/team/", mirror)];
+
+        let selected = candidates.in_first_reachable_registry(None).unwrap();
+        assert_eq!(selected.registry_url, Some(format!("{}/team/app:tag", mirror)));
+        assert!(selected.registry_candidates.is_empty());
+
+        candidates.registry_candidates = vec!["127.0.0.1:1".to_string()];
+        assert_eq!(candidates.in_first_reachable_registry(None), None);
+    }
+
+    #[test]
+    fn test_registry_credentials() {
+        let credentials = RegistryCredentials::ecr(
+            "https://123456789.dkr.ecr.eu-west-3.amazonaws.com",
+            base64::encode("AWS:password").as_str(),
+        )
+        .unwrap();
+
+        assert_eq!(credentials.server, "https://123456789.dkr.ecr.eu-west-3.amazonaws.com");
+        assert_eq!(credentials.login, "AWS");
+        assert_eq!(credentials.password, "password");
+        assert_eq!(RegistryCredentials::ecr("server", "not base64"), None);
+
+        let credentials = RegistryCredentials::docr("api-token");
+        assert_eq!(credentials.server, "registry.digitalocean.com");
+        assert_eq!(credentials.login, "api-token");
+        assert_eq!(credentials.password, "api-token");
+    }
+}
@@ -2,7 +2,7 @@ use tera::Context as TeraContext;
 
 use crate::build_platform::Image;
 use crate::cloud_provider::models::{
-    EnvironmentVariable, EnvironmentVariableDataTemplate, Storage, StorageDataTemplate,
+    EnvironmentVariable, EnvironmentVariableDataTemplate, Probe, Storage, StorageDataTemplate,
 };
 use crate::cloud_provider::service::{
     default_tera_context, delete_stateless_service, deploy_stateless_service_error, deploy_user_stateless_service,
@@ -13,7 +13,7 @@ use crate::cloud_provider::utilities::{sanitize_name, validate_k8s_required_cpu_
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
 use crate::error::EngineErrorCause::Internal;
-use crate::error::{EngineError, EngineErrorScope};
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::{Context, Listen, Listener, Listeners, ListenersHelper};
 
 pub struct Application {
@@ -30,6 +30,8 @@ pub struct Application {
     image: Image,
     storage: Vec<Storage<StorageType>>,
     environment_variables: Vec<EnvironmentVariable>,
+    liveness_probe: Option<Probe>,
+    readiness_probe: Option<Probe>,
     listeners: Listeners,
 }
 
@@ -64,9 +66,29 @@ impl Application {
             image,
             storage,
             environment_variables,
+            liveness_probe: None,
+            readiness_probe: None,
             listeners,
         }
     }
+
+    /// replace the default tcp liveness probe on the private port
+    pub fn set_liveness_probe(&mut self, liveness_probe: Option<Probe>) {
+        self.liveness_probe = liveness_probe;
+    }
+
+    /// replace the default tcp readiness probe on the private port
+    pub fn set_readiness_probe(&mut self, readiness_probe: Option<Probe>) {
+        self.readiness_probe = readiness_probe;
+    }
+
+    fn insert_probes(&self, context: &mut TeraContext) {
+        let liveness_probe = self.liveness_probe.as_ref().map(|probe| probe.to_data_template());
+        let readiness_probe = self.readiness_probe.as_ref().map(|probe| probe.to_data_template());
+
+        context.insert("liveness_probe", &liveness_probe);
+        context.insert("readiness_probe", &readiness_probe);
+    }
 }
 
 impl crate::cloud_provider::service::Application for Application {
@@ -81,7 +103,14 @@ impl crate::cloud_provider::service::Application for Application {
 
 impl Helm for Application {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("application-{}-{}", self.name(), self.id()), 50)
+        crate::string::helm_release_name_safe(format!("application-{}", self.name()).as_str(), self.id())
+    }
+
+    fn legacy_helm_release_name(&self) -> Option<String> {
+        Some(crate::string::legacy_helm_release_name(
+            format!("application-{}", self.name()).as_str(),
+            self.id(),
+        ))
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -185,6 +214,7 @@ impl Service for Application {
             .collect::<Vec<_>>();
 
         context.insert("environment_variables", &environment_variables);
+        self.insert_probes(&mut context);
 
         match self.image.registry_name.as_ref() {
             Some(registry_name) => {
@@ -272,6 +302,17 @@ impl Create for Application {
     }
 
     fn on_create_check(&self) -> Result<(), EngineError> {
+        let probes = [("liveness", &self.liveness_probe), ("readiness", &self.readiness_probe)];
+
+        for &(probe_name, probe) in &probes {
+            if let Some(Err(err)) = probe.as_ref().map(|probe| probe.validate()) {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("The health checks of the application are not valid"),
+                    format!("{} has an invalid {} probe: {}", self.name_with_id(), probe_name, err),
+                ));
+            }
+        }
+
         Ok(())
     }
 
@@ -293,7 +334,7 @@ impl Pause for Application {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, false)),
+            Box::new(|| delete_stateless_service(target, self, false, false)),
         )
     }
 
@@ -307,7 +348,7 @@ impl Pause for Application {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -319,7 +360,7 @@ impl Delete for Application {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, false)),
+            Box::new(|| delete_stateless_service(target, self, false, true)),
         )
     }
 
@@ -333,7 +374,7 @@ impl Delete for Application {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -355,3 +396,131 @@ pub enum StorageType {
     GP2,
     IO1,
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use tera::Context as TeraContext;
+
+    use crate::build_platform::Image;
+    use crate::cloud_provider::aws::application::Application;
+    use crate::cloud_provider::models::{Probe, ProbeKind};
+    use crate::cloud_provider::service::{Action, Create};
+    use crate::error::EngineErrorCause;
+    use crate::models::Context;
+
+    fn application() -> Application {
+        let context = Context::new(
+            "execution_id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        );
+
+        let image = Image {
+            application_id: "id".to_string(),
+            name: "my-app".to_string(),
+            tag: "tag".to_string(),
+            commit_id: "0123456789abcdef".to_string(),
+            registry_name: None,
+            registry_secret: None,
+            registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
+        };
+
+        Application::new(
+            context,
+            "id",
+            Action::Create,
+            "my-app",
+            Some(8080),
+            "1".to_string(),
+            "1".to_string(),
+            256,
+            1,
+            60,
+            image,
+            vec![],
+            vec![],
+            vec![],
+        )
+    }
+
+    fn http_probe(path: Option<&str>) -> Probe {
+        Probe {
+            kind: ProbeKind::Http,
+            path: path.map(|path| path.to_string()),
+            port: Some(8080),
+            command: vec![],
+            initial_delay_in_seconds: 15,
+            period_in_seconds: 10,
+            failure_threshold: 3,
+        }
+    }
+
+    #[test]
+    fn test_probes_tera_context() {
+        let mut application = application();
+        application.set_liveness_probe(Some(http_probe(Some("/health"))));
+        application.set_readiness_probe(Some(Probe {
+            kind: ProbeKind::Exec,
+            path: None,
+            port: None,
+            command: vec!["cat".to_string(), "/tmp/ready".to_string()],
+            initial_delay_in_seconds: 5,
+            period_in_seconds: 5,
+            failure_threshold: 1,
+        }));
+
+        let mut context = TeraContext::new();
+        application.insert_probes(&mut context);
+        let context = context.into_json();
+
+        assert_eq!(
+            context["liveness_probe"],
+            json!({
+                "kind": "http",
+                "path": "/health",
+                "port": 8080,
+                "command": [],
+                "initial_delay_seconds": 15,
+                "period_seconds": 10,
+                "failure_threshold": 3
+            })
+        );
+        assert_eq!(context["readiness_probe"]["kind"], "exec");
+        assert_eq!(context["readiness_probe"]["command"], json!(["cat", "/tmp/ready"]));
+    }
+
+    #[test]
+    fn test_default_probes_tera_context() {
+        let mut context = TeraContext::new();
+        application().insert_probes(&mut context);
+        let context = context.into_json();
+
+        // the chart falls back to tcp probes on the private port
+        assert!(context["liveness_probe"].is_null());
+        assert!(context["readiness_probe"].is_null());
+    }
+
+    #[test]
+    fn test_http_probe_requires_a_path() {
+        let mut application = application();
+        application.set_readiness_probe(Some(http_probe(Some("/ready"))));
+        assert!(application.on_create_check().is_ok());
+
+        application.set_liveness_probe(Some(http_probe(None)));
+        match application.on_create_check() {
+            Err(err) => match err.cause {
+                EngineErrorCause::User(_) => {}
+                EngineErrorCause::Internal => panic!("expected a user error"),
+            },
+            Ok(_) => panic!("an http probe without a path must be rejected"),
+        }
+    }
+}
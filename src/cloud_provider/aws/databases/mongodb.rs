@@ -210,7 +210,7 @@ impl Database for MongoDB {}
 
 impl Helm for MongoDB {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("mongodb-{}", self.id()), 50)
+        crate::string::helm_release_name_safe("mongodb", self.id())
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -209,7 +209,7 @@ impl Database for PostgreSQL {}
 
 impl Helm for PostgreSQL {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("postgresql-{}", self.id()), 50)
+        crate::string::helm_release_name_safe("postgresql", self.id())
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -221,7 +221,7 @@ impl Database for Redis {}
 
 impl Helm for Redis {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("redis-{}", self.id()), 50)
+        crate::string::helm_release_name_safe("redis", self.id())
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -1,16 +1,75 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
 use tera::Context as TeraContext;
 
-use crate::build_platform::Image;
-use crate::cloud_provider::models::{EnvironmentVariable, EnvironmentVariableDataTemplate};
+use crate::build_platform::{Architecture, Image, RegistryCredentials};
+use crate::cloud_provider::aws::kubernetes::fargate::FargateProfile;
+use crate::cloud_provider::models::{
+    Command, EnvironmentVariable, EnvironmentVariableDataTemplate, ImagePullPolicy, InitContainer,
+    InitContainerDataTemplate, MountedFile, MountedFileDataTemplate, SecurityContext, SidecarDataTemplate, Toleration,
+    TolerationDataTemplate, TopologySpread, TopologySpreadDataTemplate, VolumeClaim, VolumeClaimDataTemplate,
+    ENVIRONMENT_VARIABLES_MAX_SIZE_IN_BYTES, MOUNTED_FILES_MAX_SIZE_IN_BYTES,
+};
 use crate::cloud_provider::service::{
     default_tera_context, delete_stateless_service, deploy_stateless_service_error, deploy_user_stateless_service,
-    send_progress_on_long_task, Action, Application as AApplication, Create, Delete, Helm, Pause, Service, ServiceType,
-    StatelessService,
+    is_stateless_service_up_to_date, restart_stateless_service, scale_to_zero_stateless_service,
+    send_progress_on_long_task, Action, Application as AApplication, ChartSource, Create, Delete, Helm, Pause,
+    PauseStrategy, Restart, Service, ServiceType, StatelessService, DEFAULT_TERMINATION_GRACE_PERIOD_SECONDS,
 };
+use crate::cloud_provider::utilities::{interpolate_environment_variables, sanitize_name};
 use crate::cloud_provider::DeploymentTarget;
 use crate::cmd::helm::Timeout;
-use crate::error::{EngineError, EngineErrorScope};
+use crate::cmd::kubectl::KubectlOutput;
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope};
+use crate::metrics;
 use crate::models::{Context, Listen, Listener, Listeners};
+use crate::unit_conversion::cpu_string_to_float;
+
+/// the pod labels set by the engine, the pod labels of the user can't override them
+const ENGINE_POD_LABELS: [&str; 4] = ["ownerId", "envId", "appId", "app"];
+/// the pod annotations set by the engine
+const ENGINE_POD_ANNOTATIONS: [&str; 1] = ["qovery.com/redeployed-at"];
+/// the jobs running longer are killed, unless the service sets its own deadline
+const JOB_ACTIVE_DEADLINE_SECONDS: u32 = 3600;
+/// the failed pods of a job are not run again, unless the service allows some retries
+const JOB_BACKOFF_LIMIT: u32 = 0;
+
+/// the values of the q-job and q-cronjob charts, written to a values file and read by the templates from `.Values`
+#[derive(Serialize)]
+pub struct QJobValues {
+    pub image: QJobImageValues,
+    pub env: Vec<QJobEnvValue>,
+    pub resources: QJobResourcesValues,
+    pub instances: u16,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QJobImageValues {
+    pub name: String,
+    pub pull_policy: String,
+}
+
+/// a plain environment variable, the secret ones stay in the kubernetes secret of the job
+#[derive(Serialize)]
+pub struct QJobEnvValue {
+    pub name: String,
+    pub value: String,
+}
+
+#[derive(Serialize)]
+pub struct QJobResourcesValues {
+    pub requests: QJobResourceValues,
+    pub limits: QJobResourceValues,
+}
+
+#[derive(Serialize)]
+pub struct QJobResourceValues {
+    pub cpu: String,
+    /// a kubernetes quantity, e.g. `256Mi`
+    pub memory: String,
+}
 
 pub struct ExternalService {
     context: Context,
@@ -19,12 +78,51 @@ pub struct ExternalService {
     name: String,
     total_cpus: String,
     total_ram_in_mib: u32,
+    cpu_request: String,
+    cpu_limit: String,
+    ram_request_mib: u32,
+    ram_limit_mib: u32,
+    total_instances: u16,
+    private_port: Option<u16>,
+    start_timeout: Timeout<u32>,
     image: Image,
+    sidecar_images: Vec<Image>,
+    sidecar_environment_variables: BTreeMap<String, Vec<EnvironmentVariable>>,
+    init_containers: Vec<InitContainer>,
     environment_variables: Vec<EnvironmentVariable>,
+    secret_environment_variables: Vec<EnvironmentVariable>,
+    chart_version: Option<String>,
+    chart_source: Option<ChartSource>,
+    pause_strategy: PauseStrategy,
+    strict_environment_variables_interpolation: bool,
+    environment_variables_max_size_in_bytes: usize,
+    values_files: Vec<String>,
+    set_values: Vec<(String, String)>,
+    depends_on: Vec<String>,
+    namespace_override: Option<String>,
+    fargate_profile: Option<FargateProfile>,
+    node_selectors: BTreeMap<String, String>,
+    tolerations: Vec<Toleration>,
+    topology_spread_constraints: Vec<TopologySpread>,
+    security_context: SecurityContext,
+    active_deadline_seconds: Option<u32>,
+    backoff_limit: Option<u32>,
+    termination_grace_period_seconds: Option<u32>,
+    helm_wait_for_jobs: bool,
+    image_pull_policy: Option<ImagePullPolicy>,
+    pod_labels: BTreeMap<String, String>,
+    pod_annotations: BTreeMap<String, String>,
+    release_labels: BTreeMap<String, String>,
+    mounted_files: Vec<MountedFile>,
+    post_deploy_check: Option<Command>,
+    volume_claims: Vec<VolumeClaim>,
+    command: Option<Vec<String>>,
+    args: Option<Vec<String>>,
     listeners: Listeners,
 }
 
 impl ExternalService {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         context: Context,
         id: &str,
@@ -32,239 +130,3662 @@ impl ExternalService {
         name: &str,
         total_cpus: String,
         total_ram_in_mib: u32,
+        total_instances: u16,
+        private_port: Option<u16>,
+        start_timeout: Timeout<u32>,
         image: Image,
+        sidecar_images: Vec<Image>,
         environment_variables: Vec<EnvironmentVariable>,
+        secret_environment_variables: Vec<EnvironmentVariable>,
         listeners: Listeners,
     ) -> Self {
+        // the secrets are echoed by the helm and kubectl outputs, they are redacted from the logs and the errors
+        for environment_variable in &secret_environment_variables {
+            context.secrets().register(environment_variable.value.as_str());
+        }
+
         ExternalService {
             context,
             id: id.to_string(),
             action,
             name: name.to_string(),
+            cpu_request: total_cpus.clone(),
+            cpu_limit: total_cpus.clone(),
+            ram_request_mib: total_ram_in_mib,
+            ram_limit_mib: total_ram_in_mib,
             total_cpus,
             total_ram_in_mib,
+            total_instances,
+            private_port,
+            start_timeout,
             image,
+            sidecar_images,
+            sidecar_environment_variables: BTreeMap::new(),
+            init_containers: vec![],
             environment_variables,
+            secret_environment_variables,
+            chart_version: None,
+            chart_source: None,
+            pause_strategy: PauseStrategy::Delete,
+            strict_environment_variables_interpolation: false,
+            environment_variables_max_size_in_bytes: ENVIRONMENT_VARIABLES_MAX_SIZE_IN_BYTES,
+            values_files: vec![],
+            set_values: vec![],
+            depends_on: vec![],
+            namespace_override: None,
+            fargate_profile: None,
+            node_selectors: BTreeMap::new(),
+            tolerations: vec![],
+            topology_spread_constraints: vec![],
+            security_context: SecurityContext::default(),
+            active_deadline_seconds: None,
+            backoff_limit: None,
+            termination_grace_period_seconds: None,
+            helm_wait_for_jobs: false,
+            image_pull_policy: None,
+            pod_labels: BTreeMap::new(),
+            pod_annotations: BTreeMap::new(),
+            release_labels: BTreeMap::new(),
+            mounted_files: vec![],
+            post_deploy_check: None,
+            volume_claims: vec![],
+            command: None,
+            args: None,
             listeners,
         }
     }
-}
 
-impl crate::cloud_provider::service::ExternalService for ExternalService {}
+    /// pin the version of the chart used to deploy this external service
+    pub fn set_chart_version(&mut self, chart_version: Option<String>) {
+        self.chart_version = chart_version;
+    }
 
-impl crate::cloud_provider::service::Application for ExternalService {
-    fn image(&self) -> &Image {
-        &self.image
+    /// deploy a chart from a remote repository instead of the bundled job chart
+    pub fn set_chart_source(&mut self, chart_source: Option<ChartSource>) {
+        self.chart_source = chart_source;
     }
 
-    fn set_image(&mut self, image: Image) {
-        self.image = image;
+    /// scale the job to zero on pause instead of deleting it, so its configuration and secrets survive
+    pub fn set_pause_strategy(&mut self, pause_strategy: PauseStrategy) {
+        self.pause_strategy = pause_strategy;
     }
-}
 
-impl Helm for ExternalService {
-    fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("external-service-{}-{}", self.name(), self.id()), 50)
+    /// fail the deployment when an environment variable references an unknown one, it is kept as is by default
+    pub fn set_strict_environment_variables_interpolation(&mut self, strict: bool) {
+        self.strict_environment_variables_interpolation = strict;
     }
 
-    fn helm_chart_dir(&self) -> String {
-        format!("{}/common/services/q-job", self.context.lib_root_dir())
+    /// the total size of the environment variables of a container, `ENVIRONMENT_VARIABLES_MAX_SIZE_IN_BYTES` by default
+    pub fn set_environment_variables_max_size_in_bytes(&mut self, max_size_in_bytes: usize) {
+        self.environment_variables_max_size_in_bytes = max_size_in_bytes;
     }
 
-    fn helm_chart_values_dir(&self) -> String {
-        String::new()
+    /// request less cpu than the limit for burstable workloads, both default to the total cpus
+    pub fn set_cpu_request_and_limit(&mut self, cpu_request: String, cpu_limit: String) {
+        self.cpu_request = cpu_request;
+        self.cpu_limit = cpu_limit;
     }
 
-    fn helm_chart_external_name_service_dir(&self) -> String {
-        String::new()
+    /// request less memory than the limit for burstable workloads, both default to the total ram
+    pub fn set_ram_request_and_limit_in_mib(&mut self, ram_request_mib: u32, ram_limit_mib: u32) {
+        self.ram_request_mib = ram_request_mib;
+        self.ram_limit_mib = ram_limit_mib;
     }
-}
 
-impl StatelessService for ExternalService {}
+    /// layer extra values files on top of the rendered chart values, in the given order
+    pub fn set_values_files(&mut self, values_files: Vec<String>) {
+        self.values_files = values_files;
+    }
 
-impl Service for ExternalService {
-    fn context(&self) -> &Context {
-        &self.context
+    /// override single chart values (e.g. `resources.limits.memory`) with `--set`, after the values files
+    pub fn set_helm_set_values(&mut self, set_values: Vec<(String, String)>) {
+        self.set_values = set_values;
     }
 
-    fn service_type(&self) -> ServiceType {
-        ServiceType::ExternalService
+    /// ids of the services to deploy before this external service
+    pub fn set_depends_on(&mut self, depends_on: Vec<String>) {
+        self.depends_on = depends_on;
     }
 
-    fn id(&self) -> &str {
-        self.id.as_str()
+    /// deploy the job in its own namespace, created if it does not exist, instead of the environment one
+    pub fn set_namespace_override(&mut self, namespace_override: Option<String>) {
+        self.namespace_override = namespace_override;
     }
 
-    fn name(&self) -> &str {
-        self.name.as_str()
+    /// run the job on a Fargate profile of the EKS cluster: it is deployed in the namespace of the profile,
+    /// its pods having the labels of the profile
+    pub fn set_fargate_profile(&mut self, fargate_profile: Option<FargateProfile>) {
+        self.fargate_profile = fargate_profile;
     }
 
-    fn sanitized_name(&self) -> String {
-        format!("ext-service-{}", self.name())
+    /// node labels the job must be scheduled on, e.g. to run on a GPU node pool
+    pub fn set_node_selectors(&mut self, node_selectors: BTreeMap<String, String>) {
+        self.node_selectors = node_selectors;
     }
 
-    fn version(&self) -> &str {
-        self.image.commit_id.as_str()
+    /// let the job be scheduled on tainted nodes, e.g. spot instances
+    pub fn set_tolerations(&mut self, tolerations: Vec<Toleration>) {
+        self.tolerations = tolerations;
     }
 
-    fn action(&self) -> &Action {
-        &self.action
+    /// spread the pods of the job across the zones or the nodes, the scheduler's own choice by default
+    pub fn set_topology_spread_constraints(&mut self, topology_spread_constraints: Vec<TopologySpread>) {
+        self.topology_spread_constraints = topology_spread_constraints;
     }
 
-    fn private_port(&self) -> Option<u16> {
-        None
+    /// the containers of the job run as non root by default
+    pub fn set_security_context(&mut self, security_context: SecurityContext) {
+        self.security_context = security_context;
     }
 
-    fn start_timeout(&self) -> Timeout<u32> {
-        Timeout::Default
+    /// the job is killed once it has run for longer, 1 hour by default
+    pub fn set_active_deadline_seconds(&mut self, active_deadline_seconds: Option<u32>) {
+        self.active_deadline_seconds = active_deadline_seconds;
     }
 
-    fn total_cpus(&self) -> String {
-        self.total_cpus.to_string()
+    /// how many times a failed pod of the job is run again, never by default since jobs are often not idempotent
+    pub fn set_backoff_limit(&mut self, backoff_limit: Option<u32>) {
+        self.backoff_limit = backoff_limit;
     }
 
-    fn cpu_burst(&self) -> String {
-        unimplemented!()
+    /// how long the job may flush its work after SIGTERM before being killed, 30 seconds by default like kubernetes
+    pub fn set_termination_grace_period_seconds(&mut self, termination_grace_period_seconds: Option<u32>) {
+        self.termination_grace_period_seconds = termination_grace_period_seconds;
     }
 
-    fn total_ram_in_mib(&self) -> u32 {
-        self.total_ram_in_mib
+    /// let helm wait for the job to succeed within the start timeout instead of polling it with kubectl,
+    /// for the charts whose jobs helm can wait on
+    pub fn set_helm_wait_for_jobs(&mut self, helm_wait_for_jobs: bool) {
+        self.helm_wait_for_jobs = helm_wait_for_jobs;
     }
 
-    fn total_instances(&self) -> u16 {
-        1
+    /// by default, an image pinned by its digest is pulled if not present and an image with a tag is always pulled
+    pub fn set_image_pull_policy(&mut self, image_pull_policy: Option<ImagePullPolicy>) {
+        self.image_pull_policy = image_pull_policy;
     }
 
-    fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
+    pub fn image_pull_policy(&self) -> ImagePullPolicy {
+        self.image_pull_policy
+            .unwrap_or_else(|| ImagePullPolicy::default_for(&self.image))
+    }
+
+    /// extra labels of the pods, e.g. for cost attribution. They are added to the engine labels, not replacing them
+    pub fn set_pod_labels(&mut self, pod_labels: BTreeMap<String, String>) {
+        self.pod_labels = pod_labels;
+    }
+
+    /// extra annotations of the pods, e.g. `sidecar.istio.io/inject: "true"`
+    pub fn set_pod_annotations(&mut self, pod_annotations: BTreeMap<String, String>) {
+        self.pod_annotations = pod_annotations;
+    }
+
+    /// labels of the helm release, e.g. to find the releases of a team with `helm list --selector team=billing`
+    pub fn set_release_labels(&mut self, release_labels: BTreeMap<String, String>) {
+        self.release_labels = release_labels;
+    }
+
+    /// configuration files mounted in the job container from a config map, their content is rendered with Tera
+    pub fn set_mounted_files(&mut self, mounted_files: Vec<MountedFile>) {
+        self.mounted_files = mounted_files;
+    }
+
+    /// persistent volume claims mounted in the job container, created with the release and deleted with it
+    pub fn set_volume_claims(&mut self, volume_claims: Vec<VolumeClaim>) {
+        self.volume_claims = volume_claims;
+    }
+
+    /// replace the entrypoint of the image, the image one is used by default
+    pub fn set_command(&mut self, command: Option<Vec<String>>) {
+        self.command = command;
+    }
+
+    /// replace the arguments given to the entrypoint, the image ones are used by default
+    pub fn set_args(&mut self, args: Option<Vec<String>>) {
+        self.args = args;
+    }
+
+    fn volume_claims(&self) -> Vec<VolumeClaimDataTemplate> {
+        self.volume_claims
+            .iter()
+            .map(|volume_claim| VolumeClaimDataTemplate {
+                name: volume_claim.name.clone(),
+                claim_name: format!("{}-{}", self.sanitized_name(), volume_claim.name),
+                size_gib: volume_claim.size_gib,
+                storage_class: volume_claim.storage_class.clone(),
+                mount_path: volume_claim.mount_path.clone(),
+            })
+            .collect()
+    }
+
+    /// a smoke test run in the job pod once it is ready, the deployment fails if the command exits with an error
+    pub fn set_post_deploy_check(&mut self, post_deploy_check: Option<Command>) {
+        self.post_deploy_check = post_deploy_check;
+    }
+
+    fn run_post_deploy_check(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        self.run_post_deploy_check_in_job(target, self.sanitized_name().as_str())
+    }
+
+    /// run the post deploy check in the first pod of the job `job_name`
+    fn run_post_deploy_check_in_job(&self, target: &DeploymentTarget, job_name: &str) -> Result<(), EngineError> {
+        let command = match &self.post_deploy_check {
+            Some(command) => command,
+            None => return Ok(()),
+        };
+
         let (kubernetes, environment) = match target {
             DeploymentTarget::ManagedServices(k, env) => (*k, *env),
             DeploymentTarget::SelfHosted(k, env) => (*k, *env),
         };
 
-        let mut context = default_tera_context(self, kubernetes, environment);
-        let commit_id = self.image().commit_id.as_str();
+        let kubernetes_config_file_path = kubernetes.config_file_path()?;
+        let output = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::kubectl::kubectl_exec_run_in_pod(
+                kubernetes_config_file_path.as_str(),
+                self.namespace(environment),
+                format!("job/{}", job_name).as_str(),
+                &command.argv(),
+                &self.start_timeout,
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
 
-        context.insert("helm_app_version", &commit_id[..7]);
+        self.post_deploy_check_result(command, &output)
+    }
 
-        match &self.image().registry_url {
-            Some(registry_url) => context.insert("image_name_with_tag", registry_url.as_str()),
-            None => {
-                let image_name_with_tag = self.image().name_with_tag();
-                warn!(
-                    "there is no registry url, use image name with tag with the default container registry: {}",
-                    image_name_with_tag.as_str()
-                );
-                context.insert("image_name_with_tag", image_name_with_tag.as_str());
-            }
+    fn post_deploy_check_result(&self, command: &Command, output: &KubectlOutput) -> Result<(), EngineError> {
+        if output.success() {
+            info!("the post deploy check of {} succeeded", self.name_with_id());
+            return Ok(());
         }
 
-        let environment_variables = self
-            .environment_variables
+        Err(self.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "the post deploy check `{}` of {} exited with code {}: {}",
+                command.argv().join(" "),
+                self.name_with_id(),
+                output.exit_code,
+                output.stderr
+            ),
+        ))
+    }
+
+    /// whether the deployed release runs another number of instances, the completions of a job can't be changed
+    fn is_total_instances_changed(&self, target: &DeploymentTarget) -> Result<bool, EngineError> {
+        let (kubernetes, environment) = match target {
+            DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+            DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+        };
+
+        let kubernetes_config_file_path = kubernetes.config_file_path()?;
+        let deployed_values = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::helm::helm_exec_get_values(
+                kubernetes_config_file_path.as_str(),
+                self.namespace(environment),
+                self.helm_release_name().as_str(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+
+        let deployed_instances = deployed_values
+            .as_ref()
+            .and_then(|values| values.get("instances"))
+            .and_then(|instances| instances.as_u64());
+
+        Ok(match deployed_instances {
+            Some(instances) => instances != self.total_instances as u64,
+            None => false,
+        })
+    }
+
+    /// render the mounted files with the chart context, e.g. `{{ namespace }}` or `{{ environment_id }}`
+    fn mounted_files(&self, context: &TeraContext) -> Result<Vec<MountedFileDataTemplate>, EngineError> {
+        self.mounted_files
             .iter()
-            .map(|ev| EnvironmentVariableDataTemplate {
-                key: ev.key.clone(),
-                value: ev.value.clone(),
+            .enumerate()
+            .map(|(index, mounted_file)| {
+                let content = tera::Tera::one_off(mounted_file.content.as_str(), context, false).map_err(|err| {
+                    self.engine_error(
+                        EngineErrorCause::User("A mounted file is not a valid template"),
+                        format!(
+                            "{} can't render the mounted file {}: {:?}",
+                            self.name_with_id(),
+                            mounted_file.path,
+                            err
+                        ),
+                    )
+                })?;
+
+                Ok(MountedFileDataTemplate {
+                    key: mounted_file.key(index),
+                    path: mounted_file.path.clone(),
+                    content,
+                })
             })
-            .collect::<Vec<_>>();
+            .collect()
+    }
 
-        context.insert("environment_variables", &environment_variables);
+    /// the user labels or annotations, without the ones managed by the engine
+    fn user_pod_metadata(
+        &self,
+        metadata_kind: &str,
+        metadata: &BTreeMap<String, String>,
+        engine_keys: &[&str],
+    ) -> BTreeMap<String, String> {
+        metadata
+            .iter()
+            .filter(|(key, _)| {
+                let is_engine_key = engine_keys.contains(&key.as_str());
+                if is_engine_key {
+                    warn!(
+                        "the pod {} {} of {} is managed by the engine, it is ignored",
+                        metadata_kind,
+                        key,
+                        self.name_with_id()
+                    );
+                }
 
-        Ok(context)
+                !is_engine_key
+            })
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
     }
 
-    fn selector(&self) -> String {
-        format!("app={}", self.sanitized_name())
+    /// environment variables given to the sidecar running the image with the given name, none by default
+    pub fn set_sidecar_environment_variables(
+        &mut self,
+        image_name: &str,
+        environment_variables: Vec<EnvironmentVariable>,
+    ) {
+        self.sidecar_environment_variables
+            .insert(image_name.to_string(), environment_variables);
     }
 
-    fn engine_error_scope(&self) -> EngineErrorScope {
-        EngineErrorScope::ExternalService(self.id().to_string(), self.name().to_string())
+    /// containers run in order before the job container, the job fails if one of them fails
+    pub fn set_init_containers(&mut self, init_containers: Vec<InitContainer>) {
+        self.init_containers = init_containers;
     }
-}
 
-impl Create for ExternalService {
-    fn on_create(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
-        info!("AWS.external_service.on_create() called for {}", self.name());
+    fn sidecars(&self) -> Vec<SidecarDataTemplate> {
+        let names = container_names("sidecar", self.sidecar_images.iter());
 
-        send_progress_on_long_task(
-            self,
-            crate::cloud_provider::service::Action::Create,
-            Box::new(|| deploy_user_stateless_service(target, self)),
-        )
-    }
+        self.sidecar_images
+            .iter()
+            .zip(names)
+            .map(|(image, name)| {
+                let environment_variables = self
+                    .sidecar_environment_variables
+                    .get(image.name.as_str())
+                    .map(|evs| {
+                        evs.iter()
+                            .map(|ev| EnvironmentVariableDataTemplate {
+                                key: ev.key.clone(),
+                                value: ev.value.clone(),
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or(vec![]);
 
-    fn on_create_check(&self) -> Result<(), EngineError> {
-        Ok(())
+                SidecarDataTemplate {
+                    name,
+                    image_name_with_tag: container_image_name_with_tag(image),
+                    environment_variables,
+                }
+            })
+            .collect()
     }
 
-    fn on_create_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
-        warn!("AWS.external_service.on_create_error() called for {}", self.name());
+    fn init_containers(&self) -> Vec<InitContainerDataTemplate> {
+        let names = container_names("init", self.init_containers.iter().map(|container| &container.image));
 
-        send_progress_on_long_task(
-            self,
-            crate::cloud_provider::service::Action::Create,
-            Box::new(|| deploy_stateless_service_error(target, self)),
-        )
+        self.init_containers
+            .iter()
+            .zip(names)
+            .map(|(container, name)| InitContainerDataTemplate {
+                name,
+                image_name_with_tag: container_image_name_with_tag(&container.image),
+                share_environment_variables: container.share_environment_variables,
+            })
+            .collect()
     }
-}
-
-impl Pause for ExternalService {
-    fn on_pause(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
-        info!("AWS.external_service.on_pause() called for {}", self.name());
 
-        send_progress_on_long_task(
-            self,
-            crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, false)),
+    /// same as `new(..)` but with a single instance, which is what most external services need
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_with_single_instance(
+        context: Context,
+        id: &str,
+        action: Action,
+        name: &str,
+        total_cpus: String,
+        total_ram_in_mib: u32,
+        private_port: Option<u16>,
+        start_timeout: Timeout<u32>,
+        image: Image,
+        sidecar_images: Vec<Image>,
+        environment_variables: Vec<EnvironmentVariable>,
+        secret_environment_variables: Vec<EnvironmentVariable>,
+        listeners: Listeners,
+    ) -> Self {
+        ExternalService::new(
+            context,
+            id,
+            action,
+            name,
+            total_cpus,
+            total_ram_in_mib,
+            1,
+            private_port,
+            start_timeout,
+            image,
+            sidecar_images,
+            environment_variables,
+            secret_environment_variables,
+            listeners,
         )
     }
 
-    fn on_pause_check(&self) -> Result<(), EngineError> {
-        Ok(())
+    fn image_name_with_tag(&self, image: &Image) -> String {
+        // an image pinned by its digest is always deployed as is, a tag may have been moved since the build
+        match (image.name_with_digest(), &image.registry_url) {
+            (Some(image_name_with_digest), _) => image_name_with_digest,
+            (None, Some(registry_url)) => registry_url.clone(),
+            (None, None) => {
+                let image_name_with_tag = image.name_with_tag();
+                warn!(
+                    "there is no registry url, use image name with tag with the default container registry: {}",
+                    image_name_with_tag.as_str()
+                );
+                image_name_with_tag
+            }
+        }
     }
 
-    fn on_pause_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
-        warn!("AWS.external_service.on_pause_error() called for {}", self.name());
+    /// an image which has never been pushed would only be noticed once its pods can't pull it, the registry is only
+    /// queried right before deploying so the checks of the service stay offline
+    fn check_image_exists(&self) -> Result<(), EngineError> {
+        if self.image.registry_url.is_none() {
+            return Ok(());
+        }
 
-        send_progress_on_long_task(
-            self,
-            crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, true)),
-        )
+        match self.image.exists(self.image.registry_credentials.as_ref()) {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(self.engine_error(
+                EngineErrorCause::User("The image does not exist in the registry"),
+                format!(
+                    "{} image {} does not exist in the registry",
+                    self.name_with_id(),
+                    container_image_name_with_tag(&self.image)
+                ),
+            )),
+            Err(err) => {
+                warn!(
+                    "unable to check the image of {} exists in the registry: {:?}",
+                    self.name_with_id(),
+                    err
+                );
+                Ok(())
+            }
+        }
     }
-}
 
-impl Delete for ExternalService {
-    fn on_delete(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
-        info!("AWS.external_service.on_delete() called for {}", self.name());
+    /// the image pulled from its first reachable registry candidate when it has no registry url
+    fn image_in_reachable_registry(&self, image: Image) -> Result<Image, EngineError> {
+        if image.registry_url.is_some() || image.registry_candidates.is_empty() {
+            return Ok(image);
+        }
 
-        send_progress_on_long_task(
-            self,
-            crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, false)),
-        )
+        match image.in_first_reachable_registry(image.registry_credentials.as_ref()) {
+            Some(image_in_registry) => {
+                info!(
+                    "pull the image {} from the registry {}",
+                    image.name_with_tag(),
+                    image_in_registry.registry_url.as_deref().unwrap_or_default()
+                );
+                Ok(image_in_registry)
+            }
+            None => Err(self.engine_error(
+                EngineErrorCause::User("None of the registry candidates of the image is reachable"),
+                format!(
+                    "the image {} is not found in any of the registries {}",
+                    image.name_with_tag(),
+                    image.registry_candidates.join(", ")
+                ),
+            )),
+        }
     }
 
-    fn on_delete_check(&self) -> Result<(), EngineError> {
+    /// the image, plain environment variables, resources and instances of the job, the rest is rendered by tera.
+    /// The image is the one of the architecture of the nodes
+    pub fn q_job_values(&self, node_architecture: Architecture) -> Result<QJobValues, EngineError> {
+        let (environment_variables, _) = self.interpolate_environment_variables()?;
+        let image = self.image_in_reachable_registry(self.image().for_architecture(node_architecture))?;
+
+        Ok(QJobValues {
+            image: QJobImageValues {
+                name: self.image_name_with_tag(&image),
+                pull_policy: self
+                    .image_pull_policy
+                    .unwrap_or_else(|| ImagePullPolicy::default_for(&image))
+                    .name()
+                    .to_string(),
+            },
+            env: environment_variables
+                .into_iter()
+                .map(|ev| QJobEnvValue {
+                    name: ev.key,
+                    value: ev.value,
+                })
+                .collect(),
+            resources: QJobResourcesValues {
+                requests: QJobResourceValues {
+                    cpu: self.cpu_request(),
+                    memory: format!("{}Mi", self.ram_request_mib()),
+                },
+                limits: QJobResourceValues {
+                    cpu: self.cpu_limit(),
+                    memory: format!("{}Mi", self.ram_limit_mib()),
+                },
+            },
+            instances: self.total_instances,
+        })
+    }
+
+    /// insert everything specific to an external service into the tera context
+    fn extend_tera_context(&self, context: &mut TeraContext) -> Result<(), EngineError> {
+        let commit_id = self.image().commit_id.as_str();
+
+        context.insert("helm_app_version", &commit_id[..7]);
+
+        if let Some(command) = &self.command {
+            context.insert("command", command);
+        }
+
+        if let Some(args) = &self.args {
+            context.insert("args", args);
+        }
+
+        match &self.image().registry_credentials {
+            Some(_) => {
+                context.insert("is_image_pull_secret", &true);
+                context.insert("image_pull_secret_name", self.image_pull_secret_name().as_str());
+            }
+            None => context.insert("is_image_pull_secret", &false),
+        }
+
+        let (_, secret_environment_variables) = self.interpolate_environment_variables()?;
+
+        // secrets are rendered into a kubernetes secret, they must never be logged
+        let secret_environment_variables = secret_environment_variables
+            .iter()
+            .map(|ev| EnvironmentVariableDataTemplate {
+                key: ev.key.clone(),
+                value: ev.value.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        context.insert("secret_environment_variables", &secret_environment_variables);
+        context.insert("sidecars", &self.sidecars());
+        context.insert("init_containers", &self.init_containers());
+        context.insert("node_selectors", &self.node_selectors);
+        let mut pod_labels = self.user_pod_metadata("label", &self.pod_labels, &ENGINE_POD_LABELS);
+        if let Some(fargate_profile) = &self.fargate_profile {
+            pod_labels.extend(fargate_profile.labels.clone());
+        }
+
+        context.insert("pod_labels", &pod_labels);
+        context.insert(
+            "pod_annotations",
+            &self.user_pod_metadata("annotation", &self.pod_annotations, &ENGINE_POD_ANNOTATIONS),
+        );
+
+        let tolerations = self
+            .tolerations
+            .iter()
+            .map(|toleration| TolerationDataTemplate {
+                key: toleration.key.clone(),
+                operator: toleration.operator.clone(),
+                value: toleration.value.clone(),
+                effect: toleration.effect.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        context.insert("tolerations", &tolerations);
+
+        let topology_spread_constraints = self
+            .topology_spread_constraints
+            .iter()
+            .map(|constraint| TopologySpreadDataTemplate {
+                max_skew: constraint.max_skew,
+                topology_key: constraint.topology_key.clone(),
+                when_unsatisfiable: constraint.when_unsatisfiable.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        context.insert("topology_spread_constraints", &topology_spread_constraints);
+        context.insert("security_context", &self.security_context.to_data_template());
+        context.insert(
+            "active_deadline_seconds",
+            &self.active_deadline_seconds.unwrap_or(JOB_ACTIVE_DEADLINE_SECONDS),
+        );
+        context.insert("backoff_limit", &self.backoff_limit.unwrap_or(JOB_BACKOFF_LIMIT));
+        context.insert(
+            "termination_grace_period_seconds",
+            &self
+                .termination_grace_period_seconds
+                .unwrap_or(DEFAULT_TERMINATION_GRACE_PERIOD_SECONDS),
+        );
+        context.insert("volume_claims", &self.volume_claims());
+
+        // rendered last, so the files can use everything else in the context
+        let mounted_files = self.mounted_files(context)?;
+        context.insert("mounted_files", &mounted_files);
+
         Ok(())
     }
 
-    fn on_delete_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
-        warn!("AWS.external_service.on_delete_error() called for {}", self.name());
+    /// the plain and the secret environment variables with their references resolved, each sorted by key so the
+    /// rendered manifests do not depend on the order the variables are given in
+    fn interpolate_environment_variables(
+        &self,
+    ) -> Result<(Vec<EnvironmentVariable>, Vec<EnvironmentVariable>), EngineError> {
+        let strict = self.strict_environment_variables_interpolation;
 
-        send_progress_on_long_task(
-            self,
-            crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, true)),
-        )
+        // plain variables can only reference plain variables, a secret must not end up in a plain variable
+        let environment_variables = interpolate_environment_variables(&self.environment_variables, strict);
+        let all_environment_variables = self
+            .environment_variables
+            .iter()
+            .chain(self.secret_environment_variables.iter())
+            .cloned()
+            .collect::<Vec<_>>();
+        let all_environment_variables = interpolate_environment_variables(&all_environment_variables, strict);
+
+        match (environment_variables, all_environment_variables) {
+            (Ok(mut environment_variables), Ok(mut all_environment_variables)) => {
+                let mut secret_environment_variables = all_environment_variables.split_off(environment_variables.len());
+                environment_variables.sort_by(|a, b| a.key.cmp(&b.key));
+                secret_environment_variables.sort_by(|a, b| a.key.cmp(&b.key));
+                Ok((environment_variables, secret_environment_variables))
+            }
+            (environment_variables, all_environment_variables) => {
+                // a failing plain variable is reported by both, each failure is listed once
+                let errors = [environment_variables.err(), all_environment_variables.err()];
+                let mut failures: Vec<&str> = vec![];
+                for failure in errors.iter().flatten().flat_map(|err| err.lines()) {
+                    if !failures.contains(&failure) {
+                        failures.push(failure);
+                    }
+                }
+
+                Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "The environment variables reference each other in a way that can't be resolved. \
+                        Please fix the circular or unknown references.",
+                    ),
+                    failures.join("\n"),
+                ))
+            }
+        }
     }
 }
 
-impl Listen for ExternalService {
-    fn listeners(&self) -> &Listeners {
-        &self.listeners
-    }
+impl crate::cloud_provider::service::ExternalService for ExternalService {}
 
-    fn add_listener(&mut self, listener: Listener) {
-        self.listeners.push(listener);
+impl crate::cloud_provider::service::Application for ExternalService {
+    fn image(&self) -> &Image {
+        &self.image
+    }
+
+    fn set_image(&mut self, image: Image) {
+        self.image = image;
+    }
+}
+
+impl Helm for ExternalService {
+    fn helm_release_name(&self) -> String {
+        crate::string::helm_release_name_safe(format!("external-service-{}", self.name()).as_str(), self.id())
+    }
+
+    fn legacy_helm_release_name(&self) -> Option<String> {
+        Some(crate::string::legacy_helm_release_name(
+            format!("external-service-{}", self.name()).as_str(),
+            self.id(),
+        ))
+    }
+
+    fn helm_chart_dir(&self) -> String {
+        format!("{}/common/services/q-job", self.context.lib_root_dir())
+    }
+
+    fn helm_chart_source(&self) -> ChartSource {
+        match &self.chart_source {
+            Some(chart_source) => chart_source.clone(),
+            None => ChartSource::Local(self.helm_chart_dir()),
+        }
+    }
+
+    fn helm_chart_values_dir(&self) -> String {
+        String::new()
+    }
+
+    fn helm_chart_external_name_service_dir(&self) -> String {
+        String::new()
+    }
+
+    fn helm_chart_version(&self) -> Option<String> {
+        self.chart_version.clone()
+    }
+
+    fn helm_chart_values_files(&self) -> Vec<String> {
+        self.values_files.clone()
+    }
+
+    fn helm_set_values(&self) -> Vec<(String, String)> {
+        self.set_values.clone()
+    }
+
+    fn helm_chart_engine_values(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        let kubernetes = match target {
+            DeploymentTarget::ManagedServices(k, _) => *k,
+            DeploymentTarget::SelfHosted(k, _) => *k,
+        };
+
+        let values = self.q_job_values(kubernetes.node_architecture())?;
+
+        match serde_yaml::to_string(&values) {
+            Ok(values) => Ok(Some(values)),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "unable to serialize the chart values of {}: {}",
+                    self.name_with_id(),
+                    err
+                ),
+            )),
+        }
+    }
+
+    fn registry_credentials(&self) -> Option<RegistryCredentials> {
+        self.image.registry_credentials.clone()
+    }
+
+    /// a failed job deployment must not leave a partially upgraded release behind
+    fn helm_atomic_upgrade(&self) -> bool {
+        true
+    }
+
+    fn helm_wait_for_jobs(&self) -> bool {
+        self.helm_wait_for_jobs
+    }
+
+    fn helm_release_labels(&self) -> BTreeMap<String, String> {
+        self.release_labels.clone()
+    }
+}
+
+impl StatelessService for ExternalService {}
+
+impl Service for ExternalService {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn service_type(&self) -> ServiceType {
+        ServiceType::ExternalService
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn sanitized_name(&self) -> String {
+        format!("ext-service-{}", self.name())
+    }
+
+    fn version(&self) -> &str {
+        self.image.commit_id.as_str()
+    }
+
+    fn action(&self) -> &Action {
+        &self.action
+    }
+
+    fn private_port(&self) -> Option<u16> {
+        self.private_port
+    }
+
+    fn start_timeout(&self) -> Timeout<u32> {
+        self.start_timeout.clone()
+    }
+
+    fn total_cpus(&self) -> String {
+        self.total_cpus.to_string()
+    }
+
+    fn cpu_burst(&self) -> String {
+        // the job is limited to the cpus it requests
+        self.total_cpus()
+    }
+
+    fn total_ram_in_mib(&self) -> u32 {
+        self.total_ram_in_mib
+    }
+
+    fn cpu_request(&self) -> String {
+        self.cpu_request.clone()
+    }
+
+    fn cpu_limit(&self) -> String {
+        self.cpu_limit.clone()
+    }
+
+    fn ram_request_mib(&self) -> u32 {
+        self.ram_request_mib
+    }
+
+    fn ram_limit_mib(&self) -> u32 {
+        self.ram_limit_mib
+    }
+
+    fn total_instances(&self) -> u16 {
+        self.total_instances
+    }
+
+    fn termination_grace_period_seconds(&self) -> Option<u32> {
+        self.termination_grace_period_seconds
+    }
+
+    fn depends_on(&self) -> Vec<String> {
+        self.depends_on.clone()
+    }
+
+    fn namespace_override(&self) -> Option<&str> {
+        match &self.fargate_profile {
+            Some(fargate_profile) => Some(fargate_profile.namespace.as_str()),
+            None => self.namespace_override.as_deref(),
+        }
+    }
+
+    fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
+        let (kubernetes, environment) = match target {
+            DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+            DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+        };
+
+        let mut context = default_tera_context(self, kubernetes, environment);
+        self.extend_tera_context(&mut context)?;
+
+        Ok(context)
+    }
+
+    fn selector(&self) -> String {
+        format!("app={}", self.sanitized_name())
+    }
+
+    fn engine_error_scope(&self) -> EngineErrorScope {
+        EngineErrorScope::ExternalService(self.id().to_string(), self.name().to_string())
+    }
+}
+
+impl Create for ExternalService {
+    fn on_create(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.external_service.on_create() called for {}", self.name());
+
+        metrics::observe(self.service_type().name(), "create", || {
+            send_progress_on_long_task(
+                self,
+                crate::cloud_provider::service::Action::Create,
+                Box::new(|| {
+                    if is_stateless_service_up_to_date(target, self)? {
+                        info!("no changes for {}, the helm upgrade is skipped", self.name_with_id());
+                        return Ok(());
+                    }
+
+                    if !self.context().is_dry_run_deploy() {
+                        self.check_image_exists()?;
+                    }
+
+                    // the pods and the completions of a job can't be changed, it is deleted so helm creates it again
+                    if !self.context().is_dry_run_deploy()
+                        && (self.context().is_force_redeploy() || self.is_total_instances_changed(target)?)
+                    {
+                        restart_stateless_service(target, self, "job")?;
+                    }
+
+                    deploy_and_check(
+                        || deploy_user_stateless_service(target, self),
+                        || self.run_post_deploy_check(target),
+                    )
+                }),
+            )
+        })
+    }
+
+    fn on_create_check(&self) -> Result<(), EngineError> {
+        if self.total_instances < 1 {
+            return Err(self.engine_error(
+                EngineErrorCause::User("An external service must run with at least 1 instance"),
+                format!(
+                    "{} has an invalid number of instances: {}",
+                    self.name_with_id(),
+                    self.total_instances
+                ),
+            ));
+        }
+
+        if let Some(private_port) = self.private_port {
+            // a u16 can't be greater than 65535, so only 0 is out of range
+            if private_port < 1 {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("The private port must be between 1 and 65535"),
+                    format!("{} has an invalid private port: {}", self.name_with_id(), private_port),
+                ));
+            }
+        }
+
+        if let (Some(namespace), Some(fargate_profile)) = (&self.namespace_override, &self.fargate_profile) {
+            if namespace != &fargate_profile.namespace {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("The namespace must be the one of the Fargate profile"),
+                    format!(
+                        "{} has the namespace {} while its Fargate profile {} selects the namespace {}",
+                        self.name_with_id(),
+                        namespace,
+                        fargate_profile.name,
+                        fargate_profile.namespace
+                    ),
+                ));
+            }
+        }
+
+        if let Some(namespace) = self.namespace_override() {
+            if !is_valid_namespace_name(namespace) {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "The namespace must be at most 63 lowercase alphanumeric characters or '-', \
+                        starting and ending with an alphanumeric character",
+                    ),
+                    format!("{} has an invalid namespace: {}", self.name_with_id(), namespace),
+                ));
+            }
+        }
+
+        if cpu_string_to_float(self.cpu_request.as_str()) > cpu_string_to_float(self.cpu_limit.as_str()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The cpu request must be lower or equal to the cpu limit"),
+                format!(
+                    "{} has a cpu request {} greater than its cpu limit {}",
+                    self.name_with_id(),
+                    self.cpu_request,
+                    self.cpu_limit
+                ),
+            ));
+        }
+
+        if self.ram_request_mib > self.ram_limit_mib {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The ram request must be lower or equal to the ram limit"),
+                format!(
+                    "{} has a ram request {}Mib greater than its ram limit {}Mib",
+                    self.name_with_id(),
+                    self.ram_request_mib,
+                    self.ram_limit_mib
+                ),
+            ));
+        }
+
+        // the environment variables of the job and of each sidecar are injected in their own container
+        let containers_environment_variables = std::iter::once(
+            self.environment_variables
+                .iter()
+                .chain(self.secret_environment_variables.iter())
+                .cloned()
+                .collect::<Vec<_>>(),
+        )
+        .chain(self.sidecar_environment_variables.values().cloned());
+
+        for environment_variables in containers_environment_variables {
+            if let Err(err) =
+                EnvironmentVariable::validate_all(&environment_variables, self.environment_variables_max_size_in_bytes)
+            {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "Some environment variables are not valid, their names must match [A-Za-z_][A-Za-z0-9_]* \
+                        and their total size must not exceed the limit",
+                    ),
+                    format!("{} has invalid environment variables: {}", self.name_with_id(), err),
+                ));
+            }
+        }
+
+        for toleration in &self.tolerations {
+            if let Err(err) = toleration.validate() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "Some tolerations are not valid, their effect must be NoSchedule, PreferNoSchedule or \
+                        NoExecute and their operator Equal or Exists",
+                    ),
+                    format!("{} has an invalid toleration: {}", self.name_with_id(), err),
+                ));
+            }
+        }
+
+        for topology_spread_constraint in &self.topology_spread_constraints {
+            if let Err(err) = topology_spread_constraint.validate() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "Some topology spread constraints are not valid, their max skew must be at least 1 \
+                        and their when unsatisfiable DoNotSchedule or ScheduleAnyway",
+                    ),
+                    format!(
+                        "{} has an invalid topology spread constraint: {}",
+                        self.name_with_id(),
+                        err
+                    ),
+                ));
+            }
+        }
+
+        if let Err(err) = self.security_context.validate() {
+            return Err(self.engine_error(
+                EngineErrorCause::User(
+                    "The security context is not valid, a non root container can't run as the user 0 \
+                    and the user and group ids must not exceed 2147483647",
+                ),
+                format!("{} has an invalid security context: {}", self.name_with_id(), err),
+            ));
+        }
+
+        if self.active_deadline_seconds == Some(0) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The active deadline seconds of a job must be positive"),
+                format!("{} has an active deadline of 0 seconds", self.name_with_id()),
+            ));
+        }
+
+        if let Err(err) = MountedFile::validate_all(&self.mounted_files, MOUNTED_FILES_MAX_SIZE_IN_BYTES) {
+            return Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Some mounted files are not valid, their paths must be absolute and distinct \
+                    and their total size must not exceed 1MiB",
+                ),
+                format!("{} has invalid mounted files: {}", self.name_with_id(), err),
+            ));
+        }
+
+        for volume_claim in &self.volume_claims {
+            if let Err(err) = volume_claim.validate() {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "Some volume claims are not valid, their size must be at least 1GiB \
+                        and their mount path must be absolute",
+                    ),
+                    format!("{} has an invalid volume claim: {}", self.name_with_id(), err),
+                ));
+            }
+        }
+
+        let missing_values_files = self
+            .values_files
+            .iter()
+            .filter(|values_file| !std::path::Path::new(values_file.as_str()).is_file())
+            .map(|values_file| values_file.as_str())
+            .collect::<Vec<_>>();
+
+        if !missing_values_files.is_empty() {
+            return Err(self.engine_error(
+                EngineErrorCause::User("Some helm values files do not exist"),
+                format!(
+                    "{} has missing values files: {}",
+                    self.name_with_id(),
+                    missing_values_files.join(", ")
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn on_create_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.external_service.on_create_error() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Create,
+            Box::new(|| deploy_stateless_service_error(target, self)),
+        )
+    }
+}
+
+impl Pause for ExternalService {
+    fn on_pause(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.external_service.on_pause() called for {}", self.name());
+
+        metrics::observe(self.service_type().name(), "pause", || {
+            send_progress_on_long_task(
+                self,
+                crate::cloud_provider::service::Action::Pause,
+                Box::new(|| match self.pause_strategy() {
+                    PauseStrategy::Delete => delete_stateless_service(target, self, false, true),
+                    PauseStrategy::ScaleToZero => scale_to_zero_stateless_service(target, self, "job"),
+                }),
+            )
+        })
+    }
+
+    fn on_pause_check(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_pause_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.external_service.on_pause_error() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Pause,
+            Box::new(|| delete_stateless_service(target, self, true, false)),
+        )
+    }
+
+    fn pause_strategy(&self) -> PauseStrategy {
+        self.pause_strategy
+    }
+}
+
+impl Delete for ExternalService {
+    fn on_delete(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.external_service.on_delete() called for {}", self.name());
+
+        metrics::observe(self.service_type().name(), "delete", || {
+            send_progress_on_long_task(
+                self,
+                crate::cloud_provider::service::Action::Delete,
+                Box::new(|| delete_stateless_service(target, self, false, true)),
+            )
+        })
+    }
+
+    fn on_delete_check(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.external_service.on_delete_error() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Delete,
+            Box::new(|| delete_stateless_service(target, self, true, false)),
+        )
+    }
+}
+
+impl Restart for ExternalService {
+    fn on_restart(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.external_service.on_restart() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Restart,
+            Box::new(|| {
+                // the job is deleted then installed again by helm, which creates its missing resources
+                restart_stateless_service(target, self, "job")?;
+
+                deploy_and_check(
+                    || deploy_user_stateless_service(target, self),
+                    || self.run_post_deploy_check(target),
+                )
+            }),
+        )
+    }
+
+    fn on_restart_check(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_restart_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.external_service.on_restart_error() called for {}", self.name());
+
+        Ok(())
+    }
+}
+
+impl Listen for ExternalService {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
+
+/// An external service run on a schedule: it is deployed as a CronJob instead of a Job.
+pub struct CronExternalService {
+    external_service: ExternalService,
+    schedule: String,
+}
+
+impl CronExternalService {
+    pub fn new(external_service: ExternalService, schedule: &str) -> Self {
+        CronExternalService {
+            external_service,
+            schedule: schedule.to_string(),
+        }
+    }
+
+    pub fn schedule(&self) -> &str {
+        self.schedule.as_str()
+    }
+
+    /// a cron job has no pod until it is scheduled, the post deploy check runs in a job created from it right away.
+    /// The job is deleted once checked, it is not part of the release
+    fn run_post_deploy_check(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        if self.external_service.post_deploy_check.is_none() {
+            return Ok(());
+        }
+
+        let (kubernetes, environment) = match target {
+            DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+            DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+        };
+
+        let kubernetes_config_file_path = kubernetes.config_file_path()?;
+        let job_name = format!("{}-check", self.sanitized_name());
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::kubectl::kubectl_exec_create_job_from_cronjob(
+                kubernetes_config_file_path.as_str(),
+                self.namespace(environment),
+                self.sanitized_name().as_str(),
+                job_name.as_str(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+
+        let is_job_ready = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::kubectl::kubectl_exec_is_job_ready_with_policy(
+                kubernetes_config_file_path.as_str(),
+                self.namespace(environment),
+                job_name.as_str(),
+                crate::cmd::kubectl::RetryPolicy::from_timeout(self.start_timeout())
+                    .with_cancellation_token(self.context().cancellation_token().clone()),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        );
+
+        let result = match is_job_ready {
+            Ok(Some(true)) => self
+                .external_service
+                .run_post_deploy_check_in_job(target, job_name.as_str()),
+            Ok(_) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "the post deploy check job {} of {} is not ready",
+                    job_name,
+                    self.name_with_id()
+                ),
+            )),
+            Err(err) => Err(err),
+        };
+
+        // a job is deleted when it is restarted
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::kubectl::kubectl_exec_rollout_restart(
+                kubernetes_config_file_path.as_str(),
+                self.namespace(environment),
+                "job",
+                job_name.as_str(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+
+        result
+    }
+}
+
+impl Helm for CronExternalService {
+    fn helm_release_name(&self) -> String {
+        crate::string::helm_release_name_safe(format!("cron-external-service-{}", self.name()).as_str(), self.id())
+    }
+
+    fn legacy_helm_release_name(&self) -> Option<String> {
+        Some(crate::string::legacy_helm_release_name(
+            format!("cron-external-service-{}", self.name()).as_str(),
+            self.id(),
+        ))
+    }
+
+    fn helm_chart_dir(&self) -> String {
+        format!("{}/common/services/q-cronjob", self.context().lib_root_dir())
+    }
+
+    fn helm_chart_values_dir(&self) -> String {
+        String::new()
+    }
+
+    fn helm_chart_external_name_service_dir(&self) -> String {
+        String::new()
+    }
+
+    fn helm_chart_version(&self) -> Option<String> {
+        self.external_service.helm_chart_version()
+    }
+
+    fn helm_chart_values_files(&self) -> Vec<String> {
+        self.external_service.helm_chart_values_files()
+    }
+
+    fn helm_set_values(&self) -> Vec<(String, String)> {
+        self.external_service.helm_set_values()
+    }
+
+    fn helm_chart_engine_values(&self, target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        self.external_service.helm_chart_engine_values(target)
+    }
+
+    fn registry_credentials(&self) -> Option<RegistryCredentials> {
+        self.external_service.registry_credentials()
+    }
+
+    fn image_pull_secret_name(&self) -> String {
+        self.external_service.image_pull_secret_name()
+    }
+
+    fn helm_atomic_upgrade(&self) -> bool {
+        self.external_service.helm_atomic_upgrade()
+    }
+
+    fn helm_release_labels(&self) -> BTreeMap<String, String> {
+        self.external_service.helm_release_labels()
+    }
+}
+
+impl StatelessService for CronExternalService {}
+
+impl crate::cloud_provider::service::ExternalService for CronExternalService {}
+
+impl Service for CronExternalService {
+    fn context(&self) -> &Context {
+        self.external_service.context()
+    }
+
+    fn service_type(&self) -> ServiceType {
+        ServiceType::CronJob
+    }
+
+    fn id(&self) -> &str {
+        self.external_service.id()
+    }
+
+    fn name(&self) -> &str {
+        self.external_service.name()
+    }
+
+    fn sanitized_name(&self) -> String {
+        format!("cron-service-{}", self.name())
+    }
+
+    fn version(&self) -> &str {
+        self.external_service.version()
+    }
+
+    fn action(&self) -> &Action {
+        self.external_service.action()
+    }
+
+    fn private_port(&self) -> Option<u16> {
+        self.external_service.private_port()
+    }
+
+    fn start_timeout(&self) -> Timeout<u32> {
+        self.external_service.start_timeout()
+    }
+
+    fn total_cpus(&self) -> String {
+        self.external_service.total_cpus()
+    }
+
+    fn cpu_burst(&self) -> String {
+        self.external_service.cpu_burst()
+    }
+
+    fn total_ram_in_mib(&self) -> u32 {
+        self.external_service.total_ram_in_mib()
+    }
+
+    fn cpu_request(&self) -> String {
+        self.external_service.cpu_request()
+    }
+
+    fn cpu_limit(&self) -> String {
+        self.external_service.cpu_limit()
+    }
+
+    fn ram_request_mib(&self) -> u32 {
+        self.external_service.ram_request_mib()
+    }
+
+    fn ram_limit_mib(&self) -> u32 {
+        self.external_service.ram_limit_mib()
+    }
+
+    fn total_instances(&self) -> u16 {
+        self.external_service.total_instances()
+    }
+
+    fn termination_grace_period_seconds(&self) -> Option<u32> {
+        self.external_service.termination_grace_period_seconds()
+    }
+
+    fn depends_on(&self) -> Vec<String> {
+        self.external_service.depends_on()
+    }
+
+    fn namespace_override(&self) -> Option<&str> {
+        self.external_service.namespace_override()
+    }
+
+    fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError> {
+        let (kubernetes, environment) = match target {
+            DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+            DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+        };
+
+        let mut context = default_tera_context(self, kubernetes, environment);
+        self.external_service.extend_tera_context(&mut context)?;
+        context.insert("schedule", self.schedule.as_str());
+
+        Ok(context)
+    }
+
+    fn selector(&self) -> String {
+        format!("app={}", self.sanitized_name())
+    }
+
+    fn engine_error_scope(&self) -> EngineErrorScope {
+        self.external_service.engine_error_scope()
+    }
+}
+
+impl Create for CronExternalService {
+    fn on_create(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.cron_external_service.on_create() called for {}", self.name());
+
+        metrics::observe(self.service_type().name(), "create", || {
+            send_progress_on_long_task(
+                self,
+                crate::cloud_provider::service::Action::Create,
+                Box::new(|| {
+                    if is_stateless_service_up_to_date(target, self)? {
+                        info!("no changes for {}, the helm upgrade is skipped", self.name_with_id());
+                        return Ok(());
+                    }
+
+                    if !self.context().is_dry_run_deploy() {
+                        self.external_service.check_image_exists()?;
+                    }
+
+                    // unlike a job, the job template of a cron job can be changed: only its next runs use it
+                    deploy_and_check(
+                        || deploy_user_stateless_service(target, self),
+                        || self.run_post_deploy_check(target),
+                    )
+                }),
+            )
+        })
+    }
+
+    fn on_create_check(&self) -> Result<(), EngineError> {
+        if !is_valid_cron_schedule(self.schedule.as_str()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The schedule must be a valid cron expression, e.g. \"*/5 * * * *\""),
+                format!("{} has an invalid schedule: {}", self.name_with_id(), self.schedule),
+            ));
+        }
+
+        self.external_service.on_create_check()
+    }
+
+    fn on_create_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.cron_external_service.on_create_error() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Create,
+            Box::new(|| deploy_stateless_service_error(target, self)),
+        )
+    }
+}
+
+impl Pause for CronExternalService {
+    fn on_pause(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.cron_external_service.on_pause() called for {}", self.name());
+
+        metrics::observe(self.service_type().name(), "pause", || {
+            send_progress_on_long_task(
+                self,
+                crate::cloud_provider::service::Action::Pause,
+                Box::new(|| match self.pause_strategy() {
+                    PauseStrategy::Delete => delete_stateless_service(target, self, false, true),
+                    PauseStrategy::ScaleToZero => scale_to_zero_stateless_service(target, self, "cronjob"),
+                }),
+            )
+        })
+    }
+
+    fn on_pause_check(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_pause_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.cron_external_service.on_pause_error() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Pause,
+            Box::new(|| delete_stateless_service(target, self, true, false)),
+        )
+    }
+
+    fn pause_strategy(&self) -> PauseStrategy {
+        self.external_service.pause_strategy()
+    }
+}
+
+impl Delete for CronExternalService {
+    fn on_delete(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.cron_external_service.on_delete() called for {}", self.name());
+
+        metrics::observe(self.service_type().name(), "delete", || {
+            send_progress_on_long_task(
+                self,
+                crate::cloud_provider::service::Action::Delete,
+                Box::new(|| delete_stateless_service(target, self, false, true)),
+            )
+        })
+    }
+
+    fn on_delete_check(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete_error(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!("AWS.cron_external_service.on_delete_error() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Delete,
+            Box::new(|| delete_stateless_service(target, self, true, false)),
+        )
+    }
+}
+
+impl Restart for CronExternalService {
+    fn on_restart(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        info!("AWS.cron_external_service.on_restart() called for {}", self.name());
+
+        send_progress_on_long_task(
+            self,
+            crate::cloud_provider::service::Action::Restart,
+            Box::new(|| {
+                // each run of a cron job starts new pods, upgrading the release is enough for the next runs
+                deploy_and_check(
+                    || deploy_user_stateless_service(target, self),
+                    || self.run_post_deploy_check(target),
+                )
+            }),
+        )
+    }
+
+    fn on_restart_check(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_restart_error(&self, _target: &DeploymentTarget) -> Result<(), EngineError> {
+        warn!(
+            "AWS.cron_external_service.on_restart_error() called for {}",
+            self.name()
+        );
+
+        Ok(())
+    }
+}
+
+impl Listen for CronExternalService {
+    fn listeners(&self) -> &Listeners {
+        self.external_service.listeners()
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.external_service.add_listener(listener);
+    }
+}
+
+/// the post deploy check only runs once the job is ready, which `deploy` waits for
+fn deploy_and_check<D, C>(deploy: D, check: C) -> Result<(), EngineError>
+where
+    D: FnOnce() -> Result<(), EngineError>,
+    C: FnOnce() -> Result<(), EngineError>,
+{
+    deploy()?;
+    check()
+}
+
+/// extra containers are named after their image (without the registry path), suffixed by their position when two
+/// images share the same name
+fn container_names<'a, I>(prefix: &str, images: I) -> Vec<String>
+where
+    I: Iterator<Item = &'a Image>,
+{
+    let mut names: Vec<String> = vec![];
+
+    for (idx, image) in images.enumerate() {
+        let short_name = image.name.rsplit('/').next().unwrap_or(image.name.as_str());
+        let mut name = sanitize_name(prefix, short_name);
+        if names.contains(&name) {
+            name = format!("{}-{}", name, idx);
+        }
+        names.push(name);
+    }
+
+    names
+}
+
+fn container_image_name_with_tag(image: &Image) -> String {
+    match &image.registry_url {
+        Some(registry_url) => registry_url.clone(),
+        None => image.name_with_tag(),
+    }
+}
+
+/// a namespace name is a RFC 1123 label
+fn is_valid_namespace_name(name: &str) -> bool {
+    let is_alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+
+    !name.is_empty()
+        && name.len() <= 63
+        && name.chars().all(|c| is_alphanumeric(c) || c == '-')
+        && name.starts_with(is_alphanumeric)
+        && name.ends_with(is_alphanumeric)
+}
+
+/// syntactic check of a kubernetes cron schedule: 5 fields (minute hour day-of-month month day-of-week)
+/// or one of the @hourly, @daily... macros
+fn is_valid_cron_schedule(schedule: &str) -> bool {
+    const MONTHS: [&str; 12] = [
+        "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+    ];
+    const DAYS: [&str; 7] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+    let schedule = schedule.trim();
+    if schedule.starts_with('@') {
+        return match schedule {
+            "@yearly" | "@annually" | "@monthly" | "@weekly" | "@daily" | "@midnight" | "@hourly" => true,
+            _ => false,
+        };
+    }
+
+    let fields = schedule.split_whitespace().collect::<Vec<_>>();
+    if fields.len() != 5 {
+        return false;
+    }
+
+    is_valid_cron_field(fields[0], 0, 59, &[], 0)
+        && is_valid_cron_field(fields[1], 0, 23, &[], 0)
+        && is_valid_cron_field(fields[2], 1, 31, &[], 0)
+        && is_valid_cron_field(fields[3], 1, 12, &MONTHS, 1)
+        && is_valid_cron_field(fields[4], 0, 7, &DAYS, 0)
+}
+
+fn is_valid_cron_field(field: &str, min: u32, max: u32, names: &[&str], first_name_value: u32) -> bool {
+    field.split(',').all(|part| {
+        let (range, step) = match part.find('/') {
+            Some(index) => (&part[..index], Some(&part[index + 1..])),
+            None => (part, None),
+        };
+
+        if let Some(step) = step {
+            match step.parse::<u32>() {
+                Ok(step) if step > 0 => {}
+                _ => return false,
+            }
+        }
+
+        if range == "*" {
+            return true;
+        }
+
+        let bounds = range
+            .split('-')
+            .map(|bound| match bound.parse::<u32>() {
+                Ok(value) => Some(value),
+                Err(_) => names
+                    .iter()
+                    .position(|name| name.eq_ignore_ascii_case(bound))
+                    .map(|index| index as u32 + first_name_value),
+            })
+            .collect::<Option<Vec<_>>>();
+
+        match bounds {
+            Some(bounds) if bounds.len() == 1 => bounds[0] >= min && bounds[0] <= max,
+            Some(bounds) if bounds.len() == 2 => bounds[0] >= min && bounds[1] <= max && bounds[0] <= bounds[1],
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::build_platform::{Architecture, Image, ImageArchitectureVariant, RegistryCredentials};
+    use crate::cloud_provider::aws::external_service::{
+        deploy_and_check, is_valid_cron_schedule, is_valid_namespace_name, CronExternalService, ExternalService,
+    };
+    use crate::cloud_provider::aws::kubernetes::fargate::FargateProfile;
+    use crate::cloud_provider::aws::kubernetes::Options;
+    use crate::cloud_provider::environment::{Environment, Kind};
+    use crate::cloud_provider::models::{
+        Command, EnvironmentVariable, ImagePullPolicy, InitContainer, MountedFile, SecurityContext, Toleration,
+        TopologySpread, VolumeClaim, ENVIRONMENT_VARIABLES_MAX_SIZE_IN_BYTES, MOUNTED_FILES_MAX_SIZE_IN_BYTES,
+    };
+    use crate::cloud_provider::service::{
+        helm_release_labels, helm_upgrade_error, is_stateless_service_up_to_date, job_failure_error,
+        lint_stateless_service, pods_deletion_timeout, send_progress_on_long_task, Action, ChartSource, Create,
+        Endpoint, Helm, Pause, PauseStrategy, ReleaseLock, Service,
+    };
+    use crate::cloud_provider::DeploymentTarget;
+    use crate::cmd::helm::{helm_exec_upgrade_args, HelmRepository, Timeout};
+    use crate::cmd::kubectl::{kubectl_exec_get_job_pod_logs_args, kubectl_exec_scale_args, KubectlOutput};
+    use crate::cmd::structs::KubernetesJobStatus;
+    use crate::error::{EngineError, EngineErrorCause};
+    use crate::models::Context;
+    use crate::template::generate_j2_template_files;
+    use serde_json::json;
+    use std::collections::BTreeMap;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use tera::Context as TeraContext;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id};
+    use tracing::{Event, Subscriber};
+    use tracing_subscriber::layer::{Context as LayerContext, Layer, SubscriberExt};
+    use tracing_subscriber::Registry;
+
+    /// records the fields of the created spans, and the span each event is emitted in
+    #[derive(Clone, Default)]
+    struct SpanRecorder {
+        span_fields: Arc<Mutex<Vec<(String, String)>>>,
+        event_spans: Arc<Mutex<Vec<String>>>,
+    }
+
+    struct FieldVisitor<'a>(&'a mut Vec<(String, String)>);
+
+    impl<'a> Visit for FieldVisitor<'a> {
+        fn record_str(&mut self, field: &Field, value: &str) {
+            self.0.push((field.name().to_string(), value.to_string()));
+        }
+
+        fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+            self.0.push((field.name().to_string(), format!("{:?}", value)));
+        }
+    }
+
+    impl<S: Subscriber> Layer<S> for SpanRecorder {
+        fn new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: LayerContext<'_, S>) {
+            let mut span_fields = self.span_fields.lock().unwrap();
+            attrs.record(&mut FieldVisitor(&mut span_fields));
+        }
+
+        fn on_event(&self, _event: &Event<'_>, ctx: LayerContext<'_, S>) {
+            if let Some(metadata) = ctx.current_span().metadata() {
+                self.event_spans.lock().unwrap().push(metadata.name().to_string());
+            }
+        }
+    }
+
+    fn context() -> Context {
+        Context::new(
+            "execution_id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        )
+    }
+
+    /// the external service of the tests and the cluster it is deployed on, a test only sets what it checks
+    struct Fixture {
+        context: Context,
+        total_instances: u16,
+        private_port: Option<u16>,
+        start_timeout: Timeout<u32>,
+        cluster_options: Options,
+    }
+
+    impl Fixture {
+        fn new() -> Self {
+            Fixture {
+                context: context(),
+                total_instances: 1,
+                private_port: None,
+                start_timeout: Timeout::Default,
+                cluster_options: Options::default(),
+            }
+        }
+
+        fn context(mut self, context: Context) -> Self {
+            self.context = context;
+            self
+        }
+
+        fn total_instances(mut self, total_instances: u16) -> Self {
+            self.total_instances = total_instances;
+            self
+        }
+
+        fn private_port(mut self, private_port: u16) -> Self {
+            self.private_port = Some(private_port);
+            self
+        }
+
+        fn start_timeout(mut self, start_timeout: Timeout<u32>) -> Self {
+            self.start_timeout = start_timeout;
+            self
+        }
+
+        fn cluster_options(mut self, cluster_options: Options) -> Self {
+            self.cluster_options = cluster_options;
+            self
+        }
+
+        fn service(&self) -> ExternalService {
+            let image = Image {
+                application_id: "id".to_string(),
+                name: "my-job".to_string(),
+                tag: "tag".to_string(),
+                commit_id: "0123456789abcdef".to_string(),
+                registry_name: None,
+                registry_secret: None,
+                registry_url: None,
+                registry_credentials: None,
+                digest: None,
+                architecture_variants: vec![],
+                registry_candidates: vec![],
+            };
+
+            ExternalService::new(
+                self.context.clone(),
+                "id",
+                Action::Create,
+                "my-job",
+                "1".to_string(),
+                256,
+                self.total_instances,
+                self.private_port,
+                self.start_timeout.clone(),
+                image,
+                vec![],
+                vec![],
+                vec![],
+                vec![],
+            )
+        }
+
+        /// give a deployment target on a cluster which does not exist, only for the steps not reaching the cluster
+        fn with_deployment_target<F>(&self, f: F)
+        where
+            F: FnOnce(&DeploymentTarget),
+        {
+            use crate::cloud_provider::aws::kubernetes::EKS;
+            use crate::cloud_provider::aws::AWS;
+            use crate::cloud_provider::TerraformStateCredentials;
+            use crate::dns_provider::route53::Route53;
+
+            let cloud_provider = AWS::new(
+                self.context.clone(),
+                "cloud-provider-id",
+                "organization-id",
+                "cloud-provider",
+                "access-key-id",
+                "secret-access-key",
+                TerraformStateCredentials {
+                    access_key_id: "access-key-id".to_string(),
+                    secret_access_key: "secret-access-key".to_string(),
+                    region: "eu-west-3".to_string(),
+                },
+            );
+            let dns_provider = Route53::new(
+                self.context.clone(),
+                "dns-provider-id",
+                "dns-provider",
+                "example.com",
+                "hosted-zone-id",
+            );
+            let kubernetes = EKS::new(
+                self.context.clone(),
+                "cluster-id",
+                "cluster",
+                "1.16",
+                "eu-west-3",
+                &cloud_provider,
+                &dns_provider,
+                self.cluster_options.clone(),
+                vec![],
+            );
+            let environment = Environment::new(
+                Kind::Development,
+                "environment-id",
+                "project-id",
+                "owner-id",
+                "organization-id",
+                vec![],
+                vec![],
+            );
+
+            f(&DeploymentTarget::SelfHosted(&kubernetes, &environment));
+        }
+    }
+
+    fn assert_user_error(err: &EngineError) {
+        if let EngineErrorCause::Internal = err.cause {
+            panic!("expected a user error: {:?}", err.message);
+        }
+    }
+
+    /// the arguments of the helm upgrade of a service, in the namespace of the tests unless it is set
+    struct HelmUpgradeArgs<'a> {
+        service: &'a ExternalService,
+        namespace: &'a str,
+    }
+
+    impl<'a> HelmUpgradeArgs<'a> {
+        fn of(service: &'a ExternalService) -> Self {
+            HelmUpgradeArgs {
+                service,
+                namespace: "namespace",
+            }
+        }
+
+        fn namespace(mut self, namespace: &'a str) -> Self {
+            self.namespace = namespace;
+            self
+        }
+
+        fn build(self) -> Vec<String> {
+            helm_exec_upgrade_args(
+                "kubeconfig",
+                self.namespace,
+                self.service.helm_release_name().as_str(),
+                self.service.helm_chart_dir().as_str(),
+                self.service.helm_chart_version().as_deref(),
+                &self.service.helm_chart_values_files(),
+                &self.service.helm_set_values(),
+                self.service.start_timeout(),
+                self.service.helm_atomic_upgrade(),
+                self.service.helm_wait_for_jobs(),
+                &helm_release_labels(self.service),
+            )
+        }
+    }
+
+    #[test]
+    fn test_total_instances() {
+        let service = Fixture::new().total_instances(3).service();
+        assert_eq!(service.total_instances(), 3);
+        assert!(service.on_create_check().is_ok());
+
+        let service = Fixture::new().total_instances(0).service();
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("0 instance must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_environment_variables_validation() {
+        let mut service = Fixture::new().service();
+        service.environment_variables = vec![EnvironmentVariable {
+            key: "DATABASE_URL".to_string(),
+            value: "postgres://db".to_string(),
+        }];
+        service.secret_environment_variables = vec![EnvironmentVariable {
+            key: "DATABASE_PASSWORD".to_string(),
+            value: "p@ssw0rd".to_string(),
+        }];
+        assert!(service.on_create_check().is_ok());
+
+        // the secrets count in the budget of the container
+        service.set_environment_variables_max_size_in_bytes(30);
+        let err = service.on_create_check().unwrap_err();
+        assert_user_error(&err);
+        assert!(err.message.unwrap().contains("DATABASE_PASSWORD"));
+
+        service.set_environment_variables_max_size_in_bytes(ENVIRONMENT_VARIABLES_MAX_SIZE_IN_BYTES);
+        service.set_sidecar_environment_variables(
+            "my-sidecar",
+            vec![EnvironmentVariable {
+                key: "LOG-LEVEL".to_string(),
+                value: "debug".to_string(),
+            }],
+        );
+        let err = service.on_create_check().unwrap_err();
+        assert_user_error(&err);
+        assert!(err.message.unwrap().contains("\"LOG-LEVEL\""));
+    }
+
+    #[test]
+    fn test_environment_variables_order() {
+        let environment_variable = |key: &str, value: &str| EnvironmentVariable {
+            key: key.to_string(),
+            value: value.to_string(),
+        };
+
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+        service.environment_variables = vec![
+            environment_variable("PORT", "8080"),
+            environment_variable("DATABASE_HOST", "db"),
+            environment_variable("DATABASE_URL", "postgres://${DATABASE_HOST}"),
+        ];
+        service.secret_environment_variables = vec![
+            environment_variable("STRIPE_KEY", "sk_test"),
+            environment_variable("DATABASE_PASSWORD", "p@ssw0rd"),
+        ];
+
+        let mut shuffled_service = Fixture::new().service();
+        shuffled_service.environment_variables = vec![
+            environment_variable("DATABASE_URL", "postgres://${DATABASE_HOST}"),
+            environment_variable("PORT", "8080"),
+            environment_variable("DATABASE_HOST", "db"),
+        ];
+        shuffled_service.secret_environment_variables = vec![
+            environment_variable("DATABASE_PASSWORD", "p@ssw0rd"),
+            environment_variable("STRIPE_KEY", "sk_test"),
+        ];
+
+        let values = service.q_job_values(Architecture::Amd64).unwrap();
+        assert_eq!(
+            values.env.iter().map(|env| env.name.as_str()).collect::<Vec<_>>(),
+            vec!["DATABASE_HOST", "DATABASE_URL", "PORT"]
+        );
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            let shuffled_tera_context = shuffled_service.tera_context(target).unwrap().into_json();
+
+            assert_eq!(tera_context, shuffled_tera_context);
+            // the values file is byte identical
+            assert_eq!(
+                service.helm_chart_engine_values(target).unwrap(),
+                shuffled_service.helm_chart_engine_values(target).unwrap()
+            );
+            assert_eq!(
+                tera_context["secret_environment_variables"][0]["key"],
+                "DATABASE_PASSWORD"
+            );
+            assert_eq!(tera_context["secret_environment_variables"][1]["key"], "STRIPE_KEY");
+        });
+    }
+
+    #[test]
+    fn test_pause_strategy() {
+        let mut service = Fixture::new().service();
+        assert_eq!(service.pause_strategy(), PauseStrategy::Delete);
+
+        service.set_pause_strategy(PauseStrategy::ScaleToZero);
+        assert_eq!(service.pause_strategy(), PauseStrategy::ScaleToZero);
+
+        let resource = format!("job/{}", service.sanitized_name());
+        assert_eq!(
+            kubectl_exec_scale_args("namespace", resource.as_str(), 0),
+            vec![
+                "patch",
+                "job/ext-service-my-job",
+                "-n",
+                "namespace",
+                "--type=merge",
+                "-p",
+                r#"{"spec":{"parallelism":0}}"#
+            ]
+        );
+
+        // a cron job is paused the same way, it is suspended instead of scaled
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        assert_eq!(service.pause_strategy(), PauseStrategy::ScaleToZero);
+    }
+
+    #[test]
+    fn test_namespace_override() {
+        let environment = Environment::new(
+            Kind::Development,
+            "environment-id",
+            "project-id",
+            "owner-id",
+            "organization-id",
+            vec![],
+            vec![],
+        );
+
+        let mut service = Fixture::new().service();
+        assert_eq!(service.namespace(&environment), "project-id-environment-id");
+
+        service.set_namespace_override(Some("isolated-jobs".to_string()));
+        assert!(service.on_create_check().is_ok());
+
+        let namespace = service.namespace(&environment);
+        assert_eq!(namespace, "isolated-jobs");
+
+        let args = HelmUpgradeArgs::of(&service).namespace(namespace).build();
+        let namespace_index = args.iter().position(|arg| arg == "--namespace").unwrap();
+        assert_eq!(args[namespace_index + 1], "isolated-jobs");
+        assert!(args.contains(&"--create-namespace".to_string()));
+
+        let args = kubectl_exec_get_job_pod_logs_args(namespace, service.sanitized_name().as_str(), 100);
+        let namespace_index = args.iter().position(|arg| arg == "-n").unwrap();
+        assert_eq!(args[namespace_index + 1], "isolated-jobs");
+
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        assert_eq!(service.namespace(&environment), "isolated-jobs");
+
+        for namespace in &["Isolated", "isolated_jobs", "-isolated", "isolated-", ""] {
+            let mut service = Fixture::new().service();
+            service.set_namespace_override(Some(namespace.to_string()));
+
+            match service.on_create_check() {
+                Err(err) => assert_user_error(&err),
+                Ok(_) => panic!("namespace {:?} must be rejected", namespace),
+            }
+        }
+
+        assert!(is_valid_namespace_name("a"));
+        assert!(is_valid_namespace_name(&"a".repeat(63)));
+        assert!(!is_valid_namespace_name(&"a".repeat(64)));
+    }
+
+    #[test]
+    fn test_workspace_directory() {
+        let service = Fixture::new().service();
+        let mut other_service = Fixture::new().service();
+        other_service.id = "other-id".to_string();
+
+        assert_ne!(service.workspace_directory(), other_service.workspace_directory());
+        assert_eq!(
+            service.workspace_directory(),
+            Fixture::new().service().workspace_directory()
+        );
+
+        assert_eq!(context().workspace_root(), "/tmp/.qovery-workspace/execution_id");
+        assert!(service
+            .workspace_directory()
+            .starts_with(service.context().workspace_root().as_str()));
+    }
+
+    #[test]
+    fn test_endpoints() {
+        let service = Fixture::new().service();
+        assert!(service.endpoints("my-namespace").is_empty());
+
+        let service = Fixture::new().private_port(8080).service();
+        assert_eq!(
+            service.endpoints("my-namespace"),
+            vec![Endpoint::internal(
+                "ext-service-my-job.my-namespace.svc.cluster.local:8080"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_private_port() {
+        let service = Fixture::new().service();
+        assert_eq!(service.private_port(), None);
+        assert!(service.on_create_check().is_ok());
+
+        let service = Fixture::new().private_port(8080).service();
+        assert_eq!(service.private_port(), Some(8080));
+        assert!(service.on_create_check().is_ok());
+
+        let service = Fixture::new().private_port(0).service();
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("port 0 must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_start_timeout_in_helm_args() {
+        let service = Fixture::new().start_timeout(Timeout::Value(600)).service();
+
+        let args = HelmUpgradeArgs::of(&service).build();
+
+        let timeout_index = args.iter().position(|arg| arg == "--timeout").unwrap();
+        // helm default timeout is added on top of the start timeout
+        assert_eq!(args[timeout_index + 1], "900s");
+        // a failed upgrade is rolled back by helm
+        assert!(args.contains(&"--atomic".to_string()));
+    }
+
+    #[test]
+    fn test_secret_environment_variables_are_not_plain() {
+        let mut service = Fixture::new().service();
+        service.environment_variables = vec![EnvironmentVariable {
+            key: "PLAIN".to_string(),
+            value: "plain value".to_string(),
+        }];
+        service.secret_environment_variables = vec![EnvironmentVariable {
+            key: "SECRET".to_string(),
+            value: "secret value".to_string(),
+        }];
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+
+        let plain = serde_yaml::to_string(&service.q_job_values(Architecture::Amd64).unwrap().env).unwrap();
+        assert!(plain.contains("PLAIN"));
+        assert!(!plain.contains("SECRET"));
+        assert!(!plain.contains("secret value"));
+
+        let secrets = context["secret_environment_variables"].to_string();
+        assert!(secrets.contains("SECRET"));
+        assert!(!secrets.contains("PLAIN"));
+    }
+
+    #[test]
+    fn test_secret_environment_variables_are_escaped() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+        service.secret_environment_variables = vec![EnvironmentVariable {
+            key: "DATABASE_PASSWORD".to_string(),
+            value: r#"p@ss"word\"#.to_string(),
+        }];
+
+        fixture.with_deployment_target(|target| {
+            let secret = rendered_chart_file(&service, target, "secret.yaml");
+            assert!(secret.contains(r#"  DATABASE_PASSWORD: "p@ss\"word\\""#));
+        });
+
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            let secret = rendered_chart_file(&service, target, "secret.yaml");
+            assert!(secret.contains(r#"  DATABASE_PASSWORD: "p@ss\"word\\""#));
+        });
+    }
+
+    #[test]
+    fn test_environment_variables_interpolation() {
+        let mut service = Fixture::new().service();
+        service.environment_variables = vec![
+            EnvironmentVariable {
+                key: "DB_HOST".to_string(),
+                value: "pg.internal".to_string(),
+            },
+            EnvironmentVariable {
+                key: "DATABASE_URL".to_string(),
+                value: "postgres://$DB_HOST/db?password=$DB_PASSWORD".to_string(),
+            },
+        ];
+        service.secret_environment_variables = vec![EnvironmentVariable {
+            key: "DB_PASSWORD".to_string(),
+            value: "${DB_HOST}-secret".to_string(),
+        }];
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+
+        // a plain variable never gets the value of a secret one
+        let env = service.q_job_values(Architecture::Amd64).unwrap().env;
+        let database_url = env.iter().find(|ev| ev.name == "DATABASE_URL").unwrap();
+        assert_eq!(database_url.value, "postgres://pg.internal/db?password=$DB_PASSWORD");
+        assert_eq!(
+            context["secret_environment_variables"][0]["value"],
+            "pg.internal-secret"
+        );
+
+        service.set_strict_environment_variables_interpolation(true);
+        assert!(service.extend_tera_context(&mut TeraContext::new()).is_err());
+
+        service.set_strict_environment_variables_interpolation(false);
+        service.environment_variables[0].value = "$DATABASE_URL".to_string();
+        match service.extend_tera_context(&mut TeraContext::new()) {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("a circular reference must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_environment_variables_interpolation_reports_every_failure() {
+        let mut service = Fixture::new().service();
+        service.set_strict_environment_variables_interpolation(true);
+        service.environment_variables = vec![
+            EnvironmentVariable {
+                key: "API_URL".to_string(),
+                value: "http://$API_HOST".to_string(),
+            },
+            EnvironmentVariable {
+                key: "PORT".to_string(),
+                value: "8080".to_string(),
+            },
+        ];
+        service.secret_environment_variables = vec![EnvironmentVariable {
+            key: "DATABASE_URL".to_string(),
+            value: "postgres://${DB_USER}@db:$PORT".to_string(),
+        }];
+
+        let err = service.extend_tera_context(&mut TeraContext::new()).unwrap_err();
+        assert_user_error(&err);
+
+        // both variables are reported at once, the plain one only once
+        assert_eq!(
+            err.message.unwrap(),
+            "environment variable API_URL references the unknown environment variable API_HOST\n\
+            environment variable DATABASE_URL references the unknown environment variable DB_USER"
+        );
+    }
+
+    #[test]
+    #[ignore] // runs the helm binary
+    fn test_lint_chart() {
+        let fixture = Fixture::new();
+        let service = fixture.service();
+        fixture.with_deployment_target(|target| {
+            assert!(lint_stateless_service(target, &service).is_ok());
+        });
+    }
+
+    #[test]
+    fn test_chart_version_in_helm_args() {
+        let mut service = Fixture::new().service();
+        assert_eq!(service.helm_chart_version(), None);
+
+        service.set_chart_version(Some("0.1.0".to_string()));
+        let args = HelmUpgradeArgs::of(&service).build();
+
+        let version_index = args.iter().position(|arg| arg == "--version").unwrap();
+        assert_eq!(args[version_index + 1], "0.1.0");
+    }
+
+    #[test]
+    fn test_lifecycle_span() {
+        let recorder = SpanRecorder::default();
+        let subscriber = Registry::default().with(recorder.clone());
+        let service = Fixture::new().service();
+
+        // on_create runs the whole deployment as the long task, helm and kubectl log from within it
+        tracing::subscriber::with_default(subscriber, || {
+            send_progress_on_long_task(&service, Action::Create, || info!("helm upgrade"))
+        });
+
+        assert_eq!(
+            *recorder.span_fields.lock().unwrap(),
+            vec![
+                ("service.id".to_string(), "id".to_string()),
+                ("service.name".to_string(), "my-job".to_string()),
+                ("execution_id".to_string(), "execution_id".to_string()),
+                ("phase".to_string(), "create".to_string()),
+            ]
+        );
+        assert_eq!(*recorder.event_spans.lock().unwrap(), vec!["lifecycle"]);
+    }
+
+    #[cfg(feature = "metrics")]
+    #[test]
+    fn test_on_create_metrics() {
+        use crate::metrics;
+
+        // a dry run renders the chart without reaching the cluster
+        let fixture = Fixture::new().context(context().with_dry_run(true));
+        let service = fixture.service();
+
+        fixture.with_deployment_target(|target| {
+            let successes = metrics::deploy_total("ExternalService", "create", metrics::OUTCOME_SUCCESS);
+            let failures = metrics::deploy_total("ExternalService", "create", metrics::OUTCOME_FAILURE);
+
+            assert!(service.on_create(target).is_ok());
+
+            assert_eq!(
+                metrics::deploy_total("ExternalService", "create", metrics::OUTCOME_SUCCESS),
+                successes + 1
+            );
+            assert_eq!(
+                metrics::deploy_total("ExternalService", "create", metrics::OUTCOME_FAILURE),
+                failures
+            );
+            assert!(metrics::encode_text().contains("engine_deploy_duration_seconds"));
+        });
+    }
+
+    #[test]
+    fn test_concurrent_deployments_of_a_release() {
+        // a dry run renders the chart without reaching the cluster
+        let fixture = Fixture::new().context(context().with_dry_run(true));
+        let mut service = fixture.service();
+        // the other tests deploying the same release run in parallel
+        service.id = "locked-service-id".to_string();
+
+        fixture.with_deployment_target(|target| {
+            let namespace = match target {
+                DeploymentTarget::ManagedServices(_, environment) => service.namespace(environment),
+                DeploymentTarget::SelfHosted(_, environment) => service.namespace(environment),
+            };
+
+            // another deployment of the release is in progress
+            let release_lock = ReleaseLock::acquire(namespace, service.helm_release_name().as_str()).unwrap();
+            assert!(ReleaseLock::acquire(namespace, service.helm_release_name().as_str()).is_none());
+
+            match service.on_create(target) {
+                Err(err) => match err.cause {
+                    EngineErrorCause::User(message) => assert!(message.starts_with("Another operation is in progress")),
+                    EngineErrorCause::Internal => panic!("expected a user error"),
+                },
+                Ok(_) => panic!("the release is locked by another deployment"),
+            }
+
+            // the lock is released once the first deployment is done, whatever its outcome
+            drop(release_lock);
+            assert!(service.on_create(target).is_ok());
+            assert!(ReleaseLock::acquire(namespace, service.helm_release_name().as_str()).is_some());
+        });
+    }
+
+    #[test]
+    fn test_force_redeploy() {
+        let fixture = Fixture::new().context(context().with_force_redeploy(true));
+        let mut service = fixture.service();
+        service.set_chart_source(Some(ChartSource::Remote {
+            repo: HelmRepository {
+                name: "my-repo".to_string(),
+                url: "https://charts.example.com".to_string(),
+                credentials: None,
+            },
+            chart: "my-job".to_string(),
+            version: Some("1.0.0".to_string()),
+        }));
+
+        fixture.with_deployment_target(|target| {
+            // the deployed release is not even looked at, helm is always called
+            assert!(!is_stateless_service_up_to_date(target, &service).unwrap());
+
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert!(tera_context["redeploy_timestamp"].is_string());
+        });
+
+        // the pods of the next runs of a cron job are recreated as well
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            assert!(!is_stateless_service_up_to_date(target, &service).unwrap());
+
+            let cronjob = rendered_chart_file(&service, target, "cronjob.yaml");
+            assert!(cronjob.contains("\n          annotations:\n            qovery.com/redeployed-at: \""));
+        });
+
+        let fixture = Fixture::new();
+        let service = fixture.service();
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert!(tera_context["redeploy_timestamp"].is_null());
+        });
+    }
+
+    /// a template of the chart of the service rendered by tera, the helm templating is left as is
+    fn rendered_chart_file<T>(service: &T, target: &DeploymentTarget, file_name: &str) -> String
+    where
+        T: Service + Helm,
+    {
+        let tera_context = service.tera_context(target).unwrap();
+
+        generate_j2_template_files(service.helm_chart_dir(), &tera_context)
+            .unwrap()
+            .into_iter()
+            .find(|rendered_template| rendered_template.file_name == file_name)
+            .unwrap()
+            .content
+    }
+
+    #[test]
+    fn test_missing_values_files() {
+        let values_file = format!("{}/qovery-engine-values.yaml", std::env::temp_dir().to_str().unwrap());
+        std::fs::write(values_file.as_str(), "replicas: 1\n").unwrap();
+
+        let mut service = Fixture::new().service();
+        service.set_values_files(vec![values_file.clone(), "/does/not/exist.yaml".to_string()]);
+
+        match service.on_create_check() {
+            Err(err) => {
+                assert_user_error(&err);
+                assert!(err
+                    .message
+                    .unwrap()
+                    .ends_with("has missing values files: /does/not/exist.yaml"));
+            }
+            Ok(_) => panic!("missing values files must be rejected"),
+        }
+
+        // a directory is not a values file
+        let values_dir = std::env::temp_dir().to_str().unwrap().to_string();
+        service.set_values_files(vec![values_dir.clone()]);
+        match service.on_create_check() {
+            Err(err) => assert!(err.message.unwrap().ends_with(values_dir.as_str())),
+            Ok(_) => panic!("a directory must be rejected"),
+        }
+
+        service.set_values_files(vec![values_file.clone()]);
+        assert!(service.on_create_check().is_ok());
+
+        let _ = std::fs::remove_file(values_file);
+    }
+
+    #[test]
+    fn test_resources_requests_and_limits() {
+        let mut service = Fixture::new().service();
+
+        let resources = service.q_job_values(Architecture::Amd64).unwrap().resources;
+        assert_eq!(resources.requests.cpu, "1");
+        assert_eq!(resources.limits.cpu, "1");
+        assert_eq!(resources.requests.memory, "256Mi");
+        assert_eq!(resources.limits.memory, "256Mi");
+
+        service.set_cpu_request_and_limit("250m".to_string(), "2".to_string());
+        service.set_ram_request_and_limit_in_mib(128, 512);
+        assert!(service.on_create_check().is_ok());
+
+        let resources = service.q_job_values(Architecture::Amd64).unwrap().resources;
+        assert_eq!(resources.requests.cpu, "250m");
+        assert_eq!(resources.limits.cpu, "2");
+        assert_eq!(resources.requests.memory, "128Mi");
+        assert_eq!(resources.limits.memory, "512Mi");
+
+        service.set_cpu_request_and_limit("1500m".to_string(), "1".to_string());
+        assert!(service.on_create_check().is_err());
+
+        service.set_cpu_request_and_limit("1".to_string(), "1".to_string());
+        service.set_ram_request_and_limit_in_mib(512, 256);
+        assert!(service.on_create_check().is_err());
+    }
+
+    #[test]
+    fn test_image_pull_secret() {
+        let mut service = Fixture::new().service();
+        assert_eq!(service.registry_credentials(), None);
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+        assert_eq!(context["is_image_pull_secret"], false);
+        assert!(context.get("image_pull_secret_name").is_none());
+
+        service.image.registry_credentials = Some(RegistryCredentials {
+            server: "registry.example.com".to_string(),
+            login: "login".to_string(),
+            password: "password".to_string(),
+        });
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+        assert_eq!(context["is_image_pull_secret"], true);
+        assert_eq!(
+            context["image_pull_secret_name"],
+            format!("{}-registry", service.helm_release_name()).as_str()
+        );
+    }
+
+    #[test]
+    fn test_sidecars() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+        assert_eq!(context["sidecars"], json!([]));
+
+        let sidecar_image = |name: &str, tag: &str| Image {
+            application_id: "id".to_string(),
+            name: name.to_string(),
+            tag: tag.to_string(),
+            commit_id: "0123456789abcdef".to_string(),
+            registry_name: None,
+            registry_secret: None,
+            registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
+        };
+
+        service.sidecar_images = vec![
+            sidecar_image("gcr.io/cloudsql-docker/gce-proxy", "1.19.1"),
+            sidecar_image("fluent/fluent-bit", "1.6"),
+        ];
+        service.set_sidecar_environment_variables(
+            "fluent/fluent-bit",
+            vec![
+                EnvironmentVariable {
+                    key: "LOG_LEVEL".to_string(),
+                    value: "debug".to_string(),
+                },
+                EnvironmentVariable {
+                    key: "LOG_FORMAT".to_string(),
+                    value: r#"{"type": "json"}"#.to_string(),
+                },
+            ],
+        );
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+
+        assert_eq!(
+            context["sidecars"],
+            json!([
+                {
+                    "name": "sidecar-gce-proxy",
+                    "image_name_with_tag": "gcr.io/cloudsql-docker/gce-proxy:1.19.1",
+                    "environment_variables": [],
+                },
+                {
+                    "name": "sidecar-fluent-bit",
+                    "image_name_with_tag": "fluent/fluent-bit:1.6",
+                    "environment_variables": [
+                        { "key": "LOG_LEVEL", "value": "debug" },
+                        { "key": "LOG_FORMAT", "value": r#"{"type": "json"}"# },
+                    ],
+                },
+            ])
+        );
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.name,
+            "my-job:tag"
+        );
+
+        // the sidecars are containers of the job pods, next to the job one
+        let sidecar_containers = |indent: usize| {
+            [
+                "- name: sidecar-gce-proxy",
+                "  image: \"gcr.io/cloudsql-docker/gce-proxy:1.19.1\"",
+                "- name: sidecar-fluent-bit",
+                "  image: \"fluent/fluent-bit:1.6\"",
+                "  env:",
+                "    - name: \"LOG_LEVEL\"",
+                "      value: \"debug\"",
+                // the values are escaped, a quote does not end the value
+                "    - name: \"LOG_FORMAT\"",
+                r#"      value: "{\"type\": \"json\"}""#,
+            ]
+            .iter()
+            .map(|line| format!("\n{}{}", " ".repeat(indent), line))
+            .collect::<String>()
+        };
+
+        fixture.with_deployment_target(|target| {
+            let job = rendered_chart_file(&service, target, "job.yaml");
+            assert!(job.contains(sidecar_containers(8).as_str()));
+        });
+
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            let cronjob = rendered_chart_file(&service, target, "cronjob.yaml");
+            assert!(cronjob.contains(sidecar_containers(12).as_str()));
+        });
+    }
+
+    #[test]
+    fn test_init_containers() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+
+        let init_image = |name: &str, tag: &str| Image {
+            application_id: "id".to_string(),
+            name: name.to_string(),
+            tag: tag.to_string(),
+            commit_id: "0123456789abcdef".to_string(),
+            registry_name: None,
+            registry_secret: None,
+            registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
+        };
+
+        service.set_init_containers(vec![
+            InitContainer {
+                image: init_image("groundnuty/k8s-wait-for", "v1.4"),
+                share_environment_variables: false,
+            },
+            InitContainer {
+                image: init_image("my-org/my-job-migrations", "1.2.0"),
+                share_environment_variables: true,
+            },
+        ]);
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+
+        assert_eq!(
+            context["init_containers"],
+            json!([
+                {
+                    "name": "init-k8s-wait-for",
+                    "image_name_with_tag": "groundnuty/k8s-wait-for:v1.4",
+                    "share_environment_variables": false,
+                },
+                {
+                    "name": "init-my-job-migrations",
+                    "image_name_with_tag": "my-org/my-job-migrations:1.2.0",
+                    "share_environment_variables": true,
+                },
+            ])
+        );
+
+        // the init containers run before the containers of the job and of the cron job pods
+        let init_containers = |indent: usize| {
+            [
+                "initContainers:",
+                "  - name: init-k8s-wait-for",
+                "    image: \"groundnuty/k8s-wait-for:v1.4\"",
+                "  - name: init-my-job-migrations",
+                "    image: \"my-org/my-job-migrations:1.2.0\"",
+                "    env:",
+            ]
+            .iter()
+            .map(|line| format!("\n{}{}", " ".repeat(indent), line))
+            .collect::<String>()
+        };
+
+        fixture.with_deployment_target(|target| {
+            let job = rendered_chart_file(&service, target, "job.yaml");
+            assert!(job.contains(init_containers(6).as_str()));
+        });
+
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            let cronjob = rendered_chart_file(&service, target, "cronjob.yaml");
+            assert!(cronjob.contains(init_containers(10).as_str()));
+        });
+    }
+
+    #[test]
+    fn test_node_selectors_and_tolerations() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+
+        let mut node_selectors = BTreeMap::new();
+        node_selectors.insert("node.kubernetes.io/instance-type".to_string(), "p3.2xlarge".to_string());
+        node_selectors.insert("qovery.com/pool".to_string(), "gpu \"large\"".to_string());
+        service.set_node_selectors(node_selectors);
+        service.set_tolerations(vec![
+            Toleration {
+                key: "nvidia.com/gpu".to_string(),
+                operator: "Exists".to_string(),
+                value: None,
+                effect: "NoSchedule".to_string(),
+            },
+            Toleration {
+                key: "lifecycle".to_string(),
+                operator: "Equal".to_string(),
+                value: Some("spot".to_string()),
+                effect: "NoExecute".to_string(),
+            },
+        ]);
+        assert!(service.on_create_check().is_ok());
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+
+        assert_eq!(
+            context["node_selectors"],
+            json!({ "node.kubernetes.io/instance-type": "p3.2xlarge", "qovery.com/pool": "gpu \"large\"" })
+        );
+        assert_eq!(
+            context["tolerations"],
+            json!([
+                { "key": "nvidia.com/gpu", "operator": "Exists", "value": null, "effect": "NoSchedule" },
+                { "key": "lifecycle", "operator": "Equal", "value": "spot", "effect": "NoExecute" },
+            ])
+        );
+
+        // the pods of the job and of the cron job are scheduled the same way
+        let scheduling = |indent: usize| {
+            [
+                "nodeSelector:",
+                "  \"node.kubernetes.io/instance-type\": \"p3.2xlarge\"",
+                r#"  "qovery.com/pool": "gpu \"large\"""#,
+                "tolerations:",
+                "  - key: \"nvidia.com/gpu\"",
+                "    operator: \"Exists\"",
+                "    effect: \"NoSchedule\"",
+                "  - key: \"lifecycle\"",
+                "    operator: \"Equal\"",
+                "    value: \"spot\"",
+                "    effect: \"NoExecute\"",
+            ]
+            .iter()
+            .map(|line| format!("\n{}{}", " ".repeat(indent), line))
+            .collect::<String>()
+        };
+
+        fixture.with_deployment_target(|target| {
+            let job = rendered_chart_file(&service, target, "job.yaml");
+            assert!(job.contains(scheduling(6).as_str()));
+        });
+
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            let cronjob = rendered_chart_file(&service, target, "cronjob.yaml");
+            assert!(cronjob.contains(scheduling(10).as_str()));
+        });
+
+        let mut service = Fixture::new().service();
+        service.set_tolerations(vec![Toleration {
+            key: "nvidia.com/gpu".to_string(),
+            operator: "Exists".to_string(),
+            value: None,
+            effect: "NoRun".to_string(),
+        }]);
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("an unknown toleration effect must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_topology_spread_constraints() {
+        let fixture = Fixture::new().total_instances(3);
+        let mut service = fixture.service();
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        assert_eq!(context.into_json()["topology_spread_constraints"], json!([]));
+
+        service.set_topology_spread_constraints(vec![TopologySpread {
+            max_skew: 1,
+            topology_key: "topology.kubernetes.io/zone".to_string(),
+            when_unsatisfiable: "DoNotSchedule".to_string(),
+        }]);
+        assert!(service.on_create_check().is_ok());
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        assert_eq!(
+            context.into_json()["topology_spread_constraints"],
+            json!([
+                { "max_skew": 1, "topology_key": "topology.kubernetes.io/zone", "when_unsatisfiable": "DoNotSchedule" },
+            ])
+        );
+
+        // the pods of the job and of the cron job are spread the same way
+        let topology_spread_constraints = |indent: usize, app: String| {
+            [
+                "topologySpreadConstraints:",
+                "  - maxSkew: 1",
+                "    topologyKey: \"topology.kubernetes.io/zone\"",
+                "    whenUnsatisfiable: DoNotSchedule",
+                "    labelSelector:",
+                "      matchLabels:",
+                format!("        app: {}", app).as_str(),
+            ]
+            .iter()
+            .map(|line| format!("\n{}{}", " ".repeat(indent), line))
+            .collect::<String>()
+        };
+
+        fixture.with_deployment_target(|target| {
+            let job = rendered_chart_file(&service, target, "job.yaml");
+            assert!(job.contains(topology_spread_constraints(6, service.sanitized_name()).as_str()));
+        });
+
+        let invalid_constraints = vec![
+            TopologySpread {
+                max_skew: 0,
+                topology_key: "topology.kubernetes.io/zone".to_string(),
+                when_unsatisfiable: "DoNotSchedule".to_string(),
+            },
+            TopologySpread {
+                max_skew: 1,
+                topology_key: "topology.kubernetes.io/zone".to_string(),
+                when_unsatisfiable: "Never".to_string(),
+            },
+        ];
+
+        for invalid_constraint in invalid_constraints {
+            service.set_topology_spread_constraints(vec![invalid_constraint]);
+            match service.on_create_check() {
+                Err(err) => assert_user_error(&err),
+                Ok(_) => panic!("an invalid topology spread constraint must be rejected"),
+            }
+        }
+
+        service.set_topology_spread_constraints(vec![TopologySpread {
+            max_skew: 1,
+            topology_key: "topology.kubernetes.io/zone".to_string(),
+            when_unsatisfiable: "DoNotSchedule".to_string(),
+        }]);
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            let cronjob = rendered_chart_file(&service, target, "cronjob.yaml");
+            assert!(cronjob.contains(topology_spread_constraints(10, service.sanitized_name()).as_str()));
+        });
+    }
+
+    #[test]
+    fn test_image_digest() {
+        let mut service = Fixture::new().service();
+        assert_eq!(service.image.name_with_digest(), None);
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.name,
+            "my-job:tag"
+        );
+
+        service.image.registry_url = Some("registry.example.com:5000/my-job:tag".to_string());
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.name,
+            "registry.example.com:5000/my-job:tag"
+        );
+
+        service.image.digest = Some("sha256:4a5b6c".to_string());
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.name,
+            "registry.example.com:5000/my-job@sha256:4a5b6c"
+        );
+
+        service.image.registry_url = None;
+        assert_eq!(
+            service.image.name_with_digest(),
+            Some("my-job@sha256:4a5b6c".to_string())
+        );
+    }
+
+    #[test]
+    fn test_unreachable_registry_candidates() {
+        let mut service = Fixture::new().service();
+        service.image.registry_candidates = vec!["127.0.0.1:1".to_string()];
+
+        match service.q_job_values(Architecture::Amd64) {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("an image without reachable registry must be rejected"),
+        }
+
+        // the registry url has priority over the candidates
+        service.image.registry_url = Some("registry.example.com/my-job:tag".to_string());
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.name,
+            "registry.example.com/my-job:tag"
+        );
+    }
+
+    #[test]
+    fn test_create_check_does_not_query_the_registry() {
+        let registry = TcpListener::bind("127.0.0.1:0").unwrap();
+        registry.set_nonblocking(true).unwrap();
+
+        let mut service = Fixture::new().service();
+        service.image.registry_url = Some(format!("{}/my-job:never-pushed", registry.local_addr().unwrap()));
+        assert!(service.on_create_check().is_ok());
+
+        // the image is only looked up in its registry right before it is deployed
+        assert!(registry.accept().is_err());
+    }
+
+    #[test]
+    fn test_image_pull_policy() {
+        let mut service = Fixture::new().service();
+
+        // a tag may have been pushed again
+        assert_eq!(service.image_pull_policy(), ImagePullPolicy::Always);
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.pull_policy,
+            "Always"
+        );
+
+        // a digest always designates the same image
+        service.image.digest = Some("sha256:4a5b6c".to_string());
+        assert_eq!(service.image_pull_policy(), ImagePullPolicy::IfNotPresent);
+        assert_eq!(
+            service.q_job_values(Architecture::Amd64).unwrap().image.pull_policy,
+            "IfNotPresent"
+        );
+
+        service.set_image_pull_policy(Some(ImagePullPolicy::Always));
+        assert_eq!(service.image_pull_policy(), ImagePullPolicy::Always);
+
+        assert_eq!(ImagePullPolicy::parse("Never"), Ok(ImagePullPolicy::Never));
+        assert!(ImagePullPolicy::parse("always").is_err());
+    }
+
+    #[test]
+    fn test_q_job_values() {
+        let fixture = Fixture::new().total_instances(2);
+        let mut service = fixture.service();
+        service.environment_variables = vec![EnvironmentVariable {
+            key: "PLAIN".to_string(),
+            value: "plain value".to_string(),
+        }];
+        service.set_ram_request_and_limit_in_mib(128, 512);
+
+        let values = serde_yaml::to_string(&service.q_job_values(Architecture::Amd64).unwrap()).unwrap();
+        let values = serde_yaml::from_str::<serde_yaml::Value>(values.as_str()).unwrap();
+
+        assert_eq!(values["image"]["name"].as_str(), Some("my-job:tag"));
+        assert_eq!(values["image"]["pullPolicy"].as_str(), Some("Always"));
+        assert_eq!(values["env"][0]["name"].as_str(), Some("PLAIN"));
+        assert_eq!(values["env"][0]["value"].as_str(), Some("plain value"));
+        assert_eq!(values["resources"]["requests"]["cpu"].as_str(), Some("1"));
+        assert_eq!(values["resources"]["requests"]["memory"].as_str(), Some("128Mi"));
+        assert_eq!(values["resources"]["limits"]["cpu"].as_str(), Some("1"));
+        assert_eq!(values["resources"]["limits"]["memory"].as_str(), Some("512Mi"));
+        assert_eq!(values["instances"].as_u64(), Some(2));
+
+        fixture.with_deployment_target(|target| {
+            let engine_values = service.helm_chart_engine_values(target).unwrap().unwrap();
+            assert!(engine_values.contains("pullPolicy: Always"));
+            assert!(!engine_values.contains("pull_policy"));
+        });
+    }
+
+    #[test]
+    fn test_node_architecture_image() {
+        let fixture = Fixture::new().cluster_options(Options {
+            node_architecture: Architecture::Arm64,
+            ..Options::default()
+        });
+        let mut service = fixture.service();
+        service.image.registry_url = Some("registry.example.com/my-job:tag".to_string());
+        service.image.architecture_variants = vec![
+            ImageArchitectureVariant {
+                architecture: Architecture::Amd64,
+                tag: "tag-amd64".to_string(),
+                digest: Some("sha256:a1a1a1".to_string()),
+            },
+            ImageArchitectureVariant {
+                architecture: Architecture::Arm64,
+                tag: "tag-arm64".to_string(),
+                digest: Some("sha256:b2b2b2".to_string()),
+            },
+        ];
+
+        fixture.with_deployment_target(|target| {
+            let engine_values = service.helm_chart_engine_values(target).unwrap().unwrap();
+            assert!(engine_values.contains("registry.example.com/my-job@sha256:b2b2b2"));
+        });
+
+        let image_name =
+            |service: &ExternalService, architecture| service.q_job_values(architecture).unwrap().image.name;
+        assert_eq!(
+            image_name(&service, Architecture::Amd64),
+            "registry.example.com/my-job@sha256:a1a1a1"
+        );
+
+        // a single variant is deployed whatever the architecture of the nodes
+        service.image.architecture_variants.truncate(1);
+        assert_eq!(
+            image_name(&service, Architecture::Arm64),
+            "registry.example.com/my-job@sha256:a1a1a1"
+        );
+
+        service.image.architecture_variants[0].digest = None;
+        assert_eq!(
+            image_name(&service, Architecture::Arm64),
+            "registry.example.com/my-job:tag-amd64"
+        );
+    }
+
+    #[test]
+    fn test_security_context() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+        assert!(service.on_create_check().is_ok());
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+
+            // the containers run as non root unless told otherwise
+            assert_eq!(
+                tera_context["security_context"],
+                json!({ "run_as_non_root": true, "read_only_root_fs": false })
+            );
+        });
+
+        service.set_security_context(SecurityContext {
+            run_as_user: Some(1000),
+            run_as_non_root: true,
+            read_only_root_fs: true,
+            fs_group: Some(2000),
+        });
+        assert!(service.on_create_check().is_ok());
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(
+                tera_context["security_context"],
+                json!({ "run_as_user": 1000, "run_as_non_root": true, "read_only_root_fs": true, "fs_group": 2000 })
+            );
+        });
+
+        service.set_security_context(SecurityContext {
+            run_as_user: Some(0),
+            ..SecurityContext::default()
+        });
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("a non root container can't run as root"),
+        }
+    }
+
+    #[test]
+    fn test_active_deadline_seconds() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["active_deadline_seconds"], json!(3600));
+        });
+
+        service.set_active_deadline_seconds(Some(600));
+        assert!(service.on_create_check().is_ok());
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["active_deadline_seconds"], json!(600));
+        });
+
+        service.set_active_deadline_seconds(Some(0));
+        assert!(service.on_create_check().is_err());
+    }
+
+    #[test]
+    fn test_termination_grace_period_seconds() {
+        let fixture = Fixture::new().start_timeout(Timeout::Value(600));
+        let mut service = fixture.service();
+
+        // the kubernetes default
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["termination_grace_period_seconds"], json!(30));
+        });
+        assert_eq!(pods_deletion_timeout(&service).in_seconds(), 630);
+
+        service.set_termination_grace_period_seconds(Some(300));
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["termination_grace_period_seconds"], json!(300));
+        });
+        // the pods may take the whole grace period to stop once uninstalled
+        assert_eq!(pods_deletion_timeout(&service).in_seconds(), 900);
+    }
+
+    #[test]
+    fn test_helm_release_labels() {
+        let mut service = Fixture::new().service();
+
+        let mut release_labels = BTreeMap::new();
+        release_labels.insert("team".to_string(), "billing".to_string());
+        release_labels.insert("serviceId".to_string(), "hijacked".to_string());
+        service.set_release_labels(release_labels);
+
+        let args = HelmUpgradeArgs::of(&service).build();
+
+        // the labels of the engine can't be overridden
+        let labels_index = args.iter().position(|arg| arg == "--labels").unwrap();
+        assert_eq!(
+            args[labels_index + 1],
+            format!(
+                "executionId={},serviceId={},serviceType=externalservice,team=billing",
+                service.context().execution_id(),
+                service.id()
+            )
+        );
+    }
+
+    #[test]
+    fn test_helm_wait_for_jobs() {
+        let mut service = Fixture::new().service();
+
+        // the job is polled with kubectl by default
+        let args = HelmUpgradeArgs::of(&service).build();
+        assert!(!args.contains(&"--wait-for-jobs".to_string()));
+
+        service.set_helm_wait_for_jobs(true);
+        let args = HelmUpgradeArgs::of(&service).build();
+        assert!(args.contains(&"--wait".to_string()));
+        assert!(args.contains(&"--wait-for-jobs".to_string()));
+
+        // a cron job has no job to wait for when it is installed
+        let cron_service = CronExternalService::new(service, "*/5 * * * *");
+        assert!(!cron_service.helm_wait_for_jobs());
+    }
+
+    #[test]
+    fn test_backoff_limit() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["backoff_limit"], json!(0));
+        });
+
+        service.set_backoff_limit(Some(3));
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["backoff_limit"], json!(3));
+        });
+
+        // no retries at all
+        service.set_backoff_limit(Some(0));
+        assert!(service.on_create_check().is_ok());
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(tera_context["backoff_limit"], json!(0));
+        });
+
+        let job_status = serde_json::from_str::<KubernetesJobStatus>(
+            r#"{
+                "conditions": [
+                    {
+                        "type": "Failed",
+                        "status": "True",
+                        "reason": "BackoffLimitExceeded",
+                        "message": "Job has reached the specified backoff limit"
+                    }
+                ],
+                "failed": 1
+            }"#,
+        )
+        .unwrap();
+        assert!(job_status.has_failed());
+        assert!(!job_status.has_exceeded_deadline());
+
+        // running the job again would run it more times than allowed
+        let not_ready = service.engine_error(EngineErrorCause::Internal, "job is not ready".to_string());
+        let err = job_failure_error(&service, Some(&job_status), not_ready);
+        assert!(err.message.as_deref().unwrap().contains("has failed"));
+        assert!(!err.is_retryable());
+    }
+
+    #[test]
+    fn test_job_exceeding_its_deadline() {
+        let service = Fixture::new().service();
+        let failed_to_start = || {
+            service.engine_error(
+                EngineErrorCause::User("Your application didn't start for some reason"),
+                "failed to start".to_string(),
+            )
+        };
+
+        let job_status = serde_json::from_str::<KubernetesJobStatus>(
+            r#"{
+                "conditions": [
+                    {
+                        "type": "Failed",
+                        "status": "True",
+                        "reason": "DeadlineExceeded",
+                        "message": "Job was active longer than specified deadline"
+                    }
+                ],
+                "failed": 1
+            }"#,
+        )
+        .unwrap();
+        assert!(job_status.has_exceeded_deadline());
+
+        // the job would be killed again on retry
+        let err = job_failure_error(&service, Some(&job_status), failed_to_start());
+        match err.cause {
+            EngineErrorCause::User(hint) => assert!(hint.contains("deadline")),
+            EngineErrorCause::Internal => panic!("expected a user error"),
+        }
+        assert!(err.message.unwrap().contains("has exceeded its deadline"));
+
+        let job_status = serde_json::from_str::<KubernetesJobStatus>(r#"{ "active": 1 }"#).unwrap();
+        assert!(!job_status.has_exceeded_deadline());
+
+        let err = job_failure_error(&service, Some(&job_status), failed_to_start());
+        assert_eq!(err.message.as_deref(), Some("failed to start"));
+
+        let err = job_failure_error(&service, None, failed_to_start());
+        assert_eq!(err.message.as_deref(), Some("failed to start"));
+    }
+
+    #[test]
+    fn test_helm_upgrade_exceeding_the_quota() {
+        let service = Fixture::new().service();
+
+        let exceeded_quota = service.engine_error(
+            EngineErrorCause::Internal,
+            "Error: UPGRADE FAILED: pods \"my-job-x2kdp\" is forbidden: exceeded quota: namespace-quota, \
+            requested: limits.cpu=2, used: limits.cpu=3, limited: limits.cpu=4"
+                .to_string(),
+        );
+        let err = helm_upgrade_error(&service, exceeded_quota.retryable());
+        match err.cause {
+            EngineErrorCause::User(hint) => assert!(hint.contains("resource quota")),
+            EngineErrorCause::Internal => panic!("expected a user error"),
+        }
+        assert!(!err.is_retryable());
+        assert!(err.message.unwrap().contains("limited: limits.cpu=4"));
+
+        let timeout = service.engine_error(EngineErrorCause::Internal, "timed out".to_string());
+        let err = helm_upgrade_error(&service, timeout.retryable());
+        assert!(err.is_retryable());
+        assert_eq!(err.message.as_deref(), Some("timed out"));
+    }
+
+    #[test]
+    fn test_pod_labels_and_annotations() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+
+        let mut pod_labels = BTreeMap::new();
+        pod_labels.insert("team".to_string(), "billing".to_string());
+        pod_labels.insert("app".to_string(), "hijacked".to_string());
+        service.set_pod_labels(pod_labels);
+
+        let mut pod_annotations = BTreeMap::new();
+        pod_annotations.insert("sidecar.istio.io/inject".to_string(), "true".to_string());
+        pod_annotations.insert(
+            "ad.datadoghq.com/job.logs".to_string(),
+            r#"[{"source": "job"}]"#.to_string(),
+        );
+        service.set_pod_annotations(pod_annotations);
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+
+            // the user metadata is rendered next to the engine labels
+            assert_eq!(
+                tera_context["pod_annotations"],
+                json!({ "ad.datadoghq.com/job.logs": r#"[{"source": "job"}]"#, "sidecar.istio.io/inject": "true" })
+            );
+            assert_eq!(tera_context["pod_labels"], json!({ "team": "billing" }));
+            assert_eq!(tera_context["id"], service.id());
+            assert_eq!(tera_context["sanitized_name"], service.sanitized_name());
+        });
+
+        // the user metadata is escaped, a quote does not end the value
+        let pod_metadata = |indent: usize, app: String| {
+            [
+                "  labels:",
+                "    ownerId: owner-id",
+                "    envId: environment-id",
+                "    appId: id",
+                &format!("    app: {}", app),
+                "    \"team\": \"billing\"",
+                "  annotations:",
+                r#"    "ad.datadoghq.com/job.logs": "[{\"source\": \"job\"}]""#,
+                "    \"sidecar.istio.io/inject\": \"true\"",
+            ]
+            .iter()
+            .map(|line| format!("\n{}{}", " ".repeat(indent), line))
+            .collect::<String>()
+        };
+
+        fixture.with_deployment_target(|target| {
+            let job = rendered_chart_file(&service, target, "job.yaml");
+            assert!(job.contains(pod_metadata(4, service.sanitized_name()).as_str()));
+        });
+
+        let service = CronExternalService::new(service, "*/5 * * * *");
+        fixture.with_deployment_target(|target| {
+            let cronjob = rendered_chart_file(&service, target, "cronjob.yaml");
+            assert!(cronjob.contains(pod_metadata(8, service.sanitized_name()).as_str()));
+        });
+    }
+
+    #[test]
+    fn test_fargate_profile() {
+        let environment = Environment::new(
+            Kind::Development,
+            "environment-id",
+            "project-id",
+            "owner-id",
+            "organization-id",
+            vec![],
+            vec![],
+        );
+
+        let mut labels = BTreeMap::new();
+        labels.insert("qovery.com/fargate".to_string(), "true".to_string());
+        let fargate_profile = FargateProfile {
+            name: "serverless-jobs".to_string(),
+            namespace: "jobs".to_string(),
+            labels,
+            pod_execution_role_arn: "arn:aws:iam::123456789012:role/fargate-pods".to_string(),
+            subnet_ids: vec![],
+        };
+
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+        let mut pod_labels = BTreeMap::new();
+        pod_labels.insert("team".to_string(), "billing".to_string());
+        service.set_pod_labels(pod_labels);
+        service.set_fargate_profile(Some(fargate_profile));
+        assert!(service.on_create_check().is_ok());
+
+        // the pods land in the namespace selected by the profile, with its labels
+        assert_eq!(service.namespace(&environment), "jobs");
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+            assert_eq!(
+                tera_context["pod_labels"],
+                json!({ "qovery.com/fargate": "true", "team": "billing" })
+            );
+        });
+
+        service.set_namespace_override(Some("jobs".to_string()));
+        assert!(service.on_create_check().is_ok());
+
+        service.set_namespace_override(Some("isolated-jobs".to_string()));
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("the namespace must be the one of the Fargate profile"),
+        }
+    }
+
+    #[test]
+    fn test_mounted_files() {
+        let fixture = Fixture::new();
+        let mut service = fixture.service();
+        service.set_mounted_files(vec![MountedFile {
+            path: "/etc/app/config.yaml".to_string(),
+            content: "name: {{ sanitized_name }}\nport: 8080\n".to_string(),
+        }]);
+        assert!(service.on_create_check().is_ok());
+
+        fixture.with_deployment_target(|target| {
+            let tera_context = service.tera_context(target).unwrap().into_json();
+
+            assert_eq!(
+                tera_context["mounted_files"],
+                json!([{
+                    "key": "file-0-config.yaml",
+                    "path": "/etc/app/config.yaml",
+                    "content": format!("name: {}\nport: 8080\n", service.sanitized_name()),
+                }])
+            );
+        });
+
+        service.set_mounted_files(vec![MountedFile {
+            path: "/etc/app/large.bin".to_string(),
+            content: "x".repeat(MOUNTED_FILES_MAX_SIZE_IN_BYTES + 1),
+        }]);
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("a mounted file larger than a config map must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_command_and_args() {
+        let mut service = Fixture::new().service();
+
+        // the image entrypoint is kept
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+        assert!(context.get("command").is_none());
+        assert!(context.get("args").is_none());
+
+        service.set_command(Some(vec!["/bin/sh".to_string(), "-c".to_string()]));
+        service.set_args(Some(vec!["pg_dump $DATABASE_URL > /var/backup/dump.sql".to_string()]));
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        let context = context.into_json();
+        assert_eq!(context["command"], json!(["/bin/sh", "-c"]));
+        assert_eq!(context["args"], json!(["pg_dump $DATABASE_URL > /var/backup/dump.sql"]));
+    }
+
+    #[test]
+    fn test_volume_claims() {
+        let mut service = Fixture::new().service();
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        assert_eq!(context.into_json()["volume_claims"], json!([]));
+
+        service.set_volume_claims(vec![VolumeClaim {
+            name: "scratch".to_string(),
+            size_gib: 10,
+            storage_class: Some("gp2".to_string()),
+            mount_path: "/var/scratch".to_string(),
+        }]);
+        assert!(service.on_create_check().is_ok());
+
+        let mut context = TeraContext::new();
+        service.extend_tera_context(&mut context).unwrap();
+        assert_eq!(
+            context.into_json()["volume_claims"],
+            json!([{
+                "name": "scratch",
+                "claim_name": format!("{}-scratch", service.sanitized_name()),
+                "size_gib": 10,
+                "storage_class": "gp2",
+                "mount_path": "/var/scratch",
+            }])
+        );
+
+        for &(size_gib, mount_path) in &[(0, "/var/scratch"), (10, "")] {
+            service.set_volume_claims(vec![VolumeClaim {
+                name: "scratch".to_string(),
+                size_gib,
+                storage_class: None,
+                mount_path: mount_path.to_string(),
+            }]);
+
+            match service.on_create_check() {
+                Err(err) => assert_user_error(&err),
+                Ok(_) => panic!("an empty claim or a claim without mount path must be rejected"),
+            }
+        }
+    }
+
+    #[test]
+    fn test_failing_post_deploy_check_fails_the_create() {
+        let mut service = Fixture::new().service();
+        let command = Command {
+            binary: "curl".to_string(),
+            args: vec!["-f".to_string(), "http://localhost:8080/health".to_string()],
+        };
+        service.set_post_deploy_check(Some(command.clone()));
+
+        let failed_check = KubectlOutput {
+            stdout: "".to_string(),
+            stderr: "curl: (22) The requested URL returned error: 503".to_string(),
+            exit_code: 22,
+        };
+
+        let err =
+            deploy_and_check(|| Ok(()), || service.post_deploy_check_result(&command, &failed_check)).unwrap_err();
+        match err.cause {
+            EngineErrorCause::Internal => {}
+            EngineErrorCause::User(_) => panic!("expected an internal error"),
+        }
+        assert_eq!(
+            err.message.unwrap(),
+            format!(
+                "the post deploy check `curl -f http://localhost:8080/health` of {} exited with code 22: \
+                curl: (22) The requested URL returned error: 503",
+                service.name_with_id()
+            )
+        );
+
+        // the check needs a ready job, it does not run if the deployment failed
+        let mut checked = false;
+        let result = deploy_and_check(
+            || Err(service.engine_error(EngineErrorCause::Internal, "helm upgrade failed".to_string())),
+            || {
+                checked = true;
+                Ok(())
+            },
+        );
+        assert!(result.is_err());
+        assert!(!checked);
+
+        let succeeded_check = KubectlOutput {
+            stdout: "ok".to_string(),
+            stderr: "".to_string(),
+            exit_code: 0,
+        };
+        assert!(deploy_and_check(
+            || Ok(()),
+            || service.post_deploy_check_result(&command, &succeeded_check)
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn test_cron_schedule() {
+        assert!(is_valid_cron_schedule("*/5 * * * *"));
+        assert!(is_valid_cron_schedule("0 3 * * MON-FRI"));
+        assert!(is_valid_cron_schedule("30 1,13 1-15 JAN,jul 0"));
+        assert!(is_valid_cron_schedule("@daily"));
+
+        assert!(!is_valid_cron_schedule(""));
+        assert!(!is_valid_cron_schedule("* * * *"));
+        assert!(!is_valid_cron_schedule("60 * * * *"));
+        assert!(!is_valid_cron_schedule("*/0 * * * *"));
+        assert!(!is_valid_cron_schedule("0 5-3 * * *"));
+        assert!(!is_valid_cron_schedule("@sometimes"));
+    }
+
+    #[test]
+    fn test_cron_external_service_invalid_schedule() {
+        let service = CronExternalService::new(Fixture::new().service(), "*/5 * * * *");
+        assert!(service.on_create_check().is_ok());
+
+        let service = CronExternalService::new(Fixture::new().service(), "every 5 minutes");
+        match service.on_create_check() {
+            Err(err) => assert_user_error(&err),
+            Ok(_) => panic!("an invalid schedule must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_cron_external_service_cpu_burst() {
+        let service = CronExternalService::new(Fixture::new().service(), "*/5 * * * *");
+        assert_eq!(service.cpu_burst(), service.total_cpus());
     }
 }
@@ -0,0 +1,188 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use rusoto_core::{Client, HttpClient, Region, RusotoError};
+use rusoto_credential::StaticProvider;
+use rusoto_eks::{
+    CreateFargateProfileRequest, DescribeFargateProfileError, DescribeFargateProfileRequest, Eks, EksClient,
+    FargateProfileSelector,
+};
+use serde::{Deserialize, Serialize};
+use tokio::runtime::Runtime;
+
+use crate::cmd::kubectl::{is_ready_with_policy, RetryPolicy};
+use crate::error::{SimpleError, SimpleErrorKind};
+
+/// the pods of the namespace having all the labels are scheduled on Fargate rather than on the nodes of the cluster
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FargateProfile {
+    pub name: String,
+    pub namespace: String,
+    #[serde(default)]
+    pub labels: BTreeMap<String, String>,
+    /// the role the kubelet of the Fargate pods runs with, e.g. to pull their images
+    pub pod_execution_role_arn: String,
+    /// private subnets only, the Fargate pods do not get a public IP. The private subnets of the cluster by default
+    #[serde(default)]
+    pub subnet_ids: Vec<String>,
+}
+
+impl FargateProfile {
+    pub fn create_request(&self, cluster_name: &str, tags: &BTreeMap<String, String>) -> CreateFargateProfileRequest {
+        let non_empty_map = |map: &BTreeMap<String, String>| match map.is_empty() {
+            true => None,
+            false => Some(map.clone().into_iter().collect::<HashMap<_, _>>()),
+        };
+
+        CreateFargateProfileRequest {
+            cluster_name: cluster_name.to_string(),
+            fargate_profile_name: self.name.clone(),
+            pod_execution_role_arn: self.pod_execution_role_arn.clone(),
+            selectors: Some(vec![FargateProfileSelector {
+                namespace: Some(self.namespace.clone()),
+                labels: non_empty_map(&self.labels),
+            }]),
+            subnets: match self.subnet_ids.is_empty() {
+                true => None,
+                false => Some(self.subnet_ids.clone()),
+            },
+            tags: non_empty_map(tags),
+            client_request_token: None,
+        }
+    }
+
+    /// create the profile if it does not exist yet and wait until it is active,
+    /// EKS creates the profiles of a cluster one at a time
+    pub fn create(
+        &self,
+        access_key: &str,
+        secret_key: &str,
+        region: &Region,
+        cluster_name: &str,
+        tags: &BTreeMap<String, String>,
+    ) -> Result<(), SimpleError> {
+        let credentials = StaticProvider::new(access_key.to_string(), secret_key.to_string(), None, None);
+        let client = Client::new_with(credentials, HttpClient::new().unwrap());
+        let eks_client = EksClient::new_with_client(client, region.clone());
+        let mut runtime = Runtime::new().expect("Failed to create Tokio runtime to create the Fargate profile");
+
+        match self.status(&mut runtime, &eks_client, cluster_name)? {
+            Some(status) => info!("Fargate profile {} already exists with status {}", self.name, status),
+            None => {
+                info!("Fargate profile {} doesn't exist, let's create it !", self.name);
+                let created =
+                    runtime.block_on(eks_client.create_fargate_profile(self.create_request(cluster_name, tags)));
+
+                if let Err(err) = created {
+                    return Err(SimpleError::new(
+                        SimpleErrorKind::Other,
+                        Some(format!(
+                            "Unable to create the Fargate profile {} of the cluster {}: {:?}",
+                            self.name, cluster_name, err
+                        )),
+                    ));
+                }
+            }
+        }
+
+        // a profile takes a few minutes to be created
+        let retry_policy = RetryPolicy {
+            max_attempts: 60,
+            interval: Duration::from_secs(10),
+            ..RetryPolicy::default()
+        };
+
+        let resource = format!("Fargate profile {}", self.name);
+        let is_active = is_ready_with_policy(&retry_policy, resource.as_str(), || {
+            match self.status(&mut runtime, &eks_client, cluster_name)? {
+                Some(status) if status == "CREATE_FAILED" => Err(SimpleError::new(
+                    SimpleErrorKind::Other,
+                    Some(format!("the creation of the Fargate profile {} has failed", self.name)),
+                )),
+                status => Ok(Some(status.as_deref() == Some("ACTIVE"))),
+            }
+        })?;
+
+        match is_active {
+            Some(true) => Ok(()),
+            _ => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!("the Fargate profile {} is still not active", self.name)),
+            )),
+        }
+    }
+
+    /// the status (`CREATING`, `ACTIVE`...) of the profile, none if it does not exist
+    fn status(
+        &self,
+        runtime: &mut Runtime,
+        eks_client: &EksClient,
+        cluster_name: &str,
+    ) -> Result<Option<String>, SimpleError> {
+        let described = runtime.block_on(eks_client.describe_fargate_profile(DescribeFargateProfileRequest {
+            cluster_name: cluster_name.to_string(),
+            fargate_profile_name: self.name.clone(),
+        }));
+
+        match described {
+            Ok(output) => Ok(Some(
+                output
+                    .fargate_profile
+                    .and_then(|profile| profile.status)
+                    .unwrap_or_default(),
+            )),
+            Err(RusotoError::Service(DescribeFargateProfileError::ResourceNotFound(_))) => Ok(None),
+            Err(err) => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!("Unable to get the Fargate profile {}: {:?}", self.name, err)),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::cloud_provider::aws::kubernetes::fargate::FargateProfile;
+
+    #[test]
+    fn test_fargate_profile_create_request() {
+        let mut labels = BTreeMap::new();
+        labels.insert("qovery.com/fargate".to_string(), "true".to_string());
+        let mut tags = BTreeMap::new();
+        tags.insert("team".to_string(), "billing".to_string());
+
+        let profile = FargateProfile {
+            name: "serverless-jobs".to_string(),
+            namespace: "jobs".to_string(),
+            labels,
+            pod_execution_role_arn: "arn:aws:iam::123456789012:role/fargate-pods".to_string(),
+            subnet_ids: vec!["subnet-0a1b2c".to_string()],
+        };
+
+        let request = profile.create_request("qovery-cluster-id", &tags);
+        assert_eq!(request.cluster_name, "qovery-cluster-id");
+        assert_eq!(request.fargate_profile_name, "serverless-jobs");
+        assert_eq!(request.pod_execution_role_arn, "arn:aws:iam::123456789012:role/fargate-pods");
+        assert_eq!(request.subnets, Some(vec!["subnet-0a1b2c".to_string()]));
+        assert_eq!(request.tags.unwrap()["team"], "billing");
+
+        let selectors = request.selectors.unwrap();
+        assert_eq!(selectors.len(), 1);
+        assert_eq!(selectors[0].namespace.as_deref(), Some("jobs"));
+        assert_eq!(selectors[0].labels.as_ref().unwrap()["qovery.com/fargate"], "true");
+
+        // the whole namespace runs on Fargate without labels, in the private subnets of the cluster
+        let profile = FargateProfile {
+            labels: BTreeMap::new(),
+            subnet_ids: vec![],
+            ..profile
+        };
+
+        let request = profile.create_request("qovery-cluster-id", &BTreeMap::new());
+        assert_eq!(request.selectors.unwrap()[0].labels, None);
+        assert_eq!(request.subnets, None);
+        assert_eq!(request.tags, None);
+    }
+}
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::str::FromStr;
 
 use itertools::Itertools;
@@ -5,11 +6,15 @@ use rusoto_core::Region;
 use serde::{Deserialize, Serialize};
 use tera::Context as TeraContext;
 
+use crate::build_platform::Architecture;
+use crate::cloud_provider::aws::kubernetes::fargate::FargateProfile;
 use crate::cloud_provider::aws::kubernetes::node::Node;
 use crate::cloud_provider::aws::kubernetes::roles::get_default_roles_to_create;
 use crate::cloud_provider::aws::AWS;
 use crate::cloud_provider::environment::Environment;
-use crate::cloud_provider::kubernetes::{uninstall_cert_manager, Kind, Kubernetes, KubernetesNode};
+use crate::cloud_provider::kubernetes::{
+    parse_kubernetes_version, uninstall_cert_manager, Kind, Kubernetes, KubernetesNode,
+};
 use crate::cloud_provider::models::WorkerNodeDataTemplate;
 use crate::cloud_provider::{kubernetes, CloudProvider};
 use crate::cmd;
@@ -30,6 +35,7 @@ use retry::delay::Fibonacci;
 use retry::Error::Operation;
 use retry::OperationResult;
 
+pub mod fargate;
 pub mod node;
 pub mod roles;
 
@@ -71,8 +77,20 @@ pub struct Options {
     pub qovery_ssh_key: String,
     // Others
     pub tls_email_report: String,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// the pods matching a profile are scheduled on Fargate, e.g. the jobs
+    #[serde(default)]
+    pub fargate_profiles: Vec<FargateProfile>,
+    /// the architecture of the instance types of the nodes, e.g. arm64 for Graviton instances
+    #[serde(default)]
+    pub node_architecture: Architecture,
 }
 
+// https://docs.aws.amazon.com/general/latest/gr/aws_tagging.html
+const AWS_TAG_KEY_MAX_LENGTH: usize = 128;
+const AWS_TAG_VALUE_MAX_LENGTH: usize = 256;
+
 pub struct EKS<'a> {
     context: Context,
     id: String,
@@ -242,6 +260,10 @@ impl<'a> EKS<'a> {
                 context.insert("cloudflare_api_token", self.dns_provider.token());
                 context.insert("cloudflare_email", self.dns_provider.account());
             }
+            dns_provider::Kind::Route53 => {
+                context.insert("external_dns_provider", "aws");
+                context.insert("route53_hosted_zone_id", self.dns_provider.account());
+            }
         };
 
         context.insert("dns_email_report", &self.options.tls_email_report); // Pierre suggested renaming to tls_email_report
@@ -291,6 +313,7 @@ impl<'a> EKS<'a> {
         context.insert("kubernetes_cluster_id", self.id());
         context.insert("eks_region_cluster_id", region_cluster_id.as_str());
         context.insert("eks_worker_nodes", &worker_nodes);
+        context.insert("eks_tags", &self.tags());
         context.insert("eks_zone_a_subnet_blocks", &eks_zone_a_subnet_blocks);
         context.insert("eks_zone_b_subnet_blocks", &eks_zone_b_subnet_blocks);
         context.insert("eks_zone_c_subnet_blocks", &eks_zone_c_subnet_blocks);
@@ -394,9 +417,40 @@ impl<'a> Kubernetes for EKS<'a> {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
+        if let Err(message) = kubernetes::validate_region(self.region(), self.cloud_provider.valid_regions()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The region of the cluster is not an EKS region"),
+                format!("invalid region of {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        if let Err(message) = parse_kubernetes_version(self.version()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The Kubernetes version of the cluster is not valid"),
+                format!("invalid version of {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        if let Err(message) =
+            kubernetes::validate_tags(&self.options.tags, AWS_TAG_KEY_MAX_LENGTH, AWS_TAG_VALUE_MAX_LENGTH)
+        {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The cluster tags exceed the length limits of AWS"),
+                format!("invalid tags of {}: {}", self.name_with_id(), message),
+            ));
+        }
+
         Ok(())
     }
 
+    fn tags(&self) -> BTreeMap<String, String> {
+        self.options.tags.clone()
+    }
+
+    fn node_architecture(&self) -> Architecture {
+        self.options.node_architecture
+    }
+
     fn on_create(&self) -> Result<(), EngineError> {
         info!("EKS.on_create() called for {}", self.name());
 
@@ -482,12 +536,48 @@ impl<'a> Kubernetes for EKS<'a> {
                 self.context.is_dry_run_deploy(),
             ),
         ) {
-            Ok(_) => Ok(()),
+            Ok(_) => {}
             Err(e) => {
                 format!("Error while deploying cluster {} with id {}.", self.name(), self.id());
-                Err(e)
+                return Err(e);
             }
         }
+
+        // the profiles are created through the EKS API once the cluster exists
+        if self.context.is_dry_run_deploy() {
+            return Ok(());
+        }
+
+        let cluster_name = format!("qovery-{}", self.id());
+        for profile in &self.options.fargate_profiles {
+            listeners_helper.deployment_in_progress(ProgressInfo::new(
+                ProgressScope::Infrastructure {
+                    execution_id: self.context.execution_id().to_string(),
+                },
+                ProgressLevel::Info,
+                Some(format!(
+                    "Creating the Fargate profile {} of the EKS {} cluster with id {}",
+                    profile.name,
+                    self.name(),
+                    self.id()
+                )),
+                self.context.execution_id(),
+            ));
+
+            let _ = cast_simple_error_to_engine_error(
+                self.engine_error_scope(),
+                self.context.execution_id(),
+                profile.create(
+                    self.cloud_provider.access_key_id.as_str(),
+                    self.cloud_provider.secret_access_key.as_str(),
+                    &self.region,
+                    cluster_name.as_str(),
+                    &self.tags(),
+                ),
+            )?;
+        }
+
+        Ok(())
     }
 
     fn on_create_error(&self) -> Result<(), EngineError> {
@@ -698,6 +788,7 @@ impl<'a> Kubernetes for EKS<'a> {
                     let _ = cmd::helm::helm_uninstall_list(
                         &kubernetes_config_file_path,
                         vec![chart],
+                        cmd::helm::Timeout::Default,
                         self.cloud_provider().credentials_environment_variables(),
                     );
                 }
@@ -4,7 +4,9 @@ use rusoto_core::{Client, HttpClient, Region};
 use rusoto_credential::StaticProvider;
 use rusoto_sts::{GetCallerIdentityRequest, Sts, StsClient};
 
-use crate::cloud_provider::{CloudProvider, EngineError, Kind, TerraformStateCredentials};
+use crate::cloud_provider::utilities::VersionsNumber;
+use crate::cloud_provider::{CloudProvider, EnvironmentVariableAliases, EngineError, Kind, TerraformStateCredentials};
+use crate::cmd::preflight::BinaryRequirement;
 use crate::constants::{AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
 use crate::error::EngineErrorCause;
 use crate::models::{Context, Listen, Listener, Listeners};
@@ -16,6 +18,31 @@ pub mod external_service;
 pub mod kubernetes;
 pub mod router;
 
+/// the public regions offering EKS
+pub const AWS_REGIONS: [&str; 21] = [
+    "af-south-1",
+    "ap-east-1",
+    "ap-northeast-1",
+    "ap-northeast-2",
+    "ap-northeast-3",
+    "ap-south-1",
+    "ap-southeast-1",
+    "ap-southeast-2",
+    "ca-central-1",
+    "eu-central-1",
+    "eu-north-1",
+    "eu-south-1",
+    "eu-west-1",
+    "eu-west-2",
+    "eu-west-3",
+    "me-south-1",
+    "sa-east-1",
+    "us-east-1",
+    "us-east-2",
+    "us-west-1",
+    "us-west-2",
+];
+
 pub struct AWS {
     context: Context,
     id: String,
@@ -24,6 +51,7 @@ pub struct AWS {
     pub access_key_id: String,
     pub secret_access_key: String,
     terraform_state_credentials: TerraformStateCredentials,
+    credentials_environment_variable_aliases: EnvironmentVariableAliases,
     listeners: Listeners,
 }
 
@@ -45,10 +73,16 @@ impl AWS {
             access_key_id: access_key_id.to_string(),
             secret_access_key: secret_access_key.to_string(),
             terraform_state_credentials,
+            credentials_environment_variable_aliases: EnvironmentVariableAliases::default(),
             listeners: vec![],
         }
     }
 
+    /// rename the credentials environment variables given to the binaries
+    pub fn set_credentials_environment_variable_aliases(&mut self, aliases: EnvironmentVariableAliases) {
+        self.credentials_environment_variable_aliases = aliases;
+    }
+
     pub fn credentials(&self) -> StaticProvider {
         StaticProvider::new(
             self.access_key_id.to_string(),
@@ -103,10 +137,10 @@ impl CloudProvider for AWS {
     }
 
     fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
-        vec![
+        self.credentials_environment_variable_aliases.rename(vec![
             (AWS_ACCESS_KEY_ID, self.access_key_id.as_str()),
             (AWS_SECRET_ACCESS_KEY, self.secret_access_key.as_str()),
-        ]
+        ])
     }
 
     fn tera_context_environment_variables(&self) -> Vec<(&str, &str)> {
@@ -120,6 +154,18 @@ impl CloudProvider for AWS {
         &self.terraform_state_credentials
     }
 
+    fn required_binaries(&self) -> Vec<BinaryRequirement> {
+        vec![BinaryRequirement::new(
+            "aws",
+            vec!["--version"],
+            VersionsNumber::new(1, 18, 0),
+        )]
+    }
+
+    fn valid_regions(&self) -> &[&'static str] {
+        &AWS_REGIONS
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
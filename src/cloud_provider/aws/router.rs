@@ -4,8 +4,8 @@ use tera::Context as TeraContext;
 
 use crate::cloud_provider::models::{CustomDomain, CustomDomainDataTemplate, Route, RouteDataTemplate};
 use crate::cloud_provider::service::{
-    default_tera_context, delete_stateless_service, send_progress_on_long_task, Action, Create, Delete, Helm, Pause,
-    Router as RRouter, Service, ServiceType, StatelessService,
+    default_tera_context, delete_stateless_service, helm_release_labels, send_progress_on_long_task, Action, Create,
+    Delete, Endpoint, Helm, Pause, Router as RRouter, Service, ServiceType, StatelessService,
 };
 use crate::cloud_provider::utilities::{check_cname_for, sanitize_name};
 use crate::cloud_provider::DeploymentTarget;
@@ -78,6 +78,12 @@ impl Service for Router {
         None
     }
 
+    fn endpoints(&self, _namespace: &str) -> Vec<Endpoint> {
+        let mut endpoints = vec![Endpoint::external(self.default_domain.as_str())];
+        endpoints.extend(self.custom_domains.iter().map(|cd| Endpoint::external(cd.domain.as_str())));
+        endpoints
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
         Timeout::Default
     }
@@ -249,7 +255,7 @@ impl crate::cloud_provider::service::Router for Router {
 
 impl Helm for Router {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("router-{}", self.id()), 50)
+        crate::string::helm_release_name_safe("router", self.id())
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -330,6 +336,7 @@ impl Create for Router {
                     format!("custom-{}", helm_release_name).as_str(),
                     into_dir.as_str(),
                     format!("{}/nginx-ingress.yaml", into_dir.as_str()).as_str(),
+                    self.start_timeout(),
                     kubernetes.cloud_provider().credentials_environment_variables(),
                 ),
             )?;
@@ -387,8 +394,16 @@ impl Create for Router {
                 environment.namespace(),
                 helm_release_name.as_str(),
                 workspace_dir.as_str(),
-                Timeout::Default,
+                None,
+                &[],
+                &[],
+                self.start_timeout(),
+                false,
+                false,
+                &helm_release_labels(self),
                 kubernetes.cloud_provider().credentials_environment_variables(),
+                self.context.cancellation_token(),
+                None,
             ),
         )?;
 
@@ -432,7 +447,7 @@ impl Create for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Create,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -444,7 +459,7 @@ impl Pause for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, false)),
+            Box::new(|| delete_stateless_service(target, self, false, false)),
         )
     }
 
@@ -458,7 +473,7 @@ impl Pause for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -470,7 +485,7 @@ impl Delete for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, false)),
+            Box::new(|| delete_stateless_service(target, self, false, true)),
         )
     }
 
@@ -484,7 +499,7 @@ impl Delete for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
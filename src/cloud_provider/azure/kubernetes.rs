@@ -0,0 +1,528 @@
+use std::any::Any;
+use std::fs::File;
+
+use itertools::Itertools;
+use tera::Context as TeraContext;
+
+use crate::cloud_provider::azure::Azure;
+use crate::cloud_provider::environment::Environment;
+use crate::cloud_provider::kubernetes::{Kind, Kubernetes, KubernetesNode};
+use crate::cloud_provider::models::WorkerNodeDataTemplate;
+use crate::cloud_provider::{kubernetes, CloudProvider};
+use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::constants::AZURE_CONFIG_DIR;
+use crate::dns_provider::DnsProvider;
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, SimpleError};
+use crate::fs::workspace_directory;
+use crate::models::{
+    Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope, StringPath,
+};
+use crate::object_storage::s3::S3;
+use crate::object_storage::ObjectStorage;
+
+pub struct Node {
+    vm_size: String,
+}
+
+impl Node {
+    pub fn new<T: Into<String>>(vm_size: T) -> Self {
+        Node { vm_size: vm_size.into() }
+    }
+}
+
+impl KubernetesNode for Node {
+    fn instance_type(&self) -> &str {
+        self.vm_size.as_str()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct AKS<'a> {
+    context: Context,
+    id: String,
+    name: String,
+    version: String,
+    region: String,
+    cloud_provider: &'a Azure,
+    nodes: Vec<Node>,
+    dns_provider: &'a dyn DnsProvider,
+    s3: S3,
+    template_directory: String,
+    listeners: Listeners,
+}
+
+impl<'a> AKS<'a> {
+    pub fn new(
+        context: Context,
+        id: &str,
+        name: &str,
+        version: &str,
+        region: &str,
+        cloud_provider: &'a Azure,
+        dns_provider: &'a dyn DnsProvider,
+        nodes: Vec<Node>,
+    ) -> Self {
+        let template_directory = format!("{}/azure/bootstrap", context.lib_root_dir());
+
+        // terraform states are stored on AWS S3, like for Digital Ocean
+        let s3 = S3::new(
+            context.clone(),
+            "s3-temp-id".to_string(),
+            "default-s3".to_string(),
+            cloud_provider.terraform_state_credentials().access_key_id.clone(),
+            cloud_provider.terraform_state_credentials().secret_access_key.clone(),
+        );
+
+        AKS {
+            context,
+            id: id.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            region: region.to_string(),
+            cloud_provider,
+            nodes,
+            dns_provider,
+            s3,
+            template_directory,
+            listeners: cloud_provider.listeners().clone(), // copy listeners from CloudProvider
+        }
+    }
+
+    /// name of the cluster on Azure, as created by terraform
+    pub fn cluster_name(&self) -> String {
+        format!("qovery-{}", self.id())
+    }
+
+    /// each cluster has its own resource group, created by terraform
+    pub fn resource_group_name(&self) -> String {
+        format!("qovery-{}", self.id())
+    }
+
+    // create a context to render tf files (terraform) contained in lib/azure/
+    fn tera_context(&self) -> TeraContext {
+        let mut context = TeraContext::new();
+
+        // AKS
+        context.insert("aks_cluster_id", &self.id());
+        context.insert("aks_master_name", &self.name());
+        context.insert("aks_version", &self.version());
+
+        // Qovery
+        context.insert("organization_id", self.cloud_provider.organization_id());
+        context.insert("test_cluster", &self.context.is_test_cluster());
+
+        // Azure
+        context.insert("azure_region", self.region.as_str());
+        for (key, value) in self.cloud_provider.tera_context_environment_variables() {
+            context.insert(key, value);
+        }
+
+        // AWS S3 tfstates storage tfstates
+        context.insert(
+            "aws_access_key_tfstates_account",
+            self.cloud_provider()
+                .terraform_state_credentials()
+                .access_key_id
+                .as_str(),
+        );
+
+        context.insert(
+            "aws_secret_key_tfstates_account",
+            self.cloud_provider()
+                .terraform_state_credentials()
+                .secret_access_key
+                .as_str(),
+        );
+
+        context.insert(
+            "aws_region_tfstates_account",
+            self.cloud_provider().terraform_state_credentials().region.as_str(),
+        );
+
+        context.insert("aws_terraform_backend_dynamodb_table", "qovery-terrafom-tfstates");
+
+        context.insert("aws_terraform_backend_bucket", "qovery-terrafom-tfstates");
+
+        // kubernetes workers
+        let worker_nodes = self
+            .nodes
+            .iter()
+            .group_by(|e| e.instance_type())
+            .into_iter()
+            .map(|(instance_type, group)| (instance_type, group.collect::<Vec<_>>()))
+            .map(|(instance_type, nodes)| WorkerNodeDataTemplate {
+                instance_type: instance_type.to_string(),
+                desired_size: "1".to_string(),
+                max_size: nodes.len().to_string(),
+                min_size: "1".to_string(),
+            })
+            .collect::<Vec<WorkerNodeDataTemplate>>();
+
+        context.insert("aks_worker_nodes", &worker_nodes);
+
+        context
+    }
+
+    /// the kubeconfig is written by `az aks get-credentials`, `az` is the path to the Azure CLI
+    fn config_file_from_az(&self, az: &str) -> Result<(StringPath, File), EngineError> {
+        let workspace_dir = workspace_directory(
+            self.context.workspace_root_dir(),
+            self.context.execution_id(),
+            format!("azure/kubeconfigs/{}", self.id()),
+        );
+
+        let config_file_path = format!("{}/{}.yaml", workspace_dir, self.id());
+
+        // the az session is kept into the workspace, not into the home directory of the user
+        let mut envs = self.cloud_provider.credentials_environment_variables();
+        envs.push((AZURE_CONFIG_DIR, workspace_dir.as_str()));
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            az_exec_login(az, self.cloud_provider, envs.clone()),
+        )?;
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            az_exec_get_credentials(
+                az,
+                self.cluster_name().as_str(),
+                self.resource_group_name().as_str(),
+                self.cloud_provider.subscription_id.as_str(),
+                config_file_path.as_str(),
+                envs,
+            ),
+        )?;
+
+        match File::open(config_file_path.as_str()) {
+            Ok(file) => Ok((config_file_path, file)),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!("unable to open kubeconfig {}: {:?}", config_file_path, err),
+            )),
+        }
+    }
+}
+
+impl<'a> Kubernetes for AKS<'a> {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Aks
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    fn region(&self) -> &str {
+        self.region.as_str()
+    }
+
+    fn cloud_provider(&self) -> &dyn CloudProvider {
+        self.cloud_provider
+    }
+
+    fn dns_provider(&self) -> &dyn DnsProvider {
+        self.dns_provider
+    }
+
+    fn config_file_store(&self) -> &dyn ObjectStorage {
+        &self.s3
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        // the default node pool of the cluster is made of the first nodes
+        if self.nodes.is_empty() {
+            return Err(self.engine_error(
+                EngineErrorCause::User("An AKS cluster requires at least one node."),
+                format!("no node defined for the AKS cluster {}", self.name_with_id()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// the kubeconfig is not stored into an object storage but generated by az
+    fn download_config_file(&self) -> Result<(StringPath, File), EngineError> {
+        self.config_file_from_az("az")
+    }
+
+    fn on_create(&self) -> Result<(), EngineError> {
+        info!("AKS.on_create() called for {}", self.name());
+
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Infrastructure {
+                execution_id: self.context.execution_id().to_string(),
+            },
+            ProgressLevel::Info,
+            Some(format!(
+                "start to create Azure Kubernetes Service cluster {} with id {}",
+                self.name(),
+                self.id()
+            )),
+            self.context.execution_id(),
+        ));
+
+        let temp_dir = workspace_directory(
+            self.context.workspace_root_dir(),
+            self.context.execution_id(),
+            format!("azure/bootstrap/{}", self.name()),
+        );
+
+        // generate terraform files and copy them into temp dir
+        let context = self.tera_context();
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            crate::template::generate_and_copy_all_files_into_dir(
+                self.template_directory.as_str(),
+                temp_dir.as_str(),
+                &context,
+            ),
+        )?;
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            crate::cmd::terraform::terraform_exec_with_init_validate_plan_apply(
+                temp_dir.as_str(),
+                self.context.is_dry_run_deploy(),
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    fn on_create_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_upgrade(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_upgrade_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_downgrade(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_downgrade_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn deploy_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("AKS.deploy_environment() called for {}", self.name());
+        kubernetes::deploy_environment(self, environment)
+    }
+
+    fn deploy_environment_error(&self, environment: &Environment) -> Result<(), EngineError> {
+        warn!("AKS.deploy_environment_error() called for {}", self.name());
+        kubernetes::deploy_environment_error(self, environment)
+    }
+
+    fn pause_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("AKS.pause_environment() called for {}", self.name());
+        kubernetes::pause_environment(self, environment)
+    }
+
+    fn pause_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+        warn!("AKS.pause_environment_error() called for {}", self.name());
+        Ok(())
+    }
+
+    fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("AKS.delete_environment() called for {}", self.name());
+        kubernetes::delete_environment(self, environment)
+    }
+
+    fn delete_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+        warn!("AKS.delete_environment_error() called for {}", self.name());
+        Ok(())
+    }
+}
+
+impl<'a> Listen for AKS<'a> {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
+
+/// log in with the service principal of the cloud provider
+fn az_exec_login(az: &str, azure: &Azure, envs: Vec<(&str, &str)>) -> Result<(), SimpleError> {
+    exec_with_envs_and_output(
+        az,
+        vec![
+            "login",
+            "--service-principal",
+            "--username",
+            azure.client_id.as_str(),
+            "--password",
+            azure.client_secret.as_str(),
+            "--tenant",
+            azure.tenant_id.as_str(),
+        ],
+        envs,
+        |out| match out {
+            // the subscriptions of the service principal
+            Ok(line) => debug!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        chrono::Duration::max_value(),
+    )
+}
+
+fn az_exec_get_credentials(
+    az: &str,
+    cluster_name: &str,
+    resource_group_name: &str,
+    subscription_id: &str,
+    config_file_path: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError> {
+    exec_with_envs_and_output(
+        az,
+        vec![
+            "aks",
+            "get-credentials",
+            "--name",
+            cluster_name,
+            "--resource-group",
+            resource_group_name,
+            "--subscription",
+            subscription_id,
+            "--file",
+            config_file_path,
+            "--overwrite-existing",
+        ],
+        envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        chrono::Duration::max_value(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::cloud_provider::azure::kubernetes::{Node, AKS};
+    use crate::cloud_provider::azure::Azure;
+    use crate::cloud_provider::TerraformStateCredentials;
+    use crate::dns_provider::route53::Route53;
+    use crate::models::Context;
+
+    /// a fake `az` writing a kubeconfig where `--file` points to
+    fn az_stub(dir: &str) -> String {
+        let _ = fs::remove_dir_all(dir);
+        fs::create_dir_all(dir).unwrap();
+
+        let path = format!("{}/az", dir);
+        fs::write(
+            path.as_str(),
+            concat!(
+                "#!/bin/sh\n",
+                "while [ $# -gt 0 ]; do\n",
+                "  if [ \"$1\" = \"--file\" ]; then echo \"apiVersion: v1\" > \"$2\"; fi\n",
+                "  shift\n",
+                "done\n",
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(path.as_str(), fs::Permissions::from_mode(0o755)).unwrap();
+
+        path
+    }
+
+    #[test]
+    fn test_config_file_from_az() {
+        let tmp_dir = std::env::temp_dir().to_str().unwrap().to_string();
+        let context = Context::new(
+            "execution_id".to_string(),
+            tmp_dir.clone(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        );
+
+        let azure = Azure::new(
+            context.clone(),
+            "azure-id",
+            "organization-id",
+            "subscription-id",
+            "tenant-id",
+            "client-id",
+            "client-secret",
+            "azure",
+            TerraformStateCredentials::new("access-key-id", "secret-access-key", "eu-west-3"),
+        );
+        let route53 = Route53::new(context.clone(), "route53-id", "route53", "example.com", "hosted-zone-id");
+        let aks = AKS::new(
+            context,
+            "my-aks",
+            "my-aks",
+            "1.19.7",
+            "westeurope",
+            &azure,
+            &route53,
+            vec![Node::new("Standard_B2s")],
+        );
+
+        let az = az_stub(format!("{}/qovery-engine-az-stub", tmp_dir).as_str());
+        let (config_file_path, _) = aks.config_file_from_az(az.as_str()).unwrap();
+
+        assert_eq!(
+            config_file_path,
+            format!(
+                "{}/.qovery-workspace/execution_id/azure/kubeconfigs/my-aks/my-aks.yaml",
+                tmp_dir
+            )
+        );
+        assert_eq!(fs::read_to_string(config_file_path).unwrap(), "apiVersion: v1\n");
+    }
+}
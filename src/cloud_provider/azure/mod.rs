@@ -0,0 +1,145 @@
+use std::any::Any;
+
+use crate::cloud_provider::utilities::VersionsNumber;
+use crate::cloud_provider::{CloudProvider, EnvironmentVariableAliases, Kind, TerraformStateCredentials};
+use crate::cmd::preflight::BinaryRequirement;
+use crate::constants::{AZURE_CLIENT_ID, AZURE_CLIENT_SECRET, AZURE_SUBSCRIPTION_ID, AZURE_TENANT_ID};
+use crate::error::{EngineError, EngineErrorCause};
+use crate::models::{Context, Listen, Listener, Listeners};
+
+pub mod kubernetes;
+
+pub struct Azure {
+    context: Context,
+    id: String,
+    organization_id: String,
+    name: String,
+    pub subscription_id: String,
+    pub tenant_id: String,
+    // the service principal used by the engine
+    pub client_id: String,
+    client_secret: String,
+    terraform_state_credentials: TerraformStateCredentials,
+    credentials_environment_variable_aliases: EnvironmentVariableAliases,
+    listeners: Listeners,
+}
+
+impl Azure {
+    pub fn new(
+        context: Context,
+        id: &str,
+        organization_id: &str,
+        subscription_id: &str,
+        tenant_id: &str,
+        client_id: &str,
+        client_secret: &str,
+        name: &str,
+        terraform_state_credentials: TerraformStateCredentials,
+    ) -> Self {
+        Azure {
+            context,
+            id: id.to_string(),
+            organization_id: organization_id.to_string(),
+            name: name.to_string(),
+            subscription_id: subscription_id.to_string(),
+            tenant_id: tenant_id.to_string(),
+            client_id: client_id.to_string(),
+            client_secret: client_secret.to_string(),
+            terraform_state_credentials,
+            credentials_environment_variable_aliases: EnvironmentVariableAliases::default(),
+            listeners: vec![],
+        }
+    }
+
+    /// rename the credentials environment variables given to the binaries
+    pub fn set_credentials_environment_variable_aliases(&mut self, aliases: EnvironmentVariableAliases) {
+        self.credentials_environment_variable_aliases = aliases;
+    }
+}
+
+impl CloudProvider for Azure {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Azure
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn organization_id(&self) -> &str {
+        self.organization_id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        let credentials = [
+            self.subscription_id.as_str(),
+            self.tenant_id.as_str(),
+            self.client_id.as_str(),
+            self.client_secret.as_str(),
+        ];
+
+        if credentials.iter().any(|credential| credential.trim().is_empty()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Your Azure service principal credentials are incomplete. \
+                    Please contact your Organization administrator to fix or change the Credentials.",
+                ),
+                format!("missing Azure credentials for {}", self.name_with_id()),
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
+        self.credentials_environment_variable_aliases.rename(vec![
+            (AZURE_SUBSCRIPTION_ID, self.subscription_id.as_str()),
+            (AZURE_TENANT_ID, self.tenant_id.as_str()),
+            (AZURE_CLIENT_ID, self.client_id.as_str()),
+            (AZURE_CLIENT_SECRET, self.client_secret.as_str()),
+        ])
+    }
+
+    fn tera_context_environment_variables(&self) -> Vec<(&str, &str)> {
+        vec![
+            ("azure_subscription_id", self.subscription_id.as_str()),
+            ("azure_tenant_id", self.tenant_id.as_str()),
+            ("azure_client_id", self.client_id.as_str()),
+            ("azure_client_secret", self.client_secret.as_str()),
+        ]
+    }
+
+    fn terraform_state_credentials(&self) -> &TerraformStateCredentials {
+        &self.terraform_state_credentials
+    }
+
+    fn required_binaries(&self) -> Vec<BinaryRequirement> {
+        vec![BinaryRequirement::new(
+            "az",
+            vec!["version"],
+            VersionsNumber::new(2, 10, 0),
+        )]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Listen for Azure {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
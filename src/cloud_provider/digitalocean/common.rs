@@ -1,9 +1,11 @@
 extern crate serde_json;
 
+use reqwest::blocking::Response;
 use reqwest::StatusCode;
 
-use crate::cloud_provider::digitalocean::models::cluster::Clusters;
+use crate::cloud_provider::digitalocean::models::cluster::{Cluster, Clusters};
 use crate::cloud_provider::digitalocean::models::load_balancers::LoadBalancer;
+use crate::cmd::retry::{retry, ExponentialBackoff};
 use crate::error::{SimpleError, SimpleErrorKind};
 use crate::utilities::get_header_with_bearer;
 use std::net::Ipv4Addr;
@@ -65,36 +67,25 @@ pub fn do_get_load_balancer_ip(token: &str, load_balancer_id: &str) -> Result<Ip
 // retrieve the digital ocean uuid of the kube cluster from our cluster name
 // each (terraform) apply may change the cluster uuid, so We need to retrieve it from the Digital Ocean API
 pub fn get_uuid_of_cluster_from_name(token: &str, kube_cluster_name: &str) -> Result<String, SimpleError> {
-    let headers = get_header_with_bearer(token);
-    let res = reqwest::blocking::Client::new()
-        .get(DO_CLUSTER_API_PATH)
-        .headers(headers)
-        .send();
+    match find_uuid_of_cluster_from_name(token, kube_cluster_name)? {
+        Some(uuid) => Ok(uuid),
+        None => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "Unable to retrieve cluster id from the cluster name {}",
+                kube_cluster_name
+            )),
+        )),
+    }
+}
 
-    return match res {
+// same as get_uuid_of_cluster_from_name(..) but a missing cluster is not an error
+pub fn find_uuid_of_cluster_from_name(token: &str, kube_cluster_name: &str) -> Result<Option<String>, SimpleError> {
+    return match do_api_get(token, DO_CLUSTER_API_PATH) {
         Ok(response) => match response.status() {
             StatusCode::OK => {
                 let content = response.text().unwrap();
-                let res_clusters = serde_json::from_str::<Clusters>(&content);
-                match res_clusters {
-                    Ok(clusters) => match search_uuid_cluster_for(kube_cluster_name, clusters) {
-                        Some(uuid) => Ok(uuid),
-                        None => Err(SimpleError::new(
-                            SimpleErrorKind::Other,
-                            Some(format!(
-                                "Unable to retrieve cluster id from the cluster name {}",
-                                kube_cluster_name
-                            )),
-                        )),
-                    },
-                    Err(e) => {
-                        print!("{}", e);
-                        Err(SimpleError::new(
-                            SimpleErrorKind::Other,
-                            Some("While trying to deserialize json received from Digital Ocean Kubernetes API"),
-                        ))
-                    }
-                }
+                get_uuid_of_cluster_from_do_api_output(content.as_str(), kube_cluster_name)
             }
             _ => Err(SimpleError::new(
                 SimpleErrorKind::Other,
@@ -108,6 +99,134 @@ pub fn get_uuid_of_cluster_from_name(token: &str, kube_cluster_name: &str) -> Re
     };
 }
 
+// the API throttles the requests (429) and sometimes fails (5xx), such requests are sent again
+fn do_api_get(token: &str, url: &str) -> Result<Response, reqwest::Error> {
+    let client = reqwest::blocking::Client::new();
+    let headers = get_header_with_bearer(token);
+
+    retry(&ExponentialBackoff::cloud_api(), is_transient_do_api_error, || {
+        client.get(url).headers(headers.clone()).send()?.error_for_status()
+    })
+}
+
+fn is_transient_do_api_error(err: &reqwest::Error) -> bool {
+    match err.status() {
+        Some(status) => is_transient_status(status),
+        // the request did not reach the API, e.g. the connection was refused or reset
+        None => err.is_timeout() || err.is_request(),
+    }
+}
+
+fn is_transient_status(status: StatusCode) -> bool {
+    status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+}
+
+pub fn get_uuid_of_cluster_from_do_api_output(
+    json_content: &str,
+    kube_cluster_name: &str,
+) -> Result<Option<String>, SimpleError> {
+    match serde_json::from_str::<Clusters>(json_content) {
+        Ok(clusters) => Ok(search_uuid_cluster_for(kube_cluster_name, clusters)),
+        Err(e) => {
+            print!("{}", e);
+            Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some("While trying to deserialize json received from Digital Ocean Kubernetes API"),
+            ))
+        }
+    }
+}
+
+// the version slug (e.g. `1.18.8-do.0`) the control plane of the cluster is running
+pub fn do_get_cluster_version(token: &str, cluster_uuid: &str) -> Result<String, SimpleError> {
+    let headers = get_header_with_bearer(token);
+    let url = format!("{}/{}", DO_CLUSTER_API_PATH, cluster_uuid);
+    let res = reqwest::blocking::Client::new().get(&url).headers(headers).send();
+
+    match res {
+        Ok(response) => match response.status() {
+            StatusCode::OK => {
+                let content = response.text().unwrap();
+                get_version_of_cluster_from_do_api_output(content.as_str())
+            }
+            _ => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some("Receive unknown status code from Digital Ocean Kubernetes API while retrieving cluster information"),
+            )),
+        },
+        Err(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some("Unable to get a response from Digital Ocean Kubernetes API"),
+        )),
+    }
+}
+
+pub fn get_version_of_cluster_from_do_api_output(json_content: &str) -> Result<String, SimpleError> {
+    match serde_json::from_str::<Cluster>(json_content) {
+        Ok(cluster) => Ok(cluster.kubernetes_cluster.version),
+        Err(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some("While trying to deserialize json received from Digital Ocean Kubernetes API"),
+        )),
+    }
+}
+
+// ask Digital Ocean to upgrade the cluster to the version slug, the upgrade itself is asynchronous
+pub fn do_upgrade_cluster(token: &str, cluster_uuid: &str, version: &str) -> Result<(), SimpleError> {
+    let headers = get_header_with_bearer(token);
+    let url = format!("{}/{}/upgrade", DO_CLUSTER_API_PATH, cluster_uuid);
+    let res = reqwest::blocking::Client::new()
+        .post(&url)
+        .headers(headers)
+        .body(serde_json::json!({ "version": version }).to_string())
+        .send();
+
+    match res {
+        Ok(response) => match response.status() {
+            StatusCode::ACCEPTED | StatusCode::NO_CONTENT => Ok(()),
+            status => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!(
+                    "Receive status code {} from Digital Ocean Kubernetes API while upgrading the cluster to {}: {}",
+                    status,
+                    version,
+                    response.text().unwrap_or_default()
+                )),
+            )),
+        },
+        Err(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some("Unable to get a response from Digital Ocean Kubernetes API"),
+        )),
+    }
+}
+
+// ask Digital Ocean to delete the cluster, the deletion itself is asynchronous
+pub fn do_delete_cluster(token: &str, cluster_uuid: &str) -> Result<(), SimpleError> {
+    let headers = get_header_with_bearer(token);
+    let url = format!("{}/{}", DO_CLUSTER_API_PATH, cluster_uuid);
+    let res = reqwest::blocking::Client::new().delete(&url).headers(headers).send();
+
+    match res {
+        Ok(response) => match response.status() {
+            // the cluster may have been deleted in the meantime
+            StatusCode::ACCEPTED | StatusCode::NO_CONTENT | StatusCode::NOT_FOUND => Ok(()),
+            status => Err(SimpleError::new(
+                SimpleErrorKind::Other,
+                Some(format!(
+                    "Receive status code {} from Digital Ocean Kubernetes API while deleting the cluster: {}",
+                    status,
+                    response.text().unwrap_or_default()
+                )),
+            )),
+        },
+        Err(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some("Unable to get a response from Digital Ocean Kubernetes API"),
+        )),
+    }
+}
+
 fn search_uuid_cluster_for(kube_name: &str, clusters: Clusters) -> Option<String> {
     for cluster in clusters.kubernetes_clusters {
         match cluster.name.eq(kube_name) {
@@ -120,7 +239,10 @@ fn search_uuid_cluster_for(kube_name: &str, clusters: Clusters) -> Option<String
 
 #[cfg(test)]
 mod tests_do_api_output {
-    use crate::cloud_provider::digitalocean::common::get_ip_from_do_load_balancer_api_output;
+    use crate::cloud_provider::digitalocean::common::{
+        get_ip_from_do_load_balancer_api_output, get_uuid_of_cluster_from_do_api_output, is_transient_status,
+    };
+    use reqwest::StatusCode;
 
     #[test]
     fn check_load_balancer_ip() {
@@ -209,4 +331,82 @@ mod tests_do_api_output {
 
         assert_eq!(ip_returned_from_api.unwrap().to_string(), "104.131.186.241");
     }
+
+    #[test]
+    fn check_uuid_of_cluster_from_name() {
+        // https://developers.digitalocean.com/documentation/v2/#list-all-kubernetes-clusters
+        let json_content = r#"
+{
+  "kubernetes_clusters": [
+    {
+      "id": "bd5f5959-5e1e-4205-a714-a914373942af",
+      "name": "qovery-z1234",
+      "region": "nyc1",
+      "version": "1.18.8-do.0",
+      "cluster_subnet": "10.244.0.0/16",
+      "service_subnet": "10.245.0.0/16",
+      "vpc_uuid": "c33931f2-a26a-4e61-b85c-4e95a2ec431b",
+      "ipv4": "68.183.121.157",
+      "endpoint": "https://bd5f5959-5e1e-4205-a714-a914373942af.k8s.ondigitalocean.com",
+      "tags": ["k8s", "k8s:bd5f5959-5e1e-4205-a714-a914373942af"],
+      "node_pools": [
+        {
+          "id": "cdda885e-7663-40c8-bc74-3a036c66545d",
+          "name": "worker-pool",
+          "size": "s-1vcpu-2gb",
+          "count": 3,
+          "tags": ["k8s", "k8s:worker"],
+          "labels": null,
+          "taints": [],
+          "auto_scale": false,
+          "min_nodes": 0,
+          "max_nodes": 0
+        }
+      ],
+      "maintenance_policy": {
+        "start_time": "00:00",
+        "duration": "4h0m0s",
+        "day": "any"
+      },
+      "auto_upgrade": false,
+      "status": {
+        "state": "running"
+      },
+      "created_at": "2018-11-15T16:00:11Z",
+      "updated_at": "2018-11-15T16:00:11Z",
+      "surge_upgrade": false,
+      "registry_enabled": false
+    }
+  ],
+  "meta": {
+    "total": 1
+  },
+  "links": {}
+}
+        "#;
+
+        // the cluster already exists
+        assert_eq!(
+            get_uuid_of_cluster_from_do_api_output(json_content, "qovery-z1234").unwrap(),
+            Some("bd5f5959-5e1e-4205-a714-a914373942af".to_string())
+        );
+
+        // the cluster does not exist yet
+        assert_eq!(
+            get_uuid_of_cluster_from_do_api_output(json_content, "qovery-z5678").unwrap(),
+            None
+        );
+        assert_eq!(
+            get_uuid_of_cluster_from_do_api_output(r#"{"kubernetes_clusters": []}"#, "qovery-z1234").unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn check_transient_status() {
+        assert!(is_transient_status(StatusCode::TOO_MANY_REQUESTS));
+        assert!(is_transient_status(StatusCode::SERVICE_UNAVAILABLE));
+        assert!(!is_transient_status(StatusCode::UNAUTHORIZED));
+        assert!(!is_transient_status(StatusCode::NOT_FOUND));
+    }
 }
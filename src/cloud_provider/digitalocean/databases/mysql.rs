@@ -192,7 +192,7 @@ impl Database for MySQL {}
 
 impl Helm for MySQL {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("mysql-{}", self.id()), 50)
+        crate::string::helm_release_name_safe("mysql", self.id())
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -1,16 +1,25 @@
+use std::collections::BTreeMap;
+
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use tera::Context as TeraContext;
 
-use crate::cloud_provider::digitalocean::kubernetes::node::Node;
+use crate::build_platform::Architecture;
+use crate::cloud_provider::digitalocean::common::{
+    do_delete_cluster, do_get_cluster_version, do_upgrade_cluster, find_uuid_of_cluster_from_name,
+    get_uuid_of_cluster_from_name,
+};
+use crate::cloud_provider::digitalocean::kubernetes::node::{Node, NodePoolAutoscaling, NodePoolDataTemplate};
 use crate::cloud_provider::digitalocean::DO;
 use crate::cloud_provider::environment::Environment;
-use crate::cloud_provider::kubernetes::{Kind, Kubernetes, KubernetesNode};
-use crate::cloud_provider::models::WorkerNodeDataTemplate;
+use crate::cloud_provider::kubernetes::{
+    parse_kubernetes_version, Kind, KubeconfigCache, Kubernetes, KubernetesNode, KUBECONFIG_CACHE_TTL,
+};
 use crate::cloud_provider::{kubernetes, CloudProvider};
 use crate::dns_provider;
 use crate::dns_provider::DnsProvider;
-use crate::error::{cast_simple_error_to_engine_error, EngineError};
+use crate::cmd::kubectl::{is_ready_with_policy, RetryPolicy};
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, SimpleError};
 use crate::fs::workspace_directory;
 use crate::models::{
     Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope,
@@ -40,6 +49,50 @@ pub struct Options {
     pub qovery_ssh_key: String,
     // Others
     pub tls_email_report: String,
+    // node pools without autoscaling bounds scale from 1 to their number of nodes
+    #[serde(default)]
+    pub node_pools_autoscaling: Vec<NodePoolAutoscaling>,
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    // the node pools are amd64 unless their droplets are arm64
+    #[serde(default)]
+    pub node_architecture: Architecture,
+}
+
+// a Digital Ocean tag is a single name of at most 255 characters, tags are rendered as `key:value`
+const DO_TAG_KEY_MAX_LENGTH: usize = 127;
+const DO_TAG_VALUE_MAX_LENGTH: usize = 127;
+
+/// the calls to the Digital Ocean Kubernetes API made by an upgrade or a deletion
+pub trait ClusterApi {
+    /// the version slug (e.g. `1.18.8-do.0`) the control plane is running
+    fn cluster_version(&self, cluster_uuid: &str) -> Result<String, SimpleError>;
+    fn upgrade_cluster(&self, cluster_uuid: &str, version: &str) -> Result<(), SimpleError>;
+    /// the uuid of the cluster, `None` when there is no cluster with this name
+    fn find_cluster_uuid(&self, cluster_name: &str) -> Result<Option<String>, SimpleError>;
+    fn delete_cluster(&self, cluster_uuid: &str) -> Result<(), SimpleError>;
+}
+
+struct DoClusterApi<'a> {
+    token: &'a str,
+}
+
+impl<'a> ClusterApi for DoClusterApi<'a> {
+    fn cluster_version(&self, cluster_uuid: &str) -> Result<String, SimpleError> {
+        do_get_cluster_version(self.token, cluster_uuid)
+    }
+
+    fn upgrade_cluster(&self, cluster_uuid: &str, version: &str) -> Result<(), SimpleError> {
+        do_upgrade_cluster(self.token, cluster_uuid, version)
+    }
+
+    fn find_cluster_uuid(&self, cluster_name: &str) -> Result<Option<String>, SimpleError> {
+        find_uuid_of_cluster_from_name(self.token, cluster_name)
+    }
+
+    fn delete_cluster(&self, cluster_uuid: &str) -> Result<(), SimpleError> {
+        do_delete_cluster(self.token, cluster_uuid)
+    }
 }
 
 pub struct DOKS<'a> {
@@ -176,6 +229,10 @@ impl<'a> DOKS<'a> {
                 context.insert("cloudflare_api_token", self.dns_provider.token());
                 context.insert("cloudflare_email", self.dns_provider.account());
             }
+            dns_provider::Kind::Route53 => {
+                context.insert("external_dns_provider", "aws");
+                context.insert("route53_hosted_zone_id", self.dns_provider.account());
+            }
         };
 
         // Digital Ocean
@@ -222,18 +279,201 @@ impl<'a> DOKS<'a> {
             .group_by(|e| e.instance_type())
             .into_iter()
             .map(|(instance_type, group)| (instance_type, group.collect::<Vec<_>>()))
-            .map(|(instance_type, nodes)| WorkerNodeDataTemplate {
-                instance_type: instance_type.to_string(),
-                desired_size: "1".to_string(),
-                max_size: nodes.len().to_string(),
-                min_size: "1".to_string(),
+            .map(|(instance_type, nodes)| {
+                let (min_nodes, max_nodes) = match self.node_pool_autoscaling(instance_type) {
+                    Some(autoscaling) => (autoscaling.min_nodes, autoscaling.max_nodes),
+                    None => (1, nodes.len() as u16),
+                };
+
+                NodePoolDataTemplate {
+                    instance_type: instance_type.to_string(),
+                    auto_scale: min_nodes != max_nodes,
+                    desired_size: min_nodes.to_string(),
+                    min_size: min_nodes.to_string(),
+                    max_size: max_nodes.to_string(),
+                }
             })
-            .collect::<Vec<WorkerNodeDataTemplate>>();
+            .collect::<Vec<NodePoolDataTemplate>>();
 
         context.insert("doks_worker_nodes", &worker_nodes);
 
+        let tags = self
+            .tags()
+            .iter()
+            .map(|(key, value)| format!("{}:{}", key, value))
+            .collect::<Vec<_>>();
+
+        context.insert("doks_tags", &tags);
+
         context
     }
+
+    fn node_pool_autoscaling(&self, instance_type: &str) -> Option<&NodePoolAutoscaling> {
+        self.options
+            .node_pools_autoscaling
+            .iter()
+            .find(|autoscaling| autoscaling.instance_type == instance_type)
+    }
+
+    fn cluster_api(&self) -> DoClusterApi {
+        DoClusterApi {
+            token: self.cloud_provider.token.as_str(),
+        }
+    }
+
+    fn cluster_uuid(&self) -> Result<String, EngineError> {
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            get_uuid_of_cluster_from_name(
+                self.cloud_provider.token.as_str(),
+                format!("qovery-{}", self.id()).as_str(),
+            ),
+        )
+    }
+
+    /// the version slug (e.g. `1.18.8-do.0`) the running cluster reports, `version()` is only the creation one
+    pub fn current_version(&self) -> Result<String, EngineError> {
+        let cluster_uuid = self.cluster_uuid()?;
+
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            self.cluster_api().cluster_version(cluster_uuid.as_str()),
+        )
+    }
+
+    fn upgrade_cluster(
+        &self,
+        cluster_api: &dyn ClusterApi,
+        cluster_uuid: &str,
+        target_version: &str,
+        retry_policy: &RetryPolicy,
+    ) -> Result<(), EngineError> {
+        let current_version = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            cluster_api.cluster_version(cluster_uuid),
+        )?;
+
+        if current_version == target_version {
+            info!("{} already runs the version {}", self.name_with_id(), target_version);
+            return Ok(());
+        }
+
+        let target = match parse_kubernetes_version(target_version) {
+            Ok(target) => target,
+            Err(message) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("The target Kubernetes version is not valid"),
+                    format!("invalid target version of {}: {}", self.name_with_id(), message),
+                ));
+            }
+        };
+
+        match parse_kubernetes_version(current_version.as_str()) {
+            Ok(current) if target < current => {
+                return Err(self.engine_error(
+                    EngineErrorCause::User("Downgrading the Kubernetes version of a cluster is not supported"),
+                    format!(
+                        "{} runs the version {}, it can't be downgraded to {}",
+                        self.name_with_id(),
+                        current_version,
+                        target_version
+                    ),
+                ));
+            }
+            Ok(_) => {}
+            // the version reported by Digital Ocean can't be compared, let it validate the upgrade
+            Err(message) => warn!("unable to compare the current version of {}: {}", self.name_with_id(), message),
+        }
+
+        info!(
+            "upgrading {} from the version {} to {}",
+            self.name_with_id(),
+            current_version,
+            target_version
+        );
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            cluster_api.upgrade_cluster(cluster_uuid, target_version),
+        )?;
+
+        let resource = format!("upgrade of {} to {}", self.name_with_id(), target_version);
+        let upgraded = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            is_ready_with_policy(retry_policy, resource.as_str(), || {
+                cluster_api
+                    .cluster_version(cluster_uuid)
+                    .map(|version| Some(version == target_version))
+            }),
+        )?;
+
+        match upgraded {
+            Some(true) => Ok(()),
+            _ => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "{} still does not report the version {} after its upgrade",
+                    self.name_with_id(),
+                    target_version
+                ),
+            )),
+        }
+    }
+
+    /// delete the cluster and wait for it to disappear, deleting an already deleted cluster succeeds
+    fn delete_cluster(&self, cluster_api: &dyn ClusterApi, retry_policy: &RetryPolicy) -> Result<(), EngineError> {
+        let cluster_name = format!("qovery-{}", self.id());
+
+        let cluster_uuid = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            cluster_api.find_cluster_uuid(cluster_name.as_str()),
+        )?;
+
+        match cluster_uuid {
+            Some(cluster_uuid) => {
+                info!("deleting {} with uuid {}", self.name_with_id(), cluster_uuid);
+
+                let _ = cast_simple_error_to_engine_error(
+                    self.engine_error_scope(),
+                    self.context.execution_id(),
+                    cluster_api.delete_cluster(cluster_uuid.as_str()),
+                )?;
+
+                let resource = format!("deletion of {}", self.name_with_id());
+                let deleted = cast_simple_error_to_engine_error(
+                    self.engine_error_scope(),
+                    self.context.execution_id(),
+                    is_ready_with_policy(retry_policy, resource.as_str(), || {
+                        cluster_api
+                            .find_cluster_uuid(cluster_name.as_str())
+                            .map(|cluster_uuid| Some(cluster_uuid.is_none()))
+                    }),
+                )?;
+
+                if deleted != Some(true) {
+                    return Err(self.engine_error(
+                        EngineErrorCause::Internal,
+                        format!("{} still exists after its deletion", self.name_with_id()),
+                    ));
+                }
+            }
+            None => info!("{} is already deleted", self.name_with_id()),
+        }
+
+        // the cached kubeconfig of a deleted cluster is useless, a new cluster with the same id gets a new one
+        let kubeconfig_cache = KubeconfigCache::new(self.context.workspace_root_dir(), KUBECONFIG_CACHE_TTL);
+        if let Err(err) = kubeconfig_cache.remove(self.id()) {
+            warn!("unable to remove the cached kubeconfig of {}: {:?}", self.name_with_id(), err);
+        }
+
+        Ok(())
+    }
 }
 
 impl<'a> Kubernetes for DOKS<'a> {
@@ -274,24 +514,103 @@ impl<'a> Kubernetes for DOKS<'a> {
     }
 
     fn is_valid(&self) -> Result<(), EngineError> {
+        if let Err(message) = kubernetes::validate_region(self.region(), self.cloud_provider.valid_regions()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The region of the cluster is not a Digital Ocean Kubernetes region"),
+                format!("invalid region of {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        if let Err(message) = parse_kubernetes_version(self.version()) {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The Kubernetes version of the cluster is not valid"),
+                format!("invalid version of {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        if let Err(message) =
+            kubernetes::validate_tags(&self.options.tags, DO_TAG_KEY_MAX_LENGTH, DO_TAG_VALUE_MAX_LENGTH)
+        {
+            return Err(self.engine_error(
+                EngineErrorCause::User("The cluster tags exceed the length limits of Digital Ocean"),
+                format!("invalid tags of {}: {}", self.name_with_id(), message),
+            ));
+        }
+
+        for autoscaling in &self.options.node_pools_autoscaling {
+            if autoscaling.min_nodes < 1 || autoscaling.min_nodes > autoscaling.max_nodes {
+                return Err(self.engine_error(
+                    EngineErrorCause::User(
+                        "The node pool autoscaling is not valid, the minimum number of nodes must be at least 1 \
+                        and lower or equal to the maximum number of nodes",
+                    ),
+                    format!(
+                        "invalid autoscaling of the {} node pool of {}: min nodes {}, max nodes {}",
+                        autoscaling.instance_type,
+                        self.name_with_id(),
+                        autoscaling.min_nodes,
+                        autoscaling.max_nodes
+                    ),
+                ));
+            }
+        }
+
         Ok(())
     }
 
+    fn tags(&self) -> BTreeMap<String, String> {
+        self.options.tags.clone()
+    }
+
+    fn node_architecture(&self) -> Architecture {
+        self.options.node_architecture
+    }
+
+    fn exists(&self) -> Result<Option<String>, EngineError> {
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            crate::cloud_provider::digitalocean::common::find_uuid_of_cluster_from_name(
+                self.cloud_provider.token.as_str(),
+                format!("qovery-{}", self.id()).as_str(),
+            ),
+        )
+    }
+
     fn on_create(&self) -> Result<(), EngineError> {
         info!("DOKS.on_create() called for {}", self.name());
 
         let listeners_helper = ListenersHelper::new(&self.listeners);
 
+        // the terraform state is remote, an existing cluster is not created again: terraform and the charts
+        // reconcile it with the expected configuration
+        let message = match self.exists()? {
+            Some(cluster_uuid) => {
+                info!(
+                    "cluster already exists, reconciling {} with uuid {}",
+                    self.name_with_id(),
+                    cluster_uuid
+                );
+
+                format!(
+                    "Digital Ocean Kubernetes cluster {} with id {} already exists, skip its creation and reconcile it",
+                    self.name(),
+                    self.id()
+                )
+            }
+            None => format!(
+                "start to create Digital Ocean Kubernetes cluster {} with id {}",
+                self.name(),
+                self.id()
+            ),
+        };
+
         listeners_helper.deployment_in_progress(ProgressInfo::new(
             ProgressScope::Infrastructure {
                 execution_id: self.context.execution_id().to_string(),
             },
             ProgressLevel::Info,
-            Some(format!(
-                "start to create Digital Ocean Kubernetes cluster {} with id {}",
-                self.name(),
-                self.id()
-            )),
+            Some(message),
             self.context.execution_id(),
         ));
 
@@ -350,6 +669,21 @@ impl<'a> Kubernetes for DOKS<'a> {
         Ok(())
     }
 
+    fn upgrade_version(&self, target_version: &str) -> Result<(), EngineError> {
+        info!("DOKS.upgrade_version() called for {}", self.name());
+
+        let cluster_uuid = self.cluster_uuid()?;
+
+        // the control plane is upgraded first, then the nodes one by one
+        let retry_policy = RetryPolicy {
+            max_attempts: 120,
+            interval: std::time::Duration::from_secs(15),
+            cancellation_token: self.context.cancellation_token().clone(),
+        };
+
+        self.upgrade_cluster(&self.cluster_api(), cluster_uuid.as_str(), target_version, &retry_policy)
+    }
+
     fn on_downgrade(&self) -> Result<(), EngineError> {
         Ok(())
     }
@@ -359,7 +693,30 @@ impl<'a> Kubernetes for DOKS<'a> {
     }
 
     fn on_delete(&self) -> Result<(), EngineError> {
-        Ok(())
+        info!("DOKS.on_delete() called for {}", self.name());
+
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+        listeners_helper.delete_in_progress(ProgressInfo::new(
+            ProgressScope::Infrastructure {
+                execution_id: self.context.execution_id().to_string(),
+            },
+            ProgressLevel::Info,
+            Some(format!(
+                "start to delete Digital Ocean Kubernetes cluster {} with id {}",
+                self.name(),
+                self.id()
+            )),
+            self.context.execution_id(),
+        ));
+
+        // the node pools and the load balancers are deleted with the cluster
+        let retry_policy = RetryPolicy {
+            max_attempts: 80,
+            interval: std::time::Duration::from_secs(15),
+            cancellation_token: self.context.cancellation_token().clone(),
+        };
+
+        self.delete_cluster(&self.cluster_api(), &retry_policy)
     }
 
     fn on_delete_error(&self) -> Result<(), EngineError> {
@@ -406,3 +763,380 @@ impl<'a> Listen for DOKS<'a> {
         self.listeners.push(listener);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::collections::BTreeMap;
+
+    use serde_json::json;
+
+    use crate::cloud_provider::digitalocean::common::{
+        get_uuid_of_cluster_from_do_api_output, get_version_of_cluster_from_do_api_output,
+    };
+    use crate::cloud_provider::digitalocean::kubernetes::node::{Node, NodePoolAutoscaling};
+    use crate::cloud_provider::digitalocean::kubernetes::{ClusterApi, Options, DOKS};
+    use crate::cloud_provider::digitalocean::DO;
+    use crate::cloud_provider::kubernetes::{KubeconfigCache, Kubernetes, KUBECONFIG_CACHE_TTL};
+    use crate::cloud_provider::TerraformStateCredentials;
+    use crate::cmd::kubectl::RetryPolicy;
+    use crate::dns_provider::route53::Route53;
+    use crate::error::{EngineErrorCause, SimpleError};
+    use crate::models::{CancellationToken, Context};
+
+    fn context() -> Context {
+        Context::new(
+            "execution_id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        )
+    }
+
+    fn cloud_provider() -> DO {
+        DO::new(
+            context(),
+            "cloud-provider-id",
+            "organization-id",
+            "token",
+            "spaces-access-id",
+            "spaces-secret-key",
+            "cloud-provider",
+            TerraformStateCredentials {
+                access_key_id: "access-key-id".to_string(),
+                secret_access_key: "secret-access-key".to_string(),
+                region: "eu-west-3".to_string(),
+            },
+        )
+    }
+
+    fn dns_provider() -> Route53 {
+        Route53::new(context(), "dns-provider-id", "dns-provider", "example.com", "hosted-zone-id")
+    }
+
+    fn doks<'a>(cloud_provider: &'a DO, dns_provider: &'a Route53, options: Options) -> DOKS<'a> {
+        DOKS::new(
+            context(),
+            "cluster-id",
+            "cluster",
+            "1.18",
+            "fra1",
+            cloud_provider,
+            dns_provider,
+            options,
+            vec![
+                Node::new("s-4vcpu-8gb"),
+                Node::new("s-4vcpu-8gb"),
+                Node::new("s-4vcpu-8gb"),
+                Node::new("s-8vcpu-32gb"),
+            ],
+        )
+    }
+
+    fn autoscaling(instance_type: &str, min_nodes: u16, max_nodes: u16) -> NodePoolAutoscaling {
+        NodePoolAutoscaling {
+            instance_type: instance_type.to_string(),
+            min_nodes,
+            max_nodes,
+        }
+    }
+
+    #[test]
+    fn test_node_pools_autoscaling() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+
+        let mut options = Options::default();
+        options.node_pools_autoscaling = vec![
+            autoscaling("s-4vcpu-8gb", 2, 10),
+            autoscaling("s-8vcpu-32gb", 3, 3),
+        ];
+
+        let kubernetes = doks(&cloud_provider, &dns_provider, options);
+        assert!(kubernetes.is_valid().is_ok());
+
+        let context = kubernetes.tera_context().into_json();
+        assert_eq!(
+            context["doks_worker_nodes"],
+            json!([
+                {
+                    "instance_type": "s-4vcpu-8gb",
+                    "auto_scale": true,
+                    "desired_size": "2",
+                    "min_size": "2",
+                    "max_size": "10",
+                },
+                {
+                    "instance_type": "s-8vcpu-32gb",
+                    "auto_scale": false,
+                    "desired_size": "3",
+                    "min_size": "3",
+                    "max_size": "3",
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn test_node_pools_without_autoscaling() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+        let kubernetes = doks(&cloud_provider, &dns_provider, Options::default());
+
+        let context = kubernetes.tera_context().into_json();
+        assert_eq!(context["doks_worker_nodes"][0]["auto_scale"], true);
+        assert_eq!(context["doks_worker_nodes"][0]["min_size"], "1");
+        assert_eq!(context["doks_worker_nodes"][0]["max_size"], "3");
+        assert_eq!(context["doks_worker_nodes"][1]["auto_scale"], false);
+        assert_eq!(context["doks_worker_nodes"][1]["desired_size"], "1");
+    }
+
+    #[test]
+    fn test_invalid_node_pools_autoscaling() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+
+        for &(min_nodes, max_nodes) in &[(0, 3), (4, 3)] {
+            let mut options = Options::default();
+            options.node_pools_autoscaling = vec![autoscaling("s-4vcpu-8gb", min_nodes, max_nodes)];
+
+            match doks(&cloud_provider, &dns_provider, options).is_valid() {
+                Err(err) => match err.cause {
+                    EngineErrorCause::User(_) => {}
+                    EngineErrorCause::Internal => panic!("expected a user error"),
+                },
+                Ok(_) => panic!("min {} and max {} nodes must be rejected", min_nodes, max_nodes),
+            }
+        }
+    }
+
+    #[test]
+    fn test_cluster_tags() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+
+        let mut options = Options::default();
+        options.tags = vec![("team", "backend"), ("env", "production")]
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect::<BTreeMap<_, _>>();
+
+        let kubernetes = doks(&cloud_provider, &dns_provider, options);
+        assert!(kubernetes.is_valid().is_ok());
+
+        let context = kubernetes.tera_context().into_json();
+        assert_eq!(context["doks_tags"], json!(["env:production", "team:backend"]));
+
+        let mut options = Options::default();
+        options.tags.insert("team".to_string(), "a".repeat(128));
+
+        match doks(&cloud_provider, &dns_provider, options).is_valid() {
+            Err(err) => match err.cause {
+                EngineErrorCause::User(_) => {}
+                EngineErrorCause::Internal => panic!("expected a user error"),
+            },
+            Ok(_) => panic!("a too long tag value must be rejected"),
+        }
+    }
+
+    #[test]
+    fn test_region_validation() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+        let mut kubernetes = doks(&cloud_provider, &dns_provider, Options::default());
+        assert!(kubernetes.is_valid().is_ok());
+
+        kubernetes.region = "fral".to_string();
+        let err = kubernetes.is_valid().unwrap_err();
+        match err.cause {
+            EngineErrorCause::User(_) => {}
+            EngineErrorCause::Internal => panic!("expected a user error"),
+        }
+
+        let message = err.message.unwrap();
+        assert!(message.contains("\"fral\""));
+        assert!(message.contains("ams3, blr1, fra1, lon1"));
+    }
+
+    /// answers with the recorded responses of the Digital Ocean API, the last one is repeated
+    struct StubClusterApi {
+        cluster_responses: RefCell<Vec<String>>,
+        clusters_responses: RefCell<Vec<String>>,
+        upgrades: RefCell<Vec<String>>,
+        deletions: RefCell<Vec<String>>,
+    }
+
+    impl StubClusterApi {
+        fn new(versions: Vec<&str>) -> Self {
+            StubClusterApi {
+                cluster_responses: RefCell::new(versions.into_iter().map(cluster_api_output).collect()),
+                clusters_responses: RefCell::new(vec![clusters_api_output(None)]),
+                upgrades: RefCell::new(vec![]),
+                deletions: RefCell::new(vec![]),
+            }
+        }
+
+        /// the uuid of the listed cluster in each response, `None` when it is not listed
+        fn with_clusters(cluster_uuids: Vec<Option<&str>>) -> Self {
+            let stub = StubClusterApi::new(vec!["1.18.8-do.0"]);
+            *stub.clusters_responses.borrow_mut() = cluster_uuids.into_iter().map(clusters_api_output).collect();
+            stub
+        }
+    }
+
+    fn next_response(responses: &RefCell<Vec<String>>) -> String {
+        let mut responses = responses.borrow_mut();
+        match responses.len() {
+            1 => responses[0].clone(),
+            _ => responses.remove(0),
+        }
+    }
+
+    impl ClusterApi for StubClusterApi {
+        fn cluster_version(&self, _cluster_uuid: &str) -> Result<String, SimpleError> {
+            get_version_of_cluster_from_do_api_output(next_response(&self.cluster_responses).as_str())
+        }
+
+        fn upgrade_cluster(&self, _cluster_uuid: &str, version: &str) -> Result<(), SimpleError> {
+            self.upgrades.borrow_mut().push(version.to_string());
+            Ok(())
+        }
+
+        fn find_cluster_uuid(&self, cluster_name: &str) -> Result<Option<String>, SimpleError> {
+            get_uuid_of_cluster_from_do_api_output(next_response(&self.clusters_responses).as_str(), cluster_name)
+        }
+
+        fn delete_cluster(&self, cluster_uuid: &str) -> Result<(), SimpleError> {
+            self.deletions.borrow_mut().push(cluster_uuid.to_string());
+            Ok(())
+        }
+    }
+
+    // https://developers.digitalocean.com/documentation/v2/#list-all-kubernetes-clusters
+    fn clusters_api_output(cluster_uuid: Option<&str>) -> String {
+        let clusters = match cluster_uuid {
+            Some(cluster_uuid) => {
+                let output = cluster_api_output("1.18.8-do.0");
+                let mut cluster = serde_json::from_str::<serde_json::Value>(output.as_str()).unwrap();
+                cluster["kubernetes_cluster"]["id"] = json!(cluster_uuid);
+                vec![cluster["kubernetes_cluster"].take()]
+            }
+            None => vec![],
+        };
+
+        json!({ "kubernetes_clusters": clusters }).to_string()
+    }
+
+    // https://developers.digitalocean.com/documentation/v2/#retrieve-an-existing-kubernetes-cluster
+    fn cluster_api_output(version: &str) -> String {
+        json!({
+            "kubernetes_cluster": {
+                "id": "bd5f5959-5e1e-4205-a714-a914373942af",
+                "name": "qovery-cluster-id",
+                "region": "fra1",
+                "version": version,
+                "cluster_subnet": "10.244.0.0/16",
+                "service_subnet": "10.245.0.0/16",
+                "vpc_uuid": "c33931f2-a26a-4e61-b85c-4e95a2ec431b",
+                "ipv4": "68.183.121.157",
+                "endpoint": "https://bd5f5959-5e1e-4205-a714-a914373942af.k8s.ondigitalocean.com",
+                "tags": ["k8s"],
+                "node_pools": [],
+                "maintenance_policy": {
+                    "start_time": "00:00",
+                    "duration": "4h0m0s",
+                    "day": "any"
+                },
+                "auto_upgrade": false,
+                "status": {
+                    "state": "running"
+                },
+                "created_at": "2018-11-15T16:00:11Z",
+                "updated_at": "2018-11-15T16:00:11Z",
+                "surge_upgrade": false,
+                "registry_enabled": false
+            }
+        })
+        .to_string()
+    }
+
+    fn retry_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            interval: std::time::Duration::from_millis(1),
+            cancellation_token: CancellationToken::default(),
+        }
+    }
+
+    #[test]
+    fn test_upgrade_cluster() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+        let kubernetes = doks(&cloud_provider, &dns_provider, Options::default());
+
+        // the control plane reports the new version once upgraded
+        let cluster_api = StubClusterApi::new(vec!["1.18.8-do.0", "1.18.8-do.0", "1.18.8-do.0", "1.19.3-do.2"]);
+        let result = kubernetes.upgrade_cluster(&cluster_api, "cluster-uuid", "1.19.3-do.2", &retry_policy());
+        assert!(result.is_ok());
+        assert_eq!(cluster_api.upgrades.borrow().clone(), vec!["1.19.3-do.2".to_string()]);
+
+        // the cluster already runs the target version
+        let cluster_api = StubClusterApi::new(vec!["1.19.3-do.2"]);
+        let result = kubernetes.upgrade_cluster(&cluster_api, "cluster-uuid", "1.19.3-do.2", &retry_policy());
+        assert!(result.is_ok());
+        assert!(cluster_api.upgrades.borrow().is_empty());
+
+        // the new version is never reported
+        let cluster_api = StubClusterApi::new(vec!["1.18.8-do.0"]);
+        let result = kubernetes.upgrade_cluster(&cluster_api, "cluster-uuid", "1.19.3-do.2", &retry_policy());
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_downgrade_cluster_is_rejected() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+        let kubernetes = doks(&cloud_provider, &dns_provider, Options::default());
+
+        let cluster_api = StubClusterApi::new(vec!["1.19.3-do.2"]);
+        match kubernetes.upgrade_cluster(&cluster_api, "cluster-uuid", "1.18.8-do.0", &retry_policy()) {
+            Err(err) => match err.cause {
+                EngineErrorCause::User(_) => {}
+                EngineErrorCause::Internal => panic!("expected a user error"),
+            },
+            Ok(_) => panic!("a downgrade must be rejected"),
+        }
+        assert!(cluster_api.upgrades.borrow().is_empty());
+    }
+
+    #[test]
+    fn test_delete_cluster() {
+        let cloud_provider = cloud_provider();
+        let dns_provider = dns_provider();
+        let kubernetes = doks(&cloud_provider, &dns_provider, Options::default());
+
+        // the kubeconfig downloaded while the cluster was running
+        let kubeconfig_cache = KubeconfigCache::new("/tmp", KUBECONFIG_CACHE_TTL);
+        let kubeconfig_path = kubeconfig_cache.path("cluster-id");
+        std::fs::create_dir_all(std::path::Path::new(kubeconfig_path.as_str()).parent().unwrap()).unwrap();
+        std::fs::write(kubeconfig_path.as_str(), "apiVersion: v1").unwrap();
+
+        // the cluster is still listed a while after its deletion is accepted
+        let cluster_uuid = "bd5f5959-5e1e-4205-a714-a914373942af";
+        let cluster_api = StubClusterApi::with_clusters(vec![Some(cluster_uuid), Some(cluster_uuid), None]);
+        assert!(kubernetes.delete_cluster(&cluster_api, &retry_policy()).is_ok());
+        assert_eq!(cluster_api.deletions.borrow().clone(), vec![cluster_uuid.to_string()]);
+        assert!(!std::path::Path::new(kubeconfig_path.as_str()).exists());
+
+        // the cluster is already gone
+        let cluster_api = StubClusterApi::with_clusters(vec![None]);
+        assert!(kubernetes.delete_cluster(&cluster_api, &retry_policy()).is_ok());
+        assert!(cluster_api.deletions.borrow().is_empty());
+
+        // the cluster never disappears
+        let cluster_api = StubClusterApi::with_clusters(vec![Some(cluster_uuid)]);
+        assert!(kubernetes.delete_cluster(&cluster_api, &retry_policy()).is_err());
+    }
+}
@@ -1,5 +1,7 @@
 use std::any::Any;
 
+use serde::{Deserialize, Serialize};
+
 use crate::cloud_provider::kubernetes::KubernetesNode;
 
 pub struct Node {
@@ -48,3 +50,20 @@ impl KubernetesNode for Node {
         self
     }
 }
+
+/// autoscaling bounds of the node pool running an instance type, the pool has a fixed size when both are equal
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct NodePoolAutoscaling {
+    pub instance_type: String,
+    pub min_nodes: u16,
+    pub max_nodes: u16,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NodePoolDataTemplate {
+    pub instance_type: String,
+    pub auto_scale: bool,
+    pub desired_size: String,
+    pub min_size: String,
+    pub max_size: String,
+}
@@ -4,7 +4,9 @@ use std::any::Any;
 
 use digitalocean::DigitalOcean;
 
-use crate::cloud_provider::{CloudProvider, Kind, TerraformStateCredentials};
+use crate::cloud_provider::utilities::VersionsNumber;
+use crate::cloud_provider::{CloudProvider, EnvironmentVariableAliases, Kind, TerraformStateCredentials};
+use crate::cmd::preflight::BinaryRequirement;
 use crate::constants::DIGITAL_OCEAN_TOKEN;
 use crate::error::{EngineError, EngineErrorCause};
 use crate::models::{Context, Listen, Listener, Listeners};
@@ -16,6 +18,11 @@ pub mod kubernetes;
 pub mod models;
 pub mod router;
 
+/// the regions offering Digital Ocean Kubernetes
+pub const DO_REGIONS: [&str; 11] = [
+    "ams3", "blr1", "fra1", "lon1", "nyc1", "nyc3", "sfo2", "sfo3", "sgp1", "syd1", "tor1",
+];
+
 pub struct DO {
     context: Context,
     id: String,
@@ -25,6 +32,7 @@ pub struct DO {
     spaces_access_id: String,
     spaces_secret_key: String,
     terraform_state_credentials: TerraformStateCredentials,
+    credentials_environment_variable_aliases: EnvironmentVariableAliases,
     listeners: Listeners,
 }
 
@@ -48,10 +56,16 @@ impl DO {
             spaces_access_id: spaces_access_id.to_string(),
             spaces_secret_key: spaces_secret_key.to_string(),
             terraform_state_credentials,
+            credentials_environment_variable_aliases: EnvironmentVariableAliases::default(),
             listeners: vec![],
         }
     }
 
+    /// rename the credentials environment variables given to the binaries
+    pub fn set_credentials_environment_variable_aliases(&mut self, aliases: EnvironmentVariableAliases) {
+        self.credentials_environment_variable_aliases = aliases;
+    }
+
     pub fn client(&self) -> DigitalOcean {
         DigitalOcean::new(self.token.as_str()).unwrap()
     }
@@ -95,7 +109,7 @@ impl CloudProvider for DO {
     }
 
     fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
-        vec![(DIGITAL_OCEAN_TOKEN, self.token.as_str())]
+        self.credentials_environment_variable_aliases.rename(vec![(DIGITAL_OCEAN_TOKEN, self.token.as_str())])
     }
 
     fn tera_context_environment_variables(&self) -> Vec<(&str, &str)> {
@@ -106,6 +120,18 @@ impl CloudProvider for DO {
         &self.terraform_state_credentials
     }
 
+    fn required_binaries(&self) -> Vec<BinaryRequirement> {
+        vec![BinaryRequirement::new(
+            "doctl",
+            vec!["version"],
+            VersionsNumber::new(1, 45, 0),
+        )]
+    }
+
+    fn valid_regions(&self) -> &[&'static str] {
+        &DO_REGIONS
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
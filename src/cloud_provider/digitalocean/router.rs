@@ -6,8 +6,8 @@ use crate::cloud_provider::digitalocean::common::do_get_load_balancer_ip;
 use crate::cloud_provider::digitalocean::DO;
 use crate::cloud_provider::models::{CustomDomain, CustomDomainDataTemplate, Route, RouteDataTemplate};
 use crate::cloud_provider::service::{
-    default_tera_context, delete_stateless_service, send_progress_on_long_task, Action, Create, Delete, Helm, Pause,
-    Service, ServiceType, StatelessService,
+    default_tera_context, delete_stateless_service, helm_release_labels, send_progress_on_long_task, Action, Create,
+    Delete, Endpoint, Helm, Pause, Service, ServiceType, StatelessService,
 };
 use crate::cloud_provider::utilities::{check_cname_for, sanitize_name};
 use crate::cloud_provider::DeploymentTarget;
@@ -82,6 +82,12 @@ impl Service for Router {
         None
     }
 
+    fn endpoints(&self, _namespace: &str) -> Vec<Endpoint> {
+        let mut endpoints = vec![Endpoint::external(self.default_domain.as_str())];
+        endpoints.extend(self.custom_domains.iter().map(|cd| Endpoint::external(cd.domain.as_str())));
+        endpoints
+    }
+
     fn start_timeout(&self) -> Timeout<u32> {
         Timeout::Default
     }
@@ -310,7 +316,7 @@ impl crate::cloud_provider::service::Router for Router {
 
 impl Helm for Router {
     fn helm_release_name(&self) -> String {
-        crate::string::cut(format!("router-{}", self.id()), 50)
+        crate::string::helm_release_name_safe("router", self.id())
     }
 
     fn helm_chart_dir(&self) -> String {
@@ -395,6 +401,7 @@ impl Create for Router {
                     format!("custom-{}", helm_release_name).as_str(),
                     into_dir.as_str(),
                     format!("{}/nginx-ingress.yaml", into_dir.as_str()).as_str(),
+                    self.start_timeout(),
                     kubernetes.cloud_provider().credentials_environment_variables(),
                 ),
             )?;
@@ -449,8 +456,16 @@ impl Create for Router {
                 environment.namespace(),
                 helm_release_name.as_str(),
                 workspace_dir.as_str(),
-                Timeout::Default,
+                None,
+                &[],
+                &[],
+                self.start_timeout(),
+                false,
+                false,
+                &helm_release_labels(self),
                 kubernetes.cloud_provider().credentials_environment_variables(),
+                self.context.cancellation_token(),
+                None,
             ),
         )?;
 
@@ -496,7 +511,7 @@ impl Create for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Create,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -508,7 +523,7 @@ impl Pause for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, false)),
+            Box::new(|| delete_stateless_service(target, self, false, false)),
         )
     }
 
@@ -522,7 +537,7 @@ impl Pause for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Pause,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -534,7 +549,7 @@ impl Delete for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, false)),
+            Box::new(|| delete_stateless_service(target, self, false, true)),
         )
     }
 
@@ -548,7 +563,7 @@ impl Delete for Router {
         send_progress_on_long_task(
             self,
             crate::cloud_provider::service::Action::Delete,
-            Box::new(|| delete_stateless_service(target, self, true)),
+            Box::new(|| delete_stateless_service(target, self, true, false)),
         )
     }
 }
@@ -1,5 +1,11 @@
+use std::collections::{BTreeMap, HashMap};
+
 use crate::cloud_provider::service::{StatefulService, StatelessService};
-use crate::error::EngineError;
+use crate::cloud_provider::DeploymentTarget;
+use crate::cmd::helm::helm_exec_list;
+use crate::cmd::structs::HelmRelease;
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope};
+use crate::string::is_hashed_helm_release_name;
 use crate::unit_conversion::cpu_string_to_float;
 
 pub struct Environment {
@@ -11,6 +17,8 @@ pub struct Environment {
     pub organization_id: String,
     pub stateless_services: Vec<Box<dyn StatelessService>>,
     pub stateful_services: Vec<Box<dyn StatefulService>>,
+    max_parallelism: usize,
+    resource_quota: Option<ResourceQuota>,
 }
 
 impl Environment {
@@ -32,9 +40,29 @@ impl Environment {
             organization_id: organization_id.to_string(),
             stateless_services,
             stateful_services,
+            max_parallelism: 1,
+            resource_quota: None,
         }
     }
 
+    /// maximum number of stateless services deployed at the same time, 1 means sequentially
+    pub fn max_parallelism(&self) -> usize {
+        self.max_parallelism
+    }
+
+    pub fn set_max_parallelism(&mut self, max_parallelism: usize) {
+        self.max_parallelism = max_parallelism.max(1);
+    }
+
+    /// the limits of the namespace, a deployment exceeding them is rejected by kubernetes
+    pub fn resource_quota(&self) -> Option<&ResourceQuota> {
+        self.resource_quota.as_ref()
+    }
+
+    pub fn set_resource_quota(&mut self, resource_quota: Option<ResourceQuota>) {
+        self.resource_quota = resource_quota;
+    }
+
     pub fn namespace(&self) -> &str {
         self.namespace.as_str()
     }
@@ -54,9 +82,114 @@ impl Environment {
             }
         }
 
+        let _ = self.stateless_services_by_dependency_level()?;
+
         Ok(())
     }
 
+    /// group the stateless services by dependency level: dependencies come first,
+    /// and the services of a same level do not depend on each other.
+    pub fn stateless_services_by_dependency_level(&self) -> Result<Vec<Vec<&Box<dyn StatelessService>>>, EngineError> {
+        let dependencies = self
+            .stateless_services
+            .iter()
+            .map(|service| (service.id().to_string(), service.depends_on()))
+            .collect::<Vec<_>>();
+
+        match dependency_levels(&dependencies) {
+            Ok(levels) => Ok(levels
+                .into_iter()
+                .map(|level| level.into_iter().map(|i| &self.stateless_services[i]).collect())
+                .collect()),
+            Err(cycle) => {
+                let cycle = cycle
+                    .into_iter()
+                    .map(|i| self.stateless_services[i].name_with_id())
+                    .collect::<Vec<_>>();
+
+                Err(EngineError::new(
+                    EngineErrorCause::User(
+                        "Your services depend on each other in a cycle, \
+                        please remove one of the dependencies of the cycle.",
+                    ),
+                    EngineErrorScope::Environment(self.id.clone(), self.id.clone()),
+                    self.stateless_services[0].context().execution_id(),
+                    Some(format!("dependency cycle between services: {}", cycle.join(" -> "))),
+                ))
+            }
+        }
+    }
+
+    /// delete the stateless services, each one after the services depending on it so none is left orphaned.
+    /// A failed deletion does not stop the other ones, their errors are returned together
+    pub fn delete_all(&self, target: &DeploymentTarget) -> Result<(), EngineError> {
+        self.delete_all_with(|service| service.on_delete(target))
+    }
+
+    /// like `delete_all`, each service being deleted by the given function
+    pub fn delete_all_with<F>(&self, mut delete: F) -> Result<(), EngineError>
+    where
+        F: FnMut(&Box<dyn StatelessService>) -> Result<(), EngineError>,
+    {
+        let levels = self.stateless_services_by_dependency_level()?;
+        let mut errors = levels
+            .into_iter()
+            .rev()
+            .flatten()
+            .filter_map(|service| delete(service).err())
+            .collect::<Vec<_>>();
+
+        if errors.len() <= 1 {
+            return match errors.pop() {
+                Some(err) => Err(err),
+                None => Ok(()),
+            };
+        }
+
+        let messages = errors
+            .iter()
+            .map(|err| format!("{:?}: {}", err.scope, err.message.clone().unwrap_or_default()))
+            .collect::<Vec<_>>();
+
+        // the user can't fix the deletion on their own as soon as one of the failures is internal
+        let cause = match errors.iter().any(|err| matches!(err.cause, EngineErrorCause::Internal)) {
+            true => EngineErrorCause::Internal,
+            false => errors[0].cause.clone(),
+        };
+
+        Err(EngineError::new(
+            cause,
+            EngineErrorScope::Environment(self.id.clone(), self.id.clone()),
+            errors[0].execution_id.as_str(),
+            Some(format!(
+                "{} services failed to be deleted:\n{}",
+                errors.len(),
+                messages.join("\n")
+            )),
+        ))
+    }
+
+    /// the services deployed by the engine in the namespace of the environment, whether they are still part of the
+    /// environment or not. The services deployed in another namespace are not listed
+    pub fn list_deployed(&self, target: &DeploymentTarget) -> Result<Vec<DeployedService>, EngineError> {
+        let kubernetes = match target {
+            DeploymentTarget::ManagedServices(k, _) => *k,
+            DeploymentTarget::SelfHosted(k, _) => *k,
+        };
+
+        let releases = cast_simple_error_to_engine_error(
+            EngineErrorScope::Environment(self.id.clone(), self.id.clone()),
+            kubernetes.context().execution_id(),
+            helm_exec_list(
+                kubernetes.config_file_path()?,
+                self.namespace(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+
+        Ok(deployed_services(&releases))
+    }
+
     /// compute the required resources for this environment from
     /// applications, external services, routers, and databases
     /// Note: Even if external services don't run on the targeted Kubernetes cluster, it requires CPU and memory resources to run the container(s)
@@ -102,13 +235,257 @@ impl Environment {
     }
 }
 
+/// sort the nodes `(id, ids of its dependencies)` by dependency level: the nodes of a level only depend on
+/// nodes of the previous levels. Dependencies on unknown ids are ignored since they are not part of the graph.
+/// Returns the indexes of the nodes forming a cycle if there is one.
+pub fn dependency_levels(nodes: &[(String, Vec<String>)]) -> Result<Vec<Vec<usize>>, Vec<usize>> {
+    let index_by_id = nodes
+        .iter()
+        .enumerate()
+        .map(|(i, (id, _))| (id.as_str(), i))
+        .collect::<HashMap<_, _>>();
+
+    let dependencies = nodes
+        .iter()
+        .map(|(_, depends_on)| {
+            depends_on
+                .iter()
+                .filter_map(|id| index_by_id.get(id.as_str()).copied())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    let mut levels = vec![];
+    let mut resolved = vec![false; nodes.len()];
+    let mut remaining_nodes = nodes.len();
+
+    while remaining_nodes > 0 {
+        let level = (0..nodes.len())
+            .filter(|i| !resolved[*i] && dependencies[*i].iter().all(|dependency| resolved[*dependency]))
+            .collect::<Vec<_>>();
+
+        if level.is_empty() {
+            return Err(find_dependency_cycle(&dependencies, &resolved));
+        }
+
+        for i in level.iter() {
+            resolved[*i] = true;
+        }
+
+        remaining_nodes -= level.len();
+        levels.push(level);
+    }
+
+    Ok(levels)
+}
+
+fn find_dependency_cycle(dependencies: &[Vec<usize>], resolved: &[bool]) -> Vec<usize> {
+    let mut path: Vec<usize> = vec![];
+    let mut current = (0..dependencies.len()).find(|i| !resolved[*i]).unwrap();
+
+    loop {
+        if let Some(position) = path.iter().position(|i| *i == current) {
+            let mut cycle = path[position..].to_vec();
+            cycle.push(current);
+            return cycle;
+        }
+
+        path.push(current);
+        // an unresolved node always has at least one unresolved dependency, otherwise it would be resolved
+        current = *dependencies[current].iter().find(|i| !resolved[**i]).unwrap();
+    }
+}
+
 pub enum Kind {
     Production,
     Development,
 }
 
+/// a service deployed by the engine, as found from its helm release
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct DeployedService {
+    /// unknown when the release name ends with a hash of the full name instead of the service id
+    pub id: Option<String>,
+    /// the name of the service, or of its kind for the services named after it (routers and databases)
+    pub name: String,
+    /// as given by `ServiceType::name()`
+    pub service_type: String,
+    pub release_name: String,
+    pub revision: u32,
+}
+
+/// the release name prefixes of the engine, see the `helm_release_name()` of the services, and their types
+const DEPLOYED_SERVICE_TYPES: [(&str, &str); 8] = [
+    ("cron-external-service-", "CronJob"),
+    ("external-service-", "ExternalService"),
+    ("application-", "Application"),
+    ("postgresql-", "PostgreSQL database"),
+    ("mongodb-", "MongoDB database"),
+    ("router-", "Router"),
+    ("mysql-", "MySQL database"),
+    ("redis-", "Redis database"),
+];
+
+/// the services of the releases named after the engine convention `{prefix}{name}-{id}`, the other releases are
+/// ignored. A release name too long to be kept whole ends with a hash of the full name, its service id is unknown
+pub fn deployed_services(releases: &[HelmRelease]) -> Vec<DeployedService> {
+    releases
+        .iter()
+        .filter_map(|release| {
+            let (prefix, service_type) = DEPLOYED_SERVICE_TYPES
+                .iter()
+                .find(|(prefix, _)| release.name.len() > prefix.len() && release.name.starts_with(prefix))?;
+
+            let name_and_id = &release.name[prefix.len()..];
+            let (name, id) = match name_and_id.rfind('-') {
+                Some(index) => (&name_and_id[..index], &name_and_id[index + 1..]),
+                None => (prefix.trim_end_matches('-'), name_and_id),
+            };
+
+            Some(DeployedService {
+                id: match is_hashed_helm_release_name(release.name.as_str()) {
+                    true => None,
+                    false => Some(id.to_string()),
+                },
+                name: name.to_string(),
+                service_type: service_type.to_string(),
+                release_name: release.name.clone(),
+                revision: release.revision.parse::<u32>().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+/// the limits of the namespace of an environment, applied as a kubernetes `ResourceQuota`
+#[derive(Clone, Eq, PartialEq, Debug, Default)]
+pub struct ResourceQuota {
+    /// the sum of the cpu limits of the pods, e.g. `4` or `500m`
+    pub cpu: Option<String>,
+    /// the sum of the memory limits of the pods, e.g. `8Gi`
+    pub memory: Option<String>,
+    pub pods: Option<u32>,
+}
+
+impl ResourceQuota {
+    /// the `spec.hard` entries of the quota, a missing limit is not enforced
+    pub fn hard(&self) -> BTreeMap<String, String> {
+        let mut hard = BTreeMap::new();
+
+        if let Some(cpu) = &self.cpu {
+            hard.insert("limits.cpu".to_string(), cpu.clone());
+        }
+
+        if let Some(memory) = &self.memory {
+            hard.insert("limits.memory".to_string(), memory.clone());
+        }
+
+        if let Some(pods) = self.pods {
+            hard.insert("pods".to_string(), pods.to_string());
+        }
+
+        hard
+    }
+}
+
 pub struct EnvironmentResources {
     pub pods: u16,
     pub cpu: f32,
     pub ram_in_mib: u32,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::environment::{dependency_levels, deployed_services, DeployedService};
+    use crate::cmd::structs::HelmRelease;
+
+    fn node(id: &str, depends_on: Vec<&str>) -> (String, Vec<String>) {
+        (id.to_string(), depends_on.into_iter().map(|x| x.to_string()).collect())
+    }
+
+    #[test]
+    fn test_dependencies_are_resolved_first() {
+        let nodes = vec![
+            node("worker", vec!["api"]),
+            node("cron", vec!["worker", "api"]),
+            node("api", vec!["database"]),
+        ];
+
+        // the database is not part of the graph, it is ignored
+        assert_eq!(dependency_levels(&nodes).unwrap(), vec![vec![2], vec![0], vec![1]]);
+    }
+
+    #[test]
+    fn test_independent_services_share_the_same_level() {
+        let nodes = vec![
+            node("api", vec![]),
+            node("worker", vec!["api"]),
+            node("frontend", vec![]),
+        ];
+
+        assert_eq!(dependency_levels(&nodes).unwrap(), vec![vec![0, 2], vec![1]]);
+    }
+
+    #[test]
+    fn test_dependency_cycle_is_detected() {
+        let nodes = vec![
+            node("api", vec![]),
+            node("worker", vec!["cron"]),
+            node("cron", vec!["worker", "api"]),
+        ];
+
+        assert_eq!(dependency_levels(&nodes).unwrap_err(), vec![1, 2, 1]);
+
+        let nodes = vec![node("api", vec!["api"])];
+        assert_eq!(dependency_levels(&nodes).unwrap_err(), vec![0, 0]);
+    }
+
+    #[test]
+    fn test_deployed_services() {
+        let output = r#"[
+            {"name":"application-api-z1234","namespace":"env","revision":"3","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"q-application-0.1.0","app_version":"1.0"},
+            {"name":"cron-external-service-backup-z5678","namespace":"env","revision":"1","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"q-cronjob-0.1.0","app_version":"1.0"},
+            {"name":"redis-z9012","namespace":"env","revision":"12","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"failed","chart":"redis-10.7.11","app_version":"6.0"},
+            {"name":"external-service-backup-of-the-very-long-2f64ee16","namespace":"env","revision":"2","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"q-job-0.1.0","app_version":"1.0"},
+            {"name":"datadog-agent","namespace":"env","revision":"7","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"datadog-2.10.0","app_version":"7"}
+        ]"#;
+        let releases = serde_json::from_str::<Vec<HelmRelease>>(output).unwrap();
+
+        // the release installed by someone else is not a service
+        assert_eq!(
+            deployed_services(&releases),
+            vec![
+                DeployedService {
+                    id: Some("z1234".to_string()),
+                    name: "api".to_string(),
+                    service_type: "Application".to_string(),
+                    release_name: "application-api-z1234".to_string(),
+                    revision: 3,
+                },
+                DeployedService {
+                    id: Some("z5678".to_string()),
+                    name: "backup".to_string(),
+                    service_type: "CronJob".to_string(),
+                    release_name: "cron-external-service-backup-z5678".to_string(),
+                    revision: 1,
+                },
+                DeployedService {
+                    id: Some("z9012".to_string()),
+                    name: "redis".to_string(),
+                    service_type: "Redis database".to_string(),
+                    release_name: "redis-z9012".to_string(),
+                    revision: 12,
+                },
+                // the release name has been shortened with a hash
+                DeployedService {
+                    id: None,
+                    name: "backup-of-the-very-long".to_string(),
+                    service_type: "ExternalService".to_string(),
+                    release_name: "external-service-backup-of-the-very-long-2f64ee16".to_string(),
+                    revision: 2,
+                },
+            ]
+        );
+
+        assert!(deployed_services(&[]).is_empty());
+    }
+}
@@ -0,0 +1,504 @@
+use std::any::Any;
+use std::fs::File;
+
+use itertools::Itertools;
+use serde::{Deserialize, Serialize};
+use tera::Context as TeraContext;
+
+use crate::cloud_provider::environment::Environment;
+use crate::cloud_provider::gcp::GCP;
+use crate::cloud_provider::kubernetes::{Kind, Kubernetes, KubernetesNode};
+use crate::cloud_provider::models::WorkerNodeDataTemplate;
+use crate::cloud_provider::{kubernetes, CloudProvider};
+use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::constants::KUBECONFIG;
+use crate::dns_provider::DnsProvider;
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, SimpleError, SimpleErrorKind};
+use crate::fs::workspace_directory;
+use crate::models::{
+    Context, Listen, Listener, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope, StringPath,
+};
+use crate::object_storage::s3::S3;
+use crate::object_storage::ObjectStorage;
+
+pub struct Node {
+    machine_type: String,
+}
+
+impl Node {
+    pub fn new<T: Into<String>>(machine_type: T) -> Self {
+        Node {
+            machine_type: machine_type.into(),
+        }
+    }
+}
+
+impl KubernetesNode for Node {
+    fn instance_type(&self) -> &str {
+        self.machine_type.as_str()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+pub struct GKE<'a> {
+    context: Context,
+    id: String,
+    name: String,
+    version: String,
+    // a region for a regional cluster or a zone for a zonal cluster
+    region: String,
+    cloud_provider: &'a GCP,
+    nodes: Vec<Node>,
+    dns_provider: &'a dyn DnsProvider,
+    s3: S3,
+    template_directory: String,
+    listeners: Listeners,
+}
+
+impl<'a> GKE<'a> {
+    pub fn new(
+        context: Context,
+        id: &str,
+        name: &str,
+        version: &str,
+        region: &str,
+        cloud_provider: &'a GCP,
+        dns_provider: &'a dyn DnsProvider,
+        nodes: Vec<Node>,
+    ) -> Self {
+        let template_directory = format!("{}/gcp/bootstrap", context.lib_root_dir());
+
+        // terraform states are stored on AWS S3, like for Digital Ocean
+        let s3 = S3::new(
+            context.clone(),
+            "s3-temp-id".to_string(),
+            "default-s3".to_string(),
+            cloud_provider.terraform_state_credentials().access_key_id.clone(),
+            cloud_provider.terraform_state_credentials().secret_access_key.clone(),
+        );
+
+        GKE {
+            context,
+            id: id.to_string(),
+            name: name.to_string(),
+            version: version.to_string(),
+            region: region.to_string(),
+            cloud_provider,
+            nodes,
+            dns_provider,
+            s3,
+            template_directory,
+            listeners: cloud_provider.listeners().clone(), // copy listeners from CloudProvider
+        }
+    }
+
+    /// name of the cluster on GCP, as created by terraform
+    pub fn cluster_name(&self) -> String {
+        format!("qovery-{}", self.id())
+    }
+
+    /// return the GCP id of the cluster
+    pub fn cluster_id(&self) -> Result<String, EngineError> {
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            get_id_of_cluster_from_name(
+                self.cloud_provider,
+                self.region.as_str(),
+                self.cluster_name().as_str(),
+            ),
+        )
+    }
+
+    // create a context to render tf files (terraform) contained in lib/gcp/
+    fn tera_context(&self) -> TeraContext {
+        let mut context = TeraContext::new();
+
+        // GKE
+        context.insert("gke_cluster_id", &self.id());
+        context.insert("gke_master_name", &self.name());
+        context.insert("gke_version", &self.version());
+
+        // Qovery
+        context.insert("organization_id", self.cloud_provider.organization_id());
+        context.insert("test_cluster", &self.context.is_test_cluster());
+
+        // GCP
+        context.insert("gcp_project_id", self.cloud_provider.project_id.as_str());
+        context.insert("gcp_region", self.region.as_str());
+        context.insert(
+            "gcp_credentials_file_path",
+            self.cloud_provider.credentials_file_path.as_str(),
+        );
+
+        // AWS S3 tfstates storage tfstates
+        context.insert(
+            "aws_access_key_tfstates_account",
+            self.cloud_provider()
+                .terraform_state_credentials()
+                .access_key_id
+                .as_str(),
+        );
+
+        context.insert(
+            "aws_secret_key_tfstates_account",
+            self.cloud_provider()
+                .terraform_state_credentials()
+                .secret_access_key
+                .as_str(),
+        );
+
+        context.insert(
+            "aws_region_tfstates_account",
+            self.cloud_provider().terraform_state_credentials().region.as_str(),
+        );
+
+        context.insert("aws_terraform_backend_dynamodb_table", "qovery-terrafom-tfstates");
+
+        context.insert("aws_terraform_backend_bucket", "qovery-terrafom-tfstates");
+
+        // kubernetes workers
+        let worker_nodes = self
+            .nodes
+            .iter()
+            .group_by(|e| e.instance_type())
+            .into_iter()
+            .map(|(instance_type, group)| (instance_type, group.collect::<Vec<_>>()))
+            .map(|(instance_type, nodes)| WorkerNodeDataTemplate {
+                instance_type: instance_type.to_string(),
+                desired_size: "1".to_string(),
+                max_size: nodes.len().to_string(),
+                min_size: "1".to_string(),
+            })
+            .collect::<Vec<WorkerNodeDataTemplate>>();
+
+        context.insert("gke_worker_nodes", &worker_nodes);
+
+        context
+    }
+}
+
+impl<'a> Kubernetes for GKE<'a> {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Gke
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn version(&self) -> &str {
+        self.version.as_str()
+    }
+
+    fn region(&self) -> &str {
+        self.region.as_str()
+    }
+
+    fn cloud_provider(&self) -> &dyn CloudProvider {
+        self.cloud_provider
+    }
+
+    fn dns_provider(&self) -> &dyn DnsProvider {
+        self.dns_provider
+    }
+
+    fn config_file_store(&self) -> &dyn ObjectStorage {
+        &self.s3
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    /// the kubeconfig is not stored into an object storage but generated by gcloud
+    fn download_config_file(&self) -> Result<(StringPath, File), EngineError> {
+        let workspace_dir = workspace_directory(
+            self.context.workspace_root_dir(),
+            self.context.execution_id(),
+            format!("gcp/kubeconfigs/{}", self.id()),
+        );
+
+        let config_file_path = format!("{}/{}.yaml", workspace_dir, self.id());
+
+        let mut envs = self.cloud_provider.credentials_environment_variables();
+        envs.push((KUBECONFIG, config_file_path.as_str()));
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            gcloud_exec_get_credentials(
+                self.cluster_name().as_str(),
+                self.region.as_str(),
+                self.cloud_provider.project_id.as_str(),
+                envs,
+            ),
+        )?;
+
+        match File::open(config_file_path.as_str()) {
+            Ok(file) => Ok((config_file_path, file)),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!("unable to open kubeconfig {}: {:?}", config_file_path, err),
+            )),
+        }
+    }
+
+    fn on_create(&self) -> Result<(), EngineError> {
+        info!("GKE.on_create() called for {}", self.name());
+
+        let listeners_helper = ListenersHelper::new(&self.listeners);
+
+        listeners_helper.deployment_in_progress(ProgressInfo::new(
+            ProgressScope::Infrastructure {
+                execution_id: self.context.execution_id().to_string(),
+            },
+            ProgressLevel::Info,
+            Some(format!(
+                "start to create Google Kubernetes Engine cluster {} with id {}",
+                self.name(),
+                self.id()
+            )),
+            self.context.execution_id(),
+        ));
+
+        let temp_dir = workspace_directory(
+            self.context.workspace_root_dir(),
+            self.context.execution_id(),
+            format!("gcp/bootstrap/{}", self.name()),
+        );
+
+        // generate terraform files and copy them into temp dir
+        let context = self.tera_context();
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            crate::template::generate_and_copy_all_files_into_dir(
+                self.template_directory.as_str(),
+                temp_dir.as_str(),
+                &context,
+            ),
+        )?;
+
+        let _ = cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context.execution_id(),
+            crate::cmd::terraform::terraform_exec_with_init_validate_plan_apply(
+                temp_dir.as_str(),
+                self.context.is_dry_run_deploy(),
+            ),
+        )?;
+
+        Ok(())
+    }
+
+    fn on_create_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_upgrade(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_upgrade_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_downgrade(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_downgrade_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn on_delete_error(&self) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    fn deploy_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("GKE.deploy_environment() called for {}", self.name());
+        kubernetes::deploy_environment(self, environment)
+    }
+
+    fn deploy_environment_error(&self, environment: &Environment) -> Result<(), EngineError> {
+        warn!("GKE.deploy_environment_error() called for {}", self.name());
+        kubernetes::deploy_environment_error(self, environment)
+    }
+
+    fn pause_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("GKE.pause_environment() called for {}", self.name());
+        kubernetes::pause_environment(self, environment)
+    }
+
+    fn pause_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+        warn!("GKE.pause_environment_error() called for {}", self.name());
+        Ok(())
+    }
+
+    fn delete_environment(&self, environment: &Environment) -> Result<(), EngineError> {
+        info!("GKE.delete_environment() called for {}", self.name());
+        kubernetes::delete_environment(self, environment)
+    }
+
+    fn delete_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+        warn!("GKE.delete_environment_error() called for {}", self.name());
+        Ok(())
+    }
+}
+
+impl<'a> Listen for GKE<'a> {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct GcloudCluster {
+    id: String,
+    name: String,
+}
+
+/// a zonal cluster is located with `--zone`, a zone like `europe-west1-b` is its region suffixed by a letter
+fn gcloud_location_flag(region: &str) -> &'static str {
+    match region.rsplit('-').next() {
+        Some(suffix) if suffix.len() == 1 && suffix.chars().all(|c| c.is_ascii_lowercase()) => "--zone",
+        _ => "--region",
+    }
+}
+
+fn gcloud_exec_get_credentials(
+    cluster_name: &str,
+    region: &str,
+    project_id: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError> {
+    exec_with_envs_and_output(
+        "gcloud",
+        vec![
+            "container",
+            "clusters",
+            "get-credentials",
+            cluster_name,
+            gcloud_location_flag(region),
+            region,
+            "--project",
+            project_id,
+        ],
+        envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        chrono::Duration::max_value(),
+    )
+}
+
+/// return the GCP id of a GKE cluster from its name
+pub fn get_id_of_cluster_from_name(gcp: &GCP, region: &str, cluster_name: &str) -> Result<String, SimpleError> {
+    let mut output_lines: Vec<String> = Vec::new();
+
+    let _ = exec_with_envs_and_output(
+        "gcloud",
+        vec![
+            "container",
+            "clusters",
+            "describe",
+            cluster_name,
+            gcloud_location_flag(region),
+            region,
+            "--project",
+            gcp.project_id.as_str(),
+            "--format",
+            "json",
+        ],
+        gcp.credentials_environment_variables(),
+        |out| match out {
+            Ok(line) => output_lines.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        chrono::Duration::max_value(),
+    )?;
+
+    get_id_from_gcloud_cluster_output(output_lines.join("\n").as_str(), cluster_name)
+}
+
+fn get_id_from_gcloud_cluster_output(json_content: &str, cluster_name: &str) -> Result<String, SimpleError> {
+    match serde_json::from_str::<GcloudCluster>(json_content) {
+        Ok(cluster) if cluster.name == cluster_name => Ok(cluster.id),
+        Ok(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!(
+                "Unable to retrieve cluster id from the cluster name {}",
+                cluster_name
+            )),
+        )),
+        Err(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some("While trying to deserialize json received from gcloud"),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::gcp::kubernetes::{gcloud_location_flag, get_id_from_gcloud_cluster_output};
+
+    #[test]
+    fn test_gcloud_location_flag() {
+        assert_eq!(gcloud_location_flag("europe-west1"), "--region");
+        assert_eq!(gcloud_location_flag("us-central1"), "--region");
+        assert_eq!(gcloud_location_flag("europe-west1-b"), "--zone");
+        assert_eq!(gcloud_location_flag("us-central1-f"), "--zone");
+    }
+
+    #[test]
+    fn check_cluster_id() {
+        // output of `gcloud container clusters describe <name> --format json`, truncated
+        let json_content = r#"
+{
+  "currentMasterVersion": "1.18.12-gke.1210",
+  "currentNodeCount": 3,
+  "id": "e7d2b7f2a0c14e8aa93a2c5c1efc6d2f7a8f4e51c0c44df1b2f1b1f7a5d2c8e4",
+  "location": "europe-west1",
+  "name": "qovery-gqgyb7zy4ykwumak",
+  "status": "RUNNING"
+}
+"#;
+
+        assert_eq!(
+            get_id_from_gcloud_cluster_output(json_content, "qovery-gqgyb7zy4ykwumak").unwrap(),
+            "e7d2b7f2a0c14e8aa93a2c5c1efc6d2f7a8f4e51c0c44df1b2f1b1f7a5d2c8e4"
+        );
+        assert!(get_id_from_gcloud_cluster_output(json_content, "another-cluster").is_err());
+        assert!(get_id_from_gcloud_cluster_output("not json", "qovery-gqgyb7zy4ykwumak").is_err());
+    }
+}
@@ -1,61 +1,128 @@
-// use std::any::Any;
-//
-// use crate::cloud_provider::{CloudProvider, Kind, TerraformStateCredentials};
-// use crate::error::EngineError;
-// use crate::models::{Context, Listener};
-//
-// pub struct GCP {
-//     context: Context,
-//     id: String,
-//     name: String,
-//     p12_file_content: String,
-// }
-//
-// impl GCP {
-//     pub fn new(context: Context, id: &str, name: &str, p12_file_content: &str) -> Self {
-//         GCP {
-//             context,
-//             id: id.to_string(),
-//             name: name.to_string(),
-//             p12_file_content: p12_file_content.to_string(),
-//         }
-//     }
-// }
-//
-// impl<'x> CloudProvider for GCP {
-//     fn context(&self) -> &Context {
-//         &self.context
-//     }
-//
-//     fn kind(&self) -> Kind {
-//         Kind::GCP
-//     }
-//
-//     fn id(&self) -> &str {
-//         self.id.as_str()
-//     }
-//
-//     fn organization_id(&self) -> &str {
-//         unimplemented!()
-//     }
-//
-//     fn name(&self) -> &str {
-//         self.name.as_str()
-//     }
-//
-//     fn is_valid(&self) -> Result<(), EngineError> {
-//         Ok(())
-//     }
-//
-//     fn add_listener(&mut self, _listener: Listener) {
-//         // TODO
-//     }
-//
-//     fn terraform_state_credentials(&self) -> &TerraformStateCredentials {
-//         unimplemented!()
-//     }
-//
-//     fn as_any(&self) -> &dyn Any {
-//         self
-//     }
-// }
+use std::any::Any;
+
+use crate::cloud_provider::utilities::VersionsNumber;
+use crate::cloud_provider::{CloudProvider, EnvironmentVariableAliases, Kind, TerraformStateCredentials};
+use crate::cmd::preflight::BinaryRequirement;
+use crate::constants::{GCLOUD_CREDENTIALS_FILE_OVERRIDE, GKE_CREDENTIALS};
+use crate::error::{EngineError, EngineErrorCause};
+use crate::models::{Context, Listen, Listener, Listeners};
+
+pub mod kubernetes;
+
+pub struct GCP {
+    context: Context,
+    id: String,
+    organization_id: String,
+    name: String,
+    pub project_id: String,
+    // path to the JSON key of the service account
+    pub credentials_file_path: String,
+    terraform_state_credentials: TerraformStateCredentials,
+    credentials_environment_variable_aliases: EnvironmentVariableAliases,
+    listeners: Listeners,
+}
+
+impl GCP {
+    pub fn new(
+        context: Context,
+        id: &str,
+        organization_id: &str,
+        project_id: &str,
+        credentials_file_path: &str,
+        name: &str,
+        terraform_state_credentials: TerraformStateCredentials,
+    ) -> Self {
+        GCP {
+            context,
+            id: id.to_string(),
+            organization_id: organization_id.to_string(),
+            name: name.to_string(),
+            project_id: project_id.to_string(),
+            credentials_file_path: credentials_file_path.to_string(),
+            terraform_state_credentials,
+            credentials_environment_variable_aliases: EnvironmentVariableAliases::default(),
+            listeners: vec![],
+        }
+    }
+
+    /// rename the credentials environment variables given to the binaries
+    pub fn set_credentials_environment_variable_aliases(&mut self, aliases: EnvironmentVariableAliases) {
+        self.credentials_environment_variable_aliases = aliases;
+    }
+}
+
+impl CloudProvider for GCP {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Gcp
+    }
+
+    fn id(&self) -> &str {
+        self.id.as_str()
+    }
+
+    fn organization_id(&self) -> &str {
+        self.organization_id.as_str()
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_str()
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        match std::fs::metadata(self.credentials_file_path.as_str()) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Your GCP service account credentials can't be found. \
+                    Please contact your Organization administrator to fix or change the Credentials.",
+                ),
+                format!(
+                    "failed to read GCP credentials file {} for {}",
+                    self.credentials_file_path,
+                    self.name_with_id()
+                ),
+            )),
+        }
+    }
+
+    fn credentials_environment_variables(&self) -> Vec<(&str, &str)> {
+        self.credentials_environment_variable_aliases.rename(vec![
+            (GKE_CREDENTIALS, self.credentials_file_path.as_str()),
+            (GCLOUD_CREDENTIALS_FILE_OVERRIDE, self.credentials_file_path.as_str()),
+        ])
+    }
+
+    fn tera_context_environment_variables(&self) -> Vec<(&str, &str)> {
+        vec![("gcp_credentials_file_path", self.credentials_file_path.as_str())]
+    }
+
+    fn terraform_state_credentials(&self) -> &TerraformStateCredentials {
+        &self.terraform_state_credentials
+    }
+
+    fn required_binaries(&self) -> Vec<BinaryRequirement> {
+        vec![BinaryRequirement::new(
+            "gcloud",
+            vec!["version"],
+            VersionsNumber::new(300, 0, 0),
+        )]
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+impl Listen for GCP {
+    fn listeners(&self) -> &Listeners {
+        &self.listeners
+    }
+
+    fn add_listener(&mut self, listener: Listener) {
+        self.listeners.push(listener);
+    }
+}
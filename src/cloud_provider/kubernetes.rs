@@ -1,18 +1,30 @@
 use std::any::Any;
+use std::cmp;
+use std::collections::BTreeMap;
+use std::fmt;
 use std::fs::File;
 use std::os::unix::fs::PermissionsExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
 use std::thread;
 
 use serde::{Deserialize, Serialize};
 
+use crate::build_platform::Architecture;
 use crate::cloud_provider::environment::Environment;
 use crate::cloud_provider::service::CheckAction;
+use crate::cloud_provider::utilities::VersionsNumber;
 use crate::cloud_provider::{service, CloudProvider, DeploymentTarget};
 use crate::cmd::kubectl;
 use crate::cmd::kubectl::{kubectl_delete_objects_in_all_namespaces, kubectl_exec_count_all_objects};
+use crate::cmd::preflight;
+use crate::cmd::preflight::BinaryRequirement;
+use crate::cmd::retry::ExponentialBackoff;
 use crate::dns_provider::DnsProvider;
 use crate::error::SimpleErrorKind::Other;
-use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, SimpleError};
+use crate::error::{
+    cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, SimpleError, StringError,
+};
 use crate::models::{Context, Listen, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope, StringPath};
 use crate::object_storage::ObjectStorage;
 use crate::unit_conversion::{any_to_mi, cpu_string_to_float};
@@ -20,8 +32,12 @@ use retry::delay::Fibonacci;
 use retry::Error::Operation;
 use retry::OperationResult;
 use std::path::Path;
+use std::time::Duration;
 
-pub trait Kubernetes: Listen {
+/// how long a downloaded kubeconfig is reused before being downloaded again
+pub const KUBECONFIG_CACHE_TTL: Duration = Duration::from_secs(3600);
+
+pub trait Kubernetes: Listen + Sync {
     fn context(&self) -> &Context;
     fn kind(&self) -> Kind;
     fn id(&self) -> &str;
@@ -35,13 +51,54 @@ pub trait Kubernetes: Listen {
     fn dns_provider(&self) -> &dyn DnsProvider;
     fn config_file_store(&self) -> &dyn ObjectStorage;
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// binaries checked before committing a transaction targeting this cluster
+    fn required_binaries(&self) -> Vec<BinaryRequirement> {
+        let mut requirements = preflight::default_requirements(self.context().tool_config());
+        requirements.extend(self.cloud_provider().required_binaries());
+        requirements
+    }
+    /// tags (cost allocation, ownership...) applied to the cluster and to its node pools
+    fn tags(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+    /// the cpu architecture of the nodes, the images of this architecture are deployed on the cluster
+    fn node_architecture(&self) -> Architecture {
+        Architecture::Amd64
+    }
+    /// return the cloud provider id of the cluster if it already exists
+    fn exists(&self) -> Result<Option<String>, EngineError> {
+        Ok(None)
+    }
     fn config_file(&self) -> Result<(StringPath, File), EngineError> {
+        self.config_file_with_refresh(false)
+    }
+    /// the kubeconfig is cached on disk for `KUBECONFIG_CACHE_TTL`, `force_refresh` downloads it anyway
+    fn config_file_with_refresh(&self, force_refresh: bool) -> Result<(StringPath, File), EngineError> {
+        KubeconfigCache::new(self.context().workspace_root_dir(), KUBECONFIG_CACHE_TTL)
+            .get_or_download(self.id(), force_refresh, || self.download_config_file())
+    }
+    /// retrieve the kubeconfig from the config file store
+    fn download_config_file(&self) -> Result<(StringPath, File), EngineError> {
         let bucket_name = format!("qovery-kubeconfigs-{}", self.id());
         let object_key = format!("{}.yaml", self.id());
 
-        let (string_path, file) = self
-            .config_file_store()
-            .get(bucket_name.as_str(), object_key.as_str(), true)?;
+        // a missing cluster or invalid credentials won't be fixed by retrying
+        let (string_path, file) = crate::cmd::retry::retry(
+            &ExponentialBackoff::cloud_api(),
+            |err: &EngineError| {
+                let message = err.message.as_deref().unwrap_or_default();
+                KubeconfigError::classify(message) == KubeconfigError::DownloadFailed
+            },
+            || self.config_file_store().get(bucket_name.as_str(), object_key.as_str(), false),
+        )
+        .map_err(|err| {
+            let message = err.message.unwrap_or_default();
+            KubeconfigError::classify(message.as_str()).engine_error(
+                self.engine_error_scope(),
+                self.context().execution_id(),
+                format!("the kubeconfig of {} can't be retrieved: {}", self.name_with_id(), message),
+            )
+        })?;
 
         let metadata = match file.metadata() {
             Ok(metadata) => metadata,
@@ -104,6 +161,18 @@ pub trait Kubernetes: Listen {
     fn on_create_error(&self) -> Result<(), EngineError>;
     fn on_upgrade(&self) -> Result<(), EngineError>;
     fn on_upgrade_error(&self) -> Result<(), EngineError>;
+    /// upgrade the running cluster to the target Kubernetes version, a downgrade is rejected
+    fn upgrade_version(&self, target_version: &str) -> Result<(), EngineError> {
+        Err(self.engine_error(
+            EngineErrorCause::Internal,
+            format!(
+                "upgrading {} to the version {} is not supported by {:?}",
+                self.name_with_id(),
+                target_version,
+                self.kind()
+            ),
+        ))
+    }
     fn on_downgrade(&self) -> Result<(), EngineError>;
     fn on_downgrade_error(&self) -> Result<(), EngineError>;
     fn on_delete(&self) -> Result<(), EngineError>;
@@ -137,6 +206,90 @@ pub trait KubernetesNode {
 pub enum Kind {
     Eks,
     Doks,
+    Gke,
+    Aks,
+}
+
+/// a Kubernetes version, e.g. `1.16` for EKS or `1.18.8-do.0` for Digital Ocean which suffixes its own revision.
+/// Versions are ordered by major, minor and patch, a missing patch is 0 and the Digital Ocean revision is ignored
+#[derive(Clone, Debug)]
+pub struct KubernetesVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: Option<u32>,
+    /// the Digital Ocean revision, `0` of `1.18.8-do.0`
+    pub do_revision: Option<u32>,
+}
+
+impl KubernetesVersion {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        KubernetesVersion {
+            major,
+            minor,
+            patch: Some(patch),
+            do_revision: None,
+        }
+    }
+
+    fn ordering_key(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch.unwrap_or(0))
+    }
+}
+
+impl PartialEq for KubernetesVersion {
+    fn eq(&self, other: &Self) -> bool {
+        self.ordering_key() == other.ordering_key()
+    }
+}
+
+impl Eq for KubernetesVersion {}
+
+impl PartialOrd for KubernetesVersion {
+    fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for KubernetesVersion {
+    fn cmp(&self, other: &Self) -> cmp::Ordering {
+        self.ordering_key().cmp(&other.ordering_key())
+    }
+}
+
+impl fmt::Display for KubernetesVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)?;
+
+        if let Some(patch) = self.patch {
+            write!(f, ".{}", patch)?;
+        }
+
+        match self.do_revision {
+            Some(do_revision) => write!(f, "-do.{}", do_revision),
+            None => Ok(()),
+        }
+    }
+}
+
+/// parse a `major.minor[.patch][-do.revision]` Kubernetes version
+pub fn parse_kubernetes_version(version: &str) -> Result<KubernetesVersion, StringError> {
+    match VersionsNumber::parse(version) {
+        Ok(VersionsNumber {
+            major,
+            minor: Some(minor),
+            patch,
+            do_revision,
+        }) => Ok(KubernetesVersion {
+            major,
+            minor,
+            patch,
+            do_revision,
+        }),
+        _ => Err(format!(
+            "invalid Kubernetes version {:?}, it must be like 1.18.8 or 1.18.8-do.0",
+            version
+        )),
+    }
 }
 
 #[derive(Debug)]
@@ -166,6 +319,10 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
         },
         // FIXME: We don't have any managed service on DO for now
         Kind::Doks => DeploymentTarget::SelfHosted(kubernetes, environment),
+        // FIXME: We don't have any managed service on GCP for now
+        Kind::Gke => DeploymentTarget::SelfHosted(kubernetes, environment),
+        // FIXME: We don't have any managed service on Azure for now
+        Kind::Aks => DeploymentTarget::SelfHosted(kubernetes, environment),
     };
 
     // do not deploy if there is not enough resources
@@ -202,17 +359,26 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     // stateless services are deployed on kubernetes, that's why we choose the deployment target SelfHosted.
     let stateless_deployment_target = DeploymentTarget::SelfHosted(kubernetes, environment);
 
-    // create all stateless services (router, application...)
-    for service in &environment.stateless_services {
-        let _ = service::check_kubernetes_service_error(
-            service.exec_action(&stateless_deployment_target),
-            kubernetes,
-            service,
-            &stateless_deployment_target,
-            &listeners_helper,
-            "deployment",
-            CheckAction::Deploy,
-        )?;
+    // create all stateless services (router, application...), dependencies first.
+    // The services of a same dependency level are independent and can be deployed in parallel
+    for stateless_services in environment.stateless_services_by_dependency_level()? {
+        let deployment_results =
+            exec_with_max_parallelism(&stateless_services, environment.max_parallelism(), |service| {
+                service::check_kubernetes_service_error(
+                    service.exec_action(&stateless_deployment_target),
+                    kubernetes,
+                    *service,
+                    &stateless_deployment_target,
+                    &listeners_helper,
+                    "deployment",
+                    CheckAction::Deploy,
+                )
+            });
+
+        // each service has already reported its own error, the first one fails the whole deployment
+        for deployment_result in deployment_results {
+            let _ = deployment_result?;
+        }
     }
 
     // Quick fix: adding 100 ms delay to avoid race condition on service status update
@@ -249,6 +415,57 @@ pub fn deploy_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     Ok(())
 }
 
+/// execute the action on each item with at most `max_parallelism` items processed at the same time.
+/// A max parallelism of 1 processes the items sequentially and stops at the first error, otherwise
+/// every item is processed and the results are returned in the order of the items.
+pub fn exec_with_max_parallelism<T, F>(items: &[T], max_parallelism: usize, action: F) -> Vec<Result<(), EngineError>>
+where
+    T: Sync,
+    F: Fn(&T) -> Result<(), EngineError> + Sync,
+{
+    if max_parallelism <= 1 || items.len() <= 1 {
+        let mut results = Vec::with_capacity(items.len());
+
+        for item in items {
+            let result = action(item);
+            let is_error = result.is_err();
+            results.push(result);
+
+            if is_error {
+                break;
+            }
+        }
+
+        return results;
+    }
+
+    let next_item_index = AtomicUsize::new(0);
+    let indexed_results = Mutex::new(Vec::with_capacity(items.len()));
+
+    let scope_result = crossbeam_utils::thread::scope(|scope| {
+        // bounded worker pool, each worker picks the next item until there is nothing left
+        for _ in 0..max_parallelism.min(items.len()) {
+            scope.spawn(|_| loop {
+                let index = next_item_index.fetch_add(1, Ordering::SeqCst);
+                if index >= items.len() {
+                    break;
+                }
+
+                let result = action(&items[index]);
+                indexed_results.lock().unwrap().push((index, result));
+            });
+        }
+    });
+
+    if let Err(panic) = scope_result {
+        std::panic::resume_unwind(panic);
+    }
+
+    let mut indexed_results = indexed_results.into_inner().unwrap();
+    indexed_results.sort_by_key(|(index, _)| *index);
+    indexed_results.into_iter().map(|(_, result)| result).collect()
+}
+
 /// common function to react to an error when a environment deployment goes wrong
 pub fn deploy_environment_error(kubernetes: &dyn Kubernetes, environment: &Environment) -> Result<(), EngineError> {
     let listeners_helper = ListenersHelper::new(kubernetes.listeners());
@@ -395,9 +612,9 @@ pub fn delete_environment(kubernetes: &dyn Kubernetes, environment: &Environment
     // stateless services are deployed on kubernetes, that's why we choose the deployment target SelfHosted.
     let stateless_deployment_target = DeploymentTarget::SelfHosted(kubernetes, environment);
 
-    // delete all stateless services (router, application...)
-    for service in &environment.stateless_services {
-        let _ = service::check_kubernetes_service_error(
+    // delete all stateless services (router, application...), the services depending on others first
+    environment.delete_all_with(|service| {
+        service::check_kubernetes_service_error(
             service.on_delete(&stateful_deployment_target),
             kubernetes,
             service,
@@ -405,8 +622,8 @@ pub fn delete_environment(kubernetes: &dyn Kubernetes, environment: &Environment
             &listeners_helper,
             "delete",
             CheckAction::Delete,
-        )?;
-    }
+        )
+    })?;
 
     // Quick fix: adding 100 ms delay to avoid race condition on service status update
     thread::sleep(std::time::Duration::from_millis(100));
@@ -533,6 +750,44 @@ pub fn check_kubernetes_has_enough_resources_to_deploy_environment(
     Ok(())
 }
 
+/// check the tags of the cluster against the key and value length limits of the cloud provider
+pub fn validate_tags(
+    tags: &BTreeMap<String, String>,
+    max_key_length: usize,
+    max_value_length: usize,
+) -> Result<(), String> {
+    for (key, value) in tags {
+        if key.is_empty() || key.chars().count() > max_key_length {
+            return Err(format!(
+                "invalid tag key {:?}, it must be between 1 and {} characters long",
+                key, max_key_length
+            ));
+        }
+
+        if value.chars().count() > max_value_length {
+            return Err(format!(
+                "invalid value of the tag {:?}, it must be at most {} characters long",
+                key, max_value_length
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// check the region of the cluster is one of the cloud provider, before any call to its API
+pub fn validate_region(region: &str, valid_regions: &[&str]) -> Result<(), String> {
+    if valid_regions.is_empty() || valid_regions.contains(&region) {
+        return Ok(());
+    }
+
+    Err(format!(
+        "invalid region {:?}, the valid regions are: {}",
+        region,
+        valid_regions.join(", ")
+    ))
+}
+
 pub fn uninstall_cert_manager<P>(kubernetes_config: P, envs: Vec<(&str, &str)>) -> Result<(), SimpleError>
 where
     P: AsRef<Path>,
@@ -592,3 +847,384 @@ where
 
     Ok(())
 }
+
+/// kubeconfigs shared by the executions, stored under the workspace root dir and keyed by cluster id
+pub struct KubeconfigCache {
+    dir: String,
+    ttl: Duration,
+}
+
+impl KubeconfigCache {
+    pub fn new(workspace_root_dir: &str, ttl: Duration) -> Self {
+        KubeconfigCache {
+            dir: format!("{}/.qovery-workspace/kubeconfigs", workspace_root_dir),
+            ttl,
+        }
+    }
+
+    pub fn path(&self, cluster_id: &str) -> String {
+        format!("{}/{}.yaml", self.dir, cluster_id)
+    }
+
+    /// return the cached kubeconfig of the cluster, `download` is only called when it is missing, expired,
+    /// or when `force_refresh` is set
+    pub fn get_or_download<F>(
+        &self,
+        cluster_id: &str,
+        force_refresh: bool,
+        download: F,
+    ) -> Result<(StringPath, File), EngineError>
+    where
+        F: FnOnce() -> Result<(StringPath, File), EngineError>,
+    {
+        let cached_path = self.path(cluster_id);
+
+        if !force_refresh && self.is_fresh(cached_path.as_str()) {
+            if let Ok(file) = File::open(cached_path.as_str()) {
+                debug!("{} cache hit", cached_path.as_str());
+                return Ok((cached_path, file));
+            }
+        }
+
+        debug!("{} cache miss", cached_path.as_str());
+        let (downloaded_path, downloaded_file) = download()?;
+
+        // the cache is best effort, the downloaded kubeconfig is still usable
+        match self.put(cached_path.as_str(), downloaded_path.as_str()) {
+            Ok(file) => Ok((cached_path, file)),
+            Err(err) => {
+                warn!("unable to cache the kubeconfig {}: {:?}", downloaded_path, err);
+                Ok((downloaded_path, downloaded_file))
+            }
+        }
+    }
+
+    /// forget the kubeconfig of a deleted cluster, a missing kubeconfig is not an error
+    pub fn remove(&self, cluster_id: &str) -> Result<(), std::io::Error> {
+        match std::fs::remove_file(self.path(cluster_id)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        }
+    }
+
+    fn is_fresh(&self, cached_path: &str) -> bool {
+        std::fs::metadata(cached_path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|modified| modified.elapsed().ok())
+            .map(|age| age < self.ttl)
+            .unwrap_or(false)
+    }
+
+    fn put(&self, cached_path: &str, downloaded_path: &str) -> Result<File, std::io::Error> {
+        std::fs::create_dir_all(self.dir.as_str())?;
+        // the previous kubeconfig is read only
+        let _ = std::fs::remove_file(cached_path);
+        let _ = std::fs::copy(downloaded_path, cached_path)?;
+        File::open(cached_path)
+    }
+}
+
+/// why the kubeconfig of a cluster can't be retrieved, so operators know what to fix
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum KubeconfigError {
+    /// the cluster or its kubeconfig does not exist, e.g. the cluster has been deleted
+    ClusterNotFound,
+    /// the cloud provider rejects the credentials
+    InvalidCredentials,
+    /// anything else (network, throttling...), retrying may succeed
+    DownloadFailed,
+}
+
+impl KubeconfigError {
+    /// classify the error of the AWS CLI (`aws s3 cp`) or of the S3 API (S3, Spaces)
+    pub fn classify(message: &str) -> KubeconfigError {
+        let not_found_markers = ["NoSuchKey", "NoSuchBucket", "(404)", "status: 404", "does not exist"];
+        let credentials_markers = [
+            "InvalidAccessKeyId",
+            "SignatureDoesNotMatch",
+            "AccessDenied",
+            "(403)",
+            "status: 403",
+            "Unable to locate credentials",
+        ];
+
+        if not_found_markers.iter().any(|marker| message.contains(marker)) {
+            KubeconfigError::ClusterNotFound
+        } else if credentials_markers.iter().any(|marker| message.contains(marker)) {
+            KubeconfigError::InvalidCredentials
+        } else {
+            KubeconfigError::DownloadFailed
+        }
+    }
+
+    pub fn engine_error(&self, scope: EngineErrorScope, execution_id: &str, message: String) -> EngineError {
+        match self {
+            KubeconfigError::ClusterNotFound => EngineError::new(
+                EngineErrorCause::User("The Kubernetes cluster is not found, it may have been deleted"),
+                scope,
+                execution_id,
+                Some(format!("cluster not found, {}", message)),
+            ),
+            KubeconfigError::InvalidCredentials => EngineError::new(
+                EngineErrorCause::User(
+                    "The cloud provider credentials are not valid or not allowed to get the kubeconfig of the cluster",
+                ),
+                scope,
+                execution_id,
+                Some(format!("credentials invalid, {}", message)),
+            ),
+            KubeconfigError::DownloadFailed => EngineError::new(
+                EngineErrorCause::Internal,
+                scope,
+                execution_id,
+                Some(format!("download failed, {}", message)),
+            )
+            .retryable(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::fs::File;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use std::time::Duration;
+
+    use crate::cloud_provider::kubernetes::{
+        exec_with_max_parallelism, parse_kubernetes_version, KubeconfigCache, KubeconfigError, KubernetesVersion,
+    };
+    use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
+
+    /// a fake download of the kubeconfig, counting the downloads
+    fn download_kubeconfig(dir: &str, downloads: &Cell<u32>) -> Result<(String, File), EngineError> {
+        downloads.set(downloads.get() + 1);
+        let path = format!("{}/downloaded.yaml", dir);
+        let _ = std::fs::remove_file(path.as_str());
+        std::fs::write(path.as_str(), format!("download: {}", downloads.get())).unwrap();
+        Ok((path.clone(), File::open(path).unwrap()))
+    }
+
+    fn kubeconfig_cache_dir(name: &str) -> String {
+        let dir = format!("{}/qovery-engine-{}", std::env::temp_dir().to_str().unwrap(), name);
+        let _ = std::fs::remove_dir_all(dir.as_str());
+        std::fs::create_dir_all(dir.as_str()).unwrap();
+        dir
+    }
+
+    /// fake service deployment keeping track of the number of deployments running at the same time
+    fn fake_deployment(running: &AtomicUsize, max_running: &AtomicUsize, should_fail: bool) -> Result<(), EngineError> {
+        let now_running = running.fetch_add(1, Ordering::SeqCst) + 1;
+        max_running.fetch_max(now_running, Ordering::SeqCst);
+
+        thread::sleep(Duration::from_millis(200));
+        running.fetch_sub(1, Ordering::SeqCst);
+
+        if should_fail {
+            return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                "execution_id",
+                Some("fake deployment error"),
+            ));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_kubernetes_version() {
+        let version = parse_kubernetes_version("1.16").unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (1, 16, None));
+        assert_eq!(version.to_string(), "1.16");
+        assert_eq!(
+            parse_kubernetes_version("v1.19.4"),
+            Ok(KubernetesVersion::new(1, 19, 4))
+        );
+
+        for version in &[
+            "",
+            "1",
+            "1.x",
+            "1.18.8.1",
+            "latest",
+            "1.18.-1",
+            "1.18.8-rc.1",
+            "1.18.8-do.",
+        ] {
+            let result = parse_kubernetes_version(version);
+            assert!(result.is_err(), "{} must be invalid", version);
+        }
+    }
+
+    #[test]
+    fn test_parse_do_kubernetes_version() {
+        let version = parse_kubernetes_version("1.18.10-do.3").unwrap();
+        assert_eq!((version.major, version.minor, version.patch), (1, 18, Some(10)));
+        assert_eq!(version.do_revision, Some(3));
+        assert_eq!(version.to_string(), "1.18.10-do.3");
+    }
+
+    #[test]
+    fn test_kubernetes_version_ordering() {
+        let version = |version: &str| parse_kubernetes_version(version).unwrap();
+
+        // numbers are not compared as strings
+        assert!(version("1.9.0") < version("1.10.0"));
+        assert!(version("1.18.10") > version("1.18.8"));
+        assert!(version("1.18.10-do.3") < version("1.19.3-do.0"));
+
+        // a missing patch is 0
+        assert!(version("1.18") < version("1.18.8"));
+        assert_eq!(version("1.18"), version("1.18.0"));
+
+        // the Digital Ocean revision is not a newer Kubernetes
+        assert_eq!(version("1.18.8"), version("1.18.8-do.0"));
+        let revisions = version("1.19.3-do.2").cmp(&version("1.19.3-do.1"));
+        assert_eq!(revisions, std::cmp::Ordering::Equal);
+    }
+
+    #[test]
+    fn test_independent_services_are_deployed_concurrently() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let services = vec![false, false];
+
+        let results = exec_with_max_parallelism(&services, 2, |should_fail| {
+            fake_deployment(&running, &max_running, *should_fail)
+        });
+
+        assert_eq!(results.len(), 2);
+        assert!(results.iter().all(|result| result.is_ok()));
+        assert_eq!(max_running.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_max_parallelism_of_one_is_sequential() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let services = vec![false, true, false];
+
+        let results = exec_with_max_parallelism(&services, 1, |should_fail| {
+            fake_deployment(&running, &max_running, *should_fail)
+        });
+
+        // the deployment stops on the first error
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert_eq!(max_running.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_failing_service_does_not_stop_the_others() {
+        let running = AtomicUsize::new(0);
+        let max_running = AtomicUsize::new(0);
+        let services = vec![true, false, false];
+
+        let results = exec_with_max_parallelism(&services, 2, |should_fail| {
+            fake_deployment(&running, &max_running, *should_fail)
+        });
+
+        assert_eq!(results.len(), 3);
+        assert!(results[0].is_err());
+        assert!(results[1].is_ok());
+        assert!(results[2].is_ok());
+        assert!(max_running.load(Ordering::SeqCst) <= 2);
+    }
+
+    #[test]
+    fn test_kubeconfig_cache_within_ttl() {
+        let dir = kubeconfig_cache_dir("kubeconfig-cache-within-ttl");
+        let cache = KubeconfigCache::new(dir.as_str(), Duration::from_secs(3600));
+        let downloads = Cell::new(0);
+
+        let (first_path, _) = cache
+            .get_or_download("cluster-id", false, || download_kubeconfig(dir.as_str(), &downloads))
+            .unwrap();
+        let (second_path, _) = cache
+            .get_or_download("cluster-id", false, || download_kubeconfig(dir.as_str(), &downloads))
+            .unwrap();
+
+        assert_eq!(downloads.get(), 1);
+        assert_eq!(first_path, cache.path("cluster-id"));
+        assert_eq!(second_path, first_path);
+
+        // unless a refresh is forced
+        let (path, _) = cache
+            .get_or_download("cluster-id", true, || download_kubeconfig(dir.as_str(), &downloads))
+            .unwrap();
+
+        assert_eq!(downloads.get(), 2);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "download: 2");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_kubeconfig_error_classification() {
+        let classified = |message: &str| {
+            let kubeconfig_error = KubeconfigError::classify(message);
+            let err = kubeconfig_error.engine_error(EngineErrorScope::Engine, "execution_id", message.to_string());
+            (kubeconfig_error, err)
+        };
+
+        // the object of the kubeconfig is gone with the cluster
+        let (kubeconfig_error, err) = classified(
+            "unable to download s3://qovery-kubeconfigs-z1234/z1234.yaml: fatal error: An error occurred (404) when \
+            calling the HeadObject operation: Key \"z1234.yaml\" does not exist",
+        );
+        assert_eq!(kubeconfig_error, KubeconfigError::ClusterNotFound);
+        assert!(err.message.unwrap().starts_with("cluster not found, "));
+        match err.cause {
+            EngineErrorCause::User(_) => {}
+            EngineErrorCause::Internal => panic!("expected a user error"),
+        }
+        assert_eq!(
+            KubeconfigError::classify(r#"Service(NoSuchKey("The specified key does not exist."))"#),
+            KubeconfigError::ClusterNotFound
+        );
+
+        let (kubeconfig_error, err) = classified(
+            "unable to download s3://qovery-kubeconfigs-z1234/z1234.yaml: fatal error: An error occurred (403) when \
+            calling the HeadObject operation: Forbidden",
+        );
+        assert_eq!(kubeconfig_error, KubeconfigError::InvalidCredentials);
+        assert!(err.message.unwrap().starts_with("credentials invalid, "));
+        match err.cause {
+            EngineErrorCause::User(_) => {}
+            EngineErrorCause::Internal => panic!("expected a user error"),
+        }
+        assert_eq!(
+            KubeconfigError::classify("Unknown(BufferedHttpResponse {status: 403, body: \"InvalidAccessKeyId\"})"),
+            KubeconfigError::InvalidCredentials
+        );
+
+        let (kubeconfig_error, err) = classified("HttpDispatch(HttpDispatchError { message: \"connection reset\" })");
+        assert_eq!(kubeconfig_error, KubeconfigError::DownloadFailed);
+        assert!(err.is_retryable());
+        match err.cause {
+            EngineErrorCause::Internal => {}
+            EngineErrorCause::User(_) => panic!("expected an internal error"),
+        }
+    }
+
+    #[test]
+    fn test_kubeconfig_cache_expired() {
+        let dir = kubeconfig_cache_dir("kubeconfig-cache-expired");
+        let cache = KubeconfigCache::new(dir.as_str(), Duration::from_secs(0));
+        let downloads = Cell::new(0);
+
+        let _ = cache
+            .get_or_download("cluster-id", false, || download_kubeconfig(dir.as_str(), &downloads))
+            .unwrap();
+        let (path, _) = cache
+            .get_or_download("cluster-id", false, || download_kubeconfig(dir.as_str(), &downloads))
+            .unwrap();
+
+        assert_eq!(downloads.get(), 2);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "download: 2");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
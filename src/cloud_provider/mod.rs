@@ -1,13 +1,16 @@
 use std::any::Any;
+use std::collections::BTreeMap;
 
 use serde::{Deserialize, Serialize};
 
 use crate::cloud_provider::environment::Environment;
 use crate::cloud_provider::kubernetes::Kubernetes;
+use crate::cmd::preflight::BinaryRequirement;
 use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::{Context, Listen};
 
 pub mod aws;
+pub mod azure;
 pub mod digitalocean;
 pub mod environment;
 pub mod gcp;
@@ -16,7 +19,7 @@ pub mod models;
 pub mod service;
 pub mod utilities;
 
-pub trait CloudProvider: Listen {
+pub trait CloudProvider: Listen + Sync {
     fn context(&self) -> &Context;
     fn kind(&self) -> Kind;
     fn id(&self) -> &str;
@@ -31,6 +34,14 @@ pub trait CloudProvider: Listen {
     /// environment variables to inject to generate Terraform files from templates
     fn tera_context_environment_variables(&self) -> Vec<(&str, &str)>;
     fn terraform_state_credentials(&self) -> &TerraformStateCredentials;
+    /// the CLI of the cloud provider and any other binary it needs
+    fn required_binaries(&self) -> Vec<BinaryRequirement> {
+        vec![]
+    }
+    /// the regions a cluster can be created in, any region is accepted when empty
+    fn valid_regions(&self) -> &[&'static str] {
+        &[]
+    }
     fn engine_error_scope(&self) -> EngineErrorScope {
         EngineErrorScope::CloudProvider(self.id().to_string(), self.name().to_string())
     }
@@ -50,6 +61,8 @@ pub trait CloudProvider: Listen {
 pub enum Kind {
     Aws,
     Do,
+    Gcp,
+    Azure,
 }
 
 impl Kind {
@@ -57,10 +70,35 @@ impl Kind {
         match self {
             Kind::Aws => "AWS",
             Kind::Do => "Digital Ocean",
+            Kind::Gcp => "GCP",
+            Kind::Azure => "Azure",
         }
     }
 }
 
+/// caller chosen names of the credentials environment variables, for environments reserving the default ones.
+/// A variable without alias keeps its default name.
+#[derive(Clone, Debug, Default)]
+pub struct EnvironmentVariableAliases {
+    aliases: BTreeMap<String, String>,
+}
+
+impl EnvironmentVariableAliases {
+    /// `aliases` maps the default names to the names to use instead
+    pub fn new(aliases: BTreeMap<String, String>) -> Self {
+        EnvironmentVariableAliases { aliases }
+    }
+
+    pub fn rename<'a>(&'a self, envs: Vec<(&'a str, &'a str)>) -> Vec<(&'a str, &'a str)> {
+        envs.into_iter()
+            .map(|(name, value)| match self.aliases.get(name) {
+                Some(alias) => (alias.as_str(), value),
+                None => (name, value),
+            })
+            .collect()
+    }
+}
+
 pub struct TerraformStateCredentials {
     pub access_key_id: String,
     pub secret_access_key: String,
@@ -83,3 +121,50 @@ pub enum DeploymentTarget<'a> {
     // SelfHosted = Kubernetes or anything else that implies management on our side
     SelfHosted(&'a dyn Kubernetes, &'a Environment),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use crate::cloud_provider::digitalocean::DO;
+    use crate::cloud_provider::{CloudProvider, EnvironmentVariableAliases, TerraformStateCredentials};
+    use crate::constants::DIGITAL_OCEAN_TOKEN;
+    use crate::models::Context;
+
+    #[test]
+    fn test_credentials_environment_variable_aliases() {
+        let context = Context::new(
+            "execution_id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        );
+
+        let mut cloud_provider = DO::new(
+            context,
+            "id",
+            "organization_id",
+            "my-token",
+            "spaces_access_id",
+            "spaces_secret_key",
+            "my-do",
+            TerraformStateCredentials::new("access_key_id", "secret_access_key", "region"),
+        );
+        assert_eq!(cloud_provider.credentials_environment_variables(), vec![(DIGITAL_OCEAN_TOKEN, "my-token")]);
+
+        let mut aliases = BTreeMap::new();
+        aliases.insert(DIGITAL_OCEAN_TOKEN.to_string(), "CI_DO_TOKEN".to_string());
+        cloud_provider.set_credentials_environment_variable_aliases(EnvironmentVariableAliases::new(aliases));
+        assert_eq!(cloud_provider.credentials_environment_variables(), vec![("CI_DO_TOKEN", "my-token")]);
+
+        let mut aliases = BTreeMap::new();
+        aliases.insert("UNUSED".to_string(), "ALIAS".to_string());
+        let aliases = EnvironmentVariableAliases::new(aliases);
+        assert_eq!(
+            aliases.rename(vec![("KUBECONFIG", "/tmp/kubeconfig"), ("UNUSED", "value")]),
+            vec![("KUBECONFIG", "/tmp/kubeconfig"), ("ALIAS", "value")]
+        );
+    }
+}
@@ -1,5 +1,17 @@
+use std::collections::HashSet;
+use std::path::Path;
+
 use serde::{Deserialize, Serialize};
 
+use crate::build_platform::Image;
+use crate::error::StringError;
+
+/// the environment variables of a container are stored into a secret, and a secret can't exceed 1MiB
+pub const ENVIRONMENT_VARIABLES_MAX_SIZE_IN_BYTES: usize = 1024 * 1024;
+
+/// the mounted files of a container are stored into a config map, and a config map can't exceed 1MiB
+pub const MOUNTED_FILES_MAX_SIZE_IN_BYTES: usize = 1024 * 1024;
+
 #[derive(Serialize, Deserialize)]
 pub struct WorkerNodeDataTemplate {
     pub instance_type: String,
@@ -8,18 +20,508 @@ pub struct WorkerNodeDataTemplate {
     pub min_size: String,
 }
 
-#[derive(Clone, Eq, PartialEq, Hash)]
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
 pub struct EnvironmentVariable {
     pub key: String,
     pub value: String,
 }
 
+impl EnvironmentVariable {
+    /// Kubernetes rejects the names which are not C identifiers: `[A-Za-z_][A-Za-z0-9_]*`
+    pub fn validate(&self) -> Result<(), StringError> {
+        let mut chars = self.key.chars();
+
+        let is_c_identifier = match chars.next() {
+            Some(first) => {
+                (first.is_ascii_alphabetic() || first == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+            }
+            None => false,
+        };
+
+        if is_c_identifier {
+            Ok(())
+        } else {
+            Err(format!("invalid environment variable name: {:?}", self.key))
+        }
+    }
+
+    pub fn size_in_bytes(&self) -> usize {
+        self.key.len() + self.value.len()
+    }
+
+    /// validate the names of the environment variables of a container, and that they fit into `max_size_in_bytes`
+    pub fn validate_all(
+        environment_variables: &[EnvironmentVariable],
+        max_size_in_bytes: usize,
+    ) -> Result<(), StringError> {
+        let invalid_keys = environment_variables
+            .iter()
+            .filter(|environment_variable| environment_variable.validate().is_err())
+            .map(|environment_variable| format!("{:?}", environment_variable.key))
+            .collect::<Vec<_>>();
+
+        if !invalid_keys.is_empty() {
+            return Err(format!(
+                "invalid environment variable names (they must match [A-Za-z_][A-Za-z0-9_]*): {}",
+                invalid_keys.join(", ")
+            ));
+        }
+
+        let total_size_in_bytes = environment_variables.iter().map(|ev| ev.size_in_bytes()).sum::<usize>();
+        if total_size_in_bytes <= max_size_in_bytes {
+            return Ok(());
+        }
+
+        // the largest environment variables to remove to fit into the budget
+        let mut largest = environment_variables.iter().collect::<Vec<_>>();
+        largest.sort_by(|a, b| b.size_in_bytes().cmp(&a.size_in_bytes()).then(a.key.cmp(&b.key)));
+
+        let mut size_in_bytes = total_size_in_bytes;
+        let oversized_keys = largest
+            .into_iter()
+            .take_while(|environment_variable| {
+                let is_over_budget = size_in_bytes > max_size_in_bytes;
+                size_in_bytes -= environment_variable.size_in_bytes();
+                is_over_budget
+            })
+            .map(|environment_variable| environment_variable.key.as_str())
+            .collect::<Vec<_>>();
+
+        Err(format!(
+            "environment variables are too large, {} bytes for a maximum of {} bytes: {}",
+            total_size_in_bytes,
+            max_size_in_bytes,
+            oversized_keys.join(", ")
+        ))
+    }
+}
+
+/// parse the `KEY=VALUE` lines of a dotenv file, to be merged into the environment variables of a service
+pub fn load_env_vars_from_dotenv<P: AsRef<Path>>(path: P) -> Result<Vec<EnvironmentVariable>, StringError> {
+    let path = path.as_ref();
+
+    let content = match std::fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) => return Err(format!("unable to read {}: {}", path.display(), err)),
+    };
+
+    parse_dotenv(content.as_str()).map_err(|err| format!("{}:{}", path.display(), err))
+}
+
+/// blank lines, comments and a leading `export ` are ignored, values can be single or double quoted
+pub fn parse_dotenv(content: &str) -> Result<Vec<EnvironmentVariable>, StringError> {
+    let mut environment_variables = vec![];
+
+    for (idx, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = match line.strip_prefix("export ") {
+            Some(line) => line.trim_start(),
+            None => line,
+        };
+
+        let (key, value) = match line.find('=') {
+            Some(position) => (line[..position].trim(), line[position + 1..].trim_start()),
+            None => return Err(format!("{}: expected KEY=VALUE, got {:?}", idx + 1, line)),
+        };
+
+        let environment_variable = EnvironmentVariable {
+            key: key.to_string(),
+            value: parse_dotenv_value(value).map_err(|err| format!("{}: {}", idx + 1, err))?,
+        };
+
+        if let Err(err) = environment_variable.validate() {
+            return Err(format!("{}: {}", idx + 1, err));
+        }
+
+        environment_variables.push(environment_variable);
+    }
+
+    Ok(environment_variables)
+}
+
+fn parse_dotenv_value(value: &str) -> Result<String, StringError> {
+    let quote = match value.chars().next() {
+        Some(quote) if quote == '"' || quote == '\'' => quote,
+        // an unquoted value ends where its comment starts
+        _ => {
+            let value = match value.find(" #") {
+                Some(position) => &value[..position],
+                None => value,
+            };
+
+            return Ok(value.trim_end().to_string());
+        }
+    };
+
+    let mut parsed_value = String::new();
+    let mut chars = value[1..].char_indices();
+
+    while let Some((position, c)) = chars.next() {
+        match c {
+            // escape sequences are only interpreted between double quotes
+            '\\' if quote == '"' => match chars.next() {
+                Some((_, 'n')) => parsed_value.push('\n'),
+                Some((_, escaped)) => parsed_value.push(escaped),
+                None => break,
+            },
+            c if c == quote => {
+                let rest = value[1 + position + 1..].trim_start();
+                if !rest.is_empty() && !rest.starts_with('#') {
+                    return Err(format!("unexpected characters after the closing quote: {:?}", rest));
+                }
+
+                return Ok(parsed_value);
+            }
+            c => parsed_value.push(c),
+        }
+    }
+
+    Err(format!("missing closing quote {} in {:?}", quote, value))
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct EnvironmentVariableDataTemplate {
     pub key: String,
     pub value: String,
 }
 
+#[derive(Serialize, Deserialize)]
+pub struct SidecarDataTemplate {
+    pub name: String,
+    pub image_name_with_tag: String,
+    pub environment_variables: Vec<EnvironmentVariableDataTemplate>,
+}
+
+/// a container run to completion before the main container starts, e.g. to migrate a database schema
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct InitContainer {
+    pub image: Image,
+    /// give it the environment variables (plain and secret) of the main container
+    pub share_environment_variables: bool,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InitContainerDataTemplate {
+    pub name: String,
+    pub image_name_with_tag: String,
+    pub share_environment_variables: bool,
+}
+
+/// a configuration file of the container, e.g. `/etc/app/config.yaml`, stored into a config map
+/// the content is a Tera template rendered with the chart context
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct MountedFile {
+    /// the absolute path of the file in the container
+    pub path: String,
+    pub content: String,
+}
+
+impl MountedFile {
+    /// the key of the file in the config map, keys must match [-._a-zA-Z0-9]+ and be unique
+    pub fn key(&self, index: usize) -> String {
+        let file_name = self
+            .path
+            .rsplit('/')
+            .next()
+            .unwrap_or_default()
+            .chars()
+            .map(|c| match c.is_ascii_alphanumeric() || c == '-' || c == '.' || c == '_' {
+                true => c,
+                false => '_',
+            })
+            .collect::<String>();
+
+        format!("file-{}-{}", index, file_name)
+    }
+
+    /// validate the paths of the mounted files of a container, and that they fit into `max_size_in_bytes`
+    pub fn validate_all(mounted_files: &[MountedFile], max_size_in_bytes: usize) -> Result<(), StringError> {
+        let mut paths = HashSet::new();
+
+        for mounted_file in mounted_files {
+            if !mounted_file.path.starts_with('/') || mounted_file.path.ends_with('/') {
+                return Err(format!("the path {:?} is not the absolute path of a file", mounted_file.path));
+            }
+
+            if !paths.insert(mounted_file.path.as_str()) {
+                return Err(format!("the path {:?} is used by several files", mounted_file.path));
+            }
+        }
+
+        let total_size_in_bytes = mounted_files.iter().map(|file| file.content.len()).sum::<usize>();
+        if total_size_in_bytes > max_size_in_bytes {
+            return Err(format!(
+                "mounted files are too large, {} bytes for a maximum of {} bytes",
+                total_size_in_bytes, max_size_in_bytes
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MountedFileDataTemplate {
+    pub key: String,
+    pub path: String,
+    pub content: String,
+}
+
+/// a persistent volume claim mounted in the job container, e.g. a scratch storage surviving the restarts
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct VolumeClaim {
+    pub name: String,
+    pub size_gib: u32,
+    /// the default storage class of the cluster is used when not set
+    pub storage_class: Option<String>,
+    pub mount_path: String,
+}
+
+impl VolumeClaim {
+    pub fn validate(&self) -> Result<(), StringError> {
+        let is_alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+        if self.name.is_empty()
+            || !self.name.chars().all(|c| is_alphanumeric(c) || c == '-')
+            || !self.name.starts_with(is_alphanumeric)
+        {
+            return Err(format!(
+                "invalid volume claim name {:?}, it must be lowercase alphanumeric characters or '-'",
+                self.name
+            ));
+        }
+
+        if self.size_gib < 1 {
+            return Err(format!("the volume claim {} must be at least 1GiB", self.name));
+        }
+
+        if !self.mount_path.starts_with('/') {
+            return Err(format!(
+                "the mount path {:?} of the volume claim {} is not an absolute path",
+                self.mount_path, self.name
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct VolumeClaimDataTemplate {
+    pub name: String,
+    /// the name of the persistent volume claim, prefixed with the name of the service
+    pub claim_name: String,
+    pub size_gib: u32,
+    pub storage_class: Option<String>,
+    pub mount_path: String,
+}
+
+/// a command run in a container, e.g. `curl -f http://localhost:8080/health`
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Command {
+    pub binary: String,
+    pub args: Vec<String>,
+}
+
+impl Command {
+    pub fn argv(&self) -> Vec<String> {
+        std::iter::once(self.binary.clone()).chain(self.args.iter().cloned()).collect()
+    }
+}
+
+/// the effects a toleration can tolerate, as defined by Kubernetes
+pub const TOLERATION_EFFECTS: [&str; 3] = ["NoSchedule", "PreferNoSchedule", "NoExecute"];
+
+/// let the pods be scheduled on the nodes with a matching taint, e.g. a GPU or a spot node pool
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct Toleration {
+    pub key: String,
+    /// `Equal` or `Exists`
+    pub operator: String,
+    /// only for the `Equal` operator
+    pub value: Option<String>,
+    pub effect: String,
+}
+
+impl Toleration {
+    pub fn validate(&self) -> Result<(), StringError> {
+        if !TOLERATION_EFFECTS.contains(&self.effect.as_str()) {
+            return Err(format!(
+                "invalid effect {:?} for the toleration {}, it must be one of {}",
+                self.effect,
+                self.key,
+                TOLERATION_EFFECTS.join(", ")
+            ));
+        }
+
+        match (self.operator.as_str(), &self.value) {
+            ("Equal", _) | ("Exists", None) => Ok(()),
+            ("Exists", Some(_)) => Err(format!(
+                "the toleration {} can't have a value with the Exists operator",
+                self.key
+            )),
+            (operator, _) => Err(format!(
+                "invalid operator {:?} for the toleration {}, it must be Equal or Exists",
+                operator, self.key
+            )),
+        }
+    }
+}
+
+/// what the scheduler does with a pod breaking a topology spread constraint, as defined by Kubernetes
+pub const TOPOLOGY_SPREAD_WHEN_UNSATISFIABLE: [&str; 2] = ["DoNotSchedule", "ScheduleAnyway"];
+
+/// spread the pods evenly across the domains of a topology, e.g. the zones with `topology.kubernetes.io/zone`
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct TopologySpread {
+    /// the greatest difference allowed between the number of pods of two domains
+    pub max_skew: u32,
+    pub topology_key: String,
+    /// `DoNotSchedule` or `ScheduleAnyway`
+    pub when_unsatisfiable: String,
+}
+
+impl TopologySpread {
+    pub fn validate(&self) -> Result<(), StringError> {
+        if self.topology_key.trim().is_empty() {
+            return Err("the topology key of a topology spread constraint can't be empty".to_string());
+        }
+
+        if self.max_skew < 1 {
+            return Err(format!(
+                "invalid max skew {} for the topology spread constraint {}, it must be at least 1",
+                self.max_skew, self.topology_key
+            ));
+        }
+
+        if !TOPOLOGY_SPREAD_WHEN_UNSATISFIABLE.contains(&self.when_unsatisfiable.as_str()) {
+            return Err(format!(
+                "invalid when unsatisfiable {:?} for the topology spread constraint {}, it must be one of {}",
+                self.when_unsatisfiable,
+                self.topology_key,
+                TOPOLOGY_SPREAD_WHEN_UNSATISFIABLE.join(", ")
+            ));
+        }
+
+        Ok(())
+    }
+}
+
+/// when the image of a container is pulled by the nodes
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub enum ImagePullPolicy {
+    Always,
+    IfNotPresent,
+    Never,
+}
+
+impl ImagePullPolicy {
+    pub fn name(&self) -> &str {
+        match self {
+            ImagePullPolicy::Always => "Always",
+            ImagePullPolicy::IfNotPresent => "IfNotPresent",
+            ImagePullPolicy::Never => "Never",
+        }
+    }
+
+    pub fn parse(name: &str) -> Result<ImagePullPolicy, StringError> {
+        match name {
+            "Always" => Ok(ImagePullPolicy::Always),
+            "IfNotPresent" => Ok(ImagePullPolicy::IfNotPresent),
+            "Never" => Ok(ImagePullPolicy::Never),
+            _ => Err(format!("invalid image pull policy {:?}, it must be Always, IfNotPresent or Never", name)),
+        }
+    }
+
+    /// a digest always designates the same image, a tag may have been pushed again since the last pull
+    pub fn default_for(image: &Image) -> ImagePullPolicy {
+        match image.digest {
+            Some(_) => ImagePullPolicy::IfNotPresent,
+            None => ImagePullPolicy::Always,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TolerationDataTemplate {
+    pub key: String,
+    pub operator: String,
+    pub value: Option<String>,
+    pub effect: String,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct TopologySpreadDataTemplate {
+    pub max_skew: u32,
+    pub topology_key: String,
+    pub when_unsatisfiable: String,
+}
+
+/// the greatest user and group id accepted by kubernetes
+pub const SECURITY_CONTEXT_MAX_ID: u32 = 2147483647;
+
+/// the user and the file system permissions the containers of the pods run with
+#[derive(Clone, Eq, PartialEq, Hash, Debug)]
+pub struct SecurityContext {
+    /// the user of the image by default
+    pub run_as_user: Option<u32>,
+    /// the kubelet refuses to start a container running as root
+    pub run_as_non_root: bool,
+    pub read_only_root_fs: bool,
+    /// the group owning the mounted volumes
+    pub fs_group: Option<u32>,
+}
+
+impl Default for SecurityContext {
+    /// the pod security admission of the clusters rejects the containers running as root
+    fn default() -> Self {
+        SecurityContext {
+            run_as_user: None,
+            run_as_non_root: true,
+            read_only_root_fs: false,
+            fs_group: None,
+        }
+    }
+}
+
+impl SecurityContext {
+    pub fn validate(&self) -> Result<(), StringError> {
+        if self.run_as_non_root && self.run_as_user == Some(0) {
+            return Err("the user 0 is root, it can't run as non root".to_string());
+        }
+
+        for &(field, id) in &[("run_as_user", self.run_as_user), ("fs_group", self.fs_group)] {
+            if let Some(id) = id.filter(|id| *id > SECURITY_CONTEXT_MAX_ID) {
+                return Err(format!("the {} {} exceeds {}", field, id, SECURITY_CONTEXT_MAX_ID));
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn to_data_template(&self) -> SecurityContextDataTemplate {
+        SecurityContextDataTemplate {
+            run_as_user: self.run_as_user,
+            run_as_non_root: self.run_as_non_root,
+            read_only_root_fs: self.read_only_root_fs,
+            fs_group: self.fs_group,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SecurityContextDataTemplate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_as_user: Option<u32>,
+    pub run_as_non_root: bool,
+    pub read_only_root_fs: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fs_group: Option<u32>,
+}
+
 #[derive(Clone, Eq, PartialEq, Hash)]
 pub struct Storage<T> {
     pub id: String,
@@ -40,6 +542,75 @@ pub struct StorageDataTemplate {
     pub snapshot_retention_in_days: u16,
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ProbeKind {
+    Http,
+    Tcp,
+    Exec,
+}
+
+impl ProbeKind {
+    pub fn name(&self) -> &str {
+        match self {
+            ProbeKind::Http => "http",
+            ProbeKind::Tcp => "tcp",
+            ProbeKind::Exec => "exec",
+        }
+    }
+}
+
+/// a liveness or readiness probe, `path` and `port` are used by the http probes, `port` by the tcp ones
+/// and `command` by the exec ones
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Probe {
+    pub kind: ProbeKind,
+    pub path: Option<String>,
+    pub port: Option<u16>,
+    pub command: Vec<String>,
+    pub initial_delay_in_seconds: u32,
+    pub period_in_seconds: u32,
+    pub failure_threshold: u32,
+}
+
+impl Probe {
+    pub fn validate(&self) -> Result<(), StringError> {
+        let is_port_missing = self.port.map(|port| port < 1).unwrap_or(true);
+
+        match self.kind {
+            ProbeKind::Http if self.path.as_ref().map(|path| path.trim().is_empty()).unwrap_or(true) => {
+                Err("an http probe requires a path".to_string())
+            }
+            ProbeKind::Http if is_port_missing => Err("an http probe requires a port".to_string()),
+            ProbeKind::Tcp if is_port_missing => Err("a tcp probe requires a port".to_string()),
+            ProbeKind::Exec if self.command.is_empty() => Err("an exec probe requires a command".to_string()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn to_data_template(&self) -> ProbeDataTemplate {
+        ProbeDataTemplate {
+            kind: self.kind.name().to_string(),
+            path: self.path.clone(),
+            port: self.port,
+            command: self.command.clone(),
+            initial_delay_seconds: self.initial_delay_in_seconds,
+            period_seconds: self.period_in_seconds,
+            failure_threshold: self.failure_threshold,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct ProbeDataTemplate {
+    pub kind: String,
+    pub path: Option<String>,
+    pub port: Option<u16>,
+    pub command: Vec<String>,
+    pub initial_delay_seconds: u32,
+    pub period_seconds: u32,
+    pub failure_threshold: u32,
+}
+
 pub struct CustomDomain {
     pub domain: String,
     pub target_domain: String,
@@ -69,3 +640,204 @@ pub struct CpuLimits {
     pub cpu_request: String,
     pub cpu_limit: String,
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cloud_provider::models::{
+        load_env_vars_from_dotenv, parse_dotenv, EnvironmentVariable, MountedFile, Probe, ProbeKind, SecurityContext,
+    };
+
+    fn environment_variable(key: &str, value: &str) -> EnvironmentVariable {
+        EnvironmentVariable {
+            key: key.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_validate_environment_variables() {
+        let environment_variables = vec![
+            environment_variable("DATABASE_URL", "postgres://db"),
+            environment_variable("_PRIVATE", ""),
+            environment_variable("api_key2", "secret"),
+        ];
+
+        assert!(EnvironmentVariable::validate_all(&environment_variables, 1024).is_ok());
+    }
+
+    #[test]
+    fn test_validate_invalid_environment_variable_names() {
+        let environment_variables = vec![
+            environment_variable("DATABASE_URL", "postgres://db"),
+            environment_variable("1ST_KEY", "value"),
+            environment_variable("MY-KEY", "value"),
+            environment_variable("", "value"),
+        ];
+
+        assert!(environment_variables[1].validate().is_err());
+        assert_eq!(
+            EnvironmentVariable::validate_all(&environment_variables, 1024).unwrap_err(),
+            r#"invalid environment variable names (they must match [A-Za-z_][A-Za-z0-9_]*): "1ST_KEY", "MY-KEY", """#
+        );
+    }
+
+    #[test]
+    fn test_validate_oversized_environment_variables() {
+        let environment_variables = vec![
+            environment_variable("SMALL", "1234"),
+            environment_variable("LARGE", "x".repeat(100).as_str()),
+            environment_variable("MEDIUM", "x".repeat(50).as_str()),
+        ];
+
+        assert!(EnvironmentVariable::validate_all(&environment_variables, 170).is_ok());
+        assert_eq!(
+            EnvironmentVariable::validate_all(&environment_variables, 100).unwrap_err(),
+            "environment variables are too large, 170 bytes for a maximum of 100 bytes: LARGE"
+        );
+        assert_eq!(
+            EnvironmentVariable::validate_all(&environment_variables, 10).unwrap_err(),
+            "environment variables are too large, 170 bytes for a maximum of 10 bytes: LARGE, MEDIUM"
+        );
+    }
+
+    #[test]
+    fn test_validate_mounted_files() {
+        let mounted_file = |path: &str, content: &str| MountedFile {
+            path: path.to_string(),
+            content: content.to_string(),
+        };
+
+        let mounted_files = vec![
+            mounted_file("/etc/app/config.yaml", "port: 8080"),
+            mounted_file("/etc/app/my config.json", "{}"),
+        ];
+        assert!(MountedFile::validate_all(&mounted_files, 12).is_ok());
+        assert_eq!(mounted_files[0].key(0), "file-0-config.yaml");
+        assert_eq!(mounted_files[1].key(1), "file-1-my_config.json");
+
+        assert_eq!(
+            MountedFile::validate_all(&mounted_files, 10).unwrap_err(),
+            "mounted files are too large, 12 bytes for a maximum of 10 bytes"
+        );
+        assert!(MountedFile::validate_all(&[mounted_file("etc/app/config.yaml", "")], 10).is_err());
+        assert!(MountedFile::validate_all(&[mounted_file("/etc/app/", "")], 10).is_err());
+        assert!(MountedFile::validate_all(&[mounted_files[0].clone(), mounted_files[0].clone()], 100).is_err());
+    }
+
+    #[test]
+    fn test_parse_dotenv() {
+        let content = r#"
+# database
+export DATABASE_URL=postgres://db:5432/app
+
+API_KEY = abc123 # the key of the staging account
+GREETING="hello \"world\"\nbye" # a comment
+RAW='no \n escape # in single quotes'
+EMPTY=
+  EMPTY_QUOTED=""
+"#;
+
+        assert_eq!(
+            parse_dotenv(content).unwrap(),
+            vec![
+                environment_variable("DATABASE_URL", "postgres://db:5432/app"),
+                environment_variable("API_KEY", "abc123"),
+                environment_variable("GREETING", "hello \"world\"\nbye"),
+                environment_variable("RAW", "no \\n escape # in single quotes"),
+                environment_variable("EMPTY", ""),
+                environment_variable("EMPTY_QUOTED", ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_dotenv() {
+        assert_eq!(
+            parse_dotenv("A=1\n\nNOT_A_VARIABLE\n").unwrap_err(),
+            "3: expected KEY=VALUE, got \"NOT_A_VARIABLE\""
+        );
+        assert_eq!(
+            parse_dotenv("A=1\nB=\"unterminated\n").unwrap_err(),
+            "2: missing closing quote \" in \"\\\"unterminated\""
+        );
+        assert_eq!(
+            parse_dotenv("MY-KEY=1").unwrap_err(),
+            "1: invalid environment variable name: \"MY-KEY\""
+        );
+    }
+
+    #[test]
+    fn test_load_env_vars_from_dotenv() {
+        let path = format!("{}/qovery-engine-test.env", std::env::temp_dir().to_str().unwrap());
+        std::fs::write(path.as_str(), "A=1\nB='2'\nC\n").unwrap();
+
+        assert_eq!(
+            load_env_vars_from_dotenv(path.as_str()).unwrap_err(),
+            format!("{}:3: expected KEY=VALUE, got \"C\"", path)
+        );
+
+        std::fs::write(path.as_str(), "A=1\nB='2'\n").unwrap();
+        assert_eq!(
+            load_env_vars_from_dotenv(path.as_str()).unwrap(),
+            vec![environment_variable("A", "1"), environment_variable("B", "2")]
+        );
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[test]
+    fn test_validate_probe() {
+        let probe = Probe {
+            kind: ProbeKind::Http,
+            path: Some("/health".to_string()),
+            port: Some(8080),
+            command: vec![],
+            initial_delay_in_seconds: 10,
+            period_in_seconds: 5,
+            failure_threshold: 3,
+        };
+        assert!(probe.validate().is_ok());
+
+        let without_path = Probe { path: None, ..probe.clone() };
+        assert_eq!(without_path.validate().unwrap_err(), "an http probe requires a path");
+
+        let without_port = Probe { port: None, ..probe.clone() };
+        assert_eq!(without_port.validate().unwrap_err(), "an http probe requires a port");
+
+        let tcp = Probe {
+            kind: ProbeKind::Tcp,
+            path: None,
+            ..probe.clone()
+        };
+        assert!(tcp.validate().is_ok());
+
+        let exec = Probe {
+            kind: ProbeKind::Exec,
+            port: None,
+            ..probe
+        };
+        assert_eq!(exec.validate().unwrap_err(), "an exec probe requires a command");
+    }
+
+    #[test]
+    fn test_validate_security_context() {
+        assert!(SecurityContext::default().validate().is_ok());
+
+        let root = SecurityContext {
+            run_as_user: Some(0),
+            ..SecurityContext::default()
+        };
+        assert_eq!(root.validate().unwrap_err(), "the user 0 is root, it can't run as non root");
+
+        let root = SecurityContext {
+            run_as_non_root: false,
+            ..root
+        };
+        assert!(root.validate().is_ok());
+
+        let out_of_range = SecurityContext {
+            fs_group: Some(u32::MAX),
+            ..SecurityContext::default()
+        };
+        assert_eq!(out_of_range.validate().unwrap_err(), "the fs_group 4294967295 exceeds 2147483647");
+    }
+}
@@ -1,4 +1,7 @@
+use std::collections::{BTreeMap, HashSet};
+use std::hash::{Hash, Hasher};
 use std::net::TcpStream;
+use std::sync::{Arc, Mutex};
 use std::sync::mpsc;
 use std::sync::mpsc::TryRecvError;
 use std::thread;
@@ -6,20 +9,20 @@ use std::time::Duration;
 
 use tera::Context as TeraContext;
 
-use crate::build_platform::Image;
+use crate::build_platform::{Image, RegistryCredentials};
 use crate::cloud_provider::environment::Environment;
 use crate::cloud_provider::kubernetes::Kubernetes;
 use crate::cloud_provider::utilities::check_domain_for;
 use crate::cloud_provider::DeploymentTarget;
-use crate::cmd::helm::Timeout;
+use crate::cmd::helm::{HelmOciRegistry, HelmRepository, Timeout};
 use crate::cmd::kubectl::kubectl_exec_delete_secret;
-use crate::cmd::structs::LabelsContent;
-use crate::error::{cast_simple_error_to_engine_error, StringError};
+use crate::cmd::structs::{KubernetesJob, KubernetesJobStatus};
+use crate::error::{cast_simple_error_to_engine_error, SimpleError, StringError};
 use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::ProgressLevel::Info;
 use crate::models::{Context, Listen, Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope};
 
-pub trait Service {
+pub trait Service: Sync {
     fn context(&self) -> &Context;
     fn service_type(&self) -> ServiceType;
     fn id(&self) -> &str;
@@ -28,10 +31,12 @@ pub trait Service {
     fn name_with_id(&self) -> String {
         format!("{} ({})", self.name(), self.id())
     }
+    /// where the charts of the service are rendered, the same for a given execution and service id
     fn workspace_directory(&self) -> String {
         let dir_root = match self.service_type() {
             ServiceType::Application => "applications",
             ServiceType::ExternalService => "external-services",
+            ServiceType::CronJob => "cron-jobs",
             ServiceType::Database(_) => "databases",
             ServiceType::Router => "routers",
         };
@@ -39,17 +44,56 @@ pub trait Service {
         crate::fs::workspace_directory(
             self.context().workspace_root_dir(),
             self.context().execution_id(),
-            format!("{}/{}", dir_root, self.name()),
+            format!("{}/{}-{}", dir_root, self.name(), self.id()),
         )
     }
     fn version(&self) -> &str;
     fn action(&self) -> &Action;
     fn private_port(&self) -> Option<u16>;
     fn start_timeout(&self) -> Timeout<u32>;
+    /// how long the pods may take to stop after SIGTERM before being killed, the kubernetes default when unset
+    fn termination_grace_period_seconds(&self) -> Option<u32> {
+        None
+    }
     fn total_cpus(&self) -> String;
     fn cpu_burst(&self) -> String;
     fn total_ram_in_mib(&self) -> u32;
+    fn cpu_request(&self) -> String {
+        self.total_cpus()
+    }
+    fn cpu_limit(&self) -> String {
+        self.total_cpus()
+    }
+    fn ram_request_mib(&self) -> u32 {
+        self.total_ram_in_mib()
+    }
+    fn ram_limit_mib(&self) -> u32 {
+        self.total_ram_in_mib()
+    }
     fn total_instances(&self) -> u16;
+    // ids of the services which must be deployed before this one
+    fn depends_on(&self) -> Vec<String> {
+        vec![]
+    }
+    // addresses the service can be reached at once deployed, the in-cluster one when it has a private port
+    fn endpoints(&self, namespace: &str) -> Vec<Endpoint> {
+        match self.private_port() {
+            Some(private_port) => vec![Endpoint::internal(format!(
+                "{}.{}.svc.cluster.local:{}",
+                self.sanitized_name(),
+                namespace,
+                private_port
+            ))],
+            None => vec![],
+        }
+    }
+    /// deploy the service in a dedicated namespace instead of the one of its environment
+    fn namespace_override(&self) -> Option<&str> {
+        None
+    }
+    fn namespace<'b>(&'b self, environment: &'b Environment) -> &'b str {
+        self.namespace_override().unwrap_or_else(|| environment.namespace())
+    }
     fn tera_context(&self, target: &DeploymentTarget) -> Result<TeraContext, EngineError>;
     // used to retrieve logs by using Kubernetes labels (selector)
     fn selector(&self) -> String;
@@ -74,7 +118,13 @@ pub trait Service {
         )
     }
     fn is_valid(&self) -> Result<(), EngineError> {
-        let binaries = ["kubectl", "helm", "terraform", "aws-iam-authenticator"];
+        let tool_config = self.context().tool_config();
+        let binaries = [
+            tool_config.kubectl_binary(),
+            tool_config.helm_binary(),
+            "terraform",
+            "aws-iam-authenticator",
+        ];
 
         for binary in binaries.iter() {
             if !crate::cmd::utilities::does_binary_exist(binary) {
@@ -100,6 +150,7 @@ pub trait Service {
         match self.service_type() {
             ServiceType::Application => ProgressScope::Application { id },
             ServiceType::ExternalService => ProgressScope::ExternalService { id },
+            ServiceType::CronJob => ProgressScope::ExternalService { id },
             ServiceType::Database(_) => ProgressScope::Database { id },
             ServiceType::Router => ProgressScope::Router { id },
         }
@@ -112,6 +163,8 @@ pub trait StatelessService: Service + Create + Pause + Delete {
             crate::cloud_provider::service::Action::Create => self.on_create(deployment_target),
             crate::cloud_provider::service::Action::Delete => self.on_delete(deployment_target),
             crate::cloud_provider::service::Action::Pause => self.on_pause(deployment_target),
+            // a service which can't be restarted is deployed again
+            crate::cloud_provider::service::Action::Restart => self.on_create(deployment_target),
             crate::cloud_provider::service::Action::Nothing => Ok(()),
         }
     }
@@ -123,6 +176,8 @@ pub trait StatefulService: Service + Create + Pause + Delete + Backup + Clone +
             crate::cloud_provider::service::Action::Create => self.on_create(deployment_target),
             crate::cloud_provider::service::Action::Delete => self.on_delete(deployment_target),
             crate::cloud_provider::service::Action::Pause => self.on_pause(deployment_target),
+            // a service which can't be restarted is deployed again
+            crate::cloud_provider::service::Action::Restart => self.on_create(deployment_target),
             crate::cloud_provider::service::Action::Nothing => Ok(()),
         }
     }
@@ -133,7 +188,7 @@ pub trait Application: StatelessService {
     fn set_image(&mut self, image: Image);
 }
 
-pub trait ExternalService: StatelessService {}
+pub trait ExternalService: StatelessService + Restart {}
 
 pub trait Router: StatelessService + Listen {
     fn domains(&self) -> Vec<&str>;
@@ -166,10 +221,53 @@ pub trait Create {
     fn on_create_error(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
 }
 
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EndpointKind {
+    /// reachable from within the cluster only
+    Internal,
+    /// reachable from the internet through a DNS record
+    External,
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Endpoint {
+    pub kind: EndpointKind,
+    pub address: String,
+}
+
+impl Endpoint {
+    pub fn internal<T: Into<String>>(address: T) -> Self {
+        Endpoint {
+            kind: EndpointKind::Internal,
+            address: address.into(),
+        }
+    }
+
+    pub fn external<T: Into<String>>(address: T) -> Self {
+        Endpoint {
+            kind: EndpointKind::External,
+            address: address.into(),
+        }
+    }
+}
+
+/// how a service is paused
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PauseStrategy {
+    /// the helm release is uninstalled
+    Delete,
+    /// the workload is scaled to 0 replicas, its configuration and secrets are kept
+    ScaleToZero,
+}
+
 pub trait Pause {
     fn on_pause(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
     fn on_pause_check(&self) -> Result<(), EngineError>;
     fn on_pause_error(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
+    /// services are deleted on pause by default
+    fn pause_strategy(&self) -> PauseStrategy {
+        PauseStrategy::Delete
+    }
 }
 
 pub trait Delete {
@@ -178,6 +276,12 @@ pub trait Delete {
     fn on_delete_error(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
 }
 
+pub trait Restart {
+    fn on_restart(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
+    fn on_restart_check(&self) -> Result<(), EngineError>;
+    fn on_restart_error(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
+}
+
 pub trait Backup {
     fn on_backup(&self, target: &DeploymentTarget) -> Result<(), EngineError>;
     fn on_backup_check(&self) -> Result<(), EngineError>;
@@ -210,11 +314,147 @@ pub trait Terraform {
     fn terraform_resource_dir_path(&self) -> String;
 }
 
+/// where the chart of a service comes from
+#[derive(Clone)]
+pub enum ChartSource {
+    /// a chart directory bundled with the engine, rendered before being deployed
+    Local(String),
+    /// a chart published in a remote repository, deployed as is
+    Remote {
+        repo: HelmRepository,
+        chart: String,
+        version: Option<String>,
+    },
+    /// a chart stored as an OCI artifact, deployed as is
+    Oci {
+        registry: HelmOciRegistry,
+        chart: String,
+        version: Option<String>,
+    },
+}
+
+impl ChartSource {
+    pub fn is_remote(&self) -> bool {
+        match self {
+            ChartSource::Local(_) => false,
+            ChartSource::Remote { .. } | ChartSource::Oci { .. } => true,
+        }
+    }
+}
+
 pub trait Helm {
     fn helm_release_name(&self) -> String;
+    /// the release name given by the former engine versions when it differs, see `deployed_helm_release_name()`
+    fn legacy_helm_release_name(&self) -> Option<String> {
+        None
+    }
     fn helm_chart_dir(&self) -> String;
+    /// the chart is the bundled one from `helm_chart_dir()` by default
+    fn helm_chart_source(&self) -> ChartSource {
+        ChartSource::Local(self.helm_chart_dir())
+    }
     fn helm_chart_values_dir(&self) -> String;
     fn helm_chart_external_name_service_dir(&self) -> String;
+    /// chart version to deploy, the chart version is not pinned by default
+    fn helm_chart_version(&self) -> Option<String> {
+        None
+    }
+    /// extra values files given to helm after the chart values, the last one wins
+    fn helm_chart_values_files(&self) -> Vec<String> {
+        vec![]
+    }
+    /// `--set` values given to helm after the values files, they win over the files
+    fn helm_set_values(&self) -> Vec<(String, String)> {
+        vec![]
+    }
+    /// values of a local chart generated by the engine, given to helm before the extra values files
+    fn helm_chart_engine_values(&self, _target: &DeploymentTarget) -> Result<Option<String>, EngineError> {
+        Ok(None)
+    }
+    /// credentials of the private registry the image is pulled from
+    fn registry_credentials(&self) -> Option<RegistryCredentials> {
+        None
+    }
+    fn image_pull_secret_name(&self) -> String {
+        format!("{}-registry", self.helm_release_name())
+    }
+    /// let helm roll back a failed upgrade by itself
+    fn helm_atomic_upgrade(&self) -> bool {
+        false
+    }
+    /// let helm wait for the jobs of the release to complete, rather than polling them once the release is deployed
+    fn helm_wait_for_jobs(&self) -> bool {
+        false
+    }
+    /// the labels of the release given by the user, along with the ones of the engine see `helm_release_labels(..)`
+    fn helm_release_labels(&self) -> BTreeMap<String, String> {
+        BTreeMap::new()
+    }
+}
+
+pub const DEPLOYMENT_STEP_TEMPLATES_GENERATED: &str = "templates_generated";
+pub const DEPLOYMENT_STEP_HELM_UPGRADED: &str = "helm_upgraded";
+pub const DEPLOYMENT_STEP_JOB_READY: &str = "job_ready";
+pub const DEPLOYMENT_STEP_POD_READY: &str = "pod_ready";
+
+/// the file of the workspace directory the values generated by the engine are written to
+pub const ENGINE_VALUES_FILE_NAME: &str = "engine-values.yaml";
+/// the labels of the helm releases set by the engine, the labels of the user can't override them
+pub const ENGINE_RELEASE_LABELS: [&str; 3] = ["serviceId", "serviceType", "executionId"];
+/// the kubernetes default of `terminationGracePeriodSeconds`
+pub const DEFAULT_TERMINATION_GRACE_PERIOD_SECONDS: u32 = 30;
+
+const JOB_LOGS_TAIL_LINES: u32 = 100;
+const JOB_LOGS_MAX_LENGTH_IN_BYTES: usize = 4096;
+
+/// Receive the discrete steps of a service deployment, e.g. to display a progress bar.
+pub trait DeploymentListener: Send + Sync {
+    fn on_step(&self, service_type: ServiceType, step: &str);
+    /// each line printed by helm while the service is deployed, as soon as it is printed
+    fn on_output_line(&self, _service_type: ServiceType, _line: &str) {}
+}
+
+/// Deployment listeners registered on a `Context`. They are not part of the context identity,
+/// so two contexts with different listeners are still equal.
+#[derive(Clone, Default)]
+pub struct DeploymentListeners {
+    listeners: Vec<Arc<dyn DeploymentListener>>,
+}
+
+impl DeploymentListeners {
+    pub fn add(&mut self, listener: Arc<dyn DeploymentListener>) {
+        self.listeners.push(listener);
+    }
+
+    pub fn on_step<T>(&self, service: &T, step: &str)
+    where
+        T: Service + ?Sized,
+    {
+        for listener in self.listeners.iter() {
+            listener.on_step(service.service_type(), step);
+        }
+    }
+
+    pub fn on_output_line<T>(&self, service: &T, line: &str)
+    where
+        T: Service + ?Sized,
+    {
+        for listener in self.listeners.iter() {
+            listener.on_output_line(service.service_type(), line);
+        }
+    }
+}
+
+impl PartialEq for DeploymentListeners {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for DeploymentListeners {}
+
+impl Hash for DeploymentListeners {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
 }
 
 #[derive(Clone, Eq, PartialEq, Hash)]
@@ -222,6 +462,7 @@ pub enum Action {
     Create,
     Pause,
     Delete,
+    Restart,
     Nothing,
 }
 
@@ -247,6 +488,7 @@ pub enum DatabaseType<'a> {
 pub enum ServiceType<'a> {
     Application,
     ExternalService,
+    CronJob,
     Database(DatabaseType<'a>),
     Router,
 }
@@ -256,6 +498,7 @@ impl<'a> ServiceType<'a> {
         match self {
             ServiceType::Application => "Application",
             ServiceType::ExternalService => "ExternalService",
+            ServiceType::CronJob => "CronJob",
             ServiceType::Database(db_type) => match db_type {
                 DatabaseType::PostgreSQL(_) => "PostgreSQL database",
                 DatabaseType::MongoDB(_) => "MongoDB database",
@@ -305,7 +548,7 @@ pub fn default_tera_context(
     context.insert("region", kubernetes.region());
     context.insert("name", service.name());
     context.insert("sanitized_name", &service.sanitized_name());
-    context.insert("namespace", environment.namespace());
+    context.insert("namespace", service.namespace(environment));
     context.insert("cluster_name", kubernetes.name());
     context.insert("total_cpus", &service.total_cpus());
     context.insert("total_ram_in_mib", &service.total_ram_in_mib());
@@ -318,6 +561,11 @@ pub fn default_tera_context(
 
     context.insert("version", service.version());
 
+    // a changed pod annotation makes kubernetes recreate the pods, even when nothing else has changed
+    if service.context().is_force_redeploy() {
+        context.insert("redeploy_timestamp", &chrono::Utc::now().timestamp().to_string());
+    }
+
     context
 }
 
@@ -346,6 +594,90 @@ where
     )
 }
 
+lazy_static! {
+    /// the (namespace, release name) of the helm releases being upgraded or uninstalled by the engine
+    static ref LOCKED_RELEASES: Mutex<HashSet<(String, String)>> = Mutex::new(HashSet::new());
+}
+
+/// held during the helm upgrade or uninstall of a release, the release is unlocked when it is dropped
+pub struct ReleaseLock {
+    namespace: String,
+    release_name: String,
+}
+
+impl ReleaseLock {
+    /// none if another operation holds the lock of the release
+    pub fn acquire(namespace: &str, release_name: &str) -> Option<ReleaseLock> {
+        let key = (namespace.to_string(), release_name.to_string());
+        let mut locked_releases = LOCKED_RELEASES.lock().unwrap_or_else(|err| err.into_inner());
+
+        match locked_releases.insert(key) {
+            true => Some(ReleaseLock {
+                namespace: namespace.to_string(),
+                release_name: release_name.to_string(),
+            }),
+            false => None,
+        }
+    }
+}
+
+impl Drop for ReleaseLock {
+    fn drop(&mut self) {
+        let mut locked_releases = LOCKED_RELEASES.lock().unwrap_or_else(|err| err.into_inner());
+        locked_releases.remove(&(self.namespace.clone(), self.release_name.clone()));
+    }
+}
+
+/// two helm operations on the same release fail with cryptic errors, the second one is rejected instead. The lock is
+/// keyed on the name of the release the operations run against, see `deployed_helm_release_name()`
+fn lock_release<T>(service: &T, namespace: &str, helm_release_name: &str) -> Result<ReleaseLock, EngineError>
+where
+    T: Service + Helm,
+{
+    ReleaseLock::acquire(namespace, helm_release_name).ok_or_else(|| {
+        service.engine_error(
+            EngineErrorCause::User("Another operation is in progress for this service, please retry once it is done"),
+            format!(
+                "the helm release {} of the namespace {} is already being deployed or deleted",
+                helm_release_name, namespace
+            ),
+        )
+    })
+}
+
+/// the name of the release of the service in the namespace, the new one unless it is deployed with its legacy name
+pub fn deployed_helm_release_name<T>(
+    kubernetes: &dyn Kubernetes,
+    namespace: &str,
+    service: &T,
+) -> Result<String, EngineError>
+where
+    T: Service + Helm,
+{
+    // a release deployed with the legacy name keeps it, a second release with the new name would not be able to own
+    // the same resources
+    let helm_release_name = service.helm_release_name();
+    let legacy_helm_release_name = match service.legacy_helm_release_name() {
+        Some(name) if name != helm_release_name => name,
+        _ => return Ok(helm_release_name),
+    };
+
+    let releases = cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cmd::helm::helm_exec_list(
+            kubernetes.config_file_path()?,
+            namespace,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )?;
+
+    match releases.iter().any(|release| release.name == legacy_helm_release_name) {
+        true => Ok(legacy_helm_release_name),
+        false => Ok(helm_release_name),
+    }
+}
+
 /// deploy a stateless service (app, router, database...) on Kubernetes
 pub fn deploy_stateless_service<T>(
     target: &DeploymentTarget,
@@ -360,76 +692,649 @@ where
         DeploymentTarget::SelfHosted(k, env) => (*k, *env),
     };
 
+    // a dry run does not reach the cluster to look for a release deployed with the legacy name
+    let helm_release_name = match service.context().is_dry_run_deploy() {
+        true => service.helm_release_name(),
+        false => deployed_helm_release_name(kubernetes, service.namespace(environment), service)?,
+    };
+    let _release_lock = lock_release(service, service.namespace(environment), helm_release_name.as_str())?;
+
     let workspace_dir = service.workspace_directory();
-    let tera_context = service.tera_context(target)?;
+    let chart_source = service.helm_chart_source();
+    let mut values_files = vec![];
 
-    let _ = cast_simple_error_to_engine_error(
-        service.engine_error_scope(),
-        service.context().execution_id(),
-        crate::template::generate_and_copy_all_files_into_dir(
-            service.helm_chart_dir(),
+    // a remote chart is deployed as is, there is nothing to render
+    if let ChartSource::Local(chart_dir) = &chart_source {
+        let tera_context = service.tera_context(target)?;
+
+        match crate::template::generate_and_copy_all_files_into_dir(
+            chart_dir.as_str(),
             workspace_dir.as_str(),
             &tera_context,
-        ),
-    )?;
+        ) {
+            Err(err) if crate::template::is_filter_not_found_error(&err) => {
+                return Err(service.engine_error(
+                    EngineErrorCause::User("The chart templates use a filter which does not exist."),
+                    err.message.unwrap_or_default(),
+                ));
+            }
+            result => {
+                let _ = cast_simple_error_to_engine_error(
+                    service.engine_error_scope(),
+                    service.context().execution_id(),
+                    result,
+                )?;
+            }
+        }
+
+        // the engine values come first, the values files of the user override them
+        if let Some(engine_values) = service.helm_chart_engine_values(target)? {
+            let engine_values_file = format!("{}/{}", workspace_dir, ENGINE_VALUES_FILE_NAME);
+
+            std::fs::write(engine_values_file.as_str(), engine_values).map_err(|err| {
+                service.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("unable to write the values file {}: {}", engine_values_file, err),
+                )
+            })?;
+
+            values_files.push(engine_values_file);
+        }
+    }
+
+    values_files.extend(service.helm_chart_values_files());
+
+    service
+        .context()
+        .deployment_listeners()
+        .on_step(service, DEPLOYMENT_STEP_TEMPLATES_GENERATED);
+
+    if service.context().is_rendered_templates_validation() && !chart_source.is_remote() {
+        match crate::template::validate_rendered_dir(workspace_dir.as_str(), &values_files) {
+            Ok(_) => {}
+            Err(err) => {
+                return Err(service.engine_error(
+                    EngineErrorCause::User(
+                        "The generated manifests of your application are not valid. \
+                        Please check your environment variables and configuration.",
+                    ),
+                    err.message.unwrap_or("<no message>".to_string()),
+                ));
+            }
+        }
+    }
+
+    if service.context().is_dry_run_deploy() {
+        // there is no cluster to validate the chart against, helm lints it offline instead
+        if service.context().is_rendered_templates_validation() {
+            lint_stateless_service(target, service)?;
+        }
+
+        // keep the rendered workspace directory so it can be inspected
+        info!(
+            "dry run: {} {} has been rendered into {} but not deployed",
+            service.service_type().name(),
+            service.name_with_id(),
+            workspace_dir.as_str()
+        );
+
+        return Ok(());
+    }
 
-    let helm_release_name = service.helm_release_name();
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
 
     // define labels to add to namespace
     let namespace_labels = match service.context().resource_expiration_in_seconds() {
-        Some(_) => Some(vec![
-            (LabelsContent {
-                name: "ttl".to_string(),
-                value: format! {"{}", service.context().resource_expiration_in_seconds().unwrap()},
-            }),
-        ]),
+        Some(resource_expiration_in_seconds) => {
+            let mut labels = BTreeMap::new();
+            labels.insert("ttl".to_string(), resource_expiration_in_seconds.to_string());
+            Some(labels)
+        }
         None => None,
     };
 
-    // create a namespace with labels if do not exists
+    // create a namespace with labels and the resource quota of the environment if do not exists
+    let resource_quota = environment.resource_quota().map(|resource_quota| resource_quota.hard());
     let _ = cast_simple_error_to_engine_error(
         service.engine_error_scope(),
         service.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_create_namespace(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             namespace_labels,
+            None,
+            resource_quota.as_ref(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
     )?;
 
+    // the image pull secret must exist before the pods are created
+    if let Some(registry_credentials) = service.registry_credentials() {
+        let _ = cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            crate::cmd::kubectl::kubectl_exec_create_docker_registry_secret(
+                kubernetes_config_file_path.as_str(),
+                service.namespace(environment),
+                service.image_pull_secret_name().as_str(),
+                registry_credentials.server.as_str(),
+                registry_credentials.login.as_str(),
+                registry_credentials.password.as_str(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+    }
+
+    let (chart, chart_version) = match &chart_source {
+        ChartSource::Local(_) => (workspace_dir.clone(), service.helm_chart_version()),
+        ChartSource::Remote { repo, chart, version } => {
+            // the repository is added (or updated) on each deployment so its index knows the requested version
+            let _ = cast_simple_error_to_engine_error(
+                service.engine_error_scope(),
+                service.context().execution_id(),
+                crate::cmd::helm::helm_exec_repo_add(
+                    repo.name.as_str(),
+                    repo.url.as_str(),
+                    repo.credentials.as_ref(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            )?;
+
+            let _ = cast_simple_error_to_engine_error(
+                service.engine_error_scope(),
+                service.context().execution_id(),
+                crate::cmd::helm::helm_exec_repo_update(
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            )?;
+
+            (format!("{}/{}", repo.name, chart), version.clone())
+        }
+        ChartSource::Oci {
+            registry,
+            chart,
+            version,
+        } => {
+            if let Some(credentials) = &registry.credentials {
+                if let Err(err) = crate::cmd::helm::helm_exec_registry_login(
+                    registry,
+                    credentials,
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ) {
+                    return Err(service.engine_error(
+                        EngineErrorCause::User("Unable to log in the chart OCI registry, check its credentials"),
+                        format!(
+                            "helm registry login to {} failed for {}: {}",
+                            registry.host(),
+                            service.name_with_id(),
+                            err.message.unwrap_or_default()
+                        ),
+                    ));
+                }
+            }
+
+            (registry.chart_reference(chart), version.clone())
+        }
+    };
+
+    // check the requested chart version exists before trying to deploy it
+    // an OCI registry has no index to search, helm upgrade fails itself on a missing version
+    if let (Some(chart_version), false) = (&chart_version, chart.starts_with("oci://")) {
+        let available_versions = cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            crate::cmd::helm::helm_exec_chart_versions(
+                chart.as_str(),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )?;
+
+        if !available_versions.contains(chart_version) {
+            return Err(service.engine_error(
+                EngineErrorCause::User("The requested chart version does not exist, please use an available one"),
+                format!(
+                    "chart version {} is not available for {}, available versions are: {}",
+                    chart_version,
+                    service.name_with_id(),
+                    available_versions.join(", ")
+                ),
+            ));
+        }
+    }
+
+    // the listeners follow the upgrade live, it may last until the start timeout
+    let mut on_output_line = |line: &str| service.context().deployment_listeners().on_output_line(service, line);
+
     // do exec helm upgrade and return the last deployment status
     let helm_history_row = cast_simple_error_to_engine_error(
         service.engine_error_scope(),
         service.context().execution_id(),
         crate::cmd::helm::helm_exec_with_upgrade_history(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             helm_release_name.as_str(),
-            workspace_dir.as_str(),
+            chart.as_str(),
+            chart_version.as_deref(),
+            &values_files,
+            &service.helm_set_values(),
             service.start_timeout(),
+            service.helm_atomic_upgrade(),
+            service.helm_wait_for_jobs(),
+            &helm_release_labels(service),
             kubernetes.cloud_provider().credentials_environment_variables(),
+            service.context().cancellation_token(),
+            Some(&mut on_output_line),
         ),
-    )?;
+    )
+    // helm fails when the release is not ready before the timeout
+    .map_err(|err| helm_upgrade_error(service, err.retryable()))?;
 
     // check deployment status
     if helm_history_row.is_none() || !helm_history_row.unwrap().is_successfully_deployed() {
-        return Err(thrown_error);
+        if let ServiceType::ExternalService = service.service_type() {
+            rollback_stateless_service(kubernetes, environment, service);
+        }
+
+        return Err(thrown_error.retryable());
     }
 
-    let _ = cast_simple_error_to_engine_error(
+    service
+        .context()
+        .deployment_listeners()
+        .on_step(service, DEPLOYMENT_STEP_HELM_UPGRADED);
+
+    match service.service_type() {
+        // helm has already waited for the job to succeed
+        ServiceType::ExternalService if service.helm_wait_for_jobs() => {
+            service
+                .context()
+                .deployment_listeners()
+                .on_step(service, DEPLOYMENT_STEP_JOB_READY);
+        }
+        ServiceType::ExternalService => {
+            // an external service is a job, it is ready once it has succeeded
+            let is_job_ready = cast_simple_error_to_engine_error(
+                service.engine_error_scope(),
+                service.context().execution_id(),
+                crate::cmd::kubectl::kubectl_exec_is_job_ready_with_policy(
+                    kubernetes_config_file_path.as_str(),
+                    service.namespace(environment),
+                    service.sanitized_name().as_str(),
+                    crate::cmd::kubectl::RetryPolicy::from_timeout(service.start_timeout())
+                        .with_cancellation_token(service.context().cancellation_token().clone()),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            );
+
+            let error = match is_job_ready {
+                Ok(Some(true)) => None,
+                Ok(_) => Some(thrown_error),
+                Err(err) => Some(err),
+            };
+
+            if let Some(error) = error {
+                let job = crate::cmd::kubectl::kubectl_exec_get_if_exists::<_, KubernetesJob>(
+                    kubernetes_config_file_path.as_str(),
+                    service.namespace(environment),
+                    "job",
+                    service.sanitized_name().as_str(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                )
+                .unwrap_or_default();
+
+                let error = job_failure_error(service, job.as_ref().map(|job| &job.status), error);
+                return Err(append_job_logs_to_error(kubernetes, environment, service, error));
+            }
+
+            service
+                .context()
+                .deployment_listeners()
+                .on_step(service, DEPLOYMENT_STEP_JOB_READY);
+        }
+        ServiceType::CronJob => {
+            // a cron job does not run right away, so there is nothing to wait for but its presence
+            let is_cronjob_present = cast_simple_error_to_engine_error(
+                service.engine_error_scope(),
+                service.context().execution_id(),
+                crate::cmd::kubectl::kubectl_exec_is_cronjob_present(
+                    kubernetes_config_file_path.as_str(),
+                    service.namespace(environment),
+                    service.sanitized_name().as_str(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            )?;
+
+            if !is_cronjob_present {
+                return Err(thrown_error);
+            }
+        }
+        _ => {
+            let _ = cast_simple_error_to_engine_error(
+                service.engine_error_scope(),
+                service.context().execution_id(),
+                crate::cmd::kubectl::kubectl_exec_is_pod_ready_with_retry(
+                    kubernetes_config_file_path.as_str(),
+                    service.namespace(environment),
+                    service.selector().as_str(),
+                    kubernetes.cloud_provider().credentials_environment_variables(),
+                ),
+            )
+            .map_err(|err| err.retryable())?;
+
+            service
+                .context()
+                .deployment_listeners()
+                .on_step(service, DEPLOYMENT_STEP_POD_READY);
+        }
+    }
+
+    Ok(())
+}
+
+/// lint the local chart of a stateless service rendered with its context, without any cluster nor kubeconfig
+pub fn lint_stateless_service<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: Service + Helm,
+{
+    // a remote chart is deployed as is, it is not rendered by the engine
+    let chart_dir = match service.helm_chart_source() {
+        ChartSource::Local(chart_dir) => chart_dir,
+        _ => return Ok(()),
+    };
+
+    let tera_context = service.tera_context(target)?;
+
+    // the templates read the image and the resources from the engine values, like on a real upgrade
+    let mut values_files = vec![];
+    if let Some(engine_values) = service.helm_chart_engine_values(target)? {
+        let workspace_dir = service.workspace_directory();
+        let engine_values_file = format!("{}/{}", workspace_dir, ENGINE_VALUES_FILE_NAME);
+
+        std::fs::create_dir_all(workspace_dir.as_str())
+            .and_then(|_| std::fs::write(engine_values_file.as_str(), engine_values))
+            .map_err(|err| {
+                service.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("unable to write the values file {}: {}", engine_values_file, err),
+                )
+            })?;
+
+        values_files.push(engine_values_file);
+    }
+    values_files.extend(service.helm_chart_values_files());
+
+    crate::template::lint_with_values_files(chart_dir.as_str(), &tera_context, &values_files).map_err(|messages| {
+        service.engine_error(
+            EngineErrorCause::User(
+                "The generated chart of your application does not pass helm lint. \
+                Please check your environment variables and configuration.",
+            ),
+            format!(
+                "the chart of {} {} is not valid:\n{}",
+                service.service_type().name(),
+                service.name_with_id(),
+                messages.join("\n")
+            ),
+        )
+    })
+}
+
+/// the labels of the helm release of the service, e.g. `helm list --selector serviceId={id}` finds it back
+pub fn helm_release_labels<T>(service: &T) -> BTreeMap<String, String>
+where
+    T: Service + Helm,
+{
+    let mut labels = service
+        .helm_release_labels()
+        .into_iter()
+        .filter(|(key, _)| !ENGINE_RELEASE_LABELS.contains(&key.as_str()))
+        .collect::<BTreeMap<_, _>>();
+
+    // a label value can't have spaces (`PostgreSQL database`)
+    let service_type = service.service_type().name().to_lowercase().replace(' ', "-");
+    labels.insert("serviceId".to_string(), service.id().to_string());
+    labels.insert("serviceType".to_string(), service_type);
+    labels.insert("executionId".to_string(), service.context().execution_id().to_string());
+
+    labels
+}
+
+/// the error of a failed helm upgrade: kubernetes rejects the pods exceeding the resource quota of the namespace,
+/// they would be rejected again whatever the number of retries
+pub fn helm_upgrade_error<T>(service: &T, error: EngineError) -> EngineError
+where
+    T: Service,
+{
+    match &error.message {
+        Some(message) if message.contains("exceeded quota") => service.engine_error(
+            EngineErrorCause::User(
+                "Your environment exceeds the resource quota of its namespace. \
+                Lower the resources or the instances of your services, or raise the quota",
+            ),
+            format!(
+                "{} {} exceeds the resource quota of its namespace: {}",
+                service.service_type().name(),
+                service.name_with_id(),
+                message
+            ),
+        ),
+        _ => error,
+    }
+}
+
+/// the error of a job which has not succeeded: a job killed at its deadline or failed more than its retries allow
+/// would fail again, where the job may just not have succeeded before the timeout and is worth another try
+pub fn job_failure_error<T>(service: &T, job_status: Option<&KubernetesJobStatus>, error: EngineError) -> EngineError
+where
+    T: Service,
+{
+    match job_status {
+        Some(job_status) if job_status.has_exceeded_deadline() => service.engine_error(
+            EngineErrorCause::User(
+                "Your job has been stopped for running longer than its deadline. \
+                Make it run faster or raise its active deadline seconds",
+            ),
+            format!(
+                "{} {} has exceeded its deadline ⤬",
+                service.service_type().name(),
+                service.name_with_id()
+            ),
+        ),
+        Some(job_status) if job_status.has_failed() => service.engine_error(
+            EngineErrorCause::User(
+                "Your job has failed as many times as its backoff limit allows. \
+                You can check its log from the web interface or the CLI with `qovery log`",
+            ),
+            format!(
+                "{} {} has failed ⤬",
+                service.service_type().name(),
+                service.name_with_id()
+            ),
+        ),
+        _ => error.retryable(),
+    }
+}
+
+/// add the end of the job logs to the error, so the user knows why the job did not succeed
+fn append_job_logs_to_error<T>(
+    kubernetes: &dyn Kubernetes,
+    environment: &Environment,
+    service: &T,
+    mut error: EngineError,
+) -> EngineError
+where
+    T: Service,
+{
+    let kubernetes_config_file_path = match kubernetes.config_file_path() {
+        Ok(path) => path,
+        Err(_) => return error,
+    };
+
+    let logs = match crate::cmd::kubectl::kubectl_exec_get_job_pod_logs(
+        kubernetes_config_file_path.as_str(),
+        service.namespace(environment),
+        service.sanitized_name().as_str(),
+        JOB_LOGS_TAIL_LINES,
+        kubernetes.cloud_provider().credentials_environment_variables(),
+    ) {
+        Ok(logs) if !logs.is_empty() => logs,
+        Ok(_) => return error,
+        Err(err) => {
+            warn!("unable to get the logs of {}: {:?}", service.name_with_id(), err);
+            return error;
+        }
+    };
+
+    let logs = crate::string::tail(logs.join("\n"), JOB_LOGS_MAX_LENGTH_IN_BYTES);
+    error.message = Some(match error.message {
+        Some(message) => format!("{}\nlast logs:\n{}", message, logs),
+        None => format!("last logs:\n{}", logs),
+    });
+
+    error
+}
+
+/// whether the deployed release already has the chart version and the values to deploy, so the upgrade can be skipped
+/// only pinned remote charts are compared: a local chart is rendered from the service configuration (image,
+/// environment variables...) which is not part of the values, and an unpinned chart may have a newer version.
+/// A forced redeploy is never up to date.
+pub fn is_stateless_service_up_to_date<T>(target: &DeploymentTarget, service: &T) -> Result<bool, EngineError>
+where
+    T: Service + Helm,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    if service.context().is_dry_run_deploy() || service.context().is_force_redeploy() {
+        return Ok(false);
+    }
+
+    // helm infers the types of the `--set` values, they can't be reliably compared to the deployed values
+    if !service.helm_set_values().is_empty() {
+        return Ok(false);
+    }
+
+    let (chart, chart_version) = match service.helm_chart_source() {
+        ChartSource::Remote {
+            chart,
+            version: Some(version),
+            ..
+        }
+        | ChartSource::Oci {
+            chart,
+            version: Some(version),
+            ..
+        } => (chart, version),
+        _ => return Ok(false),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let helm_release_name = deployed_helm_release_name(kubernetes, service.namespace(environment), service)?;
+
+    let history_rows = cast_simple_error_to_engine_error(
         service.engine_error_scope(),
         service.context().execution_id(),
-        crate::cmd::kubectl::kubectl_exec_is_pod_ready_with_retry(
+        crate::cmd::helm::helm_exec_history(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
-            service.selector().as_str(),
+            service.namespace(environment),
+            helm_release_name.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
     )?;
 
-    Ok(())
+    match history_rows.first() {
+        Some(row) if row.is_successfully_deployed() && row.chart == format!("{}-{}", chart, chart_version) => {}
+        _ => return Ok(false),
+    }
+
+    let deployed_values = match cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cmd::helm::helm_exec_get_values(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            helm_release_name.as_str(),
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )? {
+        Some(values) => values,
+        None => return Ok(false),
+    };
+
+    let mut values = serde_yaml::Value::Mapping(serde_yaml::Mapping::new());
+    for values_file in service.helm_chart_values_files() {
+        let content = std::fs::read_to_string(values_file.as_str()).map_err(|err| {
+            service.engine_error(
+                EngineErrorCause::Internal,
+                format!("unable to read the values file {}: {}", values_file, err),
+            )
+        })?;
+
+        let file_values = cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            crate::cmd::helm::parse_helm_values(content.as_str()),
+        )?;
+
+        values = crate::cmd::helm::helm_merge_values(values, file_values);
+    }
+
+    Ok(crate::cmd::helm::helm_values_equal(&values, &deployed_values))
+}
+
+/// rollback a stateless service to its last deployed revision if there is one
+/// the result is only logged, the deployment error must be the one returned to the caller
+fn rollback_stateless_service<T>(kubernetes: &dyn Kubernetes, environment: &Environment, service: &T)
+where
+    T: Service + Helm,
+{
+    let kubernetes_config_file_path = match kubernetes.config_file_path() {
+        Ok(path) => path,
+        Err(err) => {
+            error!("unable to get the kubeconfig to rollback {}: {:?}", service.name_with_id(), err);
+            return;
+        }
+    };
+
+    let helm_release_name = deployed_helm_release_name(kubernetes, service.namespace(environment), service)
+        .unwrap_or_else(|_| service.helm_release_name());
+    let history_rows = match crate::cmd::helm::helm_exec_history(
+        kubernetes_config_file_path.as_str(),
+        service.namespace(environment),
+        helm_release_name.as_str(),
+        kubernetes.cloud_provider().credentials_environment_variables(),
+    ) {
+        Ok(rows) => rows,
+        Err(err) => {
+            error!("unable to get helm history of {}: {:?}", helm_release_name, err);
+            return;
+        }
+    };
+
+    let revision = match crate::cmd::helm::helm_last_deployed_revision(&history_rows) {
+        Some(revision) => revision,
+        None => {
+            info!("no deployed revision to rollback to for {}", helm_release_name);
+            return;
+        }
+    };
+
+    match crate::cmd::helm::helm_exec_rollback(
+        kubernetes_config_file_path.as_str(),
+        service.namespace(environment),
+        helm_release_name.as_str(),
+        revision,
+        service.start_timeout(),
+        kubernetes.cloud_provider().credentials_environment_variables(),
+    ) {
+        Ok(_) => info!("{} has been rolled back to revision {}", helm_release_name, revision),
+        Err(err) => error!(
+            "unable to rollback {} to revision {}: {:?}",
+            helm_release_name, revision, err
+        ),
+    }
 }
 
 /// do specific operations on a stateless service deployment error
@@ -442,15 +1347,17 @@ where
         DeploymentTarget::SelfHosted(k, env) => (*k, *env),
     };
 
+    let helm_release_name = deployed_helm_release_name(kubernetes, service.namespace(environment), service)?;
+    let _release_lock = lock_release(service, service.namespace(environment), helm_release_name.as_str())?;
+
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
-    let helm_release_name = service.helm_release_name();
 
     let history_rows = cast_simple_error_to_engine_error(
         service.engine_error_scope(),
         service.context().execution_id(),
         crate::cmd::helm::helm_exec_history(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             helm_release_name.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -462,8 +1369,9 @@ where
             service.context().execution_id(),
             crate::cmd::helm::helm_exec_uninstall(
                 kubernetes_config_file_path.as_str(),
-                environment.namespace(),
+                service.namespace(environment),
                 helm_release_name.as_str(),
+                service.start_timeout(),
                 kubernetes.cloud_provider().credentials_environment_variables(),
             ),
         )?;
@@ -472,7 +1380,13 @@ where
     Ok(())
 }
 
-pub fn delete_stateless_service<T>(target: &DeploymentTarget, service: &T, is_error: bool) -> Result<(), EngineError>
+/// when `wait` is set, it only returns once the pods of the service are gone
+pub fn delete_stateless_service<T>(
+    target: &DeploymentTarget,
+    service: &T,
+    is_error: bool,
+    wait: bool,
+) -> Result<(), EngineError>
 where
     T: Service + Helm,
 {
@@ -481,18 +1395,148 @@ where
         DeploymentTarget::SelfHosted(k, env) => (*k, *env),
     };
 
-    let helm_release_name = service.helm_release_name();
+    let helm_release_name = deployed_helm_release_name(kubernetes, service.namespace(environment), service)?;
+    let _release_lock = lock_release(service, service.namespace(environment), helm_release_name.as_str())?;
 
     if is_error {
-        let _ = get_stateless_resource_information(kubernetes, environment, service.selector().as_str())?;
+        let _ = get_stateless_resource_information(
+            kubernetes,
+            service.namespace(environment),
+            service.selector().as_str(),
+        )?;
     }
 
     // clean the resource
-    let _ = do_stateless_service_cleanup(kubernetes, environment, helm_release_name.as_str())?;
+    let _ = do_stateless_service_cleanup(
+        kubernetes,
+        service.namespace(environment),
+        helm_release_name.as_str(),
+        service.start_timeout(),
+    )?;
+
+    // the helm uninstall does not wait for the pods to terminate, a namespace deletion right after could race
+    if wait {
+        let kubernetes_config_file_path = kubernetes.config_file_path()?;
+
+        let _ = cast_simple_error_to_engine_error(
+            service.engine_error_scope(),
+            service.context().execution_id(),
+            crate::cmd::kubectl::kubectl_exec_wait_for_pods_deletion(
+                kubernetes_config_file_path.as_str(),
+                service.namespace(environment),
+                service.selector().as_str(),
+                pods_deletion_timeout(service),
+                kubernetes.cloud_provider().credentials_environment_variables(),
+                service.context().cancellation_token(),
+            ),
+        )?;
+    }
 
     Ok(())
 }
 
+/// the wait for the pods to be gone after the uninstall: the start timeout, plus the grace period the pods are given
+/// to stop before being killed
+pub fn pods_deletion_timeout<T>(service: &T) -> Timeout<u32>
+where
+    T: Service,
+{
+    let termination_grace_period_seconds = service
+        .termination_grace_period_seconds()
+        .unwrap_or(DEFAULT_TERMINATION_GRACE_PERIOD_SECONDS);
+
+    Timeout::Value(
+        service
+            .start_timeout()
+            .in_seconds()
+            .saturating_add(termination_grace_period_seconds),
+    )
+}
+
+/// scale the resource of a stateless service to 0 replicas, its helm release is kept so it can be resumed
+pub fn scale_to_zero_stateless_service<T>(target: &DeploymentTarget, service: &T, kind: &str) -> Result<(), EngineError>
+where
+    T: Service,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+
+    cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cmd::kubectl::kubectl_exec_scale(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            format!("{}/{}", kind, service.sanitized_name()).as_str(),
+            0,
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )
+}
+
+/// restart the resource of a stateless service without redeploying its helm release,
+/// a job is only deleted: the caller upgrades the release to create it again
+pub fn restart_stateless_service<T>(target: &DeploymentTarget, service: &T, kind: &str) -> Result<(), EngineError>
+where
+    T: Service + Helm,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+
+    cast_simple_error_to_engine_error(
+        service.engine_error_scope(),
+        service.context().execution_id(),
+        crate::cmd::kubectl::kubectl_exec_rollout_restart(
+            kubernetes_config_file_path.as_str(),
+            service.namespace(environment),
+            kind,
+            service.sanitized_name().as_str(),
+            kubernetes.cloud_provider().credentials_environment_variables(),
+        ),
+    )
+}
+
+/// wait for the rollout of a deployment backed service to complete, within the start timeout of the service
+pub fn wait_for_rollout<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
+where
+    T: Service,
+{
+    let (kubernetes, environment) = match target {
+        DeploymentTarget::ManagedServices(k, env) => (*k, *env),
+        DeploymentTarget::SelfHosted(k, env) => (*k, *env),
+    };
+
+    let kubernetes_config_file_path = kubernetes.config_file_path()?;
+    let resource = format!("deployment/{}", service.sanitized_name());
+
+    let result = crate::cmd::kubectl::kubectl_exec_wait_for_rollout(
+        kubernetes_config_file_path.as_str(),
+        service.namespace(environment),
+        resource.as_str(),
+        service.start_timeout(),
+        kubernetes.cloud_provider().credentials_environment_variables(),
+    );
+
+    rollout_result_to_engine_error(service.engine_error_scope(), service.context().execution_id(), result)
+}
+
+fn rollout_result_to_engine_error(
+    scope: EngineErrorScope,
+    execution_id: &str,
+    result: Result<(), SimpleError>,
+) -> Result<(), EngineError> {
+    // pods that are too slow to become ready are not a user mistake, it is worth another try
+    cast_simple_error_to_engine_error(scope, execution_id, result).map_err(|err| err.retryable())
+}
+
 pub fn deploy_stateful_service<T>(target: &DeploymentTarget, service: &T) -> Result<(), EngineError>
 where
     T: StatefulService + Helm + Terraform,
@@ -587,16 +1631,16 @@ where
 
             // define labels to add to namespace
             let namespace_labels = match service.context().resource_expiration_in_seconds() {
-                Some(_) => Some(vec![
-                    (LabelsContent {
-                        name: "ttl".into(),
-                        value: format!("{}", service.context().resource_expiration_in_seconds().unwrap()),
-                    }),
-                ]),
+                Some(resource_expiration_in_seconds) => {
+                    let mut labels = BTreeMap::new();
+                    labels.insert("ttl".to_string(), resource_expiration_in_seconds.to_string());
+                    Some(labels)
+                }
                 None => None,
             };
 
-            // create a namespace with labels if it does not exist
+            // create a namespace with labels and the resource quota of the environment if it does not exist
+            let resource_quota = environment.resource_quota().map(|resource_quota| resource_quota.hard());
             let _ = cast_simple_error_to_engine_error(
                 service.engine_error_scope(),
                 service.context().execution_id(),
@@ -604,6 +1648,8 @@ where
                     kubernetes_config_file_path.as_str(),
                     environment.namespace(),
                     namespace_labels,
+                    None,
+                    resource_quota.as_ref(),
                     kubernetes.cloud_provider().credentials_environment_variables(),
                 ),
             )?;
@@ -617,10 +1663,19 @@ where
                     environment.namespace(),
                     service.helm_release_name().as_str(),
                     workspace_dir.as_str(),
+                    None,
+                    &[],
+                    &[],
                     service.start_timeout(),
+                    false,
+                    false,
+                    &helm_release_labels(service),
                     kubernetes.cloud_provider().credentials_environment_variables(),
+                    service.context().cancellation_token(),
+                    None,
                 ),
-            )?;
+            )
+            .map_err(|err| helm_upgrade_error(service, err))?;
 
             // check deployment status
             if helm_history_row.is_none() || !helm_history_row.unwrap().is_successfully_deployed() {
@@ -724,7 +1779,12 @@ where
             let helm_release_name = service.helm_release_name();
 
             // clean the resource
-            let _ = do_stateless_service_cleanup(*kubernetes, *environment, helm_release_name.as_str())?;
+            let _ = do_stateless_service_cleanup(
+                *kubernetes,
+                environment.namespace(),
+                helm_release_name.as_str(),
+                service.start_timeout(),
+            )?;
         }
     }
 
@@ -942,7 +2002,7 @@ where
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_logs(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             selector.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -956,7 +2016,7 @@ where
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_get_pod(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             selector.as_str(),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -998,7 +2058,7 @@ where
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_get_event(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            service.namespace(environment),
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
     )?;
@@ -1018,7 +2078,7 @@ where
 /// show different output (kubectl describe, log..) for debug purpose
 pub fn get_stateless_resource_information(
     kubernetes: &dyn Kubernetes,
-    environment: &Environment,
+    namespace: &str,
     selector: &str,
 ) -> Result<(Describe, Logs), EngineError> {
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
@@ -1029,7 +2089,7 @@ pub fn get_stateless_resource_information(
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_describe_pod(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            namespace,
             selector,
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1050,7 +2110,7 @@ pub fn get_stateless_resource_information(
         kubernetes.context().execution_id(),
         crate::cmd::kubectl::kubectl_exec_logs(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            namespace,
             selector,
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1070,8 +2130,9 @@ pub fn get_stateless_resource_information(
 
 pub fn do_stateless_service_cleanup(
     kubernetes: &dyn Kubernetes,
-    environment: &Environment,
+    namespace: &str,
     helm_release_name: &str,
+    timeout: Timeout<u32>,
 ) -> Result<(), EngineError> {
     let kubernetes_config_file_path = kubernetes.config_file_path()?;
 
@@ -1080,7 +2141,7 @@ pub fn do_stateless_service_cleanup(
         kubernetes.context().execution_id(),
         crate::cmd::helm::helm_exec_history(
             kubernetes_config_file_path.as_str(),
-            environment.namespace(),
+            namespace,
             helm_release_name,
             kubernetes.cloud_provider().credentials_environment_variables(),
         ),
@@ -1095,8 +2156,9 @@ pub fn do_stateless_service_cleanup(
             kubernetes.context().execution_id(),
             crate::cmd::helm::helm_exec_uninstall(
                 kubernetes_config_file_path.as_str(),
-                environment.namespace(),
+                namespace,
                 helm_release_name,
+                timeout,
                 kubernetes.cloud_provider().credentials_environment_variables(),
             ),
         )?;
@@ -1128,6 +2190,11 @@ where
             service.service_type().name(),
             service.name_with_id()
         )),
+        Action::Restart => Some(format!(
+            "{} '{}' restart is in progress...",
+            service.service_type().name(),
+            service.name_with_id()
+        )),
         Action::Nothing => None,
     };
 
@@ -1159,6 +2226,9 @@ where
         service.context().execution_id(),
     );
 
+    // every log of the task (helm, kubectl...) is correlated to the service and the lifecycle phase
+    let span = lifecycle_span(service, &action);
+
     let (tx, rx) = mpsc::channel();
 
     // monitor thread to notify user while the blocking task is executed
@@ -1175,7 +2245,8 @@ where
                 let progress_info = std::clone::Clone::clone(&progress_info);
 
                 match action {
-                    Action::Create => listeners_helper.deployment_in_progress(progress_info),
+                    // a restart deploys the service again
+                    Action::Create | Action::Restart => listeners_helper.deployment_in_progress(progress_info),
                     Action::Pause => listeners_helper.pause_in_progress(progress_info),
                     Action::Delete => listeners_helper.delete_in_progress(progress_info),
                     Action::Nothing => {} // should not happens
@@ -1191,12 +2262,34 @@ where
             }
         });
 
-    let blocking_task_result = long_task();
+    let blocking_task_result = span.in_scope(long_task);
     let _ = tx.send(());
 
     blocking_task_result
 }
 
+/// tracing span of a lifecycle phase (create, pause, delete, restart) of a service
+pub fn lifecycle_span<S>(service: &S, action: &Action) -> tracing::Span
+where
+    S: Service + ?Sized,
+{
+    let phase = match action {
+        Action::Create => "create",
+        Action::Pause => "pause",
+        Action::Delete => "delete",
+        Action::Restart => "restart",
+        Action::Nothing => "nothing",
+    };
+
+    info_span!(
+        "lifecycle",
+        service.id = service.id(),
+        service.name = service.name(),
+        execution_id = service.context().execution_id(),
+        phase = phase
+    )
+}
+
 pub fn get_tfstate_suffix(service: &dyn Service) -> String {
     format!("{}", service.id())
 }
@@ -1207,3 +2300,31 @@ pub fn get_tfstate_suffix(service: &dyn Service) -> String {
 pub fn get_tfstate_name(service: &dyn Service) -> String {
     format!("tfstate-default-{}", service.id())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    use crate::cloud_provider::service::rollout_result_to_engine_error;
+    use crate::error::{EngineErrorCause, EngineErrorScope, SimpleError, SimpleErrorKind};
+
+    #[test]
+    fn test_rollout_non_zero_exit_code_is_an_internal_error() {
+        let result = Err(SimpleError::new(
+            SimpleErrorKind::Command(ExitStatus::from_raw(256)),
+            Some("error while executing an internal command"),
+        ));
+
+        let err = rollout_result_to_engine_error(EngineErrorScope::Engine, "123", result).unwrap_err();
+
+        match err.cause {
+            EngineErrorCause::Internal => {}
+            EngineErrorCause::User(_) => panic!("a failed rollout must be an internal error"),
+        }
+
+        assert!(err.is_retryable());
+
+        assert!(rollout_result_to_engine_error(EngineErrorScope::Engine, "123", Ok(())).is_ok());
+    }
+}
@@ -1,6 +1,7 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
-use crate::cloud_provider::models::CpuLimits;
+use crate::cloud_provider::models::{CpuLimits, EnvironmentVariable};
 use crate::error::{EngineError, StringError};
 use crate::models::{Listeners, ListenersHelper, ProgressInfo, ProgressLevel, ProgressScope};
 use chrono::Duration;
@@ -90,7 +91,7 @@ pub fn get_supported_version_to_use(
     all_supported_versions: HashMap<String, String>,
     version_to_check: &str,
 ) -> Result<String, StringError> {
-    let version = match get_version_number(version_to_check) {
+    let version = match VersionsNumber::parse(version_to_check) {
         Ok(version) => version,
         Err(e) => return Err(e),
     };
@@ -100,8 +101,8 @@ pub fn get_supported_version_to_use(
         return match all_supported_versions.get(&format!(
             "{}.{}.{}",
             version.major,
-            version.minor.unwrap().to_string(),
-            version.patch.unwrap().to_string()
+            version.minor.unwrap(),
+            version.patch.unwrap()
         )) {
             Some(version) => Ok(version.to_string()),
             None => {
@@ -127,7 +128,7 @@ pub fn get_supported_version_to_use(
     };
 
     // if only a major version is required
-    match all_supported_versions.get(&version.major) {
+    match all_supported_versions.get(&version.major.to_string()) {
         Some(version) => Ok(version.to_string()),
         None => {
             return Err(format!(
@@ -216,33 +217,110 @@ pub fn generate_supported_version(
     supported_versions
 }
 
-// unfortunately some proposed versions are not SemVer like Elasticache (6.x)
-// this is why we need ot have our own structure
+// unfortunately some proposed versions are not SemVer like Elasticache (6) or Digital Ocean Kubernetes (1.18.8-do.0)
+// this is why we need ot have our own structure. Versions are ordered by major, minor, patch and revision
+#[derive(Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
 pub struct VersionsNumber {
-    pub(crate) major: String,
-    pub(crate) minor: Option<String>,
-    pub(crate) patch: Option<String>,
+    pub major: u32,
+    pub minor: Option<u32>,
+    pub patch: Option<u32>,
+    /// the Digital Ocean revision, `0` of `1.18.8-do.0`
+    pub do_revision: Option<u32>,
 }
 
-fn get_version_number(version: &str) -> Result<VersionsNumber, StringError> {
-    let mut version_split = version.split(".");
+impl VersionsNumber {
+    pub fn new(major: u32, minor: u32, patch: u32) -> Self {
+        VersionsNumber {
+            major,
+            minor: Some(minor),
+            patch: Some(patch),
+            do_revision: None,
+        }
+    }
 
-    let major = match version_split.next() {
-        Some(major) => major.to_string(),
-        _ => return Err("please check the version you've sent, it can't be checked".to_string()),
-    };
+    /// parse a `[v]major[.minor[.patch]][-do.revision]` version
+    pub fn parse(version: &str) -> Result<VersionsNumber, StringError> {
+        let invalid = || format!("invalid version {:?}, it must be like 6, 1.18 or 1.18.8-do.0", version);
+
+        let version = version.trim();
+        let (numbers, do_revision) = match version.find('-') {
+            Some(index) => {
+                let do_revision = version[index + 1..]
+                    .strip_prefix("do.")
+                    .and_then(|revision| revision.parse::<u32>().ok())
+                    .ok_or_else(invalid)?;
+                (&version[..index], Some(do_revision))
+            }
+            None => (version, None),
+        };
 
-    let minor = match version_split.next() {
-        Some(minor) => Some(minor.to_string()),
-        _ => None,
-    };
+        let numbers = numbers
+            .trim_start_matches('v')
+            .split('.')
+            .map(|number| match number.chars().all(|c| c.is_ascii_digit()) {
+                true => number.parse::<u32>().map_err(|_| invalid()),
+                false => Err(invalid()),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        match numbers.as_slice() {
+            [major] => Ok(VersionsNumber {
+                major: *major,
+                minor: None,
+                patch: None,
+                do_revision,
+            }),
+            [major, minor] => Ok(VersionsNumber {
+                major: *major,
+                minor: Some(*minor),
+                patch: None,
+                do_revision,
+            }),
+            [major, minor, patch] => Ok(VersionsNumber {
+                major: *major,
+                minor: Some(*minor),
+                patch: Some(*patch),
+                do_revision,
+            }),
+            _ => Err(invalid()),
+        }
+    }
 
-    let patch = match version_split.next() {
-        Some(patch) => Some(patch.to_string()),
-        _ => None,
-    };
+    /// find the first `major.minor[.patch]` version (e.g. `v1.19.4`, `aws-cli/2.1.6`, `320.0.0`) in the output of a
+    /// version command, the patch defaults to 0
+    pub fn find_in(output: &str) -> Option<VersionsNumber> {
+        output
+            .split(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .filter(|token| token.contains('.'))
+            .find_map(|token| {
+                let mut numbers = token.split('.');
+
+                let major = numbers.next()?.parse::<u32>().ok()?;
+                let minor = numbers.next()?.parse::<u32>().ok()?;
+                let patch = numbers.next().and_then(|patch| patch.parse::<u32>().ok()).unwrap_or(0);
+
+                Some(VersionsNumber::new(major, minor, patch))
+            })
+    }
+}
+
+impl fmt::Display for VersionsNumber {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.major)?;
 
-    Ok(VersionsNumber { major, minor, patch })
+        if let Some(minor) = self.minor {
+            write!(f, ".{}", minor)?;
+        }
+
+        if let Some(patch) = self.patch {
+            write!(f, ".{}", patch)?;
+        }
+
+        match self.do_revision {
+            Some(do_revision) => write!(f, "-do.{}", do_revision),
+            None => Ok(()),
+        }
+    }
 }
 
 fn cloudflare_dns_resolver() -> Resolver {
@@ -421,6 +499,136 @@ pub fn check_domain_for(
     Ok(())
 }
 
+/// resolve the `$VAR` and `${VAR}` references of the environment variable values against the other variables,
+/// `$$` is a literal `$`. an unknown reference is an error when `strict`, it is kept as is otherwise.
+/// Each variable is resolved on its own, the error lists every variable which can't be resolved, one per line.
+pub fn interpolate_environment_variables(
+    environment_variables: &[EnvironmentVariable],
+    strict: bool,
+) -> Result<Vec<EnvironmentVariable>, StringError> {
+    let values = environment_variables
+        .iter()
+        .map(|ev| (ev.key.as_str(), ev.value.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    let mut resolved_values: HashMap<String, String> = HashMap::new();
+    let mut resolved_environment_variables = Vec::with_capacity(environment_variables.len());
+    let mut failures = vec![];
+    // the variables already reported, a variable referencing one of them fails the same way
+    let mut failed_keys = HashSet::new();
+
+    for ev in environment_variables {
+        if failed_keys.contains(ev.key.as_str()) {
+            continue;
+        }
+
+        match resolve_environment_variable(ev.key.as_str(), &values, &mut resolved_values, &mut vec![], strict) {
+            Ok(value) => resolved_environment_variables.push(EnvironmentVariable {
+                key: ev.key.clone(),
+                value,
+            }),
+            Err((message, keys)) => {
+                if !keys.iter().all(|key| failed_keys.contains(key.as_str())) {
+                    failures.push(message);
+                }
+                failed_keys.extend(keys.into_iter().chain(std::iter::once(ev.key.clone())));
+            }
+        }
+    }
+
+    match failures.is_empty() {
+        true => Ok(resolved_environment_variables),
+        false => Err(failures.join("\n")),
+    }
+}
+
+/// the error is returned with the variables causing it, e.g. the variables of a cycle
+fn resolve_environment_variable(
+    key: &str,
+    values: &HashMap<&str, &str>,
+    resolved_values: &mut HashMap<String, String>,
+    resolving_keys: &mut Vec<String>,
+    strict: bool,
+) -> Result<String, (StringError, Vec<String>)> {
+    if let Some(value) = resolved_values.get(key) {
+        return Ok(value.clone());
+    }
+
+    if let Some(idx) = resolving_keys.iter().position(|k| k == key) {
+        let mut cycle = resolving_keys[idx..].to_vec();
+        let keys = cycle.clone();
+        cycle.push(key.to_string());
+        return Err((
+            format!("circular reference between environment variables: {}", cycle.join(" -> ")),
+            keys,
+        ));
+    }
+
+    resolving_keys.push(key.to_string());
+
+    let chars = values[key].chars().collect::<Vec<_>>();
+    let mut value = String::with_capacity(chars.len());
+    let mut idx = 0;
+
+    while idx < chars.len() {
+        if chars[idx] != '$' {
+            value.push(chars[idx]);
+            idx += 1;
+            continue;
+        }
+
+        // `$$` is an escaped `$`
+        if chars.get(idx + 1) == Some(&'$') {
+            value.push('$');
+            idx += 2;
+            continue;
+        }
+
+        // the name of the reference and the index right after it
+        let (name, end) = match chars.get(idx + 1) {
+            Some('{') => match chars[idx + 2..].iter().position(|c| *c == '}') {
+                Some(len) => (chars[idx + 2..idx + 2 + len].iter().collect::<String>(), idx + 3 + len),
+                None => (String::new(), idx + 1),
+            },
+            Some(c) if c.is_ascii_alphabetic() || *c == '_' => {
+                let len = chars[idx + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                    .count();
+                (chars[idx + 1..idx + 1 + len].iter().collect::<String>(), idx + 1 + len)
+            }
+            _ => (String::new(), idx + 1),
+        };
+
+        // a lone `$` is kept as is
+        if name.is_empty() {
+            value.push('$');
+            idx += 1;
+            continue;
+        }
+
+        if values.contains_key(name.as_str()) {
+            let reference_value =
+                resolve_environment_variable(name.as_str(), values, resolved_values, resolving_keys, strict)?;
+            value.push_str(reference_value.as_str());
+        } else if strict {
+            return Err((
+                format!("environment variable {} references the unknown environment variable {}", key, name),
+                vec![key.to_string()],
+            ));
+        } else {
+            value.extend(chars[idx..end].iter());
+        }
+
+        idx = end;
+    }
+
+    resolving_keys.pop();
+    resolved_values.insert(key.to_string(), value.clone());
+
+    Ok(value)
+}
+
 pub fn sanitize_name(prefix: &str, name: &str) -> String {
     format!("{}-{}", prefix, name).replace("_", "-")
 }
@@ -482,13 +690,110 @@ pub fn validate_k8s_required_cpu_and_burstable(
 
 #[cfg(test)]
 mod tests {
-    use crate::cloud_provider::models::CpuLimits;
+    use crate::cloud_provider::models::{CpuLimits, EnvironmentVariable};
     use crate::cloud_provider::utilities::{
-        cloudflare_dns_resolver, convert_k8s_cpu_value_to_f32, get_cname_record_value,
-        validate_k8s_required_cpu_and_burstable,
+        cloudflare_dns_resolver, convert_k8s_cpu_value_to_f32, get_cname_record_value, get_self_hosted_redis_version,
+        interpolate_environment_variables, validate_k8s_required_cpu_and_burstable,
     };
     use crate::models::ListenersHelper;
 
+    fn environment_variables(key_values: &[(&str, &str)]) -> Vec<EnvironmentVariable> {
+        key_values
+            .iter()
+            .map(|(key, value)| EnvironmentVariable {
+                key: key.to_string(),
+                value: value.to_string(),
+            })
+            .collect()
+    }
+
+    fn values(environment_variables: Vec<EnvironmentVariable>) -> Vec<String> {
+        environment_variables.into_iter().map(|ev| ev.value).collect()
+    }
+
+    #[test]
+    pub fn test_environment_variables_interpolation() {
+        let evs = environment_variables(&[
+            ("DATABASE_URL", "postgres://$DB_HOST:${DB_PORT}/db"),
+            ("DB_HOST", "${DB_NAME}.internal"),
+            ("DB_NAME", "pg"),
+            ("DB_PORT", "5432"),
+        ]);
+
+        assert_eq!(
+            values(interpolate_environment_variables(&evs, true).unwrap()),
+            vec!["postgres://pg.internal:5432/db", "pg.internal", "pg", "5432"]
+        );
+    }
+
+    #[test]
+    pub fn test_environment_variables_interpolation_escaping() {
+        let evs = environment_variables(&[
+            ("PASSWORD", "pa$$word"),
+            ("PRICE", "5$"),
+            ("NOT_A_REFERENCE", "$1 ${"),
+            ("HOST", "pg"),
+            ("LITERAL", "$$HOST"),
+        ]);
+
+        assert_eq!(
+            values(interpolate_environment_variables(&evs, true).unwrap()),
+            vec!["pa$word", "5$", "$1 ${", "pg", "$HOST"]
+        );
+    }
+
+    #[test]
+    pub fn test_environment_variables_interpolation_unknown_reference() {
+        let evs = environment_variables(&[("URL", "http://$HOST:${PORT}")]);
+
+        assert_eq!(
+            values(interpolate_environment_variables(&evs, false).unwrap()),
+            vec!["http://$HOST:${PORT}"]
+        );
+
+        assert_eq!(
+            interpolate_environment_variables(&evs, true).unwrap_err(),
+            "environment variable URL references the unknown environment variable HOST"
+        );
+    }
+
+    #[test]
+    pub fn test_environment_variables_interpolation_reports_every_failure() {
+        let evs = environment_variables(&[
+            ("URL", "http://$HOST"),
+            ("BACKUP_URL", "$URL/backup"),
+            ("PORT", "5432"),
+            ("DSN", "${USER}@db:$PORT"),
+        ]);
+
+        assert_eq!(
+            interpolate_environment_variables(&evs, true).unwrap_err(),
+            "environment variable URL references the unknown environment variable HOST\n\
+            environment variable DSN references the unknown environment variable USER"
+        );
+    }
+
+    #[test]
+    pub fn test_environment_variables_interpolation_cycle() {
+        let evs = environment_variables(&[("A", "$B"), ("B", "${C}/b"), ("C", "$A"), ("D", "d")]);
+
+        assert_eq!(
+            interpolate_environment_variables(&evs, false).unwrap_err(),
+            "circular reference between environment variables: A -> B -> C -> A"
+        );
+
+        let evs = environment_variables(&[("SELF", "$SELF")]);
+        assert!(interpolate_environment_variables(&evs, false).is_err());
+    }
+
+    #[test]
+    pub fn test_supported_database_version() {
+        assert_eq!(get_self_hosted_redis_version("6"), Ok("6.0.9".to_string()));
+        assert_eq!(get_self_hosted_redis_version("5.0"), Ok("5.0.10".to_string()));
+        assert!(get_self_hosted_redis_version("4").is_err());
+        assert!(get_self_hosted_redis_version("6.x").is_err());
+    }
+
     #[test]
     pub fn test_k8s_milli_cpu_convert() {
         let milli_cpu = "250m".to_string();
@@ -1,27 +1,229 @@
+use std::collections::BTreeMap;
 use std::io::Error;
 use std::path::Path;
 
 use tracing::{error, info, span, Level};
 
-use crate::cmd::structs::{Helm, HelmHistoryRow, HelmList};
-use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::cmd::structs::{parse_helm_date, HelmChartVersion, HelmHistoryRow, HelmList, HelmRelease};
+use crate::cmd::tools::helm_binary;
+use crate::cmd::utilities::{
+    exec_with_envs_and_output, exec_with_envs_and_output_cancellable, exec_with_envs_and_output_lines_cancellable,
+    OutputLines,
+};
 use crate::error::{SimpleError, SimpleErrorKind};
+use crate::models::CancellationToken;
+use crate::string::is_engine_helm_release_name;
 use chrono::Duration;
+use serde_yaml::{Mapping, Value};
 
 const HELM_DEFAULT_TIMEOUT_IN_SECONDS: u32 = 300;
+// environment variable overriding the default helm timeout, useful for slow clusters
+const HELM_DEFAULT_TIMEOUT_ENV_VAR: &str = "HELM_DEFAULT_TIMEOUT_IN_SECONDS";
 
+const HELM_HISTORY_DEFAULT_MAX_ROWS: u32 = 10;
+// environment variable overriding the number of history rows fetched to check an upgrade
+const HELM_HISTORY_MAX_ROWS_ENV_VAR: &str = "HELM_HISTORY_MAX_ROWS";
+
+/// the number of history rows fetched to check an upgrade, set with the `HELM_HISTORY_MAX_ROWS` environment variable
+pub fn helm_history_max_rows() -> u32 {
+    std::env::var(HELM_HISTORY_MAX_ROWS_ENV_VAR)
+        .ok()
+        .and_then(|rows| rows.trim().parse::<u32>().ok())
+        .filter(|rows| *rows > 0)
+        .unwrap_or(HELM_HISTORY_DEFAULT_MAX_ROWS)
+}
+
+#[derive(Clone)]
 pub enum Timeout<T> {
     Default,
     Value(T),
 }
 
+impl Timeout<u32> {
+    /// the default timeout configured with the `HELM_DEFAULT_TIMEOUT_IN_SECONDS` environment variable,
+    /// `Timeout::Default` if it is not set or is not a number of seconds
+    pub fn from_env() -> Self {
+        match std::env::var(HELM_DEFAULT_TIMEOUT_ENV_VAR)
+            .ok()
+            .and_then(|seconds| seconds.trim().parse::<u32>().ok())
+        {
+            Some(seconds) => Timeout::Value(seconds),
+            None => Timeout::Default,
+        }
+    }
+
+    pub fn in_seconds(&self) -> u32 {
+        match self {
+            Timeout::Value(seconds) => *seconds,
+            Timeout::Default => match Timeout::from_env() {
+                Timeout::Value(seconds) => seconds,
+                Timeout::Default => HELM_DEFAULT_TIMEOUT_IN_SECONDS,
+            },
+        }
+    }
+
+    /// value of the helm 3 `--timeout` flag, a duration
+    pub fn to_helm_flag_value(&self) -> String {
+        format!("{}s", self.helm_timeout_in_seconds())
+    }
+
+    /// value of the helm 2 `--timeout` flag, a number of seconds
+    pub fn to_helm_2_flag_value(&self) -> String {
+        self.helm_timeout_in_seconds().to_string()
+    }
+
+    /// a given value, e.g. the start timeout of a service, is the time left to helm on top of its default timeout
+    fn helm_timeout_in_seconds(&self) -> u32 {
+        let default_timeout = Timeout::Default.in_seconds();
+
+        match self {
+            Timeout::Value(seconds) => seconds + default_timeout,
+            Timeout::Default => default_timeout,
+        }
+    }
+}
+
+/// a remote chart repository, credentials are only needed by private repositories
+#[derive(Clone)]
+pub struct HelmRepository {
+    pub name: String,
+    pub url: String,
+    pub credentials: Option<HelmRepositoryCredentials>,
+}
+
+#[derive(Clone)]
+pub struct HelmRepositoryCredentials {
+    pub username: String,
+    pub password: String,
+}
+
+/// add (or replace) a chart repository
+pub fn helm_exec_repo_add(
+    name: &str,
+    url: &str,
+    credentials: Option<&HelmRepositoryCredentials>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError> {
+    let args = helm_exec_repo_add_args(name, url, credentials);
+
+    helm_exec_with_output(
+        args.iter().map(|arg| arg.as_str()).collect(),
+        envs,
+        |out| match out {
+            Ok(line) => info!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+    )
+}
+
+/// build the arguments given to `helm repo add` by `helm_exec_repo_add(..)`
+/// the repository is replaced if it already exists, its url or credentials may have changed
+pub fn helm_exec_repo_add_args(name: &str, url: &str, credentials: Option<&HelmRepositoryCredentials>) -> Vec<String> {
+    let mut args = vec!["repo", "add", name, url, "--force-update"];
+
+    if let Some(credentials) = credentials {
+        args.push("--username");
+        args.push(credentials.username.as_str());
+        args.push("--password");
+        args.push(credentials.password.as_str());
+    }
+
+    args.iter().map(|arg| arg.to_string()).collect()
+}
+
+/// a registry storing the charts as OCI artifacts, helm 3.8 or later is required
+#[derive(Clone)]
+pub struct HelmOciRegistry {
+    /// the host of the registry and an optional path, e.g. `registry.example.com/charts`
+    pub url: String,
+    pub credentials: Option<HelmRepositoryCredentials>,
+}
+
+impl HelmOciRegistry {
+    fn url_without_scheme(&self) -> &str {
+        self.url.trim_start_matches("oci://").trim_end_matches('/')
+    }
+
+    /// the host `helm registry login` authenticates to
+    pub fn host(&self) -> &str {
+        self.url_without_scheme().split('/').next().unwrap_or_default()
+    }
+
+    /// the reference given to `helm upgrade`, e.g. `oci://registry.example.com/charts/my-job`
+    pub fn chart_reference(&self, chart: &str) -> String {
+        format!("oci://{}/{}", self.url_without_scheme(), chart)
+    }
+}
+
+/// log in an OCI registry, the credentials are stored in the helm registry config for the next commands
+pub fn helm_exec_registry_login(
+    registry: &HelmOciRegistry,
+    credentials: &HelmRepositoryCredentials,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError> {
+    let args = helm_exec_registry_login_args(registry.host(), credentials);
+
+    helm_exec_with_output(
+        args.iter().map(|arg| arg.as_str()).collect(),
+        envs,
+        |out| match out {
+            Ok(line) => info!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+    )
+}
+
+pub fn helm_exec_registry_login_args(host: &str, credentials: &HelmRepositoryCredentials) -> Vec<String> {
+    vec![
+        "registry".to_string(),
+        "login".to_string(),
+        host.to_string(),
+        "--username".to_string(),
+        credentials.username.clone(),
+        "--password".to_string(),
+        credentials.password.clone(),
+    ]
+}
+
+/// refresh the charts index of all the repositories
+pub fn helm_exec_repo_update(envs: Vec<(&str, &str)>) -> Result<(), SimpleError> {
+    helm_exec_with_output(
+        vec!["repo", "update"],
+        envs,
+        |out| match out {
+            Ok(line) => info!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+    )
+}
+
 pub fn helm_exec_with_upgrade_history<P>(
     kubernetes_config: P,
     namespace: &str,
     release_name: &str,
     chart_root_dir: P,
+    chart_version: Option<&str>,
+    values_files: &[String],
+    set_values: &[(String, String)],
     timeout: Timeout<u32>,
+    atomic: bool,
+    wait_for_jobs: bool,
+    labels: &BTreeMap<String, String>,
     envs: Vec<(&str, &str)>,
+    cancellation_token: &CancellationToken,
+    on_output_line: Option<&mut dyn FnMut(&str)>,
 ) -> Result<Option<HelmHistoryRow>, SimpleError>
 where
     P: AsRef<Path>,
@@ -33,13 +235,21 @@ where
         chart_root_dir.as_ref().to_str().unwrap()
     );
 
-    let _ = helm_exec_upgrade(
+    let upgraded_revision = helm_exec_upgrade(
         kubernetes_config.as_ref(),
         namespace,
         release_name,
         chart_root_dir.as_ref(),
+        chart_version,
+        values_files,
+        set_values,
         timeout,
+        atomic,
+        wait_for_jobs,
+        labels,
         envs.clone(),
+        cancellation_token,
+        on_output_line,
     )?;
 
     // list helm history
@@ -49,50 +259,236 @@ where
         chart_root_dir.as_ref().to_str().unwrap()
     );
 
-    let helm_history_rows = helm_exec_history(kubernetes_config.as_ref(), namespace, release_name, envs)?;
+    let helm_history_rows = helm_exec_history_rows(
+        kubernetes_config.as_ref(),
+        namespace,
+        release_name,
+        Some(helm_history_max_rows()),
+        envs,
+    )?;
 
-    // take the last deployment from helm history - or return none if there is no history
-    Ok(match helm_history_rows.first() {
-        Some(helm_history_row) => Some(helm_history_row.clone()),
-        None => None,
-    })
+    Ok(helm_upgraded_revision_row(&helm_history_rows, upgraded_revision))
 }
 
+/// return the revision created by the upgrade, if helm printed it
+/// the output lines of helm are given to `on_output_line` as soon as helm prints them
 pub fn helm_exec_upgrade<P>(
     kubernetes_config: P,
     namespace: &str,
     release_name: &str,
     chart_root_dir: P,
+    chart_version: Option<&str>,
+    values_files: &[String],
+    set_values: &[(String, String)],
     timeout: Timeout<u32>,
+    atomic: bool,
+    wait_for_jobs: bool,
+    labels: &BTreeMap<String, String>,
     envs: Vec<(&str, &str)>,
-) -> Result<(), SimpleError>
+    cancellation_token: &CancellationToken,
+    on_output_line: Option<&mut dyn FnMut(&str)>,
+) -> Result<Option<u32>, SimpleError>
 where
     P: AsRef<Path>,
 {
-    let timeout = format!(
-        "{}s",
-        match timeout {
-            Timeout::Value(v) => v + HELM_DEFAULT_TIMEOUT_IN_SECONDS,
-            Timeout::Default => HELM_DEFAULT_TIMEOUT_IN_SECONDS,
-        }
+    let args = helm_exec_upgrade_args(
+        kubernetes_config.as_ref().to_str().unwrap(),
+        namespace,
+        release_name,
+        chart_root_dir.as_ref().to_str().unwrap(),
+        chart_version,
+        values_files,
+        set_values,
+        timeout,
+        atomic,
+        wait_for_jobs,
+        labels,
     );
 
+    let mut output_lines = OutputLines::new(on_output_line);
+    helm_exec_result(exec_with_envs_and_output_lines_cancellable(
+        helm_binary(),
+        args.iter().map(|arg| arg.as_str()).collect(),
+        envs,
+        &mut output_lines,
+        cancellation_token,
+    ))?;
+
+    Ok(output_lines
+        .stdout
+        .iter()
+        .filter_map(|line| parse_helm_upgrade_revision(line.as_str()))
+        .last())
+}
+
+/// the `REVISION: 3` line printed by `helm upgrade`
+fn parse_helm_upgrade_revision(line: &str) -> Option<u32> {
+    line.trim().strip_prefix("REVISION:")?.trim().parse::<u32>().ok()
+}
+
+/// the history row of the upgraded revision, the latest row may be a pending operation started concurrently
+/// without upgraded revision, the latest row is taken
+pub fn helm_upgraded_revision_row(
+    history_rows: &[HelmHistoryRow],
+    upgraded_revision: Option<u32>,
+) -> Option<HelmHistoryRow> {
+    match upgraded_revision {
+        Some(revision) => history_rows.iter().find(|row| row.revision() == revision).cloned(),
+        None => history_rows.first().cloned(),
+    }
+}
+
+/// build the arguments given to `helm upgrade` by `helm_exec_upgrade(..)`
+/// values files are given after the chart, in order, so the last one wins, then the `--set` values win over the files
+/// an atomic upgrade is rolled back by helm itself if it fails or times out
+pub fn helm_exec_upgrade_args(
+    kubernetes_config: &str,
+    namespace: &str,
+    release_name: &str,
+    chart_root_dir: &str,
+    chart_version: Option<&str>,
+    values_files: &[String],
+    set_values: &[(String, String)],
+    timeout: Timeout<u32>,
+    atomic: bool,
+    wait_for_jobs: bool,
+    labels: &BTreeMap<String, String>,
+) -> Vec<String> {
+    let timeout = timeout.to_helm_flag_value();
+    let labels = labels
+        .iter()
+        .map(|(key, value)| format!("{}={}", key, value))
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let mut args = vec![
+        "upgrade",
+        "--kubeconfig",
+        kubernetes_config,
+        "--create-namespace",
+        "--install",
+        "--history-max",
+        "50",
+        "--timeout",
+        timeout.as_str(),
+        "--wait",
+        "--namespace",
+        namespace,
+        release_name,
+        chart_root_dir,
+    ];
+
+    if atomic {
+        args.push("--atomic");
+    }
+
+    // along with `--wait`, the jobs of the release must have completed before the timeout
+    if wait_for_jobs {
+        args.push("--wait-for-jobs");
+    }
+
+    // the labels of the release itself, `helm list --selector` filters the releases on them
+    if !labels.is_empty() {
+        args.push("--labels");
+        args.push(labels.as_str());
+    }
+
+    if let Some(chart_version) = chart_version {
+        args.push("--version");
+        args.push(chart_version);
+    }
+
+    for values_file in values_files {
+        args.push("-f");
+        args.push(values_file.as_str());
+    }
+
+    let mut args = args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    for (key, value) in set_values {
+        args.push("--set".to_string());
+        args.push(helm_set_value(key, value));
+    }
+
+    args
+}
+
+/// the `key=value` of `--set`, the dots of the key are the path of the value (e.g. `resources.limits.memory`)
+/// helm splits the pairs on the commas, they are escaped in the key and in the value like the `=` of the key
+pub fn helm_set_value(key: &str, value: &str) -> String {
+    let key = key.replace(',', "\\,").replace('=', "\\=");
+    let value = value.replace('\\', "\\\\").replace(',', "\\,");
+
+    format!("{}={}", key, value)
+}
+
+/// list the versions available for a chart
+/// a local chart directory has a single version (the one from its Chart.yaml), remote charts are searched into the repositories
+pub fn helm_exec_chart_versions(chart: &str, envs: Vec<(&str, &str)>) -> Result<Vec<String>, SimpleError> {
+    let mut output_lines: Vec<String> = Vec::new();
+
+    let args = match Path::new(chart).is_dir() {
+        true => vec!["show", "chart", chart],
+        false => vec!["search", "repo", chart, "--versions", "-o", "json"],
+    };
+
+    let _ = helm_exec_with_output(
+        args,
+        envs,
+        |out| match out {
+            Ok(line) => output_lines.push(line),
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+    )?;
+
+    let output = output_lines.join("\n");
+
+    match Path::new(chart).is_dir() {
+        true => Ok(output
+            .lines()
+            .filter(|line| line.starts_with("version:"))
+            .map(|line| line.trim_start_matches("version:").trim().trim_matches('"').to_string())
+            .collect()),
+        false => parse_helm_search_versions(output.as_str()),
+    }
+}
+
+fn parse_helm_search_versions(output: &str) -> Result<Vec<String>, SimpleError> {
+    match serde_json::from_str::<Vec<HelmChartVersion>>(output) {
+        Ok(charts) => Ok(charts.into_iter().map(|chart| chart.version).collect()),
+        Err(err) => {
+            let message = format!("Error while deserializing helm search output {}", err);
+            error!("{}", message.as_str());
+            Err(SimpleError::new(SimpleErrorKind::Other, Some(message)))
+        }
+    }
+}
+
+pub fn helm_exec_uninstall<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    release_name: &str,
+    timeout: Timeout<u32>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let timeout = timeout.to_helm_flag_value();
+
     helm_exec_with_output(
         vec![
-            "upgrade",
+            "uninstall",
             "--kubeconfig",
             kubernetes_config.as_ref().to_str().unwrap(),
-            "--create-namespace",
-            "--install",
-            "--history-max",
-            "50",
             "--timeout",
             timeout.as_str(),
-            "--wait",
             "--namespace",
             namespace,
             release_name,
-            chart_root_dir.as_ref().to_str().unwrap(),
         ],
         envs,
         |out| match out {
@@ -106,23 +502,32 @@ where
     )
 }
 
-pub fn helm_exec_uninstall<P>(
+pub fn helm_exec_rollback<P>(
     kubernetes_config: P,
     namespace: &str,
     release_name: &str,
+    revision: u32,
+    timeout: Timeout<u32>,
     envs: Vec<(&str, &str)>,
 ) -> Result<(), SimpleError>
 where
     P: AsRef<Path>,
 {
+    let revision = revision.to_string();
+    let timeout = timeout.to_helm_flag_value();
+
     helm_exec_with_output(
         vec![
-            "uninstall",
+            "rollback",
             "--kubeconfig",
             kubernetes_config.as_ref().to_str().unwrap(),
             "--namespace",
             namespace,
+            "--timeout",
+            timeout.as_str(),
+            "--wait",
             release_name,
+            revision.as_str(),
         ],
         envs,
         |out| match out {
@@ -136,6 +541,16 @@ where
     )
 }
 
+/// return the revision to rollback to: the most recent deployed revision before the latest one
+/// `history_rows` must be sorted like `helm_exec_history(..)` does (latest revision first)
+pub fn helm_last_deployed_revision(history_rows: &[HelmHistoryRow]) -> Option<u32> {
+    history_rows
+        .iter()
+        .skip(1)
+        .find(|row| row.is_successfully_deployed())
+        .map(|row| row.revision())
+}
+
 pub fn helm_exec_history<P>(
     kubernetes_config: P,
     namespace: &str,
@@ -145,22 +560,45 @@ pub fn helm_exec_history<P>(
 where
     P: AsRef<Path>,
 {
-    let mut output_string = String::new();
+    helm_exec_history_rows(kubernetes_config, namespace, release_name, None, envs)
+}
+
+/// the history limited to the `max_rows` latest revisions, all of them (up to 256 for helm) by default
+fn helm_exec_history_rows<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    release_name: &str,
+    max_rows: Option<u32>,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<HelmHistoryRow>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let max_rows = max_rows.map(|rows| rows.to_string());
+
+    // WARN: do not add argument --debug, otherwise JSON decoding will not work
+    let mut args = vec![
+        "history",
+        "--kubeconfig",
+        kubernetes_config.as_ref().to_str().unwrap(),
+        "--namespace",
+        namespace,
+        "-o",
+        "json",
+        release_name,
+    ];
+
+    if let Some(max_rows) = max_rows.as_ref() {
+        args.push("--max");
+        args.push(max_rows.as_str());
+    }
+
+    let mut output_lines: Vec<String> = Vec::new();
     match helm_exec_with_output(
-        // WARN: do not add argument --debug, otherwise JSON decoding will not work
-        vec![
-            "history",
-            "--kubeconfig",
-            kubernetes_config.as_ref().to_str().unwrap(),
-            "--namespace",
-            namespace,
-            "-o",
-            "json",
-            release_name,
-        ],
+        args,
         envs,
         |out| match out {
-            Ok(line) => output_string = line,
+            Ok(line) => output_lines.push(line),
             Err(err) => error!("{:?}", err),
         },
         |out| match out {
@@ -179,10 +617,7 @@ where
     };
     // TODO better check, release not found
 
-    let mut results = match serde_json::from_str::<Vec<HelmHistoryRow>>(output_string.as_str()) {
-        Ok(x) => x,
-        Err(_) => vec![],
-    };
+    let mut results = parse_helm_history(output_lines.join("\n").as_str());
 
     // unsort results by revision number
     let _ = results.sort_by_key(|x| x.revision);
@@ -192,15 +627,172 @@ where
     Ok(results)
 }
 
+/// the user supplied values of the deployed release, `None` if the release does not exist
+pub fn helm_exec_get_values<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    release_name: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Option<Value>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut output_lines: Vec<String> = Vec::new();
+    let mut error_lines: Vec<String> = Vec::new();
+
+    let _ = helm_exec_with_output(
+        vec![
+            "get",
+            "values",
+            "--kubeconfig",
+            kubernetes_config.as_ref().to_str().unwrap(),
+            "--namespace",
+            namespace,
+            "-o",
+            "yaml",
+            release_name,
+        ],
+        envs,
+        |out| match out {
+            Ok(line) => output_lines.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error_lines.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    // helm exits with the status code 1 on errors, which is not an error for `helm_exec_with_output(..)`
+    if error_lines.iter().any(|line| line.contains("release: not found")) {
+        return Ok(None);
+    }
+
+    if let Some(line) = error_lines.iter().find(|line| line.starts_with("Error:")) {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("helm get values of {} failed: {}", release_name, line)),
+        ));
+    }
+
+    parse_helm_values(output_lines.join("\n").as_str()).map(Some)
+}
+
+/// parse helm values, a release without values has an empty mapping
+pub fn parse_helm_values(values: &str) -> Result<Value, SimpleError> {
+    if values.trim().is_empty() {
+        return Ok(Value::Mapping(Mapping::new()));
+    }
+
+    match serde_yaml::from_str::<Value>(values) {
+        Ok(Value::Null) => Ok(Value::Mapping(Mapping::new())),
+        Ok(values) => Ok(values),
+        Err(err) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("unable to parse helm values: {}", err)),
+        )),
+    }
+}
+
+/// merge values files content the way helm does: maps are merged recursively and the last value wins
+pub fn helm_merge_values(base: Value, overlay: Value) -> Value {
+    match (base, overlay) {
+        (Value::Mapping(mut base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                let merged = match base.remove(&key) {
+                    Some(base_value) => helm_merge_values(base_value, value),
+                    None => value,
+                };
+
+                base.insert(key, merged);
+            }
+
+            Value::Mapping(base)
+        }
+        (base, Value::Null) => base,
+        (_, overlay) => overlay,
+    }
+}
+
+/// compare values structurally: the order of the keys does not matter and no values equals empty values
+pub fn helm_values_equal(left: &Value, right: &Value) -> bool {
+    match (left, right) {
+        (Value::Mapping(left), Value::Mapping(right)) => {
+            left.len() == right.len()
+                && left.iter().all(|(key, value)| match right.get(key) {
+                    Some(other) => helm_values_equal(value, other),
+                    None => false,
+                })
+        }
+        (Value::Sequence(left), Value::Sequence(right)) => {
+            left.len() == right.len() && left.iter().zip(right).all(|(value, other)| helm_values_equal(value, other))
+        }
+        (Value::Null, Value::Mapping(values)) | (Value::Mapping(values), Value::Null) => values.is_empty(),
+        _ => left == right,
+    }
+}
+
+/// parse the output of `helm history`, either in json (`-o json`) or in table format, for helm 2 and 3
+/// unparsable rows are ignored
+pub fn parse_helm_history(output: &str) -> Vec<HelmHistoryRow> {
+    let output = output.trim();
+
+    if output.starts_with('[') {
+        return match serde_json::from_str::<Vec<HelmHistoryRow>>(output) {
+            Ok(rows) => rows,
+            Err(err) => {
+                error!("unable to parse helm history json output: {}", err);
+                vec![]
+            }
+        };
+    }
+
+    let mut lines = output.lines();
+    let headers = match lines.next() {
+        Some(headers) => headers
+            .split('\t')
+            .map(|header| header.trim().to_uppercase())
+            .collect::<Vec<_>>(),
+        None => return vec![],
+    };
+
+    let column = |name: &str| headers.iter().position(|header| header == name);
+    let revision_column = column("REVISION");
+    let updated_column = column("UPDATED");
+    let status_column = column("STATUS");
+    let chart_column = column("CHART");
+    let app_version_column = column("APP VERSION");
+
+    lines
+        .filter_map(|line| {
+            let values = line.split('\t').map(|value| value.trim()).collect::<Vec<_>>();
+            let value = |index: Option<usize>| index.and_then(|i| values.get(i)).map(|v| v.to_string());
+
+            // a row without a revision can't be used
+            let revision = value(revision_column)?.parse::<u32>().ok()?;
+
+            Some(HelmHistoryRow {
+                revision,
+                updated: value(updated_column).and_then(|updated| parse_helm_date(updated.as_str())),
+                status: value(status_column).unwrap_or_default(),
+                chart: value(chart_column).unwrap_or_default(),
+                app_version: value(app_version_column).unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
 pub fn helm_uninstall_list<P>(
     kubernetes_config: P,
     helm_list: Vec<HelmList>,
+    timeout: Timeout<u32>,
     envs: Vec<(&str, &str)>,
 ) -> Result<String, SimpleError>
 where
     P: AsRef<Path>,
 {
     let mut output_vec: Vec<String> = Vec::new();
+    let timeout = timeout.to_helm_flag_value();
 
     for chart in helm_list {
         match helm_exec_with_output(
@@ -211,6 +803,8 @@ where
                 chart.name.as_str(),
                 "--kubeconfig",
                 kubernetes_config.as_ref().to_str().unwrap(),
+                "--timeout",
+                timeout.as_str(),
             ],
             envs.clone(),
             |out| match out {
@@ -242,11 +836,15 @@ pub fn helm_exec_upgrade_with_override_file<P>(
     release_name: &str,
     chart_root_dir: P,
     override_file: &str,
+    timeout: Timeout<u32>,
     envs: Vec<(&str, &str)>,
-) -> Result<(), SimpleError>
+) -> Result<Option<u32>, SimpleError>
 where
     P: AsRef<Path>,
 {
+    let timeout = timeout.to_helm_flag_value();
+
+    let mut upgraded_revision = None;
     helm_exec_with_output(
         vec![
             "upgrade",
@@ -256,6 +854,8 @@ where
             "--install",
             "--history-max",
             "50",
+            "--timeout",
+            timeout.as_str(),
             "--wait",
             "--namespace",
             namespace,
@@ -266,7 +866,12 @@ where
         ],
         envs,
         |out| match out {
-            Ok(line) => info!("{}", line.as_str()),
+            Ok(line) => {
+                info!("{}", line.as_str());
+                if let Some(revision) = parse_helm_upgrade_revision(line.as_str()) {
+                    upgraded_revision = Some(revision);
+                }
+            }
             Err(err) => error!("{}", err),
         },
         |out| match out {
@@ -275,7 +880,9 @@ where
             Ok(line) => error!("{}", line.as_str()),
             Err(err) => error!("{}", err),
         },
-    )
+    )?;
+
+    Ok(upgraded_revision)
 }
 
 pub fn helm_exec_with_upgrade_history_with_override<P>(
@@ -284,6 +891,7 @@ pub fn helm_exec_with_upgrade_history_with_override<P>(
     release_name: &str,
     chart_root_dir: P,
     override_file: &str,
+    timeout: Timeout<u32>,
     envs: Vec<(&str, &str)>,
 ) -> Result<Option<HelmHistoryRow>, SimpleError>
 where
@@ -296,12 +904,13 @@ where
         chart_root_dir.as_ref().to_str().unwrap()
     );
 
-    let _ = helm_exec_upgrade_with_override_file(
+    let upgraded_revision = helm_exec_upgrade_with_override_file(
         kubernetes_config.as_ref(),
         namespace,
         release_name,
         chart_root_dir.as_ref(),
         override_file,
+        timeout,
         envs.clone(),
     )?;
 
@@ -312,13 +921,15 @@ where
         chart_root_dir.as_ref().to_str().unwrap()
     );
 
-    let helm_history_rows = helm_exec_history(kubernetes_config.as_ref(), namespace, release_name, envs)?;
+    let helm_history_rows = helm_exec_history_rows(
+        kubernetes_config.as_ref(),
+        namespace,
+        release_name,
+        Some(helm_history_max_rows()),
+        envs,
+    )?;
 
-    // take the last deployment from helm history - or return none if there is no history
-    Ok(match helm_history_rows.first() {
-        Some(helm_history_row) => Some(helm_history_row.clone()),
-        None => None,
-    })
+    Ok(helm_upgraded_revision_row(&helm_history_rows, upgraded_revision))
 }
 
 pub fn helm_list<P>(kubernetes_config: P, envs: Vec<(&str, &str)>) -> Result<Vec<HelmList>, SimpleError>
@@ -347,23 +958,94 @@ where
     );
 
     let output_string: String = output_vec.join("");
-    let values = serde_json::from_str::<Vec<Helm>>(output_string.as_str());
-    let mut helms_charts: Vec<HelmList> = Vec::new();
+    let helms_charts = parse_helm_list(output_string.as_str())?
+        .into_iter()
+        .map(|helm| HelmList::new(helm.name, helm.namespace))
+        .collect::<Vec<_>>();
 
-    match values {
-        Ok(all_helms) => {
-            for helm in all_helms {
-                helms_charts.push(HelmList::new(helm.name, helm.namespace))
-            }
-        }
+    Ok(helms_charts)
+}
+
+/// the releases of a single namespace
+pub fn helm_exec_list<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<HelmRelease>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut output_vec: Vec<String> = Vec::new();
+    let _ = helm_exec_with_output(
+        vec![
+            "list",
+            "--kubeconfig",
+            kubernetes_config.as_ref().to_str().unwrap(),
+            "--namespace",
+            namespace,
+            // the failed and pending releases too, and not only the first 256 ones
+            "--all",
+            "--max",
+            "0",
+            "-o",
+            "json",
+        ],
+        envs,
+        |out| match out {
+            Ok(line) => output_vec.push(line),
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line.as_str()),
+            Err(err) => error!("{}", err),
+        },
+    )?;
+
+    parse_helm_list(output_vec.join("").as_str())
+}
+
+fn parse_helm_list(output: &str) -> Result<Vec<HelmRelease>, SimpleError> {
+    match serde_json::from_str::<Vec<HelmRelease>>(output) {
+        Ok(releases) => Ok(releases),
         Err(e) => {
             let message = format!("Error while deserializing all helms names {}", e);
             error!("{}", message.as_str());
-            return Err(SimpleError::new(SimpleErrorKind::Other, Some(message)));
+            Err(SimpleError::new(SimpleErrorKind::Other, Some(message)))
         }
     }
+}
 
-    Ok(helms_charts)
+/// the releases deployed by the engine which are not in `keep`, the releases installed by others are never orphans
+pub fn helm_orphan_releases(releases: &[HelmRelease], keep: &[String]) -> Vec<String> {
+    releases
+        .iter()
+        .map(|release| release.name.as_str())
+        .filter(|name| is_engine_helm_release_name(name))
+        .filter(|name| !keep.iter().any(|kept| kept == name))
+        .map(|name| name.to_string())
+        .collect()
+}
+
+/// uninstall the orphan releases of the namespace, returns the names of the uninstalled releases
+pub fn helm_uninstall_orphans<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    keep: &[String],
+    timeout: Timeout<u32>,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<String>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let releases = helm_exec_list(kubernetes_config.as_ref(), namespace, envs.clone())?;
+    let orphans = helm_orphan_releases(&releases, keep);
+
+    for orphan in &orphans {
+        info!("uninstalling the orphan release {} of the namespace {}", orphan, namespace);
+        helm_exec_uninstall(kubernetes_config.as_ref(), namespace, orphan.as_str(), timeout.clone(), envs.clone())?;
+    }
+
+    Ok(orphans)
 }
 
 pub fn helm_exec(args: Vec<&str>, envs: Vec<(&str, &str)>) -> Result<(), SimpleError> {
@@ -389,10 +1071,43 @@ where
     F: FnMut(Result<String, Error>),
     X: FnMut(Result<String, Error>),
 {
+    helm_exec_result(exec_with_envs_and_output(
+        helm_binary(),
+        args,
+        envs,
+        stdout_output,
+        stderr_output,
+        Duration::max_value(),
+    ))
+}
+
+/// same as `helm_exec_with_output`, helm is killed as soon as the token is cancelled
+pub fn helm_exec_with_output_cancellable<F, X>(
+    args: Vec<&str>,
+    envs: Vec<(&str, &str)>,
+    stdout_output: F,
+    stderr_output: X,
+    cancellation_token: &CancellationToken,
+) -> Result<(), SimpleError>
+where
+    F: FnMut(Result<String, Error>),
+    X: FnMut(Result<String, Error>),
+{
+    helm_exec_result(exec_with_envs_and_output_cancellable(
+        helm_binary(),
+        args,
+        envs,
+        stdout_output,
+        stderr_output,
+        cancellation_token,
+    ))
+}
+
+fn helm_exec_result(result: Result<(), SimpleError>) -> Result<(), SimpleError> {
     // Note: Helm CLI use spf13/cobra lib for the CLI; One function is mainly used to return an error if a command failed.
     // Helm returns an error each time a command does not succeed as they want. Which leads to handling error with status code 1
     // It means that the command successfully ran, but it didn't terminate as expected
-    match exec_with_envs_and_output("helm", args, envs, stdout_output, stderr_output, Duration::max_value()) {
+    match result {
         Err(err) => match err.kind {
             SimpleErrorKind::Command(exit_status) => match exit_status.code() {
                 Some(exit_status_code) => {
@@ -404,8 +1119,509 @@ where
                 }
                 None => Err(err),
             },
-            SimpleErrorKind::Other => Err(err),
+            SimpleErrorKind::Cancelled | SimpleErrorKind::Other => Err(err),
         },
         _ => Ok(()),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::cmd::helm::{
+        helm_exec_registry_login_args, helm_exec_repo_add_args, helm_exec_upgrade_args, helm_exec_with_output,
+        helm_last_deployed_revision, helm_merge_values, helm_orphan_releases, helm_upgraded_revision_row,
+        helm_values_equal, parse_helm_history, parse_helm_list, parse_helm_search_versions, parse_helm_upgrade_revision,
+        parse_helm_values, HelmOciRegistry, HelmRepositoryCredentials, Timeout,
+    };
+    use crate::cmd::structs::HelmHistoryRow;
+    use crate::cmd::tools::set_tool_config;
+    use crate::models::ToolConfig;
+    use std::collections::BTreeMap;
+    use std::os::unix::fs::PermissionsExt;
+    use chrono::{TimeZone, Utc};
+
+    fn history_row(revision: u32, status: &str) -> HelmHistoryRow {
+        HelmHistoryRow {
+            revision,
+            updated: None,
+            status: status.to_string(),
+            chart: "job-0.1.0".to_string(),
+            app_version: "1.16.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_helm_last_deployed_revision() {
+        // first install has failed, there is nothing to rollback to
+        assert_eq!(helm_last_deployed_revision(&[history_row(1, "failed")]), None);
+
+        // latest upgrade has failed, rollback to the previous deployed revision
+        let rows = vec![
+            history_row(3, "failed"),
+            history_row(2, "deployed"),
+            history_row(1, "superseded"),
+        ];
+        assert_eq!(helm_last_deployed_revision(&rows), Some(2));
+
+        // several failed upgrades in a row and no deployed revision left
+        let rows = vec![
+            history_row(3, "failed"),
+            history_row(2, "failed"),
+            history_row(1, "superseded"),
+        ];
+        assert_eq!(helm_last_deployed_revision(&rows), None);
+    }
+
+    #[test]
+    fn test_helm_upgraded_revision_row() {
+        // a concurrent upgrade is still pending after ours
+        let rows = vec![
+            history_row(4, "pending-upgrade"),
+            history_row(3, "deployed"),
+            history_row(2, "superseded"),
+        ];
+
+        let row = helm_upgraded_revision_row(&rows, Some(3)).unwrap();
+        assert_eq!(row.revision(), 3);
+        assert!(row.is_successfully_deployed());
+
+        assert_eq!(helm_upgraded_revision_row(&rows, None).unwrap().revision(), 4);
+        assert!(helm_upgraded_revision_row(&rows, Some(5)).is_none());
+
+        assert_eq!(parse_helm_upgrade_revision("REVISION: 3"), Some(3));
+        assert_eq!(parse_helm_upgrade_revision("STATUS: deployed"), None);
+    }
+
+    #[test]
+    fn test_parse_helm_3_history() {
+        let json = r#"[{"revision":1,"updated":"2020-09-21T13:58:50.407016+02:00","status":"superseded","chart":"job-0.1.0","app_version":"1.16.0","description":"Install complete"},{"revision":2,"updated":"2020-09-21T14:10:12.112233+02:00","status":"deployed","chart":"job-0.1.0","app_version":"1.16.0","description":"Upgrade complete"}]"#;
+
+        let rows = parse_helm_history(json);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].revision(), 2);
+        assert!(rows[1].is_successfully_deployed());
+        assert_eq!(
+            rows[0].updated().unwrap().timestamp(),
+            Utc.ymd(2020, 9, 21).and_hms(11, 58, 50).timestamp()
+        );
+
+        let table = "REVISION\tUPDATED                 \tSTATUS    \tCHART    \tAPP VERSION\tDESCRIPTION     \n\
+                     1       \tMon Sep 21 13:58:50 2020\tsuperseded\tjob-0.1.0\t1.16.0     \tInstall complete\n\
+                     2       \tMon Sep 21 14:10:12 2020\tdeployed  \tjob-0.1.0\t1.16.0     \tUpgrade complete";
+
+        let rows = parse_helm_history(table);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].revision(), 1);
+        assert_eq!(rows[0].status, "superseded");
+        assert_eq!(rows[1].app_version, "1.16.0");
+        assert_eq!(rows[1].updated(), Some(&Utc.ymd(2020, 9, 21).and_hms(14, 10, 12)));
+    }
+
+    #[test]
+    fn test_parse_helm_2_history() {
+        // helm 2 has no app version and uses upper case status
+        let json = r#"[{"revision":1,"updated":"Mon Sep 21 13:58:50 2020","status":"SUPERSEDED","chart":"job-0.1.0","description":"Install complete"},{"revision":2,"updated":"Mon Sep 21 14:10:12 2020","status":"DEPLOYED","chart":"job-0.1.0","description":"Upgrade complete"}]"#;
+
+        let rows = parse_helm_history(json);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].revision(), 2);
+        assert_eq!(rows[1].app_version, "");
+        assert!(rows[1].is_successfully_deployed());
+        assert_eq!(rows[0].updated(), Some(&Utc.ymd(2020, 9, 21).and_hms(13, 58, 50)));
+
+        let table = "REVISION\tUPDATED                 \tSTATUS    \tCHART    \tDESCRIPTION     \n\
+                     1       \tMon Sep 21 13:58:50 2020\tSUPERSEDED\tjob-0.1.0\tInstall complete\n\
+                     2       \tnot a date              \tDEPLOYED  \tjob-0.1.0\tUpgrade complete";
+
+        let rows = parse_helm_history(table);
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[1].revision(), 2);
+        assert_eq!(rows[1].updated(), None);
+        assert!(rows[1].is_successfully_deployed());
+        assert_eq!(rows[0].chart, "job-0.1.0");
+    }
+
+    #[test]
+    fn test_helm_upgrade_args_with_chart_version() {
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Default,
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+        assert!(!args.contains(&"--version".to_string()));
+
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            Some("0.2.1"),
+            &[],
+            &[],
+            Timeout::Default,
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+        let version_index = args.iter().position(|arg| arg == "--version").unwrap();
+        assert_eq!(args[version_index + 1], "0.2.1");
+    }
+
+    #[test]
+    fn test_helm_upgrade_args_with_values_files() {
+        let values_files = vec!["/etc/qovery/defaults.yaml".to_string(), "overrides.yaml".to_string()];
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &values_files,
+            &[],
+            Timeout::Default,
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+
+        let chart_index = args.iter().position(|arg| arg == "chart").unwrap();
+        assert_eq!(
+            args[chart_index + 1..].to_vec(),
+            vec!["-f", "/etc/qovery/defaults.yaml", "-f", "overrides.yaml"]
+        );
+    }
+
+    #[test]
+    fn test_helm_upgrade_args_with_set_values() {
+        let values_files = vec!["overrides.yaml".to_string()];
+        let set_values = vec![
+            ("resources.limits.memory".to_string(), "2Gi".to_string()),
+            ("nodeSelector.kubernetes\\.io/os".to_string(), "linux".to_string()),
+            ("args".to_string(), "--hosts=a,b".to_string()),
+            ("weird,key".to_string(), "C:\\temp".to_string()),
+        ];
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &values_files,
+            &set_values,
+            Timeout::Default,
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+
+        // the set values come after the values files so they win
+        let chart_index = args.iter().position(|arg| arg == "chart").unwrap();
+        assert_eq!(
+            args[chart_index + 1..].to_vec(),
+            vec![
+                "-f",
+                "overrides.yaml",
+                "--set",
+                "resources.limits.memory=2Gi",
+                "--set",
+                "nodeSelector.kubernetes\\.io/os=linux",
+                "--set",
+                "args=--hosts=a\\,b",
+                "--set",
+                "weird\\,key=C:\\\\temp",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_helm_search_versions() {
+        let json = r#"[{"name":"qovery/q-job","version":"0.2.0","app_version":"1.16.0","description":"A Helm chart for Kubernetes"},{"name":"qovery/q-job","version":"0.1.0","app_version":"1.16.0","description":"A Helm chart for Kubernetes"}]"#;
+
+        assert_eq!(parse_helm_search_versions(json).unwrap(), vec!["0.2.0", "0.1.0"]);
+        assert!(parse_helm_search_versions("Error: no repositories configured").is_err());
+    }
+
+    #[test]
+    fn test_helm_timeout_flag() {
+        // helm default timeout is added on top of the value
+        let timeout = Timeout::Value(900);
+        assert_eq!(timeout.to_helm_flag_value(), "1200s");
+        assert_eq!(timeout.to_helm_2_flag_value(), "1200");
+        assert_eq!(Timeout::Default.to_helm_flag_value(), "300s");
+
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            timeout,
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+        let timeout_index = args.iter().position(|arg| arg == "--timeout").unwrap();
+        assert_eq!(format!("{} {}", args[timeout_index], args[timeout_index + 1]), "--timeout 1200s");
+    }
+
+    #[test]
+    fn test_helm_upgrade_args_with_wait_for_jobs() {
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Value(600),
+            true,
+            true,
+            &BTreeMap::new(),
+        );
+        assert!(args.contains(&"--wait".to_string()));
+        assert!(args.contains(&"--wait-for-jobs".to_string()));
+        let timeout_index = args.iter().position(|arg| arg == "--timeout").unwrap();
+        assert_eq!(args[timeout_index + 1], "900s");
+
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Value(600),
+            true,
+            false,
+            &BTreeMap::new(),
+        );
+        assert!(args.contains(&"--wait".to_string()));
+        assert!(!args.contains(&"--wait-for-jobs".to_string()));
+    }
+
+    #[test]
+    fn test_helm_upgrade_args_with_labels() {
+        let mut labels = BTreeMap::new();
+        labels.insert("serviceId".to_string(), "z1234".to_string());
+        labels.insert("team".to_string(), "billing".to_string());
+
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Default,
+            false,
+            false,
+            &labels,
+        );
+        let labels_index = args.iter().position(|arg| arg == "--labels").unwrap();
+        assert_eq!(args[labels_index + 1], "serviceId=z1234,team=billing");
+
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Default,
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+        assert!(!args.contains(&"--labels".to_string()));
+    }
+
+    #[test]
+    fn test_helm_upgrade_args_with_atomic() {
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Value(600),
+            true,
+            false,
+            &BTreeMap::new(),
+        );
+        assert!(args.contains(&"--atomic".to_string()));
+
+        let args = helm_exec_upgrade_args(
+            "kubeconfig",
+            "namespace",
+            "release",
+            "chart",
+            None,
+            &[],
+            &[],
+            Timeout::Value(600),
+            false,
+            false,
+            &BTreeMap::new(),
+        );
+        assert!(!args.contains(&"--atomic".to_string()));
+    }
+
+    #[test]
+    fn test_helm_repo_add_args() {
+        assert_eq!(
+            helm_exec_repo_add_args("bitnami", "https://charts.bitnami.com/bitnami", None),
+            vec!["repo", "add", "bitnami", "https://charts.bitnami.com/bitnami", "--force-update"]
+        );
+
+        let credentials = HelmRepositoryCredentials {
+            username: "login".to_string(),
+            password: "password".to_string(),
+        };
+
+        assert_eq!(
+            helm_exec_repo_add_args("private", "https://charts.example.com", Some(&credentials)),
+            vec![
+                "repo",
+                "add",
+                "private",
+                "https://charts.example.com",
+                "--force-update",
+                "--username",
+                "login",
+                "--password",
+                "password"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_helm_oci_registry() {
+        let registry = HelmOciRegistry {
+            url: "oci://registry.example.com/charts/".to_string(),
+            credentials: None,
+        };
+        assert_eq!(registry.host(), "registry.example.com");
+        assert_eq!(registry.chart_reference("my-job"), "oci://registry.example.com/charts/my-job");
+
+        let registry = HelmOciRegistry {
+            url: "registry.example.com:5000".to_string(),
+            credentials: None,
+        };
+        assert_eq!(registry.host(), "registry.example.com:5000");
+        assert_eq!(registry.chart_reference("my-job"), "oci://registry.example.com:5000/my-job");
+
+        let credentials = HelmRepositoryCredentials {
+            username: "login".to_string(),
+            password: "password".to_string(),
+        };
+        assert_eq!(
+            helm_exec_registry_login_args(registry.host(), &credentials),
+            vec![
+                "registry",
+                "login",
+                "registry.example.com:5000",
+                "--username",
+                "login",
+                "--password",
+                "password"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_helm_values_equal() {
+        let values = |yaml: &str| parse_helm_values(yaml).unwrap();
+        let deployed = values("image:\n  tag: \"1.0\"\n  name: job\nreplicas: 1\nargs: [\"-v\"]\n");
+
+        // the order of the keys does not matter
+        let desired = values("replicas: 1\nargs: [\"-v\"]\nimage:\n  name: job\n  tag: \"1.0\"\n");
+        assert!(helm_values_equal(&desired, &deployed));
+
+        let desired = values("replicas: 1\nargs: [\"-v\"]\nimage:\n  name: job\n  tag: \"1.1\"\n");
+        assert!(!helm_values_equal(&desired, &deployed));
+
+        let desired = values("replicas: 1\nimage:\n  name: job\n  tag: \"1.0\"\n");
+        assert!(!helm_values_equal(&desired, &deployed));
+
+        // a release without values
+        assert!(helm_values_equal(&values(""), &values("null")));
+        assert!(!helm_values_equal(&values(""), &deployed));
+    }
+
+    #[test]
+    fn test_helm_merge_values() {
+        let values = |yaml: &str| parse_helm_values(yaml).unwrap();
+        let merged = helm_merge_values(
+            values("image:\n  name: job\n  tag: \"1.0\"\nreplicas: 1\n"),
+            values("image:\n  tag: \"1.1\"\n"),
+        );
+
+        assert!(helm_values_equal(&merged, &values("replicas: 1\nimage:\n  tag: \"1.1\"\n  name: job\n")));
+    }
+
+    #[test]
+    fn test_helm_orphan_releases() {
+        let output = r#"[
+            {"name":"application-api-z1234","namespace":"env","revision":"3","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"q-application-0.1.0","app_version":"1.0"},
+            {"name":"external-service-job-z5678","namespace":"env","revision":"1","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"failed","chart":"q-job-0.1.0","app_version":"1.0"},
+            {"name":"redis-z9012","namespace":"env","revision":"1","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"redis-10.7.11","app_version":"6.0"},
+            {"name":"datadog-agent","namespace":"env","revision":"7","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"datadog-2.10.0","app_version":"7"}
+        ]"#;
+
+        let releases = parse_helm_list(output).unwrap();
+        assert_eq!(releases.len(), 4);
+
+        // the release installed by someone else is never uninstalled
+        let keep = vec!["application-api-z1234".to_string()];
+        assert_eq!(
+            helm_orphan_releases(&releases, &keep),
+            vec!["external-service-job-z5678".to_string(), "redis-z9012".to_string()]
+        );
+
+        let keep = releases.iter().map(|release| release.name.clone()).collect::<Vec<_>>();
+        assert!(helm_orphan_releases(&releases, &keep).is_empty());
+
+        assert!(parse_helm_list("[]").unwrap().is_empty());
+        assert!(parse_helm_list("Error: Kubernetes cluster unreachable").is_err());
+    }
+
+    #[test]
+    fn test_custom_helm_binary() {
+        let dir = format!("{}/qovery-engine-custom-helm", std::env::temp_dir().to_str().unwrap());
+        let _ = std::fs::remove_dir_all(dir.as_str());
+        std::fs::create_dir_all(dir.as_str()).unwrap();
+
+        // other tests may run helm meanwhile, the custom binary records its arguments and runs the helm of the PATH
+        let helm_path = format!("{}/helm-3.4.2", dir);
+        let script = format!("#!/bin/sh\necho \"$@\" > {}/arguments\nexec helm \"$@\"\n", dir);
+        std::fs::write(helm_path.as_str(), script).unwrap();
+        std::fs::set_permissions(helm_path.as_str(), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        set_tool_config(ToolConfig {
+            helm: Some(helm_path),
+            kubectl: None,
+        });
+        let _ = helm_exec_with_output(vec!["version", "--short"], vec![], |_| {}, |_| {});
+        set_tool_config(ToolConfig::default());
+
+        let arguments = std::fs::read_to_string(format!("{}/arguments", dir)).unwrap();
+        assert_eq!(arguments.trim(), "version --short");
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
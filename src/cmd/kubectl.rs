@@ -1,7 +1,8 @@
+use std::collections::BTreeMap;
 use std::io::Error;
 use std::path::Path;
 
-use retry::delay::Fibonacci;
+use retry::delay::{Fibonacci, Fixed};
 use retry::OperationResult;
 use serde::de::DeserializeOwned;
 
@@ -10,10 +11,123 @@ use crate::cmd::structs::{
     Item, KubernetesEvent, KubernetesJob, KubernetesKind, KubernetesList, KubernetesNode, KubernetesPod,
     KubernetesPodStatusPhase, KubernetesService, LabelsContent,
 };
-use crate::cmd::utilities::exec_with_envs_and_output;
+use crate::cmd::helm::Timeout;
+use crate::cmd::retry::ExponentialBackoff;
+use crate::cmd::tools::kubectl_binary;
+use crate::cmd::utilities::{exec_with_envs_and_output, exec_with_envs_input_and_output};
 use crate::constants::KUBECONFIG;
 use crate::error::{SimpleError, SimpleErrorKind};
+use crate::models::CancellationToken;
 use chrono::Duration;
+use rand::distributions::Alphanumeric;
+use rand::Rng;
+
+/// the name of the `ResourceQuota` created with a namespace, one per namespace
+pub const RESOURCE_QUOTA_NAME: &str = "namespace-quota";
+
+/// the result of a kubectl command, a non-zero exit code is not an error by itself
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct KubectlOutput {
+    pub stdout: String,
+    pub stderr: String,
+    pub exit_code: i32,
+}
+
+impl KubectlOutput {
+    pub fn success(&self) -> bool {
+        self.exit_code == 0
+    }
+
+    /// the error of a failed command, with its stderr
+    pub fn into_result(self, args: &[&str]) -> Result<KubectlOutput, SimpleError> {
+        if self.success() {
+            return Ok(self);
+        }
+
+        Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("kubectl {} exited with code {}: {}", args.join(" "), self.exit_code, self.stderr)),
+        ))
+    }
+
+    /// parse the stdout of a command run with `-o json`
+    pub fn json<T>(&self) -> Result<T, SimpleError>
+    where
+        T: DeserializeOwned,
+    {
+        serde_json::from_str::<T>(self.stdout.as_str()).map_err(|err| {
+            error!("{:?}", err);
+            error!("{}", self.stdout.as_str());
+            SimpleError::new(SimpleErrorKind::Other, Some(self.stdout.clone()))
+        })
+    }
+
+    pub fn stdout_lines(&self) -> Vec<String> {
+        self.stdout.lines().map(|line| line.to_string()).collect()
+    }
+}
+
+/// run kubectl and capture its output, only an unexpected failure to run it is an error
+pub fn exec(args: Vec<&str>, envs: Vec<(&str, &str)>) -> Result<KubectlOutput, SimpleError> {
+    exec_binary(kubectl_binary().as_str(), args, envs, Duration::max_value())
+}
+
+fn exec_binary(
+    binary: &str,
+    args: Vec<&str>,
+    envs: Vec<(&str, &str)>,
+    timeout: Duration,
+) -> Result<KubectlOutput, SimpleError> {
+    let mut stdout: Vec<String> = Vec::with_capacity(50);
+    let mut stderr: Vec<String> = Vec::new();
+
+    let result = exec_with_envs_and_output(
+        binary,
+        args,
+        envs,
+        |out| match out {
+            Ok(line) => stdout.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => stderr.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+        timeout,
+    );
+
+    let exit_code = match result {
+        Ok(_) => 0,
+        Err(SimpleError {
+            kind: SimpleErrorKind::Command(exit_status),
+            ..
+        }) => exit_status.code().unwrap_or(-1),
+        Err(err) => return Err(err),
+    };
+
+    Ok(KubectlOutput {
+        stdout: stdout.join("\n"),
+        stderr: stderr.join("\n"),
+        exit_code,
+    })
+}
+
+/// run kubectl with the given kubeconfig, a non-zero exit code is an error
+fn exec_with_kubeconfig<P>(
+    args: Vec<&str>,
+    kubernetes_config: P,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubectlOutput, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let args_copy = args.clone();
+    exec(args, _envs)?.into_result(&args_copy)
+}
 
 pub fn kubectl_exec_with_output<F, X>(
     args: Vec<&str>,
@@ -26,7 +140,7 @@ where
     X: FnMut(Result<String, Error>),
 {
     match exec_with_envs_and_output(
-        "kubectl",
+        kubectl_binary(),
         args,
         envs,
         stdout_output,
@@ -49,12 +163,7 @@ pub fn kubectl_exec_get_number_of_restart<P>(
 where
     P: AsRef<Path>,
 {
-    let mut _envs = Vec::with_capacity(envs.len() + 1);
-    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
-    _envs.extend(envs);
-
-    let mut output_vec: Vec<String> = Vec::with_capacity(20);
-    let _ = kubectl_exec_with_output(
+    let output = exec_with_kubeconfig(
         vec![
             "get",
             "po",
@@ -63,19 +172,11 @@ where
             namespace,
             "-o=custom-columns=:.status.containerStatuses..restartCount",
         ],
-        _envs,
-        |out| match out {
-            Ok(line) => output_vec.push(line),
-            Err(err) => error!("{:?}", err),
-        },
-        |out| match out {
-            Ok(line) => error!("{}", line),
-            Err(err) => error!("{:?}", err),
-        },
+        kubernetes_config,
+        envs,
     )?;
 
-    let output_string: String = output_vec.join("");
-    Ok(output_string)
+    Ok(output.stdout_lines().join(""))
 }
 
 pub fn do_kubectl_exec_describe_service<P>(
@@ -87,37 +188,14 @@ pub fn do_kubectl_exec_describe_service<P>(
 where
     P: AsRef<Path>,
 {
-    let mut _envs = Vec::with_capacity(envs.len() + 1);
-    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
-    _envs.extend(envs);
-
-    let mut output_vec: Vec<String> = Vec::with_capacity(20);
-    let _ = kubectl_exec_with_output(
+    kubectl_exec::<P, DOKubernetesList>(
         vec![
             "get", "svc", "-o", "json", "-n", namespace, "-l", // selector
             selector,
         ],
-        _envs,
-        |out| match out {
-            Ok(line) => output_vec.push(line),
-            Err(err) => error!("{:?}", err),
-        },
-        |out| match out {
-            Ok(line) => error!("{}", line),
-            Err(err) => error!("{:?}", err),
-        },
-    )?;
-
-    let output_string: String = output_vec.join("\n");
-
-    match serde_json::from_str::<DOKubernetesList>(output_string.as_str()) {
-        Ok(x) => Ok(x),
-        Err(err) => {
-            error!("{:?}", err);
-            error!("{}", output_string.as_str());
-            return Err(SimpleError::new(SimpleErrorKind::Other, Some(output_string)));
-        }
-    }
+        kubernetes_config,
+        envs,
+    )
 }
 
 // Get ip external ingress
@@ -291,230 +369,604 @@ where
     Ok(Some(is_ready))
 }
 
+/// how many times and how often a readiness check is done
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub interval: std::time::Duration,
+    /// the checks stop as soon as it is cancelled
+    pub cancellation_token: CancellationToken,
+}
+
+impl RetryPolicy {
+    /// a policy polling every 5 seconds with a total wait matching the timeout
+    pub fn from_timeout(timeout: Timeout<u32>) -> Self {
+        match timeout {
+            Timeout::Default => RetryPolicy::default(),
+            Timeout::Value(seconds) => RetryPolicy {
+                max_attempts: (seconds / 5).max(1),
+                interval: std::time::Duration::from_secs(5),
+                cancellation_token: CancellationToken::default(),
+            },
+        }
+    }
+
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 10,
+            interval: std::time::Duration::from_secs(30),
+            cancellation_token: CancellationToken::default(),
+        }
+    }
+}
+
 pub fn kubectl_exec_is_job_ready_with_retry<P>(
     kubernetes_config: P,
     namespace: &str,
     job_name: &str,
+    timeout: Timeout<u32>,
     envs: Vec<(&str, &str)>,
+    cancellation_token: &CancellationToken,
 ) -> Result<Option<bool>, SimpleError>
 where
     P: AsRef<Path>,
 {
-    // TODO check this
-    let result = retry::retry(Fibonacci::from_millis(3000).take(10), || {
-        let r = crate::cmd::kubectl::kubectl_exec_is_job_ready(
-            kubernetes_config.as_ref(),
-            namespace,
-            job_name,
-            envs.clone(),
-        );
+    is_ready_with_delays(
+        job_ready_delays(&timeout),
+        cancellation_token,
+        format!("job {}", job_name).as_str(),
+        || {
+            crate::cmd::kubectl::kubectl_exec_is_job_ready(
+                kubernetes_config.as_ref(),
+                namespace,
+                job_name,
+                envs.clone(),
+            )
+        },
+    )
+}
 
-        match r {
-            Ok(is_ready) => match is_ready {
-                Some(true) => OperationResult::Ok(true),
-                _ => {
-                    let t = format!("job {} is not ready yet", job_name);
-                    info!("{}", t.as_str());
-                    OperationResult::Retry(t)
-                }
-            },
-            Err(err) => OperationResult::Err(format!("command error: {:?}", err)),
+/// the delays between the checks of a job, the former ones are kept when there is no timeout
+fn job_ready_delays(timeout: &Timeout<u32>) -> Vec<std::time::Duration> {
+    match timeout {
+        Timeout::Default => Fibonacci::from_millis(3000).take(10).collect(),
+        Timeout::Value(seconds) => {
+            // many services may wait for their jobs at the same time, spread their checks over time
+            let backoff = ExponentialBackoff::new(
+                std::time::Duration::from_secs(2),
+                std::time::Duration::from_secs(30),
+                0.2,
+                std::time::Duration::from_secs(*seconds as u64),
+            );
+
+            backoff.delays().collect()
         }
-    });
-
-    match result {
-        Err(err) => match err {
-            retry::Error::Operation {
-                error: _,
-                total_delay: _,
-                tries: _,
-            } => Ok(Some(false)),
-            retry::Error::Internal(err) => Err(SimpleError::new(SimpleErrorKind::Other, Some(err))),
-        },
-        Ok(_) => Ok(Some(true)),
     }
 }
 
-pub fn kubectl_exec_is_job_ready<P>(
+pub fn kubectl_exec_is_job_ready_with_policy<P>(
     kubernetes_config: P,
     namespace: &str,
     job_name: &str,
+    retry_policy: RetryPolicy,
     envs: Vec<(&str, &str)>,
 ) -> Result<Option<bool>, SimpleError>
 where
     P: AsRef<Path>,
 {
-    let job_result = kubectl_exec::<P, KubernetesJob>(
-        vec!["get", "job", "-o", "json", "-n", namespace, job_name],
-        kubernetes_config,
-        envs,
-    )?;
+    is_ready_with_policy(&retry_policy, format!("job {}", job_name).as_str(), || {
+        crate::cmd::kubectl::kubectl_exec_is_job_ready(kubernetes_config.as_ref(), namespace, job_name, envs.clone())
+    })
+}
 
-    if job_result.status.succeeded > 0 {
-        return Ok(Some(true));
-    }
+pub fn is_ready_with_policy<F>(
+    retry_policy: &RetryPolicy,
+    resource: &str,
+    is_ready: F,
+) -> Result<Option<bool>, SimpleError>
+where
+    F: FnMut() -> Result<Option<bool>, SimpleError>,
+{
+    // the first attempt is not delayed
+    let delays = Fixed::from_millis(retry_policy.interval.as_millis() as u64)
+        .take(retry_policy.max_attempts.saturating_sub(1) as usize);
 
-    Ok(Some(false))
+    is_ready_with_delays(delays, &retry_policy.cancellation_token, resource, is_ready)
 }
 
-pub fn kubectl_exec_is_namespace_present<P>(kubernetes_config: P, namespace: &str, envs: Vec<(&str, &str)>) -> bool
+fn is_ready_with_delays<I, F>(
+    delays: I,
+    cancellation_token: &CancellationToken,
+    resource: &str,
+    mut is_ready: F,
+) -> Result<Option<bool>, SimpleError>
 where
-    P: AsRef<Path>,
+    I: IntoIterator<Item = std::time::Duration>,
+    F: FnMut() -> Result<Option<bool>, SimpleError>,
 {
-    let mut _envs = Vec::with_capacity(envs.len() + 1);
-    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
-    _envs.extend(envs);
+    if cancellation_token.is_cancelled() {
+        return Err(cancelled_error(resource));
+    }
 
-    let mut output_vec: Vec<String> = Vec::new();
-    let result = kubectl_exec_with_output(
-        vec!["get", "namespace", namespace],
-        _envs,
-        |out| match out {
-            Ok(line) => output_vec.push(line),
-            Err(err) => error!("{:?}", err),
-        },
-        |out| match out {
-            Ok(line) => {
-                if line.contains("Error from server (NotFound): namespaces") {
-                    info!("{}", line)
-                } else {
-                    error!("{}", line)
-                }
-            }
-            Err(err) => error!("{:?}", err),
-        },
-    );
+    let result = retry::retry(cancellable_delays(delays, cancellation_token), || match is_ready() {
+        Ok(Some(true)) => OperationResult::Ok(true),
+        Ok(_) => {
+            let t = format!("{} is not ready yet", resource);
+            info!("{}", t.as_str());
+            OperationResult::Retry(t)
+        }
+        Err(err) => OperationResult::Err(format!("command error: {:?}", err)),
+    });
+
+    if cancellation_token.is_cancelled() {
+        return Err(cancelled_error(resource));
+    }
 
     match result {
-        Ok(_) => true,
-        Err(_) => false,
+        Err(err) => match err {
+            retry::Error::Operation {
+                error: _,
+                total_delay: _,
+                tries: _,
+            } => Ok(Some(false)),
+            retry::Error::Internal(err) => Err(SimpleError::new(SimpleErrorKind::Other, Some(err))),
+        },
+        Ok(_) => Ok(Some(true)),
     }
 }
 
-pub fn kubectl_exec_create_namespace_without_labels(namespace: &str, kube_config: &str, envs: Vec<(&str, &str)>) {
-    let _ = kubectl_exec_create_namespace(kube_config, namespace, None, envs);
+/// the delays are waited here instead of by the retry loop, the iteration ends as soon as the token is cancelled
+fn cancellable_delays<'a, I>(
+    delays: I,
+    cancellation_token: &'a CancellationToken,
+) -> impl Iterator<Item = std::time::Duration> + 'a
+where
+    I: IntoIterator<Item = std::time::Duration>,
+    I::IntoIter: 'a,
+{
+    delays
+        .into_iter()
+        .map(move |delay| cancellation_token.sleep(delay))
+        .take_while(|waited| *waited)
+        .map(|_| std::time::Duration::from_millis(0))
 }
 
-pub fn kubectl_exec_create_namespace<P>(
+fn cancelled_error(resource: &str) -> SimpleError {
+    SimpleError::new(
+        SimpleErrorKind::Cancelled,
+        Some(format!("waiting for {} has been cancelled", resource)),
+    )
+}
+
+/// poll the pods matching the selector until none remain, within the timeout
+pub fn kubectl_exec_wait_for_pods_deletion<P>(
     kubernetes_config: P,
     namespace: &str,
-    labels: Option<Vec<LabelsContent>>,
+    selector: &str,
+    timeout: Timeout<u32>,
     envs: Vec<(&str, &str)>,
+    cancellation_token: &CancellationToken,
 ) -> Result<(), SimpleError>
 where
     P: AsRef<Path>,
 {
-    // don't create the namespace if already exists and not not return error in this case
-    if !kubectl_exec_is_namespace_present(kubernetes_config.as_ref(), namespace, envs.clone()) {
-        // create namespace
-        let mut _envs = Vec::with_capacity(envs.len() + 1);
-        _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
-        _envs.extend(envs.clone());
-
-        let _ = kubectl_exec_with_output(
-            vec!["create", "namespace", namespace],
-            _envs,
-            |out| match out {
-                Ok(line) => info!("{}", line),
-                Err(err) => error!("{:?}", err),
-            },
-            |out| match out {
-                Ok(line) => error!("{}", line),
-                Err(err) => error!("{:?}", err),
-            },
-        )?;
+    let retry_policy = RetryPolicy::from_timeout(timeout).with_cancellation_token(cancellation_token.clone());
+
+    wait_for_pods_deletion(&retry_policy, selector, || {
+        kubectl_exec_get_pod(kubernetes_config.as_ref(), namespace, selector, envs.clone()).map(|pods| pods.items.len())
+    })
+}
+
+fn wait_for_pods_deletion<F>(retry_policy: &RetryPolicy, selector: &str, mut count_pods: F) -> Result<(), SimpleError>
+where
+    F: FnMut() -> Result<usize, SimpleError>,
+{
+    // the first check is not delayed
+    let delays = Fixed::from_millis(retry_policy.interval.as_millis() as u64)
+        .take(retry_policy.max_attempts.saturating_sub(1) as usize);
+
+    let resource = format!("the deletion of the pods matching {}", selector);
+    if retry_policy.cancellation_token.is_cancelled() {
+        return Err(cancelled_error(resource.as_str()));
     }
 
-    // additional labels
-    if labels.is_some() {
-        match kubectl_add_labels_to_namespace(kubernetes_config, namespace, labels.unwrap(), envs) {
-            Ok(_) => {}
-            Err(e) => return Err(e),
+    let result = retry::retry(cancellable_delays(delays, &retry_policy.cancellation_token), || match count_pods() {
+        Ok(0) => OperationResult::Ok(()),
+        Ok(pods) => {
+            let t = format!("{} pod(s) matching {} are still terminating", pods, selector);
+            info!("{}", t.as_str());
+            OperationResult::Retry(t)
         }
-    };
+        Err(err) => OperationResult::Err(format!("command error: {:?}", err)),
+    });
 
-    Ok(())
+    if retry_policy.cancellation_token.is_cancelled() {
+        return Err(cancelled_error(resource.as_str()));
+    }
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(retry::Error::Operation { error, .. }) => Err(SimpleError::new(SimpleErrorKind::Other, Some(error))),
+        Err(retry::Error::Internal(err)) => Err(SimpleError::new(SimpleErrorKind::Other, Some(err))),
+    }
 }
 
-pub fn kubectl_add_labels_to_namespace<P>(
+pub fn kubectl_exec_is_job_ready<P>(
     kubernetes_config: P,
     namespace: &str,
-    labels: Vec<LabelsContent>,
+    job_name: &str,
     envs: Vec<(&str, &str)>,
-) -> Result<(), SimpleError>
+) -> Result<Option<bool>, SimpleError>
 where
     P: AsRef<Path>,
 {
-    if labels.iter().count() == 0 {
+    let job_result = kubectl_exec_get::<P, KubernetesJob>(kubernetes_config, namespace, "job", job_name, envs)?;
+
+    // the job won't succeed anymore, there is no need to wait for it
+    if job_result.status.has_exceeded_deadline() {
         return Err(SimpleError::new(
             SimpleErrorKind::Other,
-            Some("No labels were defined, can't set them"),
+            Some(format!("job {} has exceeded its deadline", job_name)),
         ));
-    };
+    }
 
-    if !kubectl_exec_is_namespace_present(kubernetes_config.as_ref(), namespace, envs.clone()) {
+    if job_result.status.has_failed() {
         return Err(SimpleError::new(
             SimpleErrorKind::Other,
-            Some(format! {"Can't set labels on namespace {} because it doesn't exists", namespace}),
+            Some(format!("job {} has failed, it has exhausted its retries", job_name)),
         ));
     }
 
-    let mut command_args = Vec::new();
-    let mut labels_string = Vec::new();
-    command_args.extend(vec!["label", "namespace", namespace, "--overwrite"]);
-
-    for label in labels.iter() {
-        labels_string.push(format! {"{}={}", label.name, label.value});
+    if job_result.status.succeeded > 0 {
+        return Ok(Some(true));
     }
-    let labels_str = labels_string.iter().map(|x| x.as_ref()).collect::<Vec<&str>>();
-    command_args.extend(labels_str);
-
-    let mut _envs = Vec::with_capacity(envs.len() + 1);
-    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
-    _envs.extend(envs.clone());
-
-    let _ = kubectl_exec_with_output(
-        command_args,
-        _envs,
-        |out| match out {
-            Ok(line) => info!("{}", line),
-            Err(err) => error!("{:?}", err),
-        },
-        |out| match out {
-            Ok(line) => error!("{}", line),
-            Err(err) => error!("{:?}", err),
-        },
-    )?;
 
-    Ok(())
+    Ok(Some(false))
 }
 
-// used for testing the does_contain_terraform_tfstate
-pub fn does_contain_terraform_tfstate<P>(
+pub fn kubectl_exec_is_cronjob_present<P>(
     kubernetes_config: P,
     namespace: &str,
-    envs: &Vec<(&str, &str)>,
+    cronjob_name: &str,
+    envs: Vec<(&str, &str)>,
 ) -> Result<bool, SimpleError>
 where
     P: AsRef<Path>,
 {
-    let mut _envs = Vec::with_capacity(envs.len() + 1);
-    _envs.extend(envs);
-
-    let result = kubectl_exec::<P, KubernetesList<Item>>(
+    let output = exec_with_kubeconfig(
         vec![
             "get",
-            "secrets",
-            "--namespace",
+            "cronjob",
+            cronjob_name,
+            "-n",
             namespace,
-            "-l",
-            "app.kubernetes.io/managed-by=terraform,tfstate=true",
+            "--ignore-not-found",
             "-o",
-            "json",
+            "name",
         ],
         kubernetes_config,
-        _envs,
-    );
+        envs,
+    )?;
+
+    Ok(!output.stdout.trim().is_empty())
+}
+
+pub fn kubectl_exec_is_namespace_present<P>(kubernetes_config: P, namespace: &str, envs: Vec<(&str, &str)>) -> bool
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    match exec(vec!["get", "namespace", namespace], _envs) {
+        Ok(output) if output.success() => true,
+        Ok(output) => {
+            if output.stderr.contains("Error from server (NotFound): namespaces") {
+                info!("{}", output.stderr)
+            } else {
+                error!("{}", output.stderr)
+            }
+
+            false
+        }
+        Err(err) => {
+            error!("{:?}", err);
+            false
+        }
+    }
+}
+
+pub fn kubectl_exec_create_namespace_without_labels(namespace: &str, kube_config: &str, envs: Vec<(&str, &str)>) {
+    let _ = kubectl_exec_create_namespace(kube_config, namespace, None, None, None, envs);
+}
+
+/// create the namespace, or update its labels and annotations if it already exists.
+/// The resource quota (e.g. `limits.cpu: 4`) is applied in the same manifest as the namespace
+pub fn kubectl_exec_create_namespace<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    labels: Option<BTreeMap<String, String>>,
+    annotations: Option<BTreeMap<String, String>>,
+    resource_quota: Option<&BTreeMap<String, String>>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let suffix = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .collect::<String>();
+    let manifest_path = std::env::temp_dir().join(format!("namespace-{}-{}.yaml", namespace, suffix));
+
+    if let Err(err) = std::fs::write(
+        &manifest_path,
+        namespace_manifest(namespace, labels.as_ref(), annotations.as_ref(), resource_quota),
+    ) {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("unable to write the manifest of namespace {}: {:?}", namespace, err)),
+        ));
+    }
+
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let result = kubectl_exec_with_output(
+        vec!["apply", "-f", manifest_path.to_str().unwrap()],
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    );
+
+    let _ = std::fs::remove_file(&manifest_path);
+
+    result
+}
+
+/// a manifest to apply, from a file or inline
+pub enum Manifest<'a> {
+    File(&'a str),
+    Content(&'a str),
+}
+
+/// a line of the `kubectl apply` output, e.g. `configmap/my-config created`
+#[derive(Debug, Eq, PartialEq)]
+pub struct AppliedResource {
+    pub resource: String,
+    pub operation: String,
+}
+
+/// apply raw manifests (ConfigMap, NetworkPolicy...) which are not part of a chart,
+/// the manifest is given to `kubectl apply` on its standard input
+pub fn kubectl_exec_apply<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    manifest: Manifest,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<AppliedResource>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let content = match manifest {
+        Manifest::File(path) => match std::fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) => {
+                return Err(SimpleError::new(
+                    SimpleErrorKind::Other,
+                    Some(format!("unable to read the manifest {}: {:?}", path, err)),
+                ))
+            }
+        },
+        Manifest::Content(content) => content.to_string(),
+    };
+
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let mut output_lines = vec![];
+    let mut error_lines = vec![];
+
+    let result = exec_with_envs_input_and_output(
+        kubectl_binary(),
+        vec!["apply", "-n", namespace, "-f", "-"],
+        _envs,
+        content.as_str(),
+        |out| match out {
+            Ok(line) => {
+                info!("{}", line);
+                output_lines.push(line);
+            }
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => {
+                error!("{}", line);
+                error_lines.push(line);
+            }
+            Err(err) => error!("{:?}", err),
+        },
+    );
+
+    kubectl_apply_result(result, output_lines, error_lines)
+}
+
+fn kubectl_apply_result(
+    result: Result<(), SimpleError>,
+    output_lines: Vec<String>,
+    error_lines: Vec<String>,
+) -> Result<Vec<AppliedResource>, SimpleError> {
+    if let Err(err) = result {
+        let errors = error_lines.join("\n");
+
+        let message = if errors.contains("error validating") || errors.contains(" is invalid") {
+            format!("the manifest is rejected by the Kubernetes API: {}", errors)
+        } else {
+            format!("kubectl apply failed: {}", errors)
+        };
+
+        return Err(SimpleError::new(err.kind, Some(message)));
+    }
+
+    let applied_resources = output_lines
+        .iter()
+        .filter_map(|line| {
+            let mut parts = line.trim().splitn(2, ' ');
+
+            match (parts.next(), parts.next()) {
+                (Some(resource), Some(operation)) if resource.contains('/') => Some(AppliedResource {
+                    resource: resource.to_string(),
+                    operation: operation.trim().to_string(),
+                }),
+                _ => None,
+            }
+        })
+        .collect();
+
+    Ok(applied_resources)
+}
+
+/// render the manifest applied by `kubectl_exec_create_namespace(..)`,
+/// keys and values are quoted since a JSON string is a valid YAML string
+pub fn namespace_manifest(
+    namespace: &str,
+    labels: Option<&BTreeMap<String, String>>,
+    annotations: Option<&BTreeMap<String, String>>,
+    resource_quota: Option<&BTreeMap<String, String>>,
+) -> String {
+    let quote = |value: &str| serde_json::to_string(value).unwrap();
+
+    let mut manifest = format!("apiVersion: v1\nkind: Namespace\nmetadata:\n  name: {}\n", quote(namespace));
+
+    for &(section, entries) in &[("labels", labels), ("annotations", annotations)] {
+        match entries {
+            Some(entries) if !entries.is_empty() => {
+                manifest.push_str(format!("  {}:\n", section).as_str());
+
+                for (key, value) in entries {
+                    manifest.push_str(format!("    {}: {}\n", quote(key), quote(value)).as_str());
+                }
+            }
+            _ => {}
+        }
+    }
+
+    match resource_quota {
+        Some(hard) if !hard.is_empty() => {
+            manifest.push_str(
+                format!(
+                    "---\napiVersion: v1\nkind: ResourceQuota\n\
+                    metadata:\n  name: {}\n  namespace: {}\nspec:\n  hard:\n",
+                    quote(RESOURCE_QUOTA_NAME),
+                    quote(namespace)
+                )
+                .as_str(),
+            );
+
+            for (key, value) in hard {
+                manifest.push_str(format!("    {}: {}\n", quote(key), quote(value)).as_str());
+            }
+        }
+        _ => {}
+    }
+
+    manifest
+}
+
+pub fn kubectl_add_labels_to_namespace<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    labels: Vec<LabelsContent>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    if labels.iter().count() == 0 {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some("No labels were defined, can't set them"),
+        ));
+    };
+
+    if !kubectl_exec_is_namespace_present(kubernetes_config.as_ref(), namespace, envs.clone()) {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format! {"Can't set labels on namespace {} because it doesn't exists", namespace}),
+        ));
+    }
+
+    let mut command_args = Vec::new();
+    let mut labels_string = Vec::new();
+    command_args.extend(vec!["label", "namespace", namespace, "--overwrite"]);
+
+    for label in labels.iter() {
+        labels_string.push(format! {"{}={}", label.name, label.value});
+    }
+    let labels_str = labels_string.iter().map(|x| x.as_ref()).collect::<Vec<&str>>();
+    command_args.extend(labels_str);
+
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs.clone());
+
+    let _ = kubectl_exec_with_output(
+        command_args,
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    Ok(())
+}
+
+// used for testing the does_contain_terraform_tfstate
+pub fn does_contain_terraform_tfstate<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    envs: &Vec<(&str, &str)>,
+) -> Result<bool, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.extend(envs);
+
+    let result = kubectl_exec::<P, KubernetesList<Item>>(
+        vec![
+            "get",
+            "secrets",
+            "--namespace",
+            namespace,
+            "-l",
+            "app.kubernetes.io/managed-by=terraform,tfstate=true",
+            "-o",
+            "json",
+        ],
+        kubernetes_config,
+        _envs,
+    );
 
     match result {
         Ok(out) => {
@@ -624,12 +1076,16 @@ where
     Ok(())
 }
 
-pub fn kubectl_exec_logs<P>(
+/// Restarts a workload of the given kind (deployment, statefulset, job...) in a namespace.
+/// `kubectl rollout restart` does not support jobs and a completed job does not run its pods again,
+/// so a job is deleted instead: it has to be created again (e.g. by its helm release) to run from scratch.
+pub fn kubectl_exec_rollout_restart<P>(
     kubernetes_config: P,
     namespace: &str,
-    selector: &str,
+    kind: &str,
+    name: &str,
     envs: Vec<(&str, &str)>,
-) -> Result<Vec<String>, SimpleError>
+) -> Result<(), SimpleError>
 where
     P: AsRef<Path>,
 {
@@ -637,12 +1093,13 @@ where
     _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
     _envs.extend(envs);
 
-    let mut output_vec: Vec<String> = Vec::with_capacity(50);
+    let args = kubectl_exec_rollout_restart_args(namespace, kind, name);
+
     let _ = kubectl_exec_with_output(
-        vec!["logs", "--tail", "1000", "-n", namespace, "-l", selector],
+        args.iter().map(|x| x.as_str()).collect(),
         _envs,
         |out| match out {
-            Ok(line) => output_vec.push(line),
+            Ok(line) => info!("{}", line),
             Err(err) => error!("{:?}", err),
         },
         |out| match out {
@@ -651,15 +1108,38 @@ where
         },
     )?;
 
-    Ok(output_vec)
+    Ok(())
 }
 
-pub fn kubectl_exec_describe_pod<P>(
+pub fn kubectl_exec_rollout_restart_args(namespace: &str, kind: &str, name: &str) -> Vec<String> {
+    match kind {
+        "job" => vec![
+            "delete".to_string(),
+            format!("job/{}", name),
+            "-n".to_string(),
+            namespace.to_string(),
+            "--ignore-not-found=true".to_string(),
+            "--wait=true".to_string(),
+        ],
+        _ => vec![
+            "rollout".to_string(),
+            "restart".to_string(),
+            format!("{}/{}", kind, name),
+            "-n".to_string(),
+            namespace.to_string(),
+        ],
+    }
+}
+
+/// set the number of replicas of a resource (e.g. `deployment/my-app`), 0 stops all its pods but keeps the resource.
+/// A job can't be scaled, its parallelism is set instead. A cron job is suspended at 0 replicas and resumed otherwise.
+pub fn kubectl_exec_scale<P>(
     kubernetes_config: P,
     namespace: &str,
-    selector: &str,
+    resource: &str,
+    replicas: u16,
     envs: Vec<(&str, &str)>,
-) -> Result<String, SimpleError>
+) -> Result<(), SimpleError>
 where
     P: AsRef<Path>,
 {
@@ -667,12 +1147,13 @@ where
     _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
     _envs.extend(envs);
 
-    let mut output_vec: Vec<String> = Vec::with_capacity(50);
+    let args = kubectl_exec_scale_args(namespace, resource, replicas);
+
     let _ = kubectl_exec_with_output(
-        vec!["describe", "pod", "-n", namespace, "-l", selector],
+        args.iter().map(|x| x.as_str()).collect(),
         _envs,
         |out| match out {
-            Ok(line) => output_vec.push(line),
+            Ok(line) => info!("{}", line),
             Err(err) => error!("{:?}", err),
         },
         |out| match out {
@@ -681,7 +1162,306 @@ where
         },
     )?;
 
-    Ok(output_vec.join("\n"))
+    Ok(())
+}
+
+pub fn kubectl_exec_scale_args(namespace: &str, resource: &str, replicas: u16) -> Vec<String> {
+    if resource.starts_with("job/") {
+        return vec![
+            "patch".to_string(),
+            resource.to_string(),
+            "-n".to_string(),
+            namespace.to_string(),
+            "--type=merge".to_string(),
+            "-p".to_string(),
+            format!("{{\"spec\":{{\"parallelism\":{}}}}}", replicas),
+        ];
+    }
+
+    if resource.starts_with("cronjob/") {
+        return vec![
+            "patch".to_string(),
+            resource.to_string(),
+            "-n".to_string(),
+            namespace.to_string(),
+            "--type=merge".to_string(),
+            "-p".to_string(),
+            format!("{{\"spec\":{{\"suspend\":{}}}}}", replicas == 0),
+        ];
+    }
+
+    vec![
+        "scale".to_string(),
+        resource.to_string(),
+        format!("--replicas={}", replicas),
+        "-n".to_string(),
+        namespace.to_string(),
+    ]
+}
+
+/// create a job from the job template of a cron job, so it runs right away instead of on its schedule
+pub fn kubectl_exec_create_job_from_cronjob<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    cronjob_name: &str,
+    job_name: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let args = kubectl_exec_create_job_from_cronjob_args(namespace, cronjob_name, job_name);
+
+    let _ = kubectl_exec_with_output(
+        args.iter().map(|x| x.as_str()).collect(),
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn kubectl_exec_create_job_from_cronjob_args(namespace: &str, cronjob_name: &str, job_name: &str) -> Vec<String> {
+    vec![
+        "create".to_string(),
+        "job".to_string(),
+        job_name.to_string(),
+        format!("--from=cronjob/{}", cronjob_name),
+        "-n".to_string(),
+        namespace.to_string(),
+    ]
+}
+
+/// run a command in a pod, e.g. `job/my-job` for the first pod of a job, the command is killed once the timeout
+/// is reached. The exit code of the command is in the output, it is not an error
+pub fn kubectl_exec_run_in_pod<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    pod: &str,
+    command: &[String],
+    timeout: &Timeout<u32>,
+    envs: Vec<(&str, &str)>,
+) -> Result<KubectlOutput, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let args = kubectl_exec_run_in_pod_args(namespace, pod, command);
+
+    exec_binary(
+        kubectl_binary().as_str(),
+        args.iter().map(|x| x.as_str()).collect(),
+        _envs,
+        Duration::seconds(timeout.in_seconds() as i64),
+    )
+}
+
+pub fn kubectl_exec_run_in_pod_args(namespace: &str, pod: &str, command: &[String]) -> Vec<String> {
+    let mut args = vec![
+        "exec".to_string(),
+        "-n".to_string(),
+        namespace.to_string(),
+        pod.to_string(),
+        "--".to_string(),
+    ];
+
+    args.extend(command.iter().cloned());
+    args
+}
+
+/// wait until the rollout of a resource (e.g. `deployment/my-app`) is complete, or fail once the timeout is reached
+pub fn kubectl_exec_wait_for_rollout<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    resource: &str,
+    timeout: Timeout<u32>,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    let args = kubectl_exec_wait_for_rollout_args(namespace, resource, &timeout);
+
+    let _ = kubectl_exec_with_output(
+        args.iter().map(|x| x.as_str()).collect(),
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn kubectl_exec_wait_for_rollout_args(namespace: &str, resource: &str, timeout: &Timeout<u32>) -> Vec<String> {
+    vec![
+        "rollout".to_string(),
+        "status".to_string(),
+        resource.to_string(),
+        "-n".to_string(),
+        namespace.to_string(),
+        "--timeout".to_string(),
+        format!("{}s", timeout.in_seconds()),
+    ]
+}
+
+/// create (or replace) the docker-registry secret used to pull images from a private registry
+pub fn kubectl_exec_create_docker_registry_secret<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    secret_name: &str,
+    server: &str,
+    login: &str,
+    password: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let mut _envs = Vec::with_capacity(envs.len() + 1);
+    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
+    _envs.extend(envs);
+
+    // credentials may have been rotated, so the secret is always recreated
+    let _ = kubectl_exec_with_output(
+        vec!["delete", "secret", secret_name, "-n", namespace, "--ignore-not-found"],
+        _envs.clone(),
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    let args = kubectl_exec_create_docker_registry_secret_args(namespace, secret_name, server, login, password);
+
+    let _ = kubectl_exec_with_output(
+        args.iter().map(|x| x.as_str()).collect(),
+        _envs,
+        |out| match out {
+            Ok(line) => info!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => error!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    )?;
+
+    Ok(())
+}
+
+pub fn kubectl_exec_create_docker_registry_secret_args(
+    namespace: &str,
+    secret_name: &str,
+    server: &str,
+    login: &str,
+    password: &str,
+) -> Vec<String> {
+    vec![
+        "create".to_string(),
+        "secret".to_string(),
+        "docker-registry".to_string(),
+        secret_name.to_string(),
+        "-n".to_string(),
+        namespace.to_string(),
+        format!("--docker-server={}", server),
+        format!("--docker-username={}", login),
+        format!("--docker-password={}", password),
+    ]
+}
+
+pub fn kubectl_exec_logs<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    selector: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<String>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let output = exec_with_kubeconfig(
+        vec!["logs", "--tail", "1000", "-n", namespace, "-l", selector],
+        kubernetes_config,
+        envs,
+    )?;
+
+    Ok(output.stdout_lines())
+}
+
+pub fn kubectl_exec_get_job_pod_logs<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    job_name: &str,
+    tail_lines: u32,
+    envs: Vec<(&str, &str)>,
+) -> Result<Vec<String>, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let args = kubectl_exec_get_job_pod_logs_args(namespace, job_name, tail_lines);
+    let output = exec_with_kubeconfig(args.iter().map(|x| x.as_str()).collect(), kubernetes_config, envs)?;
+
+    Ok(output.stdout_lines())
+}
+
+/// pods created by a job are labeled with the name of the job
+pub fn kubectl_exec_get_job_pod_logs_args(namespace: &str, job_name: &str, tail_lines: u32) -> Vec<String> {
+    vec![
+        "logs".to_string(),
+        "--tail".to_string(),
+        tail_lines.to_string(),
+        "-n".to_string(),
+        namespace.to_string(),
+        "-l".to_string(),
+        format!("job-name={}", job_name),
+        // the init containers logs tell why the job container did not start
+        "--all-containers".to_string(),
+    ]
+}
+
+pub fn kubectl_exec_describe_pod<P>(
+    kubernetes_config: P,
+    namespace: &str,
+    selector: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<String, SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let output = exec_with_kubeconfig(
+        vec!["describe", "pod", "-n", namespace, "-l", selector],
+        kubernetes_config,
+        envs,
+    )?;
+
+    Ok(output.stdout)
 }
 
 pub fn kubectl_exec_get_node<P>(
@@ -774,39 +1554,502 @@ where
     }
 }
 
-fn kubectl_exec<P, T>(args: Vec<&str>, kubernetes_config: P, envs: Vec<(&str, &str)>) -> Result<T, SimpleError>
+/// the resource deserialized from `kubectl get <kind> <name> -o json`, a missing resource is an error
+pub fn kubectl_exec_get<P, T>(
+    kubernetes_config: P,
+    namespace: &str,
+    resource_kind: &str,
+    name: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<T, SimpleError>
 where
     P: AsRef<Path>,
     T: DeserializeOwned,
 {
-    let mut _envs = Vec::with_capacity(envs.len() + 1);
-    _envs.push((KUBECONFIG, kubernetes_config.as_ref().to_str().unwrap()));
-    _envs.extend(envs);
+    match kubectl_exec_get_if_exists(kubernetes_config, namespace, resource_kind, name, envs)? {
+        Some(resource) => Ok(resource),
+        None => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("{} {} not found in the namespace {}", resource_kind, name, namespace)),
+        )),
+    }
+}
 
-    let mut output_vec: Vec<String> = Vec::with_capacity(50);
-    let _ = kubectl_exec_with_output(
-        args,
-        _envs,
-        |out| match out {
-            Ok(line) => output_vec.push(line),
-            Err(err) => error!("{:?}", err),
-        },
-        |out| match out {
-            Ok(line) => error!("{}", line),
-            Err(err) => error!("{:?}", err),
-        },
+/// like `kubectl_exec_get`, none if the resource does not exist
+pub fn kubectl_exec_get_if_exists<P, T>(
+    kubernetes_config: P,
+    namespace: &str,
+    resource_kind: &str,
+    name: &str,
+    envs: Vec<(&str, &str)>,
+) -> Result<Option<T>, SimpleError>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    let output = exec_with_kubeconfig(
+        kubectl_exec_get_args(namespace, resource_kind, name),
+        kubernetes_config,
+        envs,
     )?;
 
-    let output_string: String = output_vec.join("");
+    get_output_json(&output)
+}
 
-    let result = match serde_json::from_str::<T>(output_string.as_str()) {
-        Ok(x) => x,
-        Err(err) => {
-            error!("{:?}", err);
-            error!("{}", output_string.as_str());
-            return Err(SimpleError::new(SimpleErrorKind::Other, Some(output_string)));
-        }
+fn kubectl_exec_get_args<'a>(namespace: &'a str, resource_kind: &'a str, name: &'a str) -> Vec<&'a str> {
+    vec!["get", resource_kind, name, "-n", namespace, "--ignore-not-found", "-o", "json"]
+}
+
+/// kubectl prints nothing for a missing resource with `--ignore-not-found`
+fn get_output_json<T>(output: &KubectlOutput) -> Result<Option<T>, SimpleError>
+where
+    T: DeserializeOwned,
+{
+    match output.stdout.trim().is_empty() {
+        true => Ok(None),
+        false => output.json::<T>().map(Some),
+    }
+}
+
+fn kubectl_exec<P, T>(args: Vec<&str>, kubernetes_config: P, envs: Vec<(&str, &str)>) -> Result<T, SimpleError>
+where
+    P: AsRef<Path>,
+    T: DeserializeOwned,
+{
+    exec_with_kubeconfig(args, kubernetes_config, envs)?.json::<T>()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+    use std::os::unix::process::ExitStatusExt;
+    use std::process::ExitStatus;
+
+    use crate::cmd::helm::Timeout;
+    use crate::cmd::kubectl::{
+        exec_binary, get_output_json, is_ready_with_policy, job_ready_delays, kubectl_apply_result,
+        kubectl_exec_create_docker_registry_secret_args, kubectl_exec_create_job_from_cronjob_args,
+        kubectl_exec_get_args, kubectl_exec_get_job_pod_logs_args, kubectl_exec_rollout_restart_args,
+        kubectl_exec_run_in_pod_args, kubectl_exec_scale_args, kubectl_exec_wait_for_rollout_args, namespace_manifest,
+        wait_for_pods_deletion, AppliedResource, KubectlOutput, RetryPolicy, RESOURCE_QUOTA_NAME,
     };
+    use crate::error::{SimpleError, SimpleErrorKind};
+    use crate::models::CancellationToken;
+    use chrono::Duration;
+    use serde::Deserialize;
+
+    #[test]
+    fn test_retry_policy_stops_after_max_attempts() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 3,
+            interval: std::time::Duration::from_millis(0),
+            ..RetryPolicy::default()
+        };
+
+        let mut checks = 0;
+        let result = is_ready_with_policy(&retry_policy, "job test", || {
+            checks += 1;
+            Ok(Some(false))
+        });
+
+        assert_eq!(result.unwrap(), Some(false));
+        assert_eq!(checks, 3);
+
+        let mut checks = 0;
+        let result = is_ready_with_policy(&retry_policy, "job test", || {
+            checks += 1;
+            Ok(Some(checks == 2))
+        });
+
+        assert_eq!(result.unwrap(), Some(true));
+        assert_eq!(checks, 2);
+    }
+
+    #[test]
+    fn test_cancelled_retry_policy_stops_polling() {
+        let cancellation_token = CancellationToken::default();
+        let retry_policy = RetryPolicy {
+            max_attempts: 100,
+            interval: std::time::Duration::from_secs(1),
+            ..RetryPolicy::default()
+        }
+        .with_cancellation_token(cancellation_token.clone());
+
+        let canceller = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(200));
+            cancellation_token.cancel();
+        });
+
+        let start = std::time::Instant::now();
+        let mut checks = 0;
+        let result = is_ready_with_policy(&retry_policy, "job test", || {
+            checks += 1;
+            Ok(Some(false))
+        });
+        canceller.join().unwrap();
+
+        assert!(start.elapsed() < std::time::Duration::from_secs(2));
+        match result.unwrap_err().kind {
+            SimpleErrorKind::Cancelled => {}
+            _ => panic!("expected a cancelled error"),
+        }
+        assert_eq!(checks, 1);
+    }
+
+    #[test]
+    fn test_wait_for_pods_deletion() {
+        let retry_policy = RetryPolicy {
+            max_attempts: 5,
+            interval: std::time::Duration::from_millis(0),
+            ..RetryPolicy::default()
+        };
+
+        let mut remaining_pods = vec![3, 2, 0].into_iter();
+        let mut checks = 0;
+        let result = wait_for_pods_deletion(&retry_policy, "app=my-app", || {
+            checks += 1;
+            Ok(remaining_pods.next().unwrap())
+        });
+
+        assert!(result.is_ok());
+        assert_eq!(checks, 3);
+
+        let mut checks = 0;
+        let result = wait_for_pods_deletion(&retry_policy, "app=my-app", || {
+            checks += 1;
+            Ok(1)
+        });
+
+        assert_eq!(
+            result.unwrap_err().message,
+            Some("1 pod(s) matching app=my-app are still terminating".to_string())
+        );
+        assert_eq!(checks, 5);
+    }
+
+    #[test]
+    fn test_job_ready_delays() {
+        let delays = job_ready_delays(&Timeout::Default)
+            .into_iter()
+            .map(|delay| delay.as_secs())
+            .collect::<Vec<_>>();
+        assert_eq!(delays, vec![3, 3, 6, 9, 15, 24, 39, 63, 102, 165]);
+
+        let total = job_ready_delays(&Timeout::Value(600))
+            .into_iter()
+            .sum::<std::time::Duration>();
+        assert!(total <= std::time::Duration::from_secs(600));
+    }
 
-    Ok(result)
+    #[test]
+    fn test_retry_policy_from_timeout() {
+        let retry_policy = RetryPolicy::from_timeout(Timeout::Value(600));
+        assert_eq!(
+            retry_policy.interval * retry_policy.max_attempts,
+            std::time::Duration::from_secs(600)
+        );
+    }
+
+    #[test]
+    fn test_rollout_restart_args() {
+        assert_eq!(
+            kubectl_exec_rollout_restart_args("my-namespace", "deployment", "my-release"),
+            vec!["rollout", "restart", "deployment/my-release", "-n", "my-namespace"]
+        );
+
+        assert_eq!(
+            kubectl_exec_rollout_restart_args("my-namespace", "job", "my-release"),
+            vec![
+                "delete",
+                "job/my-release",
+                "-n",
+                "my-namespace",
+                "--ignore-not-found=true",
+                "--wait=true"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scale_args() {
+        assert_eq!(
+            kubectl_exec_scale_args("my-namespace", "deployment/my-app", 0),
+            vec!["scale", "deployment/my-app", "--replicas=0", "-n", "my-namespace"]
+        );
+
+        assert_eq!(
+            kubectl_exec_scale_args("my-namespace", "job/my-job", 0),
+            vec![
+                "patch",
+                "job/my-job",
+                "-n",
+                "my-namespace",
+                "--type=merge",
+                "-p",
+                r#"{"spec":{"parallelism":0}}"#
+            ]
+        );
+
+        assert_eq!(
+            kubectl_exec_scale_args("my-namespace", "cronjob/my-cron", 0),
+            vec![
+                "patch",
+                "cronjob/my-cron",
+                "-n",
+                "my-namespace",
+                "--type=merge",
+                "-p",
+                r#"{"spec":{"suspend":true}}"#
+            ]
+        );
+        assert_eq!(
+            kubectl_exec_scale_args("my-namespace", "cronjob/my-cron", 1)[6],
+            r#"{"spec":{"suspend":false}}"#
+        );
+    }
+
+    #[test]
+    fn test_create_job_from_cronjob_args() {
+        assert_eq!(
+            kubectl_exec_create_job_from_cronjob_args("my-namespace", "my-cron", "my-cron-check"),
+            vec![
+                "create",
+                "job",
+                "my-cron-check",
+                "--from=cronjob/my-cron",
+                "-n",
+                "my-namespace"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_run_in_pod_args() {
+        let command = vec!["curl".to_string(), "-f".to_string(), "http://localhost:8080/health".to_string()];
+        assert_eq!(
+            kubectl_exec_run_in_pod_args("my-namespace", "job/my-job", &command),
+            vec!["exec", "-n", "my-namespace", "job/my-job", "--", "curl", "-f", "http://localhost:8080/health"]
+        );
+    }
+
+    #[test]
+    fn test_wait_for_rollout_args() {
+        assert_eq!(
+            kubectl_exec_wait_for_rollout_args("my-namespace", "deployment/my-app", &Timeout::Value(600)),
+            vec![
+                "rollout",
+                "status",
+                "deployment/my-app",
+                "-n",
+                "my-namespace",
+                "--timeout",
+                "600s"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_job_pod_logs_args() {
+        let args = kubectl_exec_get_job_pod_logs_args("my-namespace", "my-job", 100);
+
+        assert_eq!(
+            args,
+            vec![
+                "logs",
+                "--tail",
+                "100",
+                "-n",
+                "my-namespace",
+                "-l",
+                "job-name=my-job",
+                "--all-containers"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_create_docker_registry_secret_args() {
+        let args = kubectl_exec_create_docker_registry_secret_args(
+            "my-namespace",
+            "ext-service-my-job-registry",
+            "registry.example.com",
+            "login",
+            "password",
+        );
+
+        assert_eq!(
+            args,
+            vec![
+                "create",
+                "secret",
+                "docker-registry",
+                "ext-service-my-job-registry",
+                "-n",
+                "my-namespace",
+                "--docker-server=registry.example.com",
+                "--docker-username=login",
+                "--docker-password=password",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_result() {
+        assert_eq!(
+            kubectl_apply_result(Ok(()), vec!["configmap/my-config created".to_string()], vec![]).unwrap(),
+            vec![AppliedResource {
+                resource: "configmap/my-config".to_string(),
+                operation: "created".to_string(),
+            }]
+        );
+
+        let stderr = concat!(
+            "error: error validating \"STDIN\": error validating data: ValidationError(ConfigMap.data): ",
+            "invalid type for io.k8s.api.core.v1.ConfigMap.data: got \"string\", expected \"map\""
+        );
+        let err = kubectl_apply_result(
+            Err(SimpleError::new(SimpleErrorKind::Command(ExitStatus::from_raw(256)), None::<&str>)),
+            vec![],
+            vec![stderr.to_string()],
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            err.message.unwrap(),
+            format!("the manifest is rejected by the Kubernetes API: {}", stderr)
+        );
+    }
+
+    #[test]
+    fn test_namespace_manifest() {
+        assert_eq!(
+            namespace_manifest("my-namespace", None, None, None),
+            "apiVersion: v1\nkind: Namespace\nmetadata:\n  name: \"my-namespace\"\n"
+        );
+
+        let mut labels = BTreeMap::new();
+        labels.insert("ttl".to_string(), "3600".to_string());
+        labels.insert("team".to_string(), "core".to_string());
+
+        let mut annotations = BTreeMap::new();
+        annotations.insert("cost-center".to_string(), "engineering".to_string());
+
+        let manifest = namespace_manifest("my-namespace", Some(&labels), Some(&annotations), None);
+        assert!(manifest.contains("  labels:\n    \"team\": \"core\"\n    \"ttl\": \"3600\"\n"));
+        assert!(manifest.contains("  annotations:\n    \"cost-center\": \"engineering\"\n"));
+
+        // an empty map is the same as no map at all
+        assert_eq!(
+            namespace_manifest("my-namespace", Some(&BTreeMap::new()), None, None),
+            namespace_manifest("my-namespace", None, None, None)
+        );
+    }
+
+    #[test]
+    fn test_namespace_manifest_with_resource_quota() {
+        let mut hard = BTreeMap::new();
+        hard.insert("limits.cpu".to_string(), "4".to_string());
+        hard.insert("pods".to_string(), "20".to_string());
+
+        let manifest = namespace_manifest("my-namespace", None, None, Some(&hard));
+        let documents = manifest
+            .split("---\n")
+            .map(|document| serde_yaml::from_str::<serde_yaml::Value>(document).unwrap())
+            .collect::<Vec<_>>();
+
+        assert_eq!(documents.len(), 2);
+        assert_eq!(documents[0]["kind"].as_str(), Some("Namespace"));
+        assert_eq!(documents[1]["kind"].as_str(), Some("ResourceQuota"));
+        assert_eq!(documents[1]["metadata"]["name"].as_str(), Some(RESOURCE_QUOTA_NAME));
+        assert_eq!(documents[1]["metadata"]["namespace"].as_str(), Some("my-namespace"));
+        assert_eq!(documents[1]["spec"]["hard"]["limits.cpu"].as_str(), Some("4"));
+        assert_eq!(documents[1]["spec"]["hard"]["pods"].as_str(), Some("20"));
+
+        // an empty quota sets no limit at all
+        assert_eq!(
+            namespace_manifest("my-namespace", None, None, Some(&BTreeMap::new())),
+            namespace_manifest("my-namespace", None, None, None)
+        );
+    }
+
+    #[test]
+    fn test_failed_command_output() {
+        let output = exec_binary(
+            "sh",
+            vec!["-c", "echo partial; echo 'Error from server (NotFound): pods \"app\" not found' >&2; exit 3"],
+            vec![],
+            Duration::max_value(),
+        )
+        .unwrap();
+
+        assert!(!output.success());
+        assert_eq!(output.exit_code, 3);
+        assert_eq!(output.stdout, "partial");
+        assert_eq!(output.stderr, "Error from server (NotFound): pods \"app\" not found");
+
+        let err = output.into_result(&["get", "pod", "app"]).unwrap_err();
+        assert_eq!(
+            err.message.unwrap(),
+            "kubectl get pod app exited with code 3: Error from server (NotFound): pods \"app\" not found"
+        );
+
+        let output = exec_binary("sh", vec!["-c", "echo '{\"items\": []}'"], vec![], Duration::max_value()).unwrap();
+        assert!(output.success());
+        let json = output.json::<serde_json::Value>().unwrap();
+        assert_eq!(json["items"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn test_get_resource_json() {
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Job {
+            status: JobStatus,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct JobStatus {
+            #[serde(default)]
+            succeeded: u32,
+            #[serde(default)]
+            failed: u32,
+            completion_time: Option<String>,
+        }
+
+        assert_eq!(
+            kubectl_exec_get_args("namespace", "job", "my-job"),
+            vec!["get", "job", "my-job", "-n", "namespace", "--ignore-not-found", "-o", "json"]
+        );
+
+        let output = KubectlOutput {
+            stdout: r#"{
+                "apiVersion": "batch/v1",
+                "kind": "Job",
+                "metadata": {"name": "my-job", "namespace": "namespace"},
+                "status": {
+                    "completionTime": "2021-03-02T10:12:40Z",
+                    "conditions": [{"status": "True", "type": "Complete"}],
+                    "startTime": "2021-03-02T10:12:31Z",
+                    "succeeded": 1
+                }
+            }"#
+            .to_string(),
+            stderr: "".to_string(),
+            exit_code: 0,
+        };
+
+        let job = get_output_json::<Job>(&output).unwrap().unwrap();
+        assert_eq!(job.status.succeeded, 1);
+        assert_eq!(job.status.failed, 0);
+        assert_eq!(job.status.completion_time.as_deref(), Some("2021-03-02T10:12:40Z"));
+
+        // nothing is printed for a missing resource
+        let output = KubectlOutput {
+            stdout: "".to_string(),
+            stderr: "".to_string(),
+            exit_code: 0,
+        };
+        assert!(get_output_json::<Job>(&output).unwrap().is_none());
+    }
 }
@@ -1,5 +1,8 @@
 pub mod helm;
 pub mod kubectl;
+pub mod preflight;
+pub mod retry;
 pub mod structs;
 pub mod terraform;
+pub mod tools;
 pub mod utilities;
@@ -0,0 +1,197 @@
+use std::os::unix::fs::PermissionsExt;
+use std::path::PathBuf;
+
+use crate::cloud_provider::utilities::VersionsNumber;
+use crate::cmd::utilities::exec_with_output;
+use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
+use crate::models::ToolConfig;
+
+/// a binary the engine runs and the minimum version it must satisfy
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct BinaryRequirement {
+    pub binary: String,
+    /// arguments printing the version of the binary
+    pub version_args: Vec<String>,
+    pub min_version: VersionsNumber,
+}
+
+impl BinaryRequirement {
+    pub fn new(binary: &str, version_args: Vec<&str>, min_version: VersionsNumber) -> Self {
+        BinaryRequirement {
+            binary: binary.to_string(),
+            version_args: version_args.into_iter().map(|arg| arg.to_string()).collect(),
+            min_version,
+        }
+    }
+}
+
+/// binaries required whatever the cloud provider, each cloud provider adds its own CLI
+pub fn default_requirements(tool_config: &ToolConfig) -> Vec<BinaryRequirement> {
+    vec![
+        BinaryRequirement::new(
+            tool_config.helm_binary(),
+            vec!["version", "--short"],
+            VersionsNumber::new(3, 0, 0),
+        ),
+        BinaryRequirement::new(
+            tool_config.kubectl_binary(),
+            vec!["version", "--client", "--short"],
+            VersionsNumber::new(1, 16, 0),
+        ),
+        BinaryRequirement::new("terraform", vec!["version"], VersionsNumber::new(0, 13, 0)),
+    ]
+}
+
+/// check the required binaries are in the PATH and recent enough
+pub fn check_binaries(requirements: &[BinaryRequirement], execution_id: &str) -> Result<(), EngineError> {
+    let path = std::env::var("PATH").unwrap_or_default();
+    check_binaries_in_path(requirements, path.as_str(), execution_id)
+}
+
+fn check_binaries_in_path(
+    requirements: &[BinaryRequirement],
+    path: &str,
+    execution_id: &str,
+) -> Result<(), EngineError> {
+    for requirement in requirements {
+        let message = match find_binary(requirement.binary.as_str(), path) {
+            None => Some(format!(
+                "{} binary not found in the PATH, version {} or later is required",
+                requirement.binary, requirement.min_version
+            )),
+            Some(binary_path) => {
+                let mut output = String::new();
+
+                let _ = exec_with_output(
+                    binary_path.as_path(),
+                    requirement.version_args.iter().map(|arg| arg.as_str()).collect(),
+                    |out| {
+                        if let Ok(line) = out {
+                            output.push_str(line.as_str());
+                            output.push('\n');
+                        }
+                    },
+                    |_| {},
+                );
+
+                match VersionsNumber::find_in(output.as_str()) {
+                    Some(version) if version >= requirement.min_version => None,
+                    Some(version) => Some(format!(
+                        "{} version {} is installed, version {} or later is required",
+                        requirement.binary, version, requirement.min_version
+                    )),
+                    None => Some(format!(
+                        "unable to get the version of {}, version {} or later is required",
+                        requirement.binary, requirement.min_version
+                    )),
+                }
+            }
+        };
+
+        if let Some(message) = message {
+            return Err(EngineError::new(
+                EngineErrorCause::Internal,
+                EngineErrorScope::Engine,
+                execution_id,
+                Some(message),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+fn find_binary(binary: &str, path: &str) -> Option<PathBuf> {
+    std::env::split_paths(path)
+        .map(|dir| dir.join(binary))
+        .find(|binary_path| match binary_path.metadata() {
+            Ok(metadata) => metadata.is_file() && metadata.permissions().mode() & 0o111 != 0,
+            Err(_) => false,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::os::unix::fs::PermissionsExt;
+
+    use crate::cloud_provider::utilities::VersionsNumber;
+    use crate::cmd::preflight::{check_binaries_in_path, default_requirements, BinaryRequirement};
+    use crate::models::ToolConfig;
+
+    /// a directory containing fake binaries printing their version
+    fn fake_path(name: &str, binaries: Vec<(&str, &str)>) -> String {
+        let dir = format!("{}/qovery-engine-{}", std::env::temp_dir().to_str().unwrap(), name);
+        let _ = std::fs::remove_dir_all(dir.as_str());
+        std::fs::create_dir_all(dir.as_str()).unwrap();
+
+        for (binary, version_output) in binaries {
+            let binary_path = format!("{}/{}", dir, binary);
+            std::fs::write(binary_path.as_str(), format!("#!/bin/sh\necho \"{}\"\n", version_output)).unwrap();
+            std::fs::set_permissions(binary_path.as_str(), std::fs::Permissions::from_mode(0o755)).unwrap();
+        }
+
+        dir
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(
+            VersionsNumber::find_in("v3.4.2+g23dd3af"),
+            Some(VersionsNumber::new(3, 4, 2))
+        );
+        assert_eq!(
+            VersionsNumber::find_in("Client Version: v1.19.4"),
+            Some(VersionsNumber::new(1, 19, 4))
+        );
+        assert_eq!(
+            VersionsNumber::find_in("aws-cli/2.1.6 Python/3.7.3 Linux/5.4.0 exe/x86_64.ubuntu.20"),
+            Some(VersionsNumber::new(2, 1, 6))
+        );
+        assert_eq!(
+            VersionsNumber::find_in("Terraform v0.13"),
+            Some(VersionsNumber::new(0, 13, 0))
+        );
+        assert_eq!(VersionsNumber::find_in("no version here"), None);
+    }
+
+    #[test]
+    fn test_missing_binary() {
+        let path = fake_path(
+            "preflight-missing",
+            vec![
+                ("kubectl", "Client Version: v1.19.4"),
+                ("terraform", "Terraform v0.13.5"),
+            ],
+        );
+
+        let requirements = default_requirements(&ToolConfig::default());
+        let err = check_binaries_in_path(&requirements, path.as_str(), "execution_id").unwrap_err();
+        assert_eq!(
+            err.message.unwrap(),
+            "helm binary not found in the PATH, version 3.0.0 or later is required"
+        );
+    }
+
+    #[test]
+    fn test_outdated_binary() {
+        let path = fake_path(
+            "preflight-outdated",
+            vec![("helm", "v2.16.1+gbbdfe5e"), ("kubectl", "Client Version: v1.19.4")],
+        );
+
+        let requirements = default_requirements(&ToolConfig::default());
+        let err = check_binaries_in_path(&requirements[..2], path.as_str(), "execution_id").unwrap_err();
+        assert_eq!(
+            err.message.unwrap(),
+            "helm version 2.16.1 is installed, version 3.0.0 or later is required"
+        );
+
+        let path = fake_path("preflight-satisfied", vec![("gcloud", "Google Cloud SDK 320.0.0")]);
+        let requirements = vec![BinaryRequirement::new(
+            "gcloud",
+            vec!["version"],
+            VersionsNumber::new(300, 0, 0),
+        )];
+        assert!(check_binaries_in_path(&requirements, path.as_str(), "execution_id").is_ok());
+    }
+}
@@ -0,0 +1,215 @@
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// exponential backoff between retries: the n-th delay is `base * 2^n` capped to `max`, then moved by a random jitter
+/// of up to `jitter` (a ratio of the delay) so parallel retries do not all happen at the same time.
+/// delays stop once their sum reaches `total`, the last one is shortened to fit
+#[derive(Clone, Debug)]
+pub struct ExponentialBackoff {
+    pub base: Duration,
+    pub max: Duration,
+    pub jitter: f64,
+    pub total: Duration,
+}
+
+impl ExponentialBackoff {
+    pub fn new(base: Duration, max: Duration, jitter: f64, total: Duration) -> Self {
+        ExponentialBackoff {
+            base,
+            max,
+            jitter: jitter.clamp(0.0, 1.0),
+            total,
+        }
+    }
+
+    /// the backoff of the cloud provider API calls, throttled or failing calls are retried for up to a minute
+    pub fn cloud_api() -> Self {
+        ExponentialBackoff::new(Duration::from_secs(1), Duration::from_secs(10), 0.2, Duration::from_secs(60))
+    }
+
+    pub fn delays(&self) -> Delays<StdRng> {
+        self.delays_with_rng(StdRng::from_entropy())
+    }
+
+    /// same delays for the same seed, useful to get reproducible delays
+    pub fn delays_with_seed(&self, seed: u64) -> Delays<StdRng> {
+        self.delays_with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    pub fn delays_with_rng<R: Rng>(&self, rng: R) -> Delays<R> {
+        Delays {
+            backoff: self.clone(),
+            rng,
+            attempt: 0,
+            elapsed: Duration::from_secs(0),
+        }
+    }
+}
+
+pub struct Delays<R: Rng> {
+    backoff: ExponentialBackoff,
+    rng: R,
+    attempt: u32,
+    elapsed: Duration,
+}
+
+impl<R: Rng> Iterator for Delays<R> {
+    type Item = Duration;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.elapsed >= self.backoff.total {
+            return None;
+        }
+
+        let delay = match 2u32.checked_pow(self.attempt) {
+            Some(factor) => self.backoff.base.checked_mul(factor).unwrap_or(self.backoff.max),
+            None => self.backoff.max,
+        }
+        .min(self.backoff.max);
+
+        let jitter = match self.backoff.jitter > 0.0 {
+            true => self.rng.gen_range(-self.backoff.jitter, self.backoff.jitter),
+            false => 0.0,
+        };
+
+        let delay =
+            Duration::from_secs_f64(delay.as_secs_f64() * (1.0 + jitter)).min(self.backoff.total - self.elapsed);
+
+        self.attempt += 1;
+        self.elapsed += delay;
+
+        Some(delay)
+    }
+}
+
+/// run `operation` until it succeeds, a failure is retried after the next delay of `policy` as long as `is_transient`
+/// says so. The last error is returned when it is not transient or once `policy.total` has elapsed
+pub fn retry<T, E, P, O>(policy: &ExponentialBackoff, is_transient: P, mut operation: O) -> Result<T, E>
+where
+    P: Fn(&E) -> bool,
+    O: FnMut() -> Result<T, E>,
+{
+    let started_at = Instant::now();
+    let mut delays = policy.delays();
+
+    loop {
+        let err = match operation() {
+            Ok(value) => return Ok(value),
+            Err(err) => err,
+        };
+
+        match delays.next() {
+            Some(delay) if is_transient(&err) && started_at.elapsed() + delay <= policy.total => {
+                std::thread::sleep(delay)
+            }
+            _ => return Err(err),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::time::Duration;
+
+    use crate::cmd::retry::{retry, ExponentialBackoff};
+
+    fn backoff(total: Duration) -> ExponentialBackoff {
+        ExponentialBackoff::new(Duration::from_millis(1), Duration::from_millis(4), 0.0, total)
+    }
+
+    #[test]
+    fn test_retry_transient_errors() {
+        let attempts = Cell::new(0);
+
+        let result = retry(
+            &backoff(Duration::from_secs(1)),
+            |err: &&str| *err == "429",
+            || {
+                attempts.set(attempts.get() + 1);
+                match attempts.get() {
+                    1 | 2 => Err("429"),
+                    _ => Ok("cluster-uuid"),
+                }
+            },
+        );
+
+        assert_eq!(result, Ok("cluster-uuid"));
+        assert_eq!(attempts.get(), 3);
+    }
+
+    #[test]
+    fn test_retry_returns_the_last_error() {
+        let attempts = Cell::new(0);
+
+        // a non transient error is returned at once
+        let result: Result<(), String> = retry(
+            &backoff(Duration::from_secs(1)),
+            |err: &String| err.as_str() == "503",
+            || {
+                attempts.set(attempts.get() + 1);
+                Err("401".to_string())
+            },
+        );
+
+        assert_eq!(result, Err("401".to_string()));
+        assert_eq!(attempts.get(), 1);
+
+        // a transient error is retried until the policy is exhausted
+        attempts.set(0);
+        let result: Result<(), String> = retry(
+            &backoff(Duration::from_millis(20)),
+            |_| true,
+            || {
+                attempts.set(attempts.get() + 1);
+                Err(format!("503 on attempt {}", attempts.get()))
+            },
+        );
+
+        assert!(attempts.get() > 1);
+        assert_eq!(result, Err(format!("503 on attempt {}", attempts.get())));
+    }
+
+    #[test]
+    fn test_exponential_backoff_without_jitter() {
+        let backoff = ExponentialBackoff::new(
+            Duration::from_secs(1),
+            Duration::from_secs(8),
+            0.0,
+            Duration::from_secs(30),
+        );
+
+        assert_eq!(
+            backoff.delays().collect::<Vec<_>>(),
+            vec![1, 2, 4, 8, 8, 7]
+                .into_iter()
+                .map(Duration::from_secs)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn test_exponential_backoff_with_seeded_jitter() {
+        let backoff = ExponentialBackoff::new(
+            Duration::from_secs(1),
+            Duration::from_secs(8),
+            0.5,
+            Duration::from_secs(60),
+        );
+
+        let delays = backoff.delays_with_seed(42).collect::<Vec<_>>();
+        assert_eq!(delays, backoff.delays_with_seed(42).collect::<Vec<_>>());
+
+        // each delay stays around its exponential value, the sum is bounded by the total
+        let expected = [1.0, 2.0, 4.0, 8.0, 8.0, 8.0];
+        for (delay, expected) in delays.iter().zip(expected.iter()) {
+            assert!(delay.as_secs_f64() >= expected * 0.5);
+            assert!(delay.as_secs_f64() <= expected * 1.5);
+        }
+
+        assert!(delays.len() > expected.len());
+        assert_eq!(delays.iter().sum::<Duration>(), Duration::from_secs(60));
+    }
+}
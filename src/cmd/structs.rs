@@ -1,4 +1,5 @@
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "camelCase")]
@@ -157,7 +158,40 @@ pub struct KubernetesJob {
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 #[serde(rename_all = "camelCase")]
 pub struct KubernetesJobStatus {
+    // omitted by kubernetes until a pod has succeeded
+    #[serde(default)]
     pub succeeded: u32,
+    #[serde(default)]
+    pub conditions: Vec<KubernetesJobCondition>,
+}
+
+impl KubernetesJobStatus {
+    /// whether the job won't succeed anymore, its pods having failed more than its `backoffLimit`
+    /// or it having run longer than its `activeDeadlineSeconds`
+    pub fn has_failed(&self) -> bool {
+        self.failed_condition().is_some()
+    }
+
+    /// whether the job has been killed for running longer than its `activeDeadlineSeconds`
+    pub fn has_exceeded_deadline(&self) -> bool {
+        self.failed_condition()
+            .map_or(false, |condition| condition.reason.as_deref() == Some("DeadlineExceeded"))
+    }
+
+    fn failed_condition(&self) -> Option<&KubernetesJobCondition> {
+        self.conditions
+            .iter()
+            .find(|condition| condition.typee == "Failed" && condition.status == "True")
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+#[serde(rename_all = "camelCase")]
+pub struct KubernetesJobCondition {
+    #[serde(rename = "type")]
+    pub typee: String,
+    pub status: String,
+    pub reason: Option<String>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
@@ -204,9 +238,10 @@ pub struct KubernetesKind {
     pub kind: String,
 }
 
+/// a row of `helm list -o json`
 #[derive(Default, Debug, Clone, PartialEq, serde_derive::Serialize, serde_derive::Deserialize)]
 #[serde(rename_all = "camelCase")]
-pub struct Helm {
+pub struct HelmRelease {
     pub name: String,
     pub namespace: String,
     pub revision: String,
@@ -228,20 +263,64 @@ impl HelmList {
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct HelmChartVersion {
+    pub name: String,
+    pub version: String,
+}
+
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub struct HelmHistoryRow {
-    pub revision: u16,
+    #[serde(default)]
+    pub revision: u32,
+    #[serde(default, deserialize_with = "deserialize_helm_date")]
+    pub updated: Option<DateTime<Utc>>,
+    #[serde(default)]
     pub status: String,
+    #[serde(default)]
     pub chart: String,
+    #[serde(default)]
     pub app_version: String,
 }
 
 impl HelmHistoryRow {
+    pub fn revision(&self) -> u32 {
+        self.revision
+    }
+
+    pub fn updated(&self) -> Option<&DateTime<Utc>> {
+        self.updated.as_ref()
+    }
+
     pub fn is_successfully_deployed(&self) -> bool {
-        self.status == "deployed"
+        // helm 2 uses upper case status (DEPLOYED) where helm 3 uses lower case (deployed)
+        self.status.to_lowercase() == "deployed"
     }
 }
 
+/// parse the dates returned by helm
+/// helm 3 uses RFC 3339 dates in json output, helm 2 json and both table outputs use `Mon Sep 21 13:58:50 2020`
+pub fn parse_helm_date(date: &str) -> Option<DateTime<Utc>> {
+    let date = date.trim();
+
+    if let Ok(date) = DateTime::parse_from_rfc3339(date) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    match NaiveDateTime::parse_from_str(date, "%a %b %e %H:%M:%S %Y") {
+        Ok(date) => Some(Utc.from_utc_datetime(&date)),
+        Err(_) => None,
+    }
+}
+
+fn deserialize_helm_date<'de, D>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let date = Option::<String>::deserialize(deserializer)?;
+    Ok(date.and_then(|date| parse_helm_date(date.as_str())))
+}
+
 #[cfg(test)]
 mod tests {
     use crate::cmd::structs::{KubernetesList, KubernetesPod};
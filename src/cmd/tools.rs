@@ -0,0 +1,31 @@
+//! The binaries run by the command wrappers, `Context::with_tool_config(..)` gives their paths to the whole process.
+
+use std::sync::RwLock;
+
+use crate::models::ToolConfig;
+
+lazy_static! {
+    static ref TOOL_CONFIG: RwLock<ToolConfig> = RwLock::new(ToolConfig::default());
+}
+
+pub fn set_tool_config(tool_config: ToolConfig) {
+    if let Ok(mut current) = TOOL_CONFIG.write() {
+        *current = tool_config;
+    }
+}
+
+/// the configured helm binary, `helm` from the PATH by default
+pub fn helm_binary() -> String {
+    match TOOL_CONFIG.read() {
+        Ok(tool_config) => tool_config.helm_binary().to_string(),
+        Err(_) => "helm".to_string(),
+    }
+}
+
+/// the configured kubectl binary, `kubectl` from the PATH by default
+pub fn kubectl_binary() -> String {
+    match TOOL_CONFIG.read() {
+        Ok(tool_config) => tool_config.kubectl_binary().to_string(),
+        Err(_) => "kubectl".to_string(),
+    }
+}
@@ -1,11 +1,15 @@
+use std::cell::RefCell;
 use std::ffi::OsStr;
 use std::io::Error;
-use std::io::{BufRead, BufReader};
+use std::io::{BufRead, BufReader, Write};
 use std::path::Path;
 use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use crate::error::SimpleErrorKind::Other;
 use crate::error::{SimpleError, SimpleErrorKind};
+use crate::models::{CancellationToken, CANCELLATION_CHECK_INTERVAL};
 use chrono::Duration;
 use std::time::Instant;
 
@@ -211,6 +215,205 @@ where
     ))
 }
 
+/// same as `exec_with_envs_and_output`, without timeout, the command is killed as soon as the token is cancelled
+pub fn exec_with_envs_and_output_cancellable<P, F, X>(
+    binary: P,
+    args: Vec<&str>,
+    envs: Vec<(&str, &str)>,
+    mut stdout_output: F,
+    mut stderr_output: X,
+    cancellation_token: &CancellationToken,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+    F: FnMut(Result<String, Error>),
+    X: FnMut(Result<String, Error>),
+{
+    let command_string = command_with_envs_to_string(binary.as_ref(), &args, &envs);
+    info!("command: {}", command_string.as_str());
+
+    if cancellation_token.is_cancelled() {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Cancelled,
+            Some(format!("{} has been cancelled before it starts", command_string)),
+        ));
+    }
+
+    let mut child = match command(binary, args, Some(envs), true).spawn() {
+        Ok(child) => child,
+        Err(err) => return Err(SimpleError::from(err)),
+    };
+
+    // the outputs are read here while another thread watches the token and kills the command on cancellation
+    let stdout = child.stdout.take().unwrap();
+    let stderr = child.stderr.take().unwrap();
+    let child = Arc::new(Mutex::new(child));
+    let is_done = Arc::new(AtomicBool::new(false));
+
+    let watcher = {
+        let child = child.clone();
+        let is_done = is_done.clone();
+        let cancellation_token = cancellation_token.clone();
+
+        std::thread::spawn(move || {
+            while !is_done.load(Ordering::SeqCst) {
+                if cancellation_token.is_cancelled() {
+                    let _ = child.lock().unwrap().kill();
+                    return;
+                }
+
+                std::thread::sleep(CANCELLATION_CHECK_INTERVAL);
+            }
+        })
+    };
+
+    for line in BufReader::new(stdout).lines() {
+        stdout_output(line);
+    }
+
+    for line in BufReader::new(stderr).lines() {
+        stderr_output(line);
+    }
+
+    is_done.store(true, Ordering::SeqCst);
+    let _ = watcher.join();
+
+    let exit_status = match child.lock().unwrap().wait() {
+        Ok(x) => x,
+        Err(err) => return Err(SimpleError::from(err)),
+    };
+
+    if exit_status.success() {
+        return Ok(());
+    }
+
+    if cancellation_token.is_cancelled() {
+        return Err(SimpleError::new(
+            SimpleErrorKind::Cancelled,
+            Some(format!("{} has been cancelled", command_string)),
+        ));
+    }
+
+    Err(SimpleError::new(
+        SimpleErrorKind::Command(exit_status),
+        Some("error while executing an internal command"),
+    ))
+}
+
+/// the output lines of a command, collected for the caller and given to the callback as soon as they are read
+pub struct OutputLines<'a> {
+    pub stdout: Vec<String>,
+    pub stderr: Vec<String>,
+    on_line: Option<&'a mut dyn FnMut(&str)>,
+}
+
+impl<'a> OutputLines<'a> {
+    pub fn new(on_line: Option<&'a mut dyn FnMut(&str)>) -> Self {
+        OutputLines {
+            stdout: vec![],
+            stderr: vec![],
+            on_line,
+        }
+    }
+
+    fn push_stdout(&mut self, line: String) {
+        if let Some(on_line) = self.on_line.as_mut() {
+            on_line(line.as_str());
+        }
+
+        self.stdout.push(line);
+    }
+
+    fn push_stderr(&mut self, line: String) {
+        if let Some(on_line) = self.on_line.as_mut() {
+            on_line(line.as_str());
+        }
+
+        self.stderr.push(line);
+    }
+}
+
+/// same as `exec_with_envs_and_output_cancellable`, the lines are logged (stderr as errors) and go to `output_lines`
+pub fn exec_with_envs_and_output_lines_cancellable<P>(
+    binary: P,
+    args: Vec<&str>,
+    envs: Vec<(&str, &str)>,
+    output_lines: &mut OutputLines,
+    cancellation_token: &CancellationToken,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let output_lines = RefCell::new(output_lines);
+
+    exec_with_envs_and_output_cancellable(
+        binary,
+        args,
+        envs,
+        |out| match out {
+            Ok(line) => {
+                info!("{}", line.as_str());
+                output_lines.borrow_mut().push_stdout(line);
+            }
+            Err(err) => error!("{}", err),
+        },
+        |out| match out {
+            Ok(line) => {
+                error!("{}", line.as_str());
+                output_lines.borrow_mut().push_stderr(line);
+            }
+            Err(err) => error!("{}", err),
+        },
+        cancellation_token,
+    )
+}
+
+/// same as `exec_with_output`, `input` is written to the standard input of the command
+pub fn exec_with_envs_input_and_output<P, F, X>(
+    binary: P,
+    args: Vec<&str>,
+    envs: Vec<(&str, &str)>,
+    input: &str,
+    stdout_output: F,
+    stderr_output: X,
+) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+    F: FnMut(Result<String, Error>),
+    X: FnMut(Result<String, Error>),
+{
+    let command_string = command_with_envs_to_string(binary.as_ref(), &args, &envs);
+    info!("command: {}", command_string.as_str());
+
+    let mut child = match command(binary, args, Some(envs), true).stdin(Stdio::piped()).spawn() {
+        Ok(child) => child,
+        Err(err) => return Err(SimpleError::from(err)),
+    };
+
+    // written from another thread since the command can fill its output before having read all its input,
+    // the standard input is closed once written
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || stdin.write_all(input.as_bytes()));
+
+    let mut child = _with_output(child, stdout_output, stderr_output);
+    let _ = writer.join();
+
+    let exit_status = match child.wait() {
+        Ok(x) => x,
+        Err(err) => return Err(SimpleError::from(err)),
+    };
+
+    if exit_status.success() {
+        return Ok(());
+    }
+
+    Err(SimpleError::new(
+        SimpleErrorKind::Command(exit_status),
+        Some("error while executing an internal command"),
+    ))
+}
+
 // return the output of "binary_name" --version
 pub fn run_version_command_for(binary_name: &str) -> String {
     let mut output_from_cmd = String::new();
@@ -263,3 +466,93 @@ where
         args.join(" ")
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::cmd::utilities::{
+        exec_with_envs_and_output_cancellable, exec_with_envs_and_output_lines_cancellable,
+        exec_with_envs_input_and_output, OutputLines,
+    };
+    use crate::error::SimpleErrorKind;
+    use crate::models::CancellationToken;
+
+    #[test]
+    fn test_exec_with_input() {
+        let mut stdout_lines = vec![];
+        let mut stderr_lines = vec![];
+
+        let result = exec_with_envs_input_and_output(
+            "sh",
+            vec!["-c", "cat; echo \"done\" >&2; exit 3"],
+            vec![],
+            "apiVersion: v1\nkind: ConfigMap\n",
+            |out| stdout_lines.push(out.unwrap()),
+            |out| stderr_lines.push(out.unwrap()),
+        );
+
+        assert!(result.is_err());
+        assert_eq!(stdout_lines, vec!["apiVersion: v1", "kind: ConfigMap"]);
+        assert_eq!(stderr_lines, vec!["done"]);
+    }
+
+    #[test]
+    fn test_cancelled_command_is_killed() {
+        let cancellation_token = CancellationToken::default();
+
+        let canceller = {
+            let cancellation_token = cancellation_token.clone();
+            std::thread::spawn(move || {
+                std::thread::sleep(Duration::from_millis(300));
+                cancellation_token.cancel();
+            })
+        };
+
+        let start = Instant::now();
+        let result = exec_with_envs_and_output_cancellable(
+            "sleep",
+            vec!["30"],
+            vec![],
+            |_| {},
+            |_| {},
+            &cancellation_token,
+        );
+        canceller.join().unwrap();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        match result.unwrap_err().kind {
+            SimpleErrorKind::Cancelled => {}
+            kind => panic!("expected a cancellation, got {:?}", kind),
+        }
+
+        // an already cancelled token does not even start the command
+        let result = exec_with_envs_and_output_cancellable("true", vec![], vec![], |_| {}, |_| {}, &cancellation_token);
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn test_output_lines_are_streamed() {
+        let start = Instant::now();
+        let mut received = vec![];
+        let mut on_line = |line: &str| received.push((line.to_string(), start.elapsed()));
+        let mut output_lines = OutputLines::new(Some(&mut on_line));
+
+        let result = exec_with_envs_and_output_lines_cancellable(
+            "sh",
+            vec!["-c", "echo first; sleep 1; echo second; echo third; echo failed >&2"],
+            vec![],
+            &mut output_lines,
+            &CancellationToken::default(),
+        );
+
+        assert!(result.is_ok());
+        assert_eq!(output_lines.stdout, vec!["first", "second", "third"]);
+        assert_eq!(output_lines.stderr, vec!["failed"]);
+
+        let lines = received.iter().map(|(line, _)| line.as_str()).collect::<Vec<_>>();
+        assert_eq!(lines, vec!["first", "second", "third", "failed"]);
+        // the first line is received before the command has printed the next ones
+        assert!(received[1].1 - received[0].1 >= Duration::from_millis(500));
+    }
+}
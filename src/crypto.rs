@@ -1,5 +1,6 @@
 use crypto::digest::Digest;
 use crypto::sha1::Sha1;
+use crypto::sha2::Sha256;
 
 pub fn to_sha1(input: &str) -> String {
     let mut hasher = Sha1::new();
@@ -7,6 +8,12 @@ pub fn to_sha1(input: &str) -> String {
     hasher.result_str()
 }
 
+pub fn to_sha256(input: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.input_str(input);
+    hasher.result_str()
+}
+
 pub fn to_sha1_truncate_16(input: &str) -> String {
     let mut hash_str = to_sha1(input);
     hash_str.truncate(16);
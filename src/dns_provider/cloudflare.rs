@@ -1,9 +1,18 @@
 use std::net::Ipv4Addr;
 
-use crate::dns_provider::{DnsProvider, Kind};
+use reqwest::blocking::{Client, RequestBuilder};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::dns_provider::{DnsProvider, Kind, Record, RecordOptions};
 use crate::error::{EngineError, EngineErrorCause};
 use crate::models::Context;
 
+const CLOUDFLARE_API_URL: &str = "https://api.cloudflare.com/client/v4";
+
 pub struct Cloudflare {
     context: Context,
     id: String,
@@ -31,6 +40,107 @@ impl Cloudflare {
             cloudflare_email: cloudflare_email.to_string(),
         }
     }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        Client::new()
+            .request(method, format!("{}{}", CLOUDFLARE_API_URL, path).as_str())
+            .header("X-Auth-Email", self.cloudflare_email.as_str())
+            .header("X-Auth-Key", self.cloudflare_api_token.as_str())
+    }
+
+    /// the result of the request, the API answers with the errors of the request when it is not successful
+    fn send<T>(&self, request: RequestBuilder, action: &str) -> Result<Option<T>, EngineError>
+    where
+        T: DeserializeOwned,
+    {
+        let response = match request.send() {
+            Ok(response) => response,
+            Err(err) => {
+                return Err(self.engine_error(
+                    EngineErrorCause::Internal,
+                    format!("No response from the Cloudflare API to {}: {:?}", action, err),
+                ));
+            }
+        };
+
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+
+        match serde_json::from_str::<CloudflareResponse<T>>(body.as_str()) {
+            Ok(response) if response.success => Ok(response.result),
+            Ok(response) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!(
+                    "Unable to {} with the Cloudflare API ({}): {}",
+                    action,
+                    status,
+                    response
+                        .errors
+                        .iter()
+                        .map(|err| format!("{} ({})", err.message, err.code))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            )),
+            Err(err) => Err(self.engine_error(
+                EngineErrorCause::Internal,
+                format!("Unexpected response of the Cloudflare API to {} ({}): {:?}", action, status, err),
+            )),
+        }
+    }
+
+    fn zone_id(&self) -> Result<String, EngineError> {
+        let request = self.request(Method::GET, "/zones").query(&[("name", self.domain.as_str())]);
+        let zones = self.send::<Vec<CloudflareObject>>(request, "get the zone of the domain")?;
+
+        match zones.unwrap_or_default().into_iter().next() {
+            Some(zone) => Ok(zone.id),
+            None => Err(self.engine_error(
+                EngineErrorCause::User("Your domain is not a zone of your Cloudflare account"),
+                format!("no Cloudflare zone for the domain {} of {}", self.domain, self.name_with_id()),
+            )),
+        }
+    }
+
+    /// the id of the record having the name and the type of the given one, if any
+    fn record_id(&self, zone_id: &str, record: &Record) -> Result<Option<String>, EngineError> {
+        let request = self
+            .request(Method::GET, format!("/zones/{}/dns_records", zone_id).as_str())
+            .query(&[("type", record.record_type.name()), ("name", record.name.as_str())]);
+        let records = self.send::<Vec<CloudflareObject>>(request, "get the DNS record")?;
+
+        Ok(records.unwrap_or_default().into_iter().next().map(|record| record.id))
+    }
+}
+
+#[derive(Deserialize)]
+struct CloudflareResponse<T> {
+    success: bool,
+    #[serde(default)]
+    errors: Vec<CloudflareError>,
+    result: Option<T>,
+}
+
+#[derive(Deserialize)]
+struct CloudflareError {
+    code: u32,
+    message: String,
+}
+
+#[derive(Deserialize)]
+struct CloudflareObject {
+    id: String,
+}
+
+/// build the body of the Cloudflare API requests creating or updating a DNS record
+pub fn dns_record_payload(record: &Record, ttl: u32, options: &RecordOptions) -> serde_json::Value {
+    json!({
+        "type": record.record_type.name(),
+        "name": record.name,
+        "content": record.value,
+        "ttl": ttl,
+        "proxied": options.proxied,
+    })
 }
 
 impl DnsProvider for Cloudflare {
@@ -66,6 +176,38 @@ impl DnsProvider for Cloudflare {
         vec![Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(1, 0, 0, 1)]
     }
 
+    /// Cloudflare picks the ttl of the records with a ttl of 1
+    fn default_record_ttl(&self) -> u32 {
+        1
+    }
+
+    fn min_record_ttl(&self) -> u32 {
+        60
+    }
+
+    fn create_record(
+        &self,
+        record: &Record,
+        ttl: Option<u32>,
+        options: &RecordOptions,
+        _envs: Vec<(&str, &str)>,
+    ) -> Result<(), EngineError> {
+        let body = dns_record_payload(record, self.record_ttl(ttl)?, options).to_string();
+        let zone_id = self.zone_id()?;
+
+        let request = match self.record_id(zone_id.as_str(), record)? {
+            Some(record_id) => self.request(
+                Method::PUT,
+                format!("/zones/{}/dns_records/{}", zone_id, record_id).as_str(),
+            ),
+            None => self.request(Method::POST, format!("/zones/{}/dns_records", zone_id).as_str()),
+        };
+
+        let request = request.header(CONTENT_TYPE, "application/json").body(body);
+        self.send::<CloudflareObject>(request, "create the DNS record")?;
+        Ok(())
+    }
+
     fn is_valid(&self) -> Result<(), EngineError> {
         if self.cloudflare_api_token.is_empty() || self.cloudflare_email.is_empty() {
             Err(self.engine_error(
@@ -80,3 +222,58 @@ impl DnsProvider for Cloudflare {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::dns_provider::cloudflare::{dns_record_payload, Cloudflare};
+    use crate::dns_provider::{DnsProvider, Record, RecordOptions, RecordType};
+    use crate::error::EngineErrorCause;
+    use crate::models::Context;
+
+    #[test]
+    fn test_proxied_dns_record_payload() {
+        let record = Record {
+            name: "app.example.com".to_string(),
+            record_type: RecordType::Cname,
+            value: "my-lb.eu-west-3.elb.amazonaws.com".to_string(),
+        };
+
+        assert_eq!(
+            dns_record_payload(&record, 300, &RecordOptions { proxied: true }),
+            json!({
+                "type": "CNAME",
+                "name": "app.example.com",
+                "content": "my-lb.eu-west-3.elb.amazonaws.com",
+                "ttl": 300,
+                "proxied": true
+            })
+        );
+    }
+
+    #[test]
+    fn test_record_ttl() {
+        let context = Context::new(
+            "execution_id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        );
+        let cloudflare = Cloudflare::new(context, "id", "cloudflare", "example.com", "token", "user@example.com");
+
+        // automatic
+        assert_eq!(cloudflare.record_ttl(None).unwrap(), 1);
+        assert_eq!(cloudflare.record_ttl(Some(300)).unwrap(), 300);
+
+        match cloudflare.record_ttl(Some(30)) {
+            Err(err) => match err.cause {
+                EngineErrorCause::User(_) => {}
+                EngineErrorCause::Internal => panic!("expected a user error"),
+            },
+            Ok(_) => panic!("a ttl below 60 seconds is refused by Cloudflare"),
+        }
+    }
+}
@@ -6,8 +6,9 @@ use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::Context;
 
 pub mod cloudflare;
+pub mod route53;
 
-pub trait DnsProvider {
+pub trait DnsProvider: Sync {
     fn context(&self) -> &Context;
     fn kind(&self) -> Kind;
     fn id(&self) -> &str;
@@ -20,6 +21,34 @@ pub trait DnsProvider {
     fn domain(&self) -> &str;
     fn resolvers(&self) -> Vec<Ipv4Addr>;
     fn is_valid(&self) -> Result<(), EngineError>;
+    /// the ttl of the records created without one, in seconds
+    fn default_record_ttl(&self) -> u32;
+    /// the lowest ttl of a record accepted by the provider, in seconds
+    fn min_record_ttl(&self) -> u32;
+    /// the ttl to create a record with, the default one of the provider when none is given
+    fn record_ttl(&self, ttl: Option<u32>) -> Result<u32, EngineError> {
+        match ttl {
+            None => Ok(self.default_record_ttl()),
+            Some(ttl) if ttl < self.min_record_ttl() => Err(self.engine_error(
+                EngineErrorCause::User("The TTL of a DNS record is below the minimum of your DNS provider"),
+                format!(
+                    "the TTL of {}s is below the minimum of {}s of {}",
+                    ttl,
+                    self.min_record_ttl(),
+                    self.name_with_id()
+                ),
+            )),
+            Some(ttl) => Ok(ttl),
+        }
+    }
+    /// create or update the record, the options not supported by the provider are ignored
+    fn create_record(
+        &self,
+        record: &Record,
+        ttl: Option<u32>,
+        options: &RecordOptions,
+        envs: Vec<(&str, &str)>,
+    ) -> Result<(), EngineError>;
     fn engine_error_scope(&self) -> EngineErrorScope {
         EngineErrorScope::DnsProvider(self.id().to_string(), self.name().to_string())
     }
@@ -33,8 +62,39 @@ pub trait DnsProvider {
     }
 }
 
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RecordType {
+    A,
+    Cname,
+}
+
+impl RecordType {
+    pub fn name(&self) -> &str {
+        match self {
+            RecordType::A => "A",
+            RecordType::Cname => "CNAME",
+        }
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub record_type: RecordType,
+    pub value: String,
+}
+
+/// the options of a record only supported by some providers
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct RecordOptions {
+    /// Cloudflare only, the traffic goes through the Cloudflare proxy (the orange cloud) instead of straight to the
+    /// value of the record
+    pub proxied: bool,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Kind {
     Cloudflare,
+    Route53,
 }
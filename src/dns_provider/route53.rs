@@ -0,0 +1,185 @@
+use std::net::Ipv4Addr;
+
+use serde_json::json;
+
+use crate::dns_provider::{DnsProvider, Kind, Record, RecordOptions};
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause};
+use crate::models::Context;
+
+/// Route53 relies on the AWS credentials of the cloud provider, they are given for each record change.
+pub struct Route53 {
+    context: Context,
+    id: String,
+    name: String,
+    domain: String,
+    hosted_zone_id: String,
+}
+
+impl Route53 {
+    pub fn new(context: Context, id: &str, name: &str, domain: &str, hosted_zone_id: &str) -> Self {
+        Route53 {
+            context,
+            id: id.to_string(),
+            name: name.to_string(),
+            domain: domain.to_string(),
+            hosted_zone_id: hosted_zone_id.to_string(),
+        }
+    }
+
+    pub fn hosted_zone_id(&self) -> &str {
+        self.hosted_zone_id.as_str()
+    }
+
+    /// the ttl must be the one the record has been created with
+    pub fn delete_record(&self, record: &Record, ttl: Option<u32>, envs: Vec<(&str, &str)>) -> Result<(), EngineError> {
+        self.change_record("DELETE", record, self.record_ttl(ttl)?, envs)
+    }
+
+    fn change_record(
+        &self,
+        action: &str,
+        record: &Record,
+        ttl: u32,
+        envs: Vec<(&str, &str)>,
+    ) -> Result<(), EngineError> {
+        let change_batch = change_batch_payload(action, record, ttl).to_string();
+
+        cast_simple_error_to_engine_error(
+            self.engine_error_scope(),
+            self.context().execution_id(),
+            crate::cmd::utilities::exec_with_envs(
+                "aws",
+                vec![
+                    "route53",
+                    "change-resource-record-sets",
+                    "--hosted-zone-id",
+                    self.hosted_zone_id.as_str(),
+                    "--change-batch",
+                    change_batch.as_str(),
+                ],
+                envs,
+            ),
+        )
+    }
+}
+
+/// build the `--change-batch` payload expected by `aws route53 change-resource-record-sets`
+pub fn change_batch_payload(action: &str, record: &Record, ttl: u32) -> serde_json::Value {
+    json!({
+        "Changes": [
+            {
+                "Action": action,
+                "ResourceRecordSet": {
+                    "Name": record.name,
+                    "Type": record.record_type.name(),
+                    "TTL": ttl,
+                    "ResourceRecords": [{ "Value": record.value }]
+                }
+            }
+        ]
+    })
+}
+
+impl DnsProvider for Route53 {
+    fn context(&self) -> &Context {
+        &self.context
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Route53
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// the hosted zone id is the closest thing to an account for Route53
+    fn account(&self) -> &str {
+        &self.hosted_zone_id
+    }
+
+    /// there is no dedicated token, the AWS credentials of the cloud provider are used
+    fn token(&self) -> &str {
+        ""
+    }
+
+    fn domain(&self) -> &str {
+        self.domain.as_str()
+    }
+
+    fn resolvers(&self) -> Vec<Ipv4Addr> {
+        // public resolvers, the Amazon provided DNS is only reachable from inside a VPC
+        vec![Ipv4Addr::new(1, 1, 1, 1), Ipv4Addr::new(1, 0, 0, 1)]
+    }
+
+    fn default_record_ttl(&self) -> u32 {
+        300
+    }
+
+    fn min_record_ttl(&self) -> u32 {
+        0
+    }
+
+    /// Route53 has no proxy, the options are ignored
+    fn create_record(
+        &self,
+        record: &Record,
+        ttl: Option<u32>,
+        _options: &RecordOptions,
+        envs: Vec<(&str, &str)>,
+    ) -> Result<(), EngineError> {
+        self.change_record("UPSERT", record, self.record_ttl(ttl)?, envs)
+    }
+
+    fn is_valid(&self) -> Result<(), EngineError> {
+        if self.hosted_zone_id.is_empty() {
+            Err(self.engine_error(
+                EngineErrorCause::User(
+                    "Your Route53 hosted zone id is missing. \
+                    Please contact your Organization administrator to fix it.",
+                ),
+                format!("missing Route53 hosted zone id for {}", self.name_with_id()),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::dns_provider::route53::change_batch_payload;
+    use crate::dns_provider::{Record, RecordType};
+
+    #[test]
+    fn test_cname_change_batch_payload() {
+        let record = Record {
+            name: "app.example.com".to_string(),
+            record_type: RecordType::Cname,
+            value: "my-lb.eu-west-3.elb.amazonaws.com".to_string(),
+        };
+
+        assert_eq!(
+            change_batch_payload("UPSERT", &record, 300),
+            json!({
+                "Changes": [
+                    {
+                        "Action": "UPSERT",
+                        "ResourceRecordSet": {
+                            "Name": "app.example.com",
+                            "Type": "CNAME",
+                            "TTL": 300,
+                            "ResourceRecords": [{ "Value": "my-lb.eu-west-3.elb.amazonaws.com" }]
+                        }
+                    }
+                ]
+            })
+        );
+    }
+}
@@ -1,15 +1,21 @@
 use std::process::ExitStatus;
 
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
 pub type Type = String;
 pub type Id = String;
 pub type Name = String;
 
-#[derive(Debug)]
+/// serialized as `{"cause": "User"|"Internal", "scope": {...}, "execution_id": "...", "message": "..."}`
+#[derive(Clone, Debug, Serialize)]
 pub struct EngineError {
     pub cause: EngineErrorCause,
     pub scope: EngineErrorScope,
     pub execution_id: String,
     pub message: Option<String>,
+    #[serde(skip)]
+    retryable: bool,
 }
 
 impl EngineError {
@@ -18,19 +24,40 @@ impl EngineError {
         T: Into<String>,
         S: Into<String>,
     {
+        let execution_id = execution_id.into();
+        // the messages often echo commands and outputs, they must not leak a secret of the execution
+        let message =
+            message.map(|message| crate::string::redact_execution(execution_id.as_str(), message.into().as_str()));
+
         EngineError {
             cause,
             scope,
-            execution_id: execution_id.into(),
-            message: match message {
-                Some(message) => Some(message.into()),
-                _ => None,
-            },
+            execution_id,
+            message,
+            retryable: false,
         }
     }
+
+    /// mark the error as transient (network, timeout...), retrying the same operation may succeed
+    pub fn retryable(mut self) -> Self {
+        self.retryable = true;
+        self
+    }
+
+    /// a user error is never worth retrying, the configuration must be fixed first
+    pub fn is_retryable(&self) -> bool {
+        match self.cause {
+            EngineErrorCause::Internal => self.retryable,
+            EngineErrorCause::User(_) => false,
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).unwrap()
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum EngineErrorScope {
     Engine,
     BuildPlatform(Id, Name),
@@ -46,12 +73,67 @@ pub enum EngineErrorScope {
     ExternalService(Id, Name),
 }
 
-#[derive(Debug)]
+/// serialized as a map of the kind of scope and its identifiers, e.g. `{"type": "Router", "id": "..", "name": ".."}`
+impl Serialize for EngineErrorScope {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let (scope_type, id, database_type, name) = match self {
+            EngineErrorScope::Engine => ("Engine", None, None, None),
+            EngineErrorScope::BuildPlatform(id, name) => ("BuildPlatform", Some(id), None, Some(name)),
+            EngineErrorScope::ContainerRegistry(id, name) => ("ContainerRegistry", Some(id), None, Some(name)),
+            EngineErrorScope::CloudProvider(id, name) => ("CloudProvider", Some(id), None, Some(name)),
+            EngineErrorScope::Kubernetes(id, name) => ("Kubernetes", Some(id), None, Some(name)),
+            EngineErrorScope::DnsProvider(id, name) => ("DnsProvider", Some(id), None, Some(name)),
+            EngineErrorScope::ObjectStorage(id, name) => ("ObjectStorage", Some(id), None, Some(name)),
+            EngineErrorScope::Environment(id, name) => ("Environment", Some(id), None, Some(name)),
+            EngineErrorScope::Database(id, database_type, name) => {
+                ("Database", Some(id), Some(database_type), Some(name))
+            }
+            EngineErrorScope::Application(id, name) => ("Application", Some(id), None, Some(name)),
+            EngineErrorScope::Router(id, name) => ("Router", Some(id), None, Some(name)),
+            EngineErrorScope::ExternalService(id, name) => ("ExternalService", Some(id), None, Some(name)),
+        };
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("type", scope_type)?;
+
+        if let Some(id) = id {
+            map.serialize_entry("id", id)?;
+        }
+
+        if let Some(database_type) = database_type {
+            map.serialize_entry("database_type", database_type)?;
+        }
+
+        if let Some(name) = name {
+            map.serialize_entry("name", name)?;
+        }
+
+        map.end()
+    }
+}
+
+#[derive(Clone, Debug)]
 pub enum EngineErrorCause {
     Internal,
     User(&'static str),
 }
 
+/// only the kind of cause is serialized, it is the stable part of the error
+impl Serialize for EngineErrorCause {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        serializer.serialize_str(match self {
+            EngineErrorCause::Internal => "Internal",
+            EngineErrorCause::User(_) => "User",
+        })
+    }
+}
+
 #[derive(Debug)]
 pub struct SimpleError {
     pub kind: SimpleErrorKind,
@@ -63,6 +145,8 @@ pub type StringError = String;
 #[derive(Debug)]
 pub enum SimpleErrorKind {
     Command(ExitStatus),
+    /// the operation has been interrupted by its cancellation token
+    Cancelled,
     Other,
 }
 
@@ -91,22 +175,119 @@ pub fn cast_simple_error_to_engine_error<X, T: Into<String>>(
 ) -> Result<X, EngineError> {
     match input {
         Err(simple_error) => {
-            let message = match simple_error.kind {
-                SimpleErrorKind::Command(exit_status) => format!(
-                    "{} ({})",
+            let (cause, message) = match simple_error.kind {
+                SimpleErrorKind::Command(exit_status) => (
+                    EngineErrorCause::Internal,
+                    format!(
+                        "{} ({})",
+                        simple_error.message.unwrap_or("<no message>".into()),
+                        exit_status
+                    ),
+                ),
+                // a cancellation is requested by the user, it is not an engine failure
+                SimpleErrorKind::Cancelled => (
+                    EngineErrorCause::User("cancelled"),
+                    simple_error
+                        .message
+                        .unwrap_or("the operation has been cancelled".into()),
+                ),
+                SimpleErrorKind::Other => (
+                    EngineErrorCause::Internal,
                     simple_error.message.unwrap_or("<no message>".into()),
-                    exit_status
                 ),
-                SimpleErrorKind::Other => simple_error.message.unwrap_or("<no message>".into()),
             };
 
-            Err(EngineError::new(
-                EngineErrorCause::Internal,
-                scope,
-                execution_id,
-                Some(message),
-            ))
+            Err(EngineError::new(cause, scope, execution_id, Some(message)))
         }
         Ok(x) => Ok(x),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::error::{
+        cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope, SimpleError,
+        SimpleErrorKind,
+    };
+
+    fn engine_error(cause: EngineErrorCause) -> EngineError {
+        EngineError::new(cause, EngineErrorScope::Engine, "execution_id", Some("message"))
+    }
+
+    #[test]
+    fn test_internal_error_is_retryable_only_when_transient() {
+        assert!(!engine_error(EngineErrorCause::Internal).is_retryable());
+        assert!(engine_error(EngineErrorCause::Internal).retryable().is_retryable());
+    }
+
+    #[test]
+    fn test_user_error_is_never_retryable() {
+        assert!(!engine_error(EngineErrorCause::User("invalid port")).is_retryable());
+        assert!(!engine_error(EngineErrorCause::User("invalid port")).retryable().is_retryable());
+    }
+
+    #[test]
+    fn test_cancelled_error_is_a_user_error() {
+        let result = cast_simple_error_to_engine_error::<(), _>(
+            EngineErrorScope::Engine,
+            "execution_id",
+            Err(SimpleError::new(SimpleErrorKind::Cancelled, None::<&str>)),
+        );
+
+        let error = result.unwrap_err().retryable();
+        match error.cause {
+            EngineErrorCause::User(message) => assert_eq!(message, "cancelled"),
+            EngineErrorCause::Internal => panic!("expected a user error"),
+        }
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_engine_error_to_json() {
+        let error = EngineError::new(
+            EngineErrorCause::User("The port is invalid"),
+            EngineErrorScope::ExternalService("service-id".to_string(), "service-name".to_string()),
+            "execution_id",
+            Some("port 0 is invalid"),
+        )
+        .retryable();
+
+        let json = serde_json::from_str::<serde_json::Value>(error.to_json().as_str()).unwrap();
+
+        assert_eq!(
+            json,
+            json!({
+                "cause": "User",
+                "scope": {
+                    "type": "ExternalService",
+                    "id": "service-id",
+                    "name": "service-name"
+                },
+                "execution_id": "execution_id",
+                "message": "port 0 is invalid"
+            })
+        );
+    }
+
+    #[test]
+    fn test_engine_error_scope_to_json() {
+        let error = EngineError::new(
+            EngineErrorCause::Internal,
+            EngineErrorScope::Engine,
+            "execution_id",
+            None::<&str>,
+        );
+        let json = serde_json::from_str::<serde_json::Value>(error.to_json().as_str()).unwrap();
+        assert_eq!(json["cause"], "Internal");
+        assert_eq!(json["scope"], json!({ "type": "Engine" }));
+        assert_eq!(json["message"], serde_json::Value::Null);
+
+        let scope = EngineErrorScope::Database("db-id".to_string(), "POSTGRESQL".to_string(), "db-name".to_string());
+        assert_eq!(
+            serde_json::to_value(&scope).unwrap(),
+            json!({ "type": "Database", "id": "db-id", "database_type": "POSTGRESQL", "name": "db-name" })
+        );
+    }
+}
@@ -1,3 +1,8 @@
+#[macro_use]
+extern crate lazy_static;
+#[cfg(feature = "metrics")]
+#[macro_use]
+extern crate prometheus;
 extern crate tera;
 #[macro_use]
 extern crate tracing;
@@ -15,12 +20,14 @@ pub mod engine;
 pub mod error;
 pub mod fs;
 pub mod git;
+pub mod logger;
+pub mod metrics;
 pub mod models;
 pub mod object_storage;
 mod runtime;
 pub mod session;
 mod string;
-mod template;
+pub mod template;
 pub mod transaction;
 mod unit_conversion;
 pub mod utilities;
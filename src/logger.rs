@@ -0,0 +1,105 @@
+//! The engine logs the commands it runs and their outputs, the process embedding the engine is expected to give
+//! `RedactedMakeWriter` to its `tracing_subscriber` so the secrets do not end up in the logs.
+
+use std::io;
+
+use tracing_subscriber::fmt::MakeWriter;
+
+use crate::string::redact;
+
+/// wrap the writers of another `MakeWriter`, the formatted events are written with their secrets redacted
+pub struct RedactedMakeWriter<M> {
+    inner: M,
+}
+
+impl<M: MakeWriter> RedactedMakeWriter<M> {
+    pub fn new(inner: M) -> Self {
+        RedactedMakeWriter { inner }
+    }
+}
+
+impl<M: MakeWriter> MakeWriter for RedactedMakeWriter<M> {
+    type Writer = RedactedWriter<M::Writer>;
+
+    fn make_writer(&self) -> Self::Writer {
+        RedactedWriter {
+            inner: self.inner.make_writer(),
+        }
+    }
+}
+
+/// the fmt subscriber writes each event at once, a secret is never split between two writes
+pub struct RedactedWriter<W> {
+    inner: W,
+}
+
+impl<W: io::Write> io::Write for RedactedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let redacted = redact(String::from_utf8_lossy(buf).as_ref());
+        self.inner.write_all(redacted.as_bytes())?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+    use std::sync::{Arc, Mutex};
+
+    use crate::error::{EngineError, EngineErrorCause, EngineErrorScope};
+    use crate::logger::RedactedMakeWriter;
+    use crate::string::Secrets;
+
+    #[derive(Clone, Default)]
+    struct CapturedLogs(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for CapturedLogs {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_secrets_are_redacted() {
+        let secret = "s3cr3t-database-url";
+        let secrets = Secrets::new("execution_id");
+        secrets.register(secret);
+
+        let logs = CapturedLogs::default();
+        let writer = logs.clone();
+        let subscriber = tracing_subscriber::fmt()
+            .with_ansi(false)
+            .with_writer(RedactedMakeWriter::new(move || writer.clone()))
+            .finish();
+
+        tracing::subscriber::with_default(subscriber, || {
+            info!("connecting to {}", secret);
+            warn!("there is no registry url, use image name with tag: {}", secret);
+            error!("login failed: password={}", "not-registered");
+        });
+
+        let logs = String::from_utf8(logs.0.lock().unwrap().clone()).unwrap();
+        assert!(!logs.contains(secret));
+        assert!(!logs.contains("not-registered"));
+        assert!(logs.contains("connecting to ***"));
+        assert!(logs.contains("password=***"));
+
+        let err = EngineError::new(
+            EngineErrorCause::Internal,
+            EngineErrorScope::Engine,
+            "execution_id",
+            Some(format!("kubectl exited with code 1: {}", secret)),
+        );
+        assert_eq!(err.message.as_deref(), Some("kubectl exited with code 1: ***"));
+        assert!(!err.to_json().contains(secret));
+    }
+}
@@ -0,0 +1,72 @@
+//! Prometheus metrics of the lifecycle steps of the services, only collected with the `metrics` feature.
+//! Without it, `observe(..)` just runs the step.
+
+use crate::error::EngineError;
+
+#[cfg(feature = "metrics")]
+use prometheus::{Encoder, HistogramVec, IntCounterVec, TextEncoder};
+
+#[cfg(feature = "metrics")]
+lazy_static! {
+    static ref DEPLOY_TOTAL: IntCounterVec = register_int_counter_vec!(
+        "engine_deploy_total",
+        "Number of lifecycle steps (create, pause, delete) by service type and outcome",
+        &["service_type", "action", "outcome"]
+    )
+    .unwrap();
+    static ref DEPLOY_DURATION_SECONDS: HistogramVec = register_histogram_vec!(
+        "engine_deploy_duration_seconds",
+        "Duration of the lifecycle steps (create, pause, delete) by service type",
+        &["service_type", "action"],
+        vec![5.0, 15.0, 30.0, 60.0, 120.0, 300.0, 600.0, 1200.0]
+    )
+    .unwrap();
+}
+
+pub const OUTCOME_SUCCESS: &str = "success";
+pub const OUTCOME_FAILURE: &str = "failure";
+
+/// run a lifecycle step of a service and record its duration and outcome
+#[cfg(feature = "metrics")]
+pub fn observe<T, F>(service_type: &str, action: &str, step: F) -> Result<T, EngineError>
+where
+    F: FnOnce() -> Result<T, EngineError>,
+{
+    let timer = DEPLOY_DURATION_SECONDS
+        .with_label_values(&[service_type, action])
+        .start_timer();
+
+    let result = step();
+    timer.observe_duration();
+
+    let outcome = match result {
+        Ok(_) => OUTCOME_SUCCESS,
+        Err(_) => OUTCOME_FAILURE,
+    };
+
+    DEPLOY_TOTAL.with_label_values(&[service_type, action, outcome]).inc();
+
+    result
+}
+
+#[cfg(not(feature = "metrics"))]
+pub fn observe<T, F>(_service_type: &str, _action: &str, step: F) -> Result<T, EngineError>
+where
+    F: FnOnce() -> Result<T, EngineError>,
+{
+    step()
+}
+
+/// number of lifecycle steps recorded with this outcome
+#[cfg(feature = "metrics")]
+pub fn deploy_total(service_type: &str, action: &str, outcome: &str) -> u64 {
+    DEPLOY_TOTAL.with_label_values(&[service_type, action, outcome]).get()
+}
+
+/// the metrics in the Prometheus text format, to be served by the process embedding the engine
+#[cfg(feature = "metrics")]
+pub fn encode_text() -> String {
+    let mut buffer = vec![];
+    let _ = TextEncoder::new().encode(&prometheus::gather(), &mut buffer);
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -1,4 +1,4 @@
-use std::hash::Hash;
+use std::hash::{Hash, Hasher};
 
 use chrono::{DateTime, Utc};
 use rand::distributions::Alphanumeric;
@@ -10,12 +10,18 @@ use crate::cloud_provider::aws::databases::mongodb::MongoDB;
 use crate::cloud_provider::aws::databases::mysql::MySQL;
 use crate::cloud_provider::aws::databases::postgresql::PostgreSQL;
 use crate::cloud_provider::aws::databases::redis::Redis;
-use crate::cloud_provider::service::{DatabaseOptions, StatefulService, StatelessService};
+use crate::cloud_provider::service::{
+    DatabaseOptions, DeploymentListener, DeploymentListeners, StatefulService, StatelessService,
+};
 use crate::cloud_provider::CloudProvider;
 use crate::cloud_provider::Kind as CPKind;
+use crate::cmd::helm::Timeout;
 use crate::git::Credentials;
+use crate::string::Secrets;
 use itertools::Itertools;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 #[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub enum EnvironmentAction {
@@ -40,6 +46,7 @@ pub struct Environment {
     pub databases: Vec<Database>,
     pub external_services: Vec<ExternalService>,
     pub clone_from_environment_id: Option<String>,
+    pub resource_quota: Option<ResourceQuota>,
 }
 
 impl Environment {
@@ -100,7 +107,7 @@ impl Environment {
 
         let stateful_services = databases;
 
-        crate::cloud_provider::environment::Environment::new(
+        let mut environment = crate::cloud_provider::environment::Environment::new(
             match self.kind {
                 Kind::Production => crate::cloud_provider::environment::Kind::Production,
                 Kind::Development => crate::cloud_provider::environment::Kind::Development,
@@ -111,7 +118,29 @@ impl Environment {
             self.organization_id.as_str(),
             stateless_services,
             stateful_services,
-        )
+        );
+
+        environment.set_resource_quota(self.resource_quota.as_ref().map(|x| x.to_resource_quota()));
+
+        environment
+    }
+}
+
+/// the limits of the namespace of the environment, e.g. `cpu: 4`, `memory: 8Gi` and `pods: 20`
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct ResourceQuota {
+    pub cpu: Option<String>,
+    pub memory: Option<String>,
+    pub pods: Option<u32>,
+}
+
+impl ResourceQuota {
+    pub fn to_resource_quota(&self) -> crate::cloud_provider::environment::ResourceQuota {
+        crate::cloud_provider::environment::ResourceQuota {
+            cpu: self.cpu.clone(),
+            memory: self.memory.clone(),
+            pods: self.pods,
+        }
     }
 }
 
@@ -122,7 +151,7 @@ pub enum Kind {
     Development,
 }
 
-#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+#[derive(Serialize, Deserialize, Clone, Debug, Eq, PartialEq, Hash)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Action {
     Create,
@@ -168,7 +197,7 @@ impl Application {
         context: &Context,
         image: &Image,
         cloud_provider: &dyn CloudProvider,
-    ) -> Option<Box<(dyn crate::cloud_provider::service::Application)>> {
+    ) -> Option<Box<dyn crate::cloud_provider::service::Application>> {
         let environment_variables = self
             .environment_variables
             .iter()
@@ -213,6 +242,8 @@ impl Application {
                     listeners,
                 ),
             )),
+            CPKind::Gcp => None,
+            CPKind::Azure => None,
         }
     }
 
@@ -266,6 +297,8 @@ impl Application {
                     listeners,
                 ),
             )),
+            CPKind::Gcp => None,
+            CPKind::Azure => None,
         }
     }
 
@@ -278,6 +311,10 @@ impl Application {
             registry_name: None,
             registry_secret: None,
             registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
         }
     }
 
@@ -450,6 +487,8 @@ impl Router {
                     ));
                 Some(router)
             }
+            CPKind::Gcp => None,
+            CPKind::Azure => None,
         }
     }
 }
@@ -656,6 +695,8 @@ impl Database {
                     Some(db)
                 }
             },
+            CPKind::Gcp => None,
+            CPKind::Azure => None,
         }
     }
 }
@@ -684,6 +725,41 @@ pub struct ExternalService {
     pub on_pause_dockerfile_path: String,
     pub on_delete_dockerfile_path: String,
     pub environment_variables: Vec<EnvironmentVariable>,
+    // cron expression, the external service is run on this schedule instead of once
+    #[serde(default)]
+    pub schedule: Option<String>,
+    // ids of the services which must be deployed before this external service
+    #[serde(default)]
+    pub depends_on: Vec<String>,
+    // containers run next to the external service one, e.g. a cloud-sql proxy
+    #[serde(default)]
+    pub sidecars: Vec<Sidecar>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
+pub struct Sidecar {
+    pub image_name: String,
+    pub image_tag: String,
+    #[serde(default)]
+    pub environment_variables: Vec<EnvironmentVariable>,
+}
+
+impl Sidecar {
+    pub fn to_image(&self, application_id: &str) -> Image {
+        Image {
+            application_id: application_id.to_string(),
+            name: self.image_name.clone(),
+            tag: self.image_tag.clone(),
+            commit_id: self.image_tag.clone(),
+            registry_name: None,
+            registry_secret: None,
+            registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
+        }
+    }
 }
 
 impl ExternalService {
@@ -692,40 +768,72 @@ impl ExternalService {
         context: &Context,
         image: &Image,
         cloud_provider: &dyn CloudProvider,
-    ) -> Option<Box<(dyn crate::cloud_provider::service::Application)>> {
-        let environment_variables = self
-            .environment_variables
-            .iter()
-            .sorted_by_key(|x| &x.key)
-            .map(|ev| ev.to_environment_variable())
-            .collect::<Vec<_>>();
+    ) -> Option<Box<dyn crate::cloud_provider::service::Application>> {
+        match cloud_provider.kind() {
+            CPKind::Aws => Some(Box::new(self.to_aws_external_service(
+                context,
+                image.clone(),
+                cloud_provider,
+            ))),
+            _ => None,
+        }
+    }
 
-        let listeners = cloud_provider.listeners().clone();
+    pub fn to_stateless_service<'a>(
+        &self,
+        context: &Context,
+        image: Image,
+        cloud_provider: &dyn CloudProvider,
+    ) -> Option<Box<dyn crate::cloud_provider::service::StatelessService>> {
+        match cloud_provider.kind() {
+            CPKind::Aws => {
+                let mut external_service = self.to_aws_external_service(context, image, cloud_provider);
+                external_service.set_depends_on(self.depends_on.clone());
+
+                match &self.schedule {
+                    Some(schedule) => Some(Box::new(
+                        crate::cloud_provider::aws::external_service::CronExternalService::new(
+                            external_service,
+                            schedule.as_str(),
+                        ),
+                    )),
+                    None => Some(Box::new(external_service)),
+                }
+            }
+            _ => None,
+        }
+    }
 
+    pub fn to_external_service(
+        &self,
+        context: &Context,
+        image: Image,
+        cloud_provider: &dyn CloudProvider,
+    ) -> Option<Box<dyn crate::cloud_provider::service::ExternalService>> {
         match cloud_provider.kind() {
-            CPKind::Aws => Some(Box::new(
-                crate::cloud_provider::aws::external_service::ExternalService::new(
-                    context.clone(),
-                    self.id.as_str(),
-                    self.action.to_service_action(),
-                    self.name.as_str(),
-                    self.total_cpus.clone(),
-                    self.total_ram_in_mib,
-                    image.clone(),
-                    environment_variables,
-                    listeners,
-                ),
-            )),
+            CPKind::Aws => {
+                let external_service = self.to_aws_external_service(context, image, cloud_provider);
+
+                match &self.schedule {
+                    Some(schedule) => Some(Box::new(
+                        crate::cloud_provider::aws::external_service::CronExternalService::new(
+                            external_service,
+                            schedule.as_str(),
+                        ),
+                    )),
+                    None => Some(Box::new(external_service)),
+                }
+            }
             _ => None,
         }
     }
 
-    pub fn to_stateless_service<'a>(
+    fn to_aws_external_service(
         &self,
         context: &Context,
         image: Image,
         cloud_provider: &dyn CloudProvider,
-    ) -> Option<Box<(dyn crate::cloud_provider::service::StatelessService)>> {
+    ) -> crate::cloud_provider::aws::external_service::ExternalService {
         let environment_variables = self
             .environment_variables
             .iter()
@@ -733,23 +841,47 @@ impl ExternalService {
             .map(|ev| ev.to_environment_variable())
             .collect::<Vec<_>>();
 
-        let listeners = cloud_provider.listeners().clone();
+        let mut external_service =
+            crate::cloud_provider::aws::external_service::ExternalService::new_with_single_instance(
+                context.clone(),
+                self.id.as_str(),
+                self.action.to_service_action(),
+                self.name.as_str(),
+                self.total_cpus.clone(),
+                self.total_ram_in_mib,
+                None,
+                Timeout::Default,
+                image,
+                self.sidecar_images(),
+                environment_variables,
+                vec![],
+                cloud_provider.listeners().clone(),
+            );
+        self.set_sidecars_environment_variables(&mut external_service);
 
-        match cloud_provider.kind() {
-            CPKind::Aws => Some(Box::new(
-                crate::cloud_provider::aws::external_service::ExternalService::new(
-                    context.clone(),
-                    self.id.as_str(),
-                    self.action.to_service_action(),
-                    self.name.as_str(),
-                    self.total_cpus.clone(),
-                    self.total_ram_in_mib,
-                    image,
-                    environment_variables,
-                    listeners,
-                ),
-            )),
-            _ => None,
+        external_service
+    }
+
+    fn sidecar_images(&self) -> Vec<Image> {
+        self.sidecars
+            .iter()
+            .map(|sidecar| sidecar.to_image(self.id.as_str()))
+            .collect()
+    }
+
+    fn set_sidecars_environment_variables(
+        &self,
+        external_service: &mut crate::cloud_provider::aws::external_service::ExternalService,
+    ) {
+        for sidecar in &self.sidecars {
+            external_service.set_sidecar_environment_variables(
+                sidecar.image_name.as_str(),
+                sidecar
+                    .environment_variables
+                    .iter()
+                    .map(|ev| ev.to_environment_variable())
+                    .collect(),
+            );
         }
     }
 
@@ -762,6 +894,10 @@ impl ExternalService {
             registry_name: None,
             registry_secret: None,
             registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
         }
     }
 
@@ -927,6 +1063,24 @@ impl<'a> ListenersHelper<'a> {
     }
 }
 
+/// explicit paths of the binaries run by the engine, e.g. pinned versions on hardened images.
+/// A binary without path is looked up in the PATH
+#[derive(PartialEq, Eq, Hash, Clone, Debug, Default)]
+pub struct ToolConfig {
+    pub helm: Option<String>,
+    pub kubectl: Option<String>,
+}
+
+impl ToolConfig {
+    pub fn helm_binary(&self) -> &str {
+        self.helm.as_deref().unwrap_or("helm")
+    }
+
+    pub fn kubectl_binary(&self) -> &str {
+        self.kubectl.as_deref().unwrap_or("kubectl")
+    }
+}
+
 #[derive(PartialEq, Eq, Hash, Clone)]
 pub struct Context {
     execution_id: String,
@@ -935,6 +1089,10 @@ pub struct Context {
     test_cluster: bool,
     docker_host: Option<String>,
     metadata: Option<Metadata>,
+    deployment_listeners: DeploymentListeners,
+    cancellation_token: CancellationToken,
+    tool_config: ToolConfig,
+    secrets: Secrets,
 }
 
 // trait used to reimplement clone without same fields
@@ -953,6 +1111,7 @@ impl Clone2 for Context {
             .take(10)
             .collect::<String>();
         new.execution_id = format!("{}-{}", self.execution_id, suffix);
+        new.secrets = Secrets::new(new.execution_id.as_str());
         new
     }
 }
@@ -967,12 +1126,16 @@ impl Context {
         metadata: Option<Metadata>,
     ) -> Self {
         Context {
+            secrets: Secrets::new(execution_id.as_str()),
             execution_id,
             workspace_root_dir,
             lib_root_dir,
             test_cluster,
             docker_host,
             metadata,
+            deployment_listeners: DeploymentListeners::default(),
+            cancellation_token: CancellationToken::default(),
+            tool_config: ToolConfig::default(),
         }
     }
 
@@ -984,6 +1147,11 @@ impl Context {
         self.workspace_root_dir.as_str()
     }
 
+    /// the workspace of this execution, the services render their charts in its sub directories
+    pub fn workspace_root(&self) -> String {
+        format!("{}/.qovery-workspace/{}", self.workspace_root_dir, self.execution_id)
+    }
+
     pub fn lib_root_dir(&self) -> &str {
         self.lib_root_dir.as_str()
     }
@@ -996,6 +1164,50 @@ impl Context {
         self.metadata.as_ref()
     }
 
+    pub fn deployment_listeners(&self) -> &DeploymentListeners {
+        &self.deployment_listeners
+    }
+
+    pub fn add_deployment_listener(&mut self, listener: Arc<dyn DeploymentListener>) {
+        self.deployment_listeners.add(listener);
+    }
+
+    /// cancelling the token interrupts the waits and kills the commands of the in-flight deployment
+    pub fn with_cancellation_token(mut self, cancellation_token: CancellationToken) -> Self {
+        self.cancellation_token = cancellation_token;
+        self
+    }
+
+    pub fn cancellation_token(&self) -> &CancellationToken {
+        &self.cancellation_token
+    }
+
+    /// the command wrappers don't get the context, the binary paths are shared with them for the whole process
+    pub fn with_tool_config(mut self, tool_config: ToolConfig) -> Self {
+        crate::cmd::tools::set_tool_config(tool_config.clone());
+        self.tool_config = tool_config;
+        self
+    }
+
+    pub fn tool_config(&self) -> &ToolConfig {
+        &self.tool_config
+    }
+
+    /// the secrets redacted from the logs and the errors of this execution
+    pub fn secrets(&self) -> &Secrets {
+        &self.secrets
+    }
+
+    /// enable or disable the dry run mode: charts and terraform files are rendered but never applied
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        match self.metadata.as_mut() {
+            Some(meta) => meta.dry_run_deploy = Some(dry_run),
+            None => self.metadata = Some(Metadata::new(Some(dry_run), None)),
+        }
+
+        self
+    }
+
     pub fn is_dry_run_deploy(&self) -> bool {
         match &self.metadata {
             Some(meta) => match meta.dry_run_deploy {
@@ -1006,6 +1218,57 @@ impl Context {
         }
     }
 
+    /// enable or disable the validation of the rendered charts before they are applied, it is off by default since
+    /// it slows down every deployment
+    pub fn with_rendered_templates_validation(mut self, validate: bool) -> Self {
+        match self.metadata.as_mut() {
+            Some(meta) => meta.validate_rendered_templates = Some(validate),
+            None => {
+                let mut meta = Metadata::new(None, None);
+                meta.validate_rendered_templates = Some(validate);
+                self.metadata = Some(meta);
+            }
+        }
+
+        self
+    }
+
+    pub fn is_rendered_templates_validation(&self) -> bool {
+        match &self.metadata {
+            Some(meta) => match meta.validate_rendered_templates {
+                Some(true) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// always upgrade the services, even when nothing has changed since their last deployment.
+    /// The pods are annotated with the time of the deployment, so they are recreated even with an unchanged image.
+    /// The pods of a job can't be changed, so a job is deleted before its upgrade and created again.
+    pub fn with_force_redeploy(mut self, force: bool) -> Self {
+        match self.metadata.as_mut() {
+            Some(meta) => meta.force_redeploy = Some(force),
+            None => {
+                let mut meta = Metadata::new(None, None);
+                meta.force_redeploy = Some(force);
+                self.metadata = Some(meta);
+            }
+        }
+
+        self
+    }
+
+    pub fn is_force_redeploy(&self) -> bool {
+        match &self.metadata {
+            Some(meta) => match meta.force_redeploy {
+                Some(true) => true,
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
     pub fn is_test_cluster(&self) -> bool {
         self.test_cluster
     }
@@ -1021,12 +1284,63 @@ impl Context {
     }
 }
 
+/// Shared between the caller and the engine, the caller cancels it to stop the in-flight operation.
+/// Like the deployment listeners, it is not part of the context identity.
+#[derive(Clone, Debug, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// sleep for `duration` unless the token is cancelled in the meantime,
+    /// return whether the whole duration has been waited
+    pub fn sleep(&self, duration: Duration) -> bool {
+        let start = Instant::now();
+
+        while !self.is_cancelled() {
+            let elapsed = start.elapsed();
+            if elapsed >= duration {
+                return true;
+            }
+
+            std::thread::sleep((duration - elapsed).min(CANCELLATION_CHECK_INTERVAL));
+        }
+
+        false
+    }
+}
+
+/// how often a sleeping or a running command checks for a cancellation
+pub const CANCELLATION_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+impl PartialEq for CancellationToken {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for CancellationToken {}
+
+impl Hash for CancellationToken {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
 /// put everything you want here that is required to change the behaviour of the request.
 /// E.g you can indicate that this request is a test, then you can adapt the behaviour as you want.
 #[derive(Debug, Serialize, Deserialize, Clone, Eq, PartialEq, Hash)]
 pub struct Metadata {
     pub dry_run_deploy: Option<bool>,
     pub resource_expiration_in_seconds: Option<u32>,
+    pub validate_rendered_templates: Option<bool>,
+    pub force_redeploy: Option<bool>,
 }
 
 impl Metadata {
@@ -1034,6 +1348,8 @@ impl Metadata {
         Metadata {
             dry_run_deploy,
             resource_expiration_in_seconds,
+            validate_rendered_templates: None,
+            force_redeploy: None,
         }
     }
 }
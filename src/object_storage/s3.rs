@@ -1,6 +1,7 @@
 use std::fs::File;
 
 use retry::delay::Fibonacci;
+use chrono::Duration;
 use retry::{Error, OperationResult};
 
 use crate::constants::{AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
@@ -111,14 +112,20 @@ impl ObjectStorage for S3 {
         // retrieve config file from object storage
         let result = retry::retry(Fibonacci::from_millis(3000).take(5), || {
             // we choose to use the AWS CLI instead of Rusoto S3 due to reliability problems we faced.
-            let result = cast_simple_error_to_engine_error(
-                self.engine_error_scope(),
-                self.context().execution_id(),
-                crate::cmd::utilities::exec_with_envs(
-                    "aws",
-                    vec!["s3", "cp", s3_url.as_str(), file_path.as_str()],
-                    self.credentials_environment_variables(),
-                ),
+            let mut stderr_lines: Vec<String> = Vec::new();
+            let result = crate::cmd::utilities::exec_with_envs_and_output(
+                "aws",
+                vec!["s3", "cp", s3_url.as_str(), file_path.as_str()],
+                self.credentials_environment_variables(),
+                |out| match out {
+                    Ok(line) => debug!("{}", line),
+                    Err(err) => error!("{:?}", err),
+                },
+                |out| match out {
+                    Ok(line) => stderr_lines.push(line),
+                    Err(err) => error!("{:?}", err),
+                },
+                Duration::max_value(),
             );
 
             match result {
@@ -126,6 +133,12 @@ impl ObjectStorage for S3 {
                 Err(err) => {
                     debug!("{:?}", err);
 
+                    // the AWS CLI tells why the download failed (404, 403...) on stderr
+                    let err = self.engine_error(
+                        EngineErrorCause::Internal,
+                        format!("unable to download {}: {}", s3_url, stderr_lines.join("\n")),
+                    );
+
                     warn!("Can't download object '{}'. Let's retry...", object_key);
 
                     OperationResult::Retry(err)
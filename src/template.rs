@@ -1,3 +1,4 @@
+use std::collections::HashMap;
 use std::ffi::OsStr;
 use std::fs;
 use std::fs::File;
@@ -5,43 +6,69 @@ use std::io::Write;
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
+use rand::distributions::Alphanumeric;
+use rand::Rng;
 use tera::Error as TeraError;
-use tera::{Context, Tera};
+use tera::{Context, Tera, Value};
 use walkdir::WalkDir;
 
 use crate::error::{SimpleError, SimpleErrorKind};
 
+const FILTER_NOT_FOUND: &str = "filter not found";
+
 pub fn generate_and_copy_all_files_into_dir<S, P>(from_dir: S, to_dir: P, context: &Context) -> Result<(), SimpleError>
 where
     S: AsRef<Path>,
     P: AsRef<Path>,
+{
+    generate_and_copy_all_files_into_dir_with_registration(from_dir, to_dir, context, |_| {})
+}
+
+/// same as `generate_and_copy_all_files_into_dir`, `register` can add custom functions and filters to Tera
+/// (on top of the built-in ones, see `register_builtin_filters`) before the rendering
+pub fn generate_and_copy_all_files_into_dir_with_registration<S, P, F>(
+    from_dir: S,
+    to_dir: P,
+    context: &Context,
+    register: F,
+) -> Result<(), SimpleError>
+where
+    S: AsRef<Path>,
+    P: AsRef<Path>,
+    F: Fn(&mut Tera),
 {
     // generate j2 templates
-    let rendered_templates = match generate_j2_template_files(from_dir.as_ref(), context) {
+    let rendered_templates = match generate_j2_template_files_with_registration(from_dir.as_ref(), context, register) {
         Ok(rt) => rt,
         Err(e) => {
             error!("{:?}", &e);
-            let error_msg = match e.kind {
-                tera::ErrorKind::TemplateNotFound(x) => format!("template not found: {}", x),
-                tera::ErrorKind::Msg(x) => format!("tera error: {}", x),
-                tera::ErrorKind::CircularExtend { tpl, inheritance_chain } => format!(
-                    "circular extend - template: {}, inheritance chain: {:?}",
-                    tpl, inheritance_chain
+            let error_msg = match missing_filter(&e) {
+                Some(filter) => format!(
+                    "{}: {} (the built-in filters are b64encode and sha256)",
+                    FILTER_NOT_FOUND, filter
                 ),
-                tera::ErrorKind::MissingParent { current, parent } => {
-                    format!("missing parent - current: {}, parent: {}", current, parent)
-                }
-                tera::ErrorKind::FilterNotFound(x) => format!("filter not found: {}", x),
-                tera::ErrorKind::TestNotFound(x) => format!("test not found: {}", x),
-                tera::ErrorKind::InvalidMacroDefinition(x) => {
-                    format!("invalid macro definition: {}", x)
-                }
-                tera::ErrorKind::FunctionNotFound(x) => format!("function not found: {}", x),
-                tera::ErrorKind::Json(x) => format!("json error: {:?}", x),
-                tera::ErrorKind::CallFunction(x) => format!("call function: {}", x),
-                tera::ErrorKind::CallFilter(x) => format!("call filter: {}", x),
-                tera::ErrorKind::CallTest(x) => format!("call test: {}", x),
-                tera::ErrorKind::__Nonexhaustive => format!("non exhaustive error"),
+                None => match e.kind {
+                    tera::ErrorKind::TemplateNotFound(x) => format!("template not found: {}", x),
+                    tera::ErrorKind::Msg(x) => format!("tera error: {}", x),
+                    tera::ErrorKind::CircularExtend { tpl, inheritance_chain } => format!(
+                        "circular extend - template: {}, inheritance chain: {:?}",
+                        tpl, inheritance_chain
+                    ),
+                    tera::ErrorKind::MissingParent { current, parent } => {
+                        format!("missing parent - current: {}, parent: {}", current, parent)
+                    }
+                    tera::ErrorKind::FilterNotFound(x) => format!("filter not found: {}", x),
+                    tera::ErrorKind::TestNotFound(x) => format!("test not found: {}", x),
+                    tera::ErrorKind::InvalidMacroDefinition(x) => {
+                        format!("invalid macro definition: {}", x)
+                    }
+                    tera::ErrorKind::FunctionNotFound(x) => format!("function not found: {}", x),
+                    tera::ErrorKind::Json(x) => format!("json error: {:?}", x),
+                    tera::ErrorKind::CallFunction(x) => format!("call function: {}", x),
+                    tera::ErrorKind::CallFilter(x) => format!("call filter: {}", x),
+                    tera::ErrorKind::CallTest(x) => format!("call test: {}", x),
+                    tera::ErrorKind::__Nonexhaustive => format!("non exhaustive error"),
+                },
             };
 
             error!("{}", context.clone().into_json());
@@ -70,16 +97,53 @@ where
     }
 }
 
+/// Tera wraps the rendering errors, look for a missing filter in the causes
+fn missing_filter(error: &TeraError) -> Option<String> {
+    let mut current: Option<&(dyn std::error::Error + 'static)> = Some(error);
+
+    while let Some(err) = current {
+        if let Some(tera::ErrorKind::FilterNotFound(filter)) = err.downcast_ref::<TeraError>().map(|e| &e.kind) {
+            return Some(filter.clone());
+        }
+
+        current = err.source();
+    }
+
+    None
+}
+
+/// the rendering failed because a template uses a filter unknown to Tera
+pub fn is_filter_not_found_error(error: &SimpleError) -> bool {
+    match &error.message {
+        Some(message) => message.starts_with(FILTER_NOT_FOUND),
+        None => false,
+    }
+}
+
 pub fn generate_j2_template_files<P>(root_dir: P, context: &Context) -> Result<Vec<RenderedTemplate>, TeraError>
 where
     P: AsRef<Path>,
+{
+    generate_j2_template_files_with_registration(root_dir, context, |_| {})
+}
+
+pub fn generate_j2_template_files_with_registration<P, F>(
+    root_dir: P,
+    context: &Context,
+    register: F,
+) -> Result<Vec<RenderedTemplate>, TeraError>
+where
+    P: AsRef<Path>,
+    F: Fn(&mut Tera),
 {
     //TODO: sort on fly context should be implemented to optimize reading
     debug!("context: {:#?}", context);
     let root_dir_str = root_dir.as_ref().to_str().unwrap();
     let tera_template_string = format!("{}/**/*.j2.*", root_dir_str);
 
-    let tera = Tera::new(tera_template_string.as_str())?;
+    let mut tera = Tera::new(tera_template_string.as_str())?;
+    register_builtin_filters(&mut tera);
+    register(&mut tera);
 
     let files = WalkDir::new(root_dir_str)
         .follow_links(true)
@@ -107,6 +171,30 @@ where
     Ok(results)
 }
 
+/// filters Tera doesn't ship but our charts need, mostly to template kubernetes secrets
+pub fn register_builtin_filters(tera: &mut Tera) {
+    tera.register_filter("b64encode", b64encode_filter);
+    tera.register_filter("sha256", sha256_filter);
+}
+
+/// `{{ value | b64encode }}`
+fn b64encode_filter(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let input = string_filter_input("b64encode", value)?;
+    Ok(Value::String(base64::encode(input)))
+}
+
+/// `{{ value | sha256 }}`, hex encoded
+fn sha256_filter(value: &Value, _args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let input = string_filter_input("sha256", value)?;
+    Ok(Value::String(crate::crypto::to_sha256(input)))
+}
+
+fn string_filter_input<'a>(filter: &str, value: &'a Value) -> tera::Result<&'a str> {
+    value
+        .as_str()
+        .ok_or_else(|| TeraError::msg(format!("filter {} expects a string, got {}", filter, value)))
+}
+
 pub fn write_rendered_templates(rendered_templates: &[RenderedTemplate], into: &Path) -> Result<(), SimpleError> {
     for rt in rendered_templates {
         let dest = format!("{}/{}", into.to_str().unwrap(), rt.path_and_file_name());
@@ -138,6 +226,109 @@ pub fn write_rendered_templates(rendered_templates: &[RenderedTemplate], into: &
     Ok(())
 }
 
+/// render the helm chart of a workspace directory without applying it, to catch parse or schema errors
+/// (e.g. a bad environment variable substitution) before the real upgrade, with the values files of the upgrade
+pub fn validate_rendered_dir<P>(dir: P, values_files: &[String]) -> Result<(), SimpleError>
+where
+    P: AsRef<Path>,
+{
+    let dir = dir.as_ref().to_str().unwrap();
+    let mut errors: Vec<String> = vec![];
+
+    let mut args = vec!["template", "validation", dir];
+    for values_file in values_files {
+        args.push("-f");
+        args.push(values_file.as_str());
+    }
+
+    let result = crate::cmd::utilities::exec_with_output(
+        crate::cmd::tools::helm_binary(),
+        args,
+        |out| match out {
+            Ok(line) => debug!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => errors.push(line),
+            Err(err) => error!("{:?}", err),
+        },
+    );
+
+    match result {
+        Ok(_) => Ok(()),
+        Err(_) => Err(SimpleError::new(
+            SimpleErrorKind::Other,
+            Some(format!("rendered templates in {} are not valid: {}", dir, errors.join("\n"))),
+        )),
+    }
+}
+
+/// lint a chart rendered with the context of a service, without any cluster nor kubeconfig (e.g. in the CI).
+/// The errors are the rendering error or the `[ERROR]` and `[WARNING]` messages of `helm lint`
+pub fn lint<P>(from_dir: P, context: &Context) -> Result<(), Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    lint_with_values_files(from_dir, context, &[])
+}
+
+/// same as `lint`, the values of the chart are overridden by the values files as `helm upgrade -f` does
+pub fn lint_with_values_files<P>(from_dir: P, context: &Context, values_files: &[String]) -> Result<(), Vec<String>>
+where
+    P: AsRef<Path>,
+{
+    let suffix = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(8)
+        .collect::<String>();
+    let lint_dir = std::env::temp_dir().join(format!("lint-{}", suffix));
+
+    let result = lint_rendered_dir(from_dir.as_ref(), lint_dir.as_path(), context, values_files);
+    let _ = fs::remove_dir_all(lint_dir);
+
+    result
+}
+
+fn lint_rendered_dir(
+    from_dir: &Path,
+    lint_dir: &Path,
+    context: &Context,
+    values_files: &[String],
+) -> Result<(), Vec<String>> {
+    if let Err(err) = generate_and_copy_all_files_into_dir(from_dir, lint_dir, context) {
+        return Err(vec![err.message.unwrap_or_default()]);
+    }
+
+    let mut messages: Vec<String> = vec![];
+
+    let mut args = vec!["lint", lint_dir.to_str().unwrap()];
+    for values_file in values_files {
+        args.push("-f");
+        args.push(values_file.as_str());
+    }
+
+    let result = crate::cmd::utilities::exec_with_output(
+        crate::cmd::tools::helm_binary(),
+        args,
+        |out| match out {
+            Ok(line) if line.starts_with("[ERROR]") || line.starts_with("[WARNING]") => messages.push(line),
+            Ok(line) => debug!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+        |out| match out {
+            Ok(line) => debug!("{}", line),
+            Err(err) => error!("{:?}", err),
+        },
+    );
+
+    match (result, messages.is_empty()) {
+        (Ok(_), true) => Ok(()),
+        // helm itself has failed, e.g. on a chart without Chart.yaml
+        (Err(err), true) => Err(vec![err.message.unwrap_or_default()]),
+        (_, false) => Err(messages),
+    }
+}
+
 pub fn set_file_permission(f: &File, mode: u32) {
     let metadata = f.metadata().unwrap();
     let mut permissions = metadata.permissions();
@@ -168,3 +359,165 @@ impl RenderedTemplate {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use tera::{Context, Tera, Value};
+
+    use crate::template::{
+        generate_and_copy_all_files_into_dir, generate_j2_template_files, generate_j2_template_files_with_registration,
+        is_filter_not_found_error, lint, validate_rendered_dir,
+    };
+
+    fn template_dir(name: &str, template: &str) -> String {
+        let dir = format!("{}/qovery-engine-{}", std::env::temp_dir().to_str().unwrap(), name);
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(dir.as_str()).unwrap();
+        fs::write(format!("{}/secret.j2.yaml", dir), template).unwrap();
+        dir
+    }
+
+    fn chart_dir(name: &str, template: &str) -> String {
+        let dir = format!("{}/qovery-engine-{}", std::env::temp_dir().to_str().unwrap(), name);
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(format!("{}/templates", dir)).unwrap();
+        fs::write(
+            format!("{}/Chart.yaml", dir),
+            "apiVersion: v2\nname: validation\nversion: 0.1.0\n",
+        )
+        .unwrap();
+        fs::write(format!("{}/templates/configmap.yaml", dir), template).unwrap();
+        dir
+    }
+
+    #[test]
+    #[ignore] // runs the helm binary
+    fn test_validate_rendered_dir() {
+        let dir = chart_dir(
+            "valid-chart",
+            "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app\ndata:\n  DATABASE_URL: \"postgres://db\"\n",
+        );
+
+        assert!(validate_rendered_dir(dir.as_str(), &[]).is_ok());
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    #[ignore] // runs the helm binary
+    fn test_validate_malformed_rendered_dir() {
+        // a substituted value breaking the YAML structure
+        let dir = chart_dir(
+            "malformed-chart",
+            concat!(
+                "apiVersion: v1\nkind: ConfigMap\nmetadata:\n  name: app\n",
+                "data:\n  DATABASE_URL: postgres://db\n    - oops: [\n"
+            ),
+        );
+
+        let err = validate_rendered_dir(dir.as_str(), &[]).unwrap_err();
+        let message = err.message.unwrap();
+
+        assert!(message.starts_with(format!("rendered templates in {} are not valid", dir).as_str()));
+        assert!(message.contains("configmap.yaml"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    #[ignore] // runs the helm binary
+    fn test_lint() {
+        let dir = format!("{}/qovery-engine-lint-chart", std::env::temp_dir().to_str().unwrap());
+        let _ = fs::remove_dir_all(dir.as_str());
+        fs::create_dir_all(format!("{}/templates", dir)).unwrap();
+        fs::write(format!("{}/Chart.yaml", dir), "apiVersion: v2\nname: lint\nversion: 0.1.0\n").unwrap();
+        fs::write(format!("{}/values.j2.yaml", dir), "image: \"{{ image_name_with_tag }}\"\n").unwrap();
+        fs::write(
+            format!("{}/templates/job.yaml", dir),
+            concat!(
+                "apiVersion: batch/v1\nkind: Job\nmetadata:\n  name: app\nspec:\n  template:\n    spec:\n",
+                "      restartPolicy: Never\n      containers:\n        - name: app\n",
+                "          image: {{ required \"the image is required\" .Values.image }}\n"
+            ),
+        )
+        .unwrap();
+
+        let mut context = Context::new();
+        context.insert("image_name_with_tag", "my-job:tag");
+        assert_eq!(lint(dir.as_str(), &context), Ok(()));
+
+        // the value of the chart is empty without the image of the service
+        context.insert("image_name_with_tag", "");
+        let errors = lint(dir.as_str(), &context).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("[ERROR] templates/"));
+        assert!(errors[0].contains("the image is required"));
+
+        // the rendering fails before helm
+        let errors = lint(dir.as_str(), &Context::new()).unwrap_err();
+        assert!(errors[0].contains("values.j2.yaml"));
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_b64encode_filter() {
+        let dir = template_dir("b64encode-template", "password: {{ password | b64encode }}");
+        let mut context = Context::new();
+        context.insert("password", "p@ssw0rd");
+
+        let rendered_templates = generate_j2_template_files(dir.as_str(), &context).unwrap();
+
+        assert_eq!(rendered_templates.len(), 1);
+        let encoded = rendered_templates[0].content.trim_start_matches("password: ");
+        assert_eq!(base64::decode(encoded).unwrap(), b"p@ssw0rd");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_sha256_filter() {
+        let dir = template_dir("sha256-template", "{{ value | sha256 }}");
+        let mut context = Context::new();
+        context.insert("value", "abc");
+
+        let rendered_templates = generate_j2_template_files(dir.as_str(), &context).unwrap();
+
+        assert_eq!(
+            rendered_templates[0].content,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_custom_filter_registration() {
+        let dir = template_dir("custom-filter-template", "{{ name | shout }}");
+        let mut context = Context::new();
+        context.insert("name", "app");
+
+        let register = |tera: &mut Tera| {
+            tera.register_filter("shout", |value: &Value, _: &HashMap<String, Value>| {
+                Ok(Value::String(value.as_str().unwrap().to_uppercase()))
+            })
+        };
+
+        let rendered_templates =
+            generate_j2_template_files_with_registration(dir.as_str(), &context, register).unwrap();
+
+        assert_eq!(rendered_templates[0].content, "APP");
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_missing_filter() {
+        let dir = template_dir("missing-filter-template", "{{ name | unknown_filter }}");
+        let mut context = Context::new();
+        context.insert("name", "app");
+
+        let err = generate_and_copy_all_files_into_dir(dir.as_str(), format!("{}/out", dir), &context).unwrap_err();
+
+        assert!(is_filter_not_found_error(&err));
+        assert!(err.message.unwrap().contains("unknown_filter"));
+        let _ = fs::remove_dir_all(dir);
+    }
+}
@@ -1,12 +1,16 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::thread;
 
 use crate::build_platform::BuildResult;
-use crate::cloud_provider::kubernetes::Kubernetes;
-use crate::cloud_provider::service::{Application, Service};
+use crate::cloud_provider::kubernetes::{parse_kubernetes_version, Kubernetes};
+use crate::cloud_provider::service::{Application, Endpoint, Service, ServiceType};
+use crate::cloud_provider::DeploymentTarget;
+use crate::cmd::helm::Timeout;
+use crate::cmd::preflight;
+use crate::cmd::preflight::BinaryRequirement;
 use crate::container_registry::PushResult;
 use crate::engine::Engine;
-use crate::error::EngineError;
+use crate::error::{cast_simple_error_to_engine_error, EngineError, EngineErrorCause, EngineErrorScope};
 use crate::models::{
     Action, Environment, EnvironmentAction, EnvironmentError, ListenersHelper, ProgressInfo, ProgressLevel,
     ProgressScope,
@@ -16,6 +20,9 @@ pub struct Transaction<'a> {
     engine: &'a Engine,
     steps: Vec<Step<'a>>,
     executed_steps: Vec<Step<'a>>,
+    max_parallelism: usize,
+    endpoints: BTreeMap<String, Vec<Endpoint>>,
+    summary: TransactionSummary,
 }
 
 impl<'a> Transaction<'a> {
@@ -24,9 +31,28 @@ impl<'a> Transaction<'a> {
             engine,
             steps: vec![],
             executed_steps: vec![],
+            max_parallelism: 1,
+            endpoints: BTreeMap::new(),
+            summary: TransactionSummary::default(),
         }
     }
 
+    /// addresses of the services deployed by the committed transaction, by service id
+    pub fn endpoints(&self) -> &BTreeMap<String, Vec<Endpoint>> {
+        &self.endpoints
+    }
+
+    /// operations applied by the committed transaction, up to the first failed one
+    pub fn summary(&self) -> &TransactionSummary {
+        &self.summary
+    }
+
+    /// deploy up to `max_parallelism` independent services of an environment at the same time,
+    /// 1 (the default) deploys them sequentially.
+    pub fn set_max_parallelism(&mut self, max_parallelism: usize) {
+        self.max_parallelism = max_parallelism.max(1);
+    }
+
     pub fn create_kubernetes(&mut self, kubernetes: &'a dyn Kubernetes) -> Result<(), EngineError> {
         match kubernetes.is_valid() {
             Ok(_) => {
@@ -47,6 +73,51 @@ impl<'a> Transaction<'a> {
         }
     }
 
+    /// upgrade the running cluster to the target Kubernetes version
+    pub fn upgrade_kubernetes(
+        &mut self,
+        kubernetes: &'a dyn Kubernetes,
+        target_version: &str,
+    ) -> Result<(), EngineError> {
+        if let Err(message) = parse_kubernetes_version(target_version) {
+            return Err(kubernetes.engine_error(
+                EngineErrorCause::User("The target Kubernetes version is not valid"),
+                format!("invalid target version of {}: {}", kubernetes.name_with_id(), message),
+            ));
+        }
+
+        match kubernetes.is_valid() {
+            Ok(_) => {
+                self.steps.push(Step::UpgradeKubernetes(kubernetes, target_version.to_string()));
+                Ok(())
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    /// uninstall right away the releases of the namespace deployed by the engine and not in `keep`,
+    /// e.g. the leftovers of an interrupted deployment. Returns the names of the uninstalled releases.
+    pub fn delete_orphans(
+        &self,
+        kubernetes: &dyn Kubernetes,
+        namespace: &str,
+        keep: &[String],
+    ) -> Result<Vec<String>, EngineError> {
+        let kubernetes_config_file_path = kubernetes.config_file_path()?;
+
+        cast_simple_error_to_engine_error(
+            kubernetes.engine_error_scope(),
+            self.engine.context().execution_id(),
+            crate::cmd::helm::helm_uninstall_orphans(
+                kubernetes_config_file_path.as_str(),
+                namespace,
+                keep,
+                Timeout::Default,
+                kubernetes.cloud_provider().credentials_environment_variables(),
+            ),
+        )
+    }
+
     pub fn deploy_environment(
         &mut self,
         kubernetes: &'a dyn Kubernetes,
@@ -101,6 +172,18 @@ impl<'a> Transaction<'a> {
         Ok(())
     }
 
+    pub fn restart_service(
+        &mut self,
+        kubernetes: &'a dyn Kubernetes,
+        environment_action: &'a EnvironmentAction,
+        service_id: &str,
+    ) -> Result<(), EnvironmentError> {
+        let _ = self.check_environment_action(environment_action)?;
+
+        self.steps.push(Step::RestartService(kubernetes, environment_action, service_id.to_string()));
+        Ok(())
+    }
+
     fn check_environment_action(&self, environment_action: &EnvironmentAction) -> Result<(), EnvironmentError> {
         match environment_action {
             EnvironmentAction::Environment(te) => match te.is_valid() {
@@ -273,8 +356,10 @@ impl<'a> Transaction<'a> {
         TransactionResult::Ok
     }
 
+    /// Undo the executed steps in the reverse order of their execution (LIFO),
+    /// the last applied step depends on the previous ones and must be reverted first.
     pub fn rollback(&self) -> Result<(), RollbackError> {
-        for step in self.executed_steps.iter() {
+        for step in self.executed_steps.iter().rev() {
             match step {
                 Step::CreateKubernetes(kubernetes) => {
                     // revert kubernetes creation
@@ -283,6 +368,13 @@ impl<'a> Transaction<'a> {
                         _ => {}
                     };
                 }
+                Step::UpgradeKubernetes(kubernetes, _) => {
+                    // revert kubernetes upgrade
+                    match kubernetes.on_upgrade_error() {
+                        Err(err) => return Err(RollbackError::CommitError(err)),
+                        _ => {}
+                    };
+                }
                 Step::DeleteKubernetes(kubernetes) => {
                     // revert kubernetes deletion
                     match kubernetes.on_delete_error() {
@@ -303,6 +395,9 @@ impl<'a> Transaction<'a> {
                 Step::DeleteEnvironment(kubernetes, environment_action) => {
                     self.rollback_environment(*kubernetes, *environment_action)?;
                 }
+                Step::RestartService(_kubernetes, _environment_action, _service_id) => {
+                    // a restart does not change the deployed version, there is nothing to revert
+                }
             }
         }
 
@@ -397,8 +492,39 @@ impl<'a> Transaction<'a> {
         }
     }
 
+    /// check once the binaries required by the targeted clusters instead of failing in the middle of the steps
+    fn check_required_binaries(&self) -> Result<(), EngineError> {
+        let mut requirements: Vec<BinaryRequirement> = vec![];
+
+        for step in self.steps.iter() {
+            let kubernetes = match step {
+                Step::CreateKubernetes(kubernetes) | Step::DeleteKubernetes(kubernetes) => *kubernetes,
+                Step::UpgradeKubernetes(kubernetes, _) => *kubernetes,
+                Step::DeployEnvironment(kubernetes, _)
+                | Step::PauseEnvironment(kubernetes, _)
+                | Step::DeleteEnvironment(kubernetes, _)
+                | Step::RestartService(kubernetes, _, _) => *kubernetes,
+                Step::BuildEnvironment(_, _) => continue,
+            };
+
+            for requirement in kubernetes.required_binaries() {
+                if !requirements.iter().any(|r| r.binary == requirement.binary) {
+                    requirements.push(requirement);
+                }
+            }
+        }
+
+        preflight::check_binaries(&requirements, self.engine.context().execution_id())
+    }
+
     pub fn commit(&mut self) -> TransactionResult {
+        if let Err(err) = self.check_required_binaries() {
+            error!("Preflight check failed: {:?}", err);
+            return TransactionResult::Rollback(err);
+        }
+
         let mut applications_by_environment: HashMap<&Environment, Vec<Box<dyn Application>>> = HashMap::new();
+        self.summary = TransactionSummary::default();
 
         for step in self.steps.iter() {
             // execution loop
@@ -407,7 +533,10 @@ impl<'a> Transaction<'a> {
             match step {
                 Step::CreateKubernetes(kubernetes) => {
                     // create kubernetes
-                    match self.commit_infrastructure(*kubernetes, Action::Create, kubernetes.on_create()) {
+                    let result = self.commit_infrastructure(*kubernetes, Action::Create, kubernetes.on_create());
+                    self.summary.record(vec![kubernetes_operation(*kubernetes, Action::Create)], &result);
+
+                    match result {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while creating infrastructure: {:?}", err);
@@ -415,9 +544,26 @@ impl<'a> Transaction<'a> {
                         }
                     };
                 }
+                Step::UpgradeKubernetes(kubernetes, target_version) => {
+                    // upgrade kubernetes
+                    let result = kubernetes.upgrade_version(target_version.as_str());
+                    let result = self.commit_infrastructure(*kubernetes, Action::Create, result);
+                    self.summary.record(vec![kubernetes_operation(*kubernetes, Action::Create)], &result);
+
+                    match result {
+                        TransactionResult::Ok => {}
+                        err => {
+                            error!("Error while upgrading infrastructure: {:?}", err);
+                            return err;
+                        }
+                    };
+                }
                 Step::DeleteKubernetes(kubernetes) => {
                     // delete kubernetes
-                    match self.commit_infrastructure(*kubernetes, Action::Delete, kubernetes.on_delete()) {
+                    let result = self.commit_infrastructure(*kubernetes, Action::Delete, kubernetes.on_delete());
+                    self.summary.record(vec![kubernetes_operation(*kubernetes, Action::Delete)], &result);
+
+                    match result {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while deleting infrastructure: {:?}", err);
@@ -448,13 +594,16 @@ impl<'a> Transaction<'a> {
                         let commit_error = apps_result.err().unwrap();
                         warn!("ROLLBACK STARTED! an error occurred {:?}", commit_error);
 
-                        return match self.rollback() {
+                        let result = match self.rollback() {
                             Ok(_) => TransactionResult::Rollback(commit_error),
                             Err(err) => {
                                 error!("ROLLBACK FAILED! fatal error: {:?}", err);
-                                return TransactionResult::UnrecoverableError(commit_error, err);
+                                TransactionResult::UnrecoverableError(commit_error, err)
                             }
                         };
+
+                        self.summary.record(vec![environment_operation(target_environment)], &result);
+                        return result;
                     }
 
                     let applications = apps_result.ok().unwrap();
@@ -485,13 +634,25 @@ impl<'a> Transaction<'a> {
                 }
                 Step::DeployEnvironment(kubernetes, environment_action) => {
                     // deploy complete environment
-                    match self.commit_environment(
+                    let result = self.commit_environment(
                         *kubernetes,
                         *environment_action,
                         &applications_by_environment,
                         |qe_env| kubernetes.deploy_environment(qe_env),
-                    ) {
-                        TransactionResult::Ok => {}
+                    );
+                    let operations =
+                        self.environment_operations(*kubernetes, *environment_action, &applications_by_environment);
+                    self.summary.record(operations, &result);
+
+                    match result {
+                        TransactionResult::Ok => {
+                            let endpoints = self.environment_endpoints(
+                                *kubernetes,
+                                *environment_action,
+                                &applications_by_environment,
+                            );
+                            self.endpoints.extend(endpoints);
+                        }
                         err => {
                             error!("Error while deploying environment: {:?}", err);
                             return err;
@@ -500,12 +661,17 @@ impl<'a> Transaction<'a> {
                 }
                 Step::PauseEnvironment(kubernetes, environment_action) => {
                     // pause complete environment
-                    match self.commit_environment(
+                    let result = self.commit_environment(
                         *kubernetes,
                         *environment_action,
                         &applications_by_environment,
                         |qe_env| kubernetes.pause_environment(qe_env),
-                    ) {
+                    );
+                    let operations =
+                        self.environment_operations(*kubernetes, *environment_action, &applications_by_environment);
+                    self.summary.record(operations, &result);
+
+                    match result {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while pausing environment: {:?}", err);
@@ -515,12 +681,17 @@ impl<'a> Transaction<'a> {
                 }
                 Step::DeleteEnvironment(kubernetes, environment_action) => {
                     // delete complete environment
-                    match self.commit_environment(
+                    let result = self.commit_environment(
                         *kubernetes,
                         *environment_action,
                         &applications_by_environment,
                         |qe_env| kubernetes.delete_environment(qe_env),
-                    ) {
+                    );
+                    let operations =
+                        self.environment_operations(*kubernetes, *environment_action, &applications_by_environment);
+                    self.summary.record(operations, &result);
+
+                    match result {
                         TransactionResult::Ok => {}
                         err => {
                             error!("Error while deleting environment: {:?}", err);
@@ -528,6 +699,24 @@ impl<'a> Transaction<'a> {
                         }
                     };
                 }
+                Step::RestartService(kubernetes, environment_action, service_id) => {
+                    // restart a single service of the environment
+                    let result = self.commit_restart_service(*kubernetes, *environment_action, service_id.as_str());
+                    let operation = OpSummary {
+                        id: service_id.clone(),
+                        service_type: ServiceType::ExternalService.name().to_string(),
+                        action: Action::Create,
+                    };
+                    self.summary.record(vec![operation], &result);
+
+                    match result {
+                        TransactionResult::Ok => {}
+                        err => {
+                            error!("Error while restarting service {}: {:?}", service_id, err);
+                            return err;
+                        }
+                    };
+                }
             };
         }
 
@@ -623,11 +812,12 @@ impl<'a> Transaction<'a> {
             None => &empty_vec,
         };
 
-        let qe_environment = target_environment.to_qe_environment(
+        let mut qe_environment = target_environment.to_qe_environment(
             self.engine.context(),
             built_applications,
             kubernetes.cloud_provider(),
         );
+        qe_environment.set_max_parallelism(self.max_parallelism);
 
         let _ = match self.check_environment(&qe_environment) {
             TransactionResult::Ok => {}
@@ -713,6 +903,154 @@ impl<'a> Transaction<'a> {
 
         TransactionResult::Ok
     }
+
+    fn environment_endpoints(
+        &self,
+        kubernetes: &dyn Kubernetes,
+        environment_action: &EnvironmentAction,
+        applications_by_environment: &HashMap<&Environment, Vec<Box<dyn Application>>>,
+    ) -> BTreeMap<String, Vec<Endpoint>> {
+        let target_environment = match environment_action {
+            EnvironmentAction::Environment(te) => te,
+            EnvironmentAction::EnvironmentWithFailover(te, _) => te,
+        };
+
+        let empty_vec = Vec::with_capacity(0);
+        let built_applications = match applications_by_environment.get(target_environment) {
+            Some(applications) => applications,
+            None => &empty_vec,
+        };
+
+        let qe_environment = target_environment.to_qe_environment(
+            self.engine.context(),
+            built_applications,
+            kubernetes.cloud_provider(),
+        );
+
+        qe_environment
+            .stateless_services
+            .iter()
+            .map(|service| (service.id().to_string(), service.endpoints(service.namespace(&qe_environment))))
+            .collect()
+    }
+
+    /// the services of the environment, then the environment itself
+    fn environment_operations(
+        &self,
+        kubernetes: &dyn Kubernetes,
+        environment_action: &EnvironmentAction,
+        applications_by_environment: &HashMap<&Environment, Vec<Box<dyn Application>>>,
+    ) -> Vec<OpSummary> {
+        let target_environment = match environment_action {
+            EnvironmentAction::Environment(te) => te,
+            EnvironmentAction::EnvironmentWithFailover(te, _) => te,
+        };
+
+        let empty_vec = Vec::with_capacity(0);
+        let built_applications = match applications_by_environment.get(target_environment) {
+            Some(applications) => applications,
+            None => &empty_vec,
+        };
+
+        let qe_environment = target_environment.to_qe_environment(
+            self.engine.context(),
+            built_applications,
+            kubernetes.cloud_provider(),
+        );
+
+        let service_operation = |id: &str, service_type: ServiceType| OpSummary {
+            id: id.to_string(),
+            service_type: service_type.name().to_string(),
+            action: target_environment.action.clone(),
+        };
+
+        let mut operations = qe_environment
+            .stateful_services
+            .iter()
+            .map(|service| service_operation(service.id(), service.service_type()))
+            .collect::<Vec<_>>();
+
+        operations.extend(
+            qe_environment
+                .stateless_services
+                .iter()
+                .map(|service| service_operation(service.id(), service.service_type())),
+        );
+
+        operations.push(environment_operation(target_environment));
+        operations
+    }
+
+    fn commit_restart_service(
+        &self,
+        kubernetes: &dyn Kubernetes,
+        environment_action: &EnvironmentAction,
+        service_id: &str,
+    ) -> TransactionResult {
+        let target_environment = match environment_action {
+            EnvironmentAction::Environment(te) => te,
+            EnvironmentAction::EnvironmentWithFailover(te, _) => te,
+        };
+
+        let execution_id = self.engine.context().execution_id();
+
+        // only external services can be restarted for now
+        let external_service = match target_environment
+            .external_services
+            .iter()
+            .find(|x| x.id.as_str() == service_id)
+            .and_then(|x| x.to_external_service(self.engine.context(), x.to_image(), kubernetes.cloud_provider()))
+        {
+            Some(external_service) => external_service,
+            None => {
+                return TransactionResult::Rollback(EngineError::new(
+                    EngineErrorCause::Internal,
+                    EngineErrorScope::Environment(target_environment.id.clone(), target_environment.id.clone()),
+                    execution_id,
+                    Some(format!("no external service with id {} can be restarted", service_id)),
+                ));
+            }
+        };
+
+        let qe_environment =
+            target_environment.to_qe_environment(self.engine.context(), &vec![], kubernetes.cloud_provider());
+        let target = DeploymentTarget::SelfHosted(kubernetes, &qe_environment);
+
+        let lh = ListenersHelper::new(kubernetes.listeners());
+        let progress_info = ProgressInfo::new(
+            external_service.progress_scope(),
+            ProgressLevel::Info,
+            None::<&str>,
+            execution_id,
+        );
+
+        let result = match external_service.on_restart(&target) {
+            Ok(_) => external_service.on_restart_check(),
+            Err(err) => Err(err),
+        };
+
+        // 100 ms sleep to avoid race condition on last service status update
+        thread::sleep(std::time::Duration::from_millis(100));
+
+        match result {
+            Err(err) => {
+                let _ = external_service.on_restart_error(&target);
+                lh.deployment_error(progress_info);
+
+                match self.rollback() {
+                    Ok(_) => TransactionResult::Rollback(err),
+                    Err(rollback_err) => {
+                        error!("ROLLBACK FAILED! fatal error: {:?}", rollback_err);
+                        TransactionResult::UnrecoverableError(err, rollback_err)
+                    }
+                }
+            }
+            _ => {
+                lh.deployed(progress_info);
+                TransactionResult::Ok
+            }
+        }
+    }
 }
 
 #[derive(Clone)]
@@ -724,26 +1062,94 @@ pub struct DeploymentOption {
 enum Step<'a> {
     // init and create all the necessary resources (Network, Kubernetes)
     CreateKubernetes(&'a dyn Kubernetes),
+    UpgradeKubernetes(&'a dyn Kubernetes, String),
     DeleteKubernetes(&'a dyn Kubernetes),
     BuildEnvironment(&'a EnvironmentAction, DeploymentOption),
     DeployEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
     PauseEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
     DeleteEnvironment(&'a dyn Kubernetes, &'a EnvironmentAction),
+    RestartService(&'a dyn Kubernetes, &'a EnvironmentAction, String),
 }
 
 impl<'a> Clone for Step<'a> {
     fn clone(&self) -> Self {
         match self {
             Step::CreateKubernetes(k) => Step::CreateKubernetes(*k),
+            Step::UpgradeKubernetes(k, version) => Step::UpgradeKubernetes(*k, version.clone()),
             Step::DeleteKubernetes(k) => Step::DeleteKubernetes(*k),
             Step::BuildEnvironment(e, option) => Step::BuildEnvironment(*e, option.clone()),
             Step::DeployEnvironment(k, e) => Step::DeployEnvironment(*k, *e),
             Step::PauseEnvironment(k, e) => Step::PauseEnvironment(*k, *e),
             Step::DeleteEnvironment(k, e) => Step::DeleteEnvironment(*k, *e),
+            Step::RestartService(k, e, id) => Step::RestartService(*k, *e, id.clone()),
+        }
+    }
+}
+
+/// an operation applied by a transaction on a cluster, an environment or one of its services
+#[derive(Clone, Debug)]
+pub struct OpSummary {
+    pub id: String,
+    /// e.g. `Kubernetes`, `Environment`, `Application`, `ExternalService`
+    pub service_type: String,
+    pub action: Action,
+}
+
+#[derive(Debug, Default)]
+pub struct TransactionSummary {
+    pub succeeded: Vec<OpSummary>,
+    /// the operation which has stopped the transaction and its error
+    pub failed: Option<(OpSummary, EngineError)>,
+}
+
+impl TransactionSummary {
+    /// the failed operation is the one targeted by the error, by default the last one
+    fn record(&mut self, mut operations: Vec<OpSummary>, result: &TransactionResult) {
+        let err = match result {
+            TransactionResult::Ok => {
+                self.succeeded.extend(operations);
+                return;
+            }
+            TransactionResult::Rollback(err) | TransactionResult::UnrecoverableError(err, _) => err,
+        };
+
+        let scope_id = match &err.scope {
+            EngineErrorScope::Kubernetes(id, _)
+            | EngineErrorScope::Environment(id, _)
+            | EngineErrorScope::Database(id, _, _)
+            | EngineErrorScope::Application(id, _)
+            | EngineErrorScope::Router(id, _)
+            | EngineErrorScope::ExternalService(id, _) => Some(id.as_str()),
+            _ => None,
+        };
+
+        let failed = match operations.iter().position(|op| Some(op.id.as_str()) == scope_id) {
+            Some(index) => Some(operations.remove(index)),
+            None => operations.pop(),
+        };
+
+        if let Some(failed) = failed {
+            self.failed = Some((failed, err.clone()));
         }
     }
 }
 
+fn kubernetes_operation(kubernetes: &dyn Kubernetes, action: Action) -> OpSummary {
+    OpSummary {
+        id: kubernetes.id().to_string(),
+        service_type: "Kubernetes".to_string(),
+        action,
+    }
+}
+
+fn environment_operation(environment: &Environment) -> OpSummary {
+    OpSummary {
+        id: environment.id.clone(),
+        service_type: "Environment".to_string(),
+        action: environment.action.clone(),
+    }
+}
+
 #[derive(Debug)]
 pub enum RollbackError {
     CommitError(EngineError),
@@ -757,3 +1163,296 @@ pub enum TransactionResult {
     Rollback(EngineError),
     UnrecoverableError(EngineError, RollbackError),
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::build_platform::local_docker::LocalDocker;
+    use crate::cloud_provider::aws::AWS;
+    use crate::cloud_provider::environment::Environment;
+    use crate::cloud_provider::kubernetes::{Kind, Kubernetes};
+    use crate::cloud_provider::{CloudProvider, TerraformStateCredentials};
+    use crate::cmd::preflight::BinaryRequirement;
+    use crate::container_registry::docker_hub::DockerHub;
+    use crate::dns_provider::route53::Route53;
+    use crate::dns_provider::DnsProvider;
+    use crate::engine::Engine;
+    use crate::error::{EngineError, EngineErrorCause};
+    use crate::models::{Action, Context, Listen, Listener, Listeners};
+    use crate::object_storage::s3::S3;
+    use crate::object_storage::ObjectStorage;
+    use crate::transaction::{Transaction, TransactionResult};
+
+    fn context() -> Context {
+        Context::new(
+            "execution_id".to_string(),
+            "/tmp".to_string(),
+            "lib".to_string(),
+            false,
+            None,
+            None,
+        )
+    }
+
+    fn engine() -> Engine {
+        let context = context();
+
+        Engine::new(
+            context.clone(),
+            Box::new(LocalDocker::new(context.clone(), "build-platform-id", "build-platform")),
+            Box::new(DockerHub::new(context.clone(), "registry-id", "registry", "login", "password")),
+            Box::new(fake_cloud_provider(context.clone())),
+            Box::new(fake_dns_provider(context)),
+        )
+    }
+
+    fn fake_cloud_provider(context: Context) -> AWS {
+        AWS::new(
+            context,
+            "cloud-provider-id",
+            "organization-id",
+            "cloud-provider",
+            "access-key-id",
+            "secret-access-key",
+            TerraformStateCredentials {
+                access_key_id: "access-key-id".to_string(),
+                secret_access_key: "secret-access-key".to_string(),
+                region: "eu-west-3".to_string(),
+            },
+        )
+    }
+
+    fn fake_dns_provider(context: Context) -> Route53 {
+        Route53::new(context, "dns-provider-id", "dns-provider", "example.com", "hosted-zone-id")
+    }
+
+    /// Kubernetes target recording every call in a journal shared between all the fake targets.
+    /// The clusters of the cloud providers run terraform on creation, so the transaction can only be driven by a fake
+    struct FakeKubernetes {
+        context: Context,
+        id: String,
+        fail_on_create: bool,
+        journal: Arc<Mutex<Vec<String>>>,
+        cloud_provider: AWS,
+        dns_provider: Route53,
+        object_storage: S3,
+        listeners: Listeners,
+    }
+
+    impl FakeKubernetes {
+        fn new(id: &str, fail_on_create: bool, journal: Arc<Mutex<Vec<String>>>) -> Self {
+            let context = context();
+
+            FakeKubernetes {
+                context: context.clone(),
+                id: id.to_string(),
+                fail_on_create,
+                journal,
+                cloud_provider: fake_cloud_provider(context.clone()),
+                dns_provider: fake_dns_provider(context.clone()),
+                object_storage: S3::new(
+                    context,
+                    "object-storage-id".to_string(),
+                    "object-storage".to_string(),
+                    "access-key-id".to_string(),
+                    "secret-access-key".to_string(),
+                ),
+                listeners: vec![],
+            }
+        }
+
+        fn record(&self, call: &str) -> Result<(), EngineError> {
+            self.journal.lock().unwrap().push(format!("{}.{}", self.id, call));
+            Ok(())
+        }
+    }
+
+    impl Listen for FakeKubernetes {
+        fn listeners(&self) -> &Listeners {
+            &self.listeners
+        }
+
+        fn add_listener(&mut self, listener: Listener) {
+            self.listeners.push(listener);
+        }
+    }
+
+    impl Kubernetes for FakeKubernetes {
+        fn context(&self) -> &Context {
+            &self.context
+        }
+
+        fn kind(&self) -> Kind {
+            Kind::Eks
+        }
+
+        fn id(&self) -> &str {
+            self.id.as_str()
+        }
+
+        fn name(&self) -> &str {
+            self.id.as_str()
+        }
+
+        fn version(&self) -> &str {
+            "1.16"
+        }
+
+        fn region(&self) -> &str {
+            "eu-west-3"
+        }
+
+        fn cloud_provider(&self) -> &dyn CloudProvider {
+            &self.cloud_provider
+        }
+
+        fn dns_provider(&self) -> &dyn DnsProvider {
+            &self.dns_provider
+        }
+
+        fn config_file_store(&self) -> &dyn ObjectStorage {
+            &self.object_storage
+        }
+
+        fn is_valid(&self) -> Result<(), EngineError> {
+            Ok(())
+        }
+
+        fn required_binaries(&self) -> Vec<BinaryRequirement> {
+            vec![]
+        }
+
+        fn on_create(&self) -> Result<(), EngineError> {
+            let _ = self.record("on_create");
+
+            if self.fail_on_create {
+                return Err(self.engine_error(EngineErrorCause::Internal, "fake creation error".to_string()));
+            }
+
+            Ok(())
+        }
+
+        fn on_create_error(&self) -> Result<(), EngineError> {
+            self.record("on_create_error")
+        }
+
+        fn on_upgrade(&self) -> Result<(), EngineError> {
+            self.record("on_upgrade")
+        }
+
+        fn on_upgrade_error(&self) -> Result<(), EngineError> {
+            self.record("on_upgrade_error")
+        }
+
+        fn on_downgrade(&self) -> Result<(), EngineError> {
+            self.record("on_downgrade")
+        }
+
+        fn on_downgrade_error(&self) -> Result<(), EngineError> {
+            self.record("on_downgrade_error")
+        }
+
+        fn on_delete(&self) -> Result<(), EngineError> {
+            self.record("on_delete")
+        }
+
+        fn on_delete_error(&self) -> Result<(), EngineError> {
+            self.record("on_delete_error")
+        }
+
+        fn deploy_environment(&self, _environment: &Environment) -> Result<(), EngineError> {
+            self.record("deploy_environment")
+        }
+
+        fn deploy_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+            self.record("deploy_environment_error")
+        }
+
+        fn pause_environment(&self, _environment: &Environment) -> Result<(), EngineError> {
+            self.record("pause_environment")
+        }
+
+        fn pause_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+            self.record("pause_environment_error")
+        }
+
+        fn delete_environment(&self, _environment: &Environment) -> Result<(), EngineError> {
+            self.record("delete_environment")
+        }
+
+        fn delete_environment_error(&self, _environment: &Environment) -> Result<(), EngineError> {
+            self.record("delete_environment_error")
+        }
+    }
+
+    #[test]
+    fn test_rollback_reverts_executed_steps_in_reverse_order() {
+        let engine = engine();
+        let journal = Arc::new(Mutex::new(vec![]));
+
+        let first = FakeKubernetes::new("first", false, journal.clone());
+        let second = FakeKubernetes::new("second", false, journal.clone());
+        let third = FakeKubernetes::new("third", true, journal.clone());
+
+        let mut tx = Transaction::new(&engine);
+        tx.create_kubernetes(&first).unwrap();
+        tx.create_kubernetes(&second).unwrap();
+        tx.create_kubernetes(&third).unwrap();
+
+        match tx.commit() {
+            TransactionResult::Rollback(_) => {}
+            err => panic!("expected a rolled back transaction, got {:?}", err),
+        };
+
+        assert_eq!(
+            *journal.lock().unwrap(),
+            vec![
+                "first.on_create",
+                "second.on_create",
+                "third.on_create",
+                "third.on_create_error",
+                "second.on_create_error",
+                "first.on_create_error",
+            ]
+        );
+
+        // the caller can explicitly undo everything that has been applied
+        journal.lock().unwrap().clear();
+        tx.rollback().unwrap();
+
+        assert_eq!(
+            *journal.lock().unwrap(),
+            vec!["third.on_create_error", "second.on_create_error", "first.on_create_error"]
+        );
+    }
+
+    #[test]
+    fn test_summary_lists_succeeded_and_failed_operations() {
+        let engine = engine();
+        let journal = Arc::new(Mutex::new(vec![]));
+
+        let first = FakeKubernetes::new("first", false, journal.clone());
+        let second = FakeKubernetes::new("second", true, journal.clone());
+
+        let mut tx = Transaction::new(&engine);
+        tx.create_kubernetes(&first).unwrap();
+        tx.create_kubernetes(&second).unwrap();
+
+        match tx.commit() {
+            TransactionResult::Rollback(_) => {}
+            err => panic!("expected a rolled back transaction, got {:?}", err),
+        };
+
+        let summary = tx.summary();
+        assert_eq!(summary.succeeded.len(), 1);
+        assert_eq!(summary.succeeded[0].id, "first");
+        assert_eq!(summary.succeeded[0].service_type, "Kubernetes");
+        assert_eq!(summary.succeeded[0].action, Action::Create);
+
+        let (failed, err) = summary.failed.as_ref().expect("the failed operation must be reported");
+        assert_eq!(failed.id, "second");
+        assert_eq!(failed.action, Action::Create);
+        assert_eq!(err.message.as_deref(), Some("fake creation error"));
+    }
+}
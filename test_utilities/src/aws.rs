@@ -445,6 +445,7 @@ pub fn environment_3_apps_3_routers_3_databases(context: &Context) -> Environmen
         ],
         external_services: vec![],
         clone_from_environment_id: None,
+        resource_quota: None,
     }
 }
 
@@ -496,6 +497,7 @@ pub fn working_minimal_environment(context: &Context) -> Environment {
         databases: vec![],
         external_services: vec![],
         clone_from_environment_id: None,
+        resource_quota: None,
     }
 }
 
@@ -668,6 +670,7 @@ pub fn environnement_2_app_2_routers_1_psql(context: &Context) -> Environment {
 
         external_services: vec![],
         clone_from_environment_id: None,
+        resource_quota: None,
     }
 }
 
@@ -741,6 +744,7 @@ pub fn echo_app_environment(context: &Context) -> Environment {
         databases: vec![],
         external_services: vec![],
         clone_from_environment_id: None,
+        resource_quota: None,
     }
 }
 
@@ -781,6 +785,7 @@ pub fn environment_only_http_server(context: &Context) -> Environment {
         databases: vec![],
         external_services: vec![],
         clone_from_environment_id: None,
+        resource_quota: None,
     }
 }
 
@@ -832,5 +837,6 @@ pub fn environment_only_http_server_router(context: &Context) -> Environment {
         databases: vec![],
         external_services: vec![],
         clone_from_environment_id: None,
+        resource_quota: None,
     }
 }
@@ -1,4 +1,5 @@
 pub mod aws;
 pub mod cloudflare;
 pub mod digitalocean;
+pub mod route53;
 pub mod utilities;
@@ -0,0 +1,20 @@
+use qovery_engine::dns_provider::route53::Route53;
+use qovery_engine::models::Context;
+
+pub fn route53_hosted_zone_id() -> String {
+    std::env::var("ROUTE53_HOSTED_ZONE_ID").expect("env var ROUTE53_HOSTED_ZONE_ID is mandatory")
+}
+
+pub fn route53_domain() -> String {
+    std::env::var("ROUTE53_DOMAIN").expect("env var ROUTE53_DOMAIN is mandatory")
+}
+
+pub fn dns_provider_route53(context: &Context) -> Route53 {
+    Route53::new(
+        context.clone(),
+        "qoverytestdnsr53",
+        "Qovery Test Route53",
+        route53_domain().as_str(),
+        route53_hosted_zone_id().as_str(),
+    )
+}
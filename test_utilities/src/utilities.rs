@@ -20,6 +20,7 @@ use qovery_engine::build_platform::local_docker::LocalDocker;
 use qovery_engine::cmd;
 use qovery_engine::constants::{AWS_ACCESS_KEY_ID, AWS_SECRET_ACCESS_KEY};
 use qovery_engine::error::{SimpleError, SimpleErrorKind};
+use qovery_engine::logger::RedactedMakeWriter;
 use qovery_engine::models::{Context, Environment, Metadata};
 extern crate time;
 use time::Instant;
@@ -37,8 +38,11 @@ pub fn init() -> Instant {
             .json()
             .with_max_level(tracing::Level::INFO)
             .with_current_span(false)
+            .with_writer(RedactedMakeWriter::new(std::io::stdout))
+            .try_init(),
+        None => tracing_subscriber::fmt()
+            .with_writer(RedactedMakeWriter::new(std::io::stdout))
             .try_init(),
-        None => tracing_subscriber::fmt().try_init(),
     };
 
     info!(
@@ -115,6 +119,8 @@ pub fn context() -> Context {
     let metadata = Metadata {
         dry_run_deploy: Option::from(false),
         resource_expiration_in_seconds: Some(2700),
+        validate_rendered_templates: None,
+        force_redeploy: None,
     };
 
     Context::new(execution_id, home_dir, lib_root_dir, true, None, Option::from(metadata))
@@ -78,6 +78,10 @@ fn deploy_a_working_environment_with_no_router_on_do() {
             registry_name: None,
             registry_secret: None,
             registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
         };
 
         assert!(!registry.does_image_exists(&image));
@@ -83,6 +83,8 @@ fn create_doks_cluster_in_fra_10() {
                     file.clone(),
                     namespace_to_test.clone().as_str(),
                     None,
+                    None,
+                    None,
                     do_credentials_envs.clone(),
                 ) {
                     Ok(_) => {
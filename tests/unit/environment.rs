@@ -0,0 +1,93 @@
+use qovery_engine::cloud_provider::environment::{Environment, Kind};
+use qovery_engine::cloud_provider::service::{Service, StatelessService};
+use qovery_engine::error::{EngineError, EngineErrorCause, EngineErrorScope};
+
+use crate::unit::fake_tools::FakeTools;
+
+#[test]
+fn test_services_are_deleted_in_reverse_dependency_order() {
+    // every release is deployed and the uninstall of the worker fails, helm exiting with 1 is not an error
+    let helm_script = "case \"$1\" in\n  history) echo '[{\"revision\":1,\"status\":\"deployed\"}]' ;;\n  \
+        uninstall) case \"$*\" in *worker*) exit 2 ;; esac ;;\nesac\nexit 0";
+    // no pod is left once a release is uninstalled
+    let tools = FakeTools::new("delete-dependency-order", helm_script, "echo '{\"items\":[]}'");
+
+    // the cron job calls the worker which calls the api
+    let api = tools.named_external_service("z1", "api");
+    let mut cron = tools.named_external_service("z2", "cron");
+    cron.set_depends_on(vec!["z3".to_string()]);
+    let mut worker = tools.named_external_service("z3", "worker");
+    worker.set_depends_on(vec!["z1".to_string()]);
+
+    let stateless_services: Vec<Box<dyn StatelessService>> = vec![Box::new(api), Box::new(cron), Box::new(worker)];
+    let environment = Environment::new(
+        Kind::Development,
+        "environment-id",
+        "project-id",
+        "owner-id",
+        "organization-id",
+        stateless_services,
+        vec![],
+    );
+
+    tools.with_environment_target(&environment, |target| {
+        assert!(environment.delete_all(target).is_err());
+    });
+
+    // a failure does not stop the deletion of the services the failed one depends on
+    let uninstalled_releases = tools
+        .helm
+        .calls_of("uninstall")
+        .iter()
+        .map(|call| call.split_whitespace().last().unwrap_or_default().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        uninstalled_releases,
+        vec![
+            "external-service-cron-z2",
+            "external-service-worker-z3",
+            "external-service-api-z1"
+        ]
+    );
+}
+
+#[test]
+fn test_deletion_error_is_internal_when_any_failure_is_internal() {
+    let tools = FakeTools::new("delete-error-cause", "exit 0", "exit 0");
+
+    let api = tools.named_external_service("z1", "api");
+    let mut worker = tools.named_external_service("z2", "worker");
+    worker.set_depends_on(vec!["z1".to_string()]);
+    let stateless_services: Vec<Box<dyn StatelessService>> = vec![Box::new(api), Box::new(worker)];
+    let environment = Environment::new(
+        Kind::Development,
+        "environment-id",
+        "project-id",
+        "owner-id",
+        "organization-id",
+        stateless_services,
+        vec![],
+    );
+
+    // the worker is deleted first and fails with a user error, the api fails with an internal error
+    let err = environment
+        .delete_all_with(|service| {
+            let cause = match service.name() {
+                "worker" => EngineErrorCause::User("invalid namespace"),
+                _ => EngineErrorCause::Internal,
+            };
+
+            Err(EngineError::new(
+                cause,
+                EngineErrorScope::ExternalService(service.id().to_string(), service.name().to_string()),
+                "execution_id",
+                Some(format!("{} can't be deleted", service.name())),
+            ))
+        })
+        .unwrap_err();
+
+    assert!(matches!(err.cause, EngineErrorCause::Internal));
+    let message = err.message.unwrap();
+    assert!(message.contains("worker can't be deleted"));
+    assert!(message.contains("api can't be deleted"));
+}
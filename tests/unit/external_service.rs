@@ -0,0 +1,312 @@
+use std::sync::{Arc, Mutex};
+
+use qovery_engine::cloud_provider::aws::external_service::CronExternalService;
+use qovery_engine::cloud_provider::models::Command;
+use qovery_engine::cloud_provider::service::{
+    Create, DeploymentListener, Helm, Pause, PauseStrategy, ReleaseLock, ServiceType,
+};
+
+use crate::unit::fake_tools::FakeTools;
+
+#[test]
+fn test_pause_scale_to_zero_patches_the_job_parallelism() {
+    let tools = FakeTools::new("pause-scale-to-zero", "exit 0", "exit 0");
+    let mut service = tools.external_service();
+    service.set_pause_strategy(PauseStrategy::ScaleToZero);
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_pause(target).is_ok());
+    });
+
+    // kubectl can't scale a job
+    let calls = tools.kubectl.calls();
+    assert_eq!(
+        calls,
+        vec![r#"patch job/ext-service-my-job -n project-id-environment-id --type=merge -p {"spec":{"parallelism":0}}"#]
+    );
+    assert!(tools.helm.calls_of("uninstall").is_empty());
+}
+
+#[test]
+fn test_pause_scale_to_zero_suspends_the_cron_job() {
+    let tools = FakeTools::new("cron-pause-scale-to-zero", "exit 0", "exit 0");
+    let mut service = tools.external_service();
+    service.set_pause_strategy(PauseStrategy::ScaleToZero);
+    let service = CronExternalService::new(service, "*/5 * * * *");
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_pause(target).is_ok());
+    });
+
+    assert_eq!(
+        tools.kubectl.calls(),
+        vec![
+            r#"patch cronjob/cron-service-my-job -n project-id-environment-id --type=merge -p {"spec":{"suspend":true}}"#
+        ]
+    );
+    assert!(tools.helm.calls_of("uninstall").is_empty());
+}
+
+/// a helm whose upgrades print the given revision and whose history is `history`, the other commands succeed
+fn helm_script(upgraded_revision: u32, history: &str) -> String {
+    helm_script_with_values(upgraded_revision, history, "")
+}
+
+/// same as `helm_script`, the deployed values of the release are `values`
+fn helm_script_with_values(upgraded_revision: u32, history: &str, values: &str) -> String {
+    format!(
+        "case \"$1\" in\n  upgrade) echo \"REVISION: {}\" ;;\n  history) echo '{}' ;;\n  get) echo '{}' ;;\nesac\nexit 0",
+        upgraded_revision, history, values
+    )
+}
+
+#[test]
+fn test_failed_upgrade_rolls_back_to_the_last_deployed_revision() {
+    let history =
+        r#"[{"revision":1,"status":"deployed"},{"revision":2,"status":"superseded"},{"revision":3,"status":"failed"}]"#;
+    let tools = FakeTools::new("rollback-deployed-revision", helm_script(3, history).as_str(), "exit 0");
+    let service = tools.external_service();
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_err());
+    });
+
+    let rollbacks = tools.helm.calls_of("rollback");
+    assert_eq!(rollbacks.len(), 1);
+    let rollback_to = format!("{} 1", service.helm_release_name());
+    assert!(rollbacks[0].ends_with(rollback_to.as_str()));
+}
+
+#[test]
+fn test_failed_upgrade_without_deployed_revision_does_not_roll_back() {
+    let history = r#"[{"revision":1,"status":"failed"}]"#;
+    let tools = FakeTools::new(
+        "rollback-no-deployed-revision",
+        helm_script(1, history).as_str(),
+        "exit 0",
+    );
+    let service = tools.external_service();
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_err());
+    });
+
+    assert_eq!(tools.helm.calls_of("upgrade").len(), 1);
+    assert!(tools.helm.calls_of("rollback").is_empty());
+}
+
+#[test]
+fn test_dry_run_does_not_run_helm() {
+    let mut tools = FakeTools::new("dry-run", "exit 1", "exit 0");
+    tools.context = tools.context.clone().with_dry_run(true);
+    let service = tools.external_service();
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert!(tools.helm.calls().is_empty());
+}
+
+#[derive(Default)]
+struct RecordingListener {
+    steps: Mutex<Vec<String>>,
+}
+
+impl DeploymentListener for RecordingListener {
+    fn on_step(&self, service_type: ServiceType, step: &str) {
+        self.steps
+            .lock()
+            .unwrap()
+            .push(format!("{}:{}", service_type.name(), step));
+    }
+}
+
+#[test]
+fn test_deployment_listener_records_ordered_steps() {
+    let history = r#"[{"revision":1,"status":"deployed"}]"#;
+    let mut tools = FakeTools::new("deployment-steps", helm_script(1, history).as_str(), "exit 0");
+    let listener = Arc::new(RecordingListener::default());
+    tools.context.add_deployment_listener(listener.clone());
+
+    // helm waits for the job, so it is ready once the upgrade is done
+    let mut service = tools.external_service();
+    service.set_helm_wait_for_jobs(true);
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert_eq!(
+        *listener.steps.lock().unwrap(),
+        vec![
+            "ExternalService:templates_generated",
+            "ExternalService:helm_upgraded",
+            "ExternalService:job_ready",
+        ]
+    );
+
+    // a dry run stops once the templates are generated
+    let listener = Arc::new(RecordingListener::default());
+    tools.context = tools.context.clone().with_dry_run(true);
+    tools.context.add_deployment_listener(listener.clone());
+    let service = tools.external_service();
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert_eq!(
+        *listener.steps.lock().unwrap(),
+        vec!["ExternalService:templates_generated"]
+    );
+}
+
+#[test]
+fn test_force_redeploy_deletes_the_job_before_the_upgrade() {
+    let history = r#"[{"revision":2,"status":"deployed"}]"#;
+    let mut tools = FakeTools::new("force-redeploy", helm_script(2, history).as_str(), "exit 0");
+    tools.context = tools.context.clone().with_force_redeploy(true);
+
+    let mut service = tools.external_service();
+    service.set_helm_wait_for_jobs(true);
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert_eq!(tools.kubectl.calls_of("delete").len(), 1);
+    assert!(tools.kubectl.calls_of("delete")[0].starts_with("delete job/ext-service-my-job "));
+    assert_eq!(tools.helm.calls_of("upgrade").len(), 1);
+}
+
+#[test]
+fn test_changed_instances_delete_the_job_before_the_upgrade() {
+    // the deployed release runs 3 instances, the service only 1
+    let history = r#"[{"revision":2,"status":"deployed"}]"#;
+    let helm = helm_script_with_values(2, history, "instances: 3");
+    let tools = FakeTools::new("changed-instances", helm.as_str(), "exit 0");
+    let mut service = tools.external_service();
+    service.set_helm_wait_for_jobs(true);
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert_eq!(tools.kubectl.calls_of("delete").len(), 1);
+    assert_eq!(tools.helm.calls_of("upgrade").len(), 1);
+}
+
+#[test]
+fn test_unchanged_instances_keep_the_job() {
+    let history = r#"[{"revision":2,"status":"deployed"}]"#;
+    let helm = helm_script_with_values(2, history, "instances: 1");
+    let tools = FakeTools::new("unchanged-instances", helm.as_str(), "exit 0");
+    let mut service = tools.external_service();
+    service.set_helm_wait_for_jobs(true);
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert!(tools.kubectl.calls_of("delete").is_empty());
+    assert_eq!(tools.helm.calls_of("upgrade").len(), 1);
+}
+
+#[test]
+fn test_release_deployed_with_the_legacy_name_keeps_it() {
+    let service_name = "my-very-long-service-name-sharing-a-prefix";
+    let legacy_release_name = "external-service-my-very-long-service-name-sharin";
+
+    let history = r#"[{"revision":2,"status":"deployed"}]"#;
+    let list = format!(
+        r#"[{{"name":"{}","namespace":"project-id-environment-id","revision":"2","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"q-job-0.1.0","app_version":"1.0"}}]"#,
+        legacy_release_name
+    );
+    let helm = format!(
+        "case \"$1\" in\n  upgrade) echo \"REVISION: 2\" ;;\n  history) echo '{}' ;;\n  list) echo '{}' ;;\nesac\nexit 0",
+        history, list
+    );
+    let tools = FakeTools::new("legacy-release-name", helm.as_str(), "exit 0");
+    let mut service = tools.named_external_service("z1234", service_name);
+    service.set_helm_wait_for_jobs(true);
+    assert_ne!(service.helm_release_name(), legacy_release_name);
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    // a release with the new name would not be able to own the resources of the legacy one
+    let upgrades = tools.helm.calls_of("upgrade");
+    assert_eq!(upgrades.len(), 1);
+    assert!(upgrades[0].split_whitespace().any(|arg| arg == legacy_release_name));
+}
+
+#[test]
+fn test_release_deployed_with_the_legacy_name_is_locked_under_it() {
+    let legacy_release_name = "external-service-my-very-long-service-name-sharin";
+    let list = format!(
+        r#"[{{"name":"{}","namespace":"project-id-environment-id","revision":"2","updated":"2021-03-15 15:41:56.223 +0000 UTC","status":"deployed","chart":"q-job-0.1.0","app_version":"1.0"}}]"#,
+        legacy_release_name
+    );
+    let helm = format!("case \"$1\" in\n  list) echo '{}' ;;\nesac\nexit 0", list);
+    let tools = FakeTools::new("legacy-release-lock", helm.as_str(), "exit 0");
+    let service = tools.named_external_service("z4321", "my-very-long-service-name-sharing-a-prefix");
+
+    // another operation runs against the legacy release
+    let _release_lock = ReleaseLock::acquire("project-id-environment-id", legacy_release_name).unwrap();
+
+    tools.with_deployment_target(|target| {
+        let err = service.on_create(target).unwrap_err();
+        assert!(err.message.unwrap().contains(legacy_release_name));
+    });
+
+    assert!(tools.helm.calls_of("upgrade").is_empty());
+}
+
+#[test]
+fn test_cron_force_redeploy_upgrades_then_runs_the_post_deploy_check() {
+    let history = r#"[{"revision":2,"status":"deployed"}]"#;
+    // the cron job is deployed and the job created from it succeeds
+    let kubectl = r#"case "$1 $2" in
+  "get cronjob") echo 'cronjob.batch/cron-service-my-job' ;;
+  "get job") echo '{"status":{"succeeded":1}}' ;;
+esac
+exit 0"#;
+    let mut tools = FakeTools::new("cron-force-redeploy", helm_script(2, history).as_str(), kubectl);
+    tools.context = tools.context.clone().with_force_redeploy(true);
+
+    let mut service = tools.external_service();
+    service.set_post_deploy_check(Some(Command {
+        binary: "true".to_string(),
+        args: vec![],
+    }));
+    let service = CronExternalService::new(service, "*/5 * * * *");
+
+    tools.with_deployment_target(|target| {
+        assert!(service.on_create(target).is_ok());
+    });
+
+    assert_eq!(tools.helm.calls_of("upgrade").len(), 1);
+
+    // the cron job is upgraded in place, only the job of the check is created then deleted
+    let namespace = "project-id-environment-id";
+    assert_eq!(
+        tools.kubectl.calls_of("create"),
+        vec![format!(
+            "create job cron-service-my-job-check --from=cronjob/cron-service-my-job -n {}",
+            namespace
+        )]
+    );
+    assert_eq!(
+        tools.kubectl.calls_of("exec"),
+        vec![format!("exec -n {} job/cron-service-my-job-check -- true", namespace)]
+    );
+    assert_eq!(
+        tools.kubectl.calls_of("delete"),
+        vec![format!(
+            "delete job/cron-service-my-job-check -n {} --ignore-not-found=true --wait=true",
+            namespace
+        )]
+    );
+}
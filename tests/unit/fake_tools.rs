@@ -0,0 +1,209 @@
+use std::os::unix::fs::PermissionsExt;
+use std::sync::{Mutex, MutexGuard};
+
+use qovery_engine::build_platform::Image;
+use qovery_engine::cloud_provider::aws::external_service::ExternalService;
+use qovery_engine::cloud_provider::aws::kubernetes::{Options, EKS};
+use qovery_engine::cloud_provider::aws::AWS;
+use qovery_engine::cloud_provider::environment::{Environment, Kind};
+use qovery_engine::cloud_provider::kubernetes::{KubeconfigCache, KUBECONFIG_CACHE_TTL};
+use qovery_engine::cloud_provider::service::Action;
+use qovery_engine::cloud_provider::{DeploymentTarget, TerraformStateCredentials};
+use qovery_engine::cmd::helm::Timeout;
+use qovery_engine::dns_provider::route53::Route53;
+use qovery_engine::models::{Context, ToolConfig};
+
+lazy_static::lazy_static! {
+    // the tool config is shared by the whole process, the tests using fake tools can't run at the same time
+    static ref TOOLS_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// a shell script standing for a binary, it records the arguments of each call then runs `script`
+pub struct FakeBinary {
+    path: String,
+    calls_path: String,
+}
+
+impl FakeBinary {
+    fn new(dir: &str, name: &str, script: &str) -> Self {
+        let path = format!("{}/{}", dir, name);
+        let calls_path = format!("{}/{}.calls", dir, name);
+
+        let content = format!("#!/bin/sh\necho \"$@\" >> {}\n{}\n", calls_path, script);
+        std::fs::write(path.as_str(), content).unwrap();
+        std::fs::set_permissions(path.as_str(), std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        FakeBinary { path, calls_path }
+    }
+
+    /// the arguments of each call, in order
+    pub fn calls(&self) -> Vec<String> {
+        match std::fs::read_to_string(self.calls_path.as_str()) {
+            Ok(calls) => calls.lines().map(|line| line.to_string()).collect(),
+            Err(_) => vec![],
+        }
+    }
+
+    /// the calls whose first argument is `command`, e.g. `upgrade` or `rollback`
+    pub fn calls_of(&self, command: &str) -> Vec<String> {
+        self.calls()
+            .into_iter()
+            .filter(|call| call.split_whitespace().next() == Some(command))
+            .collect()
+    }
+}
+
+pub struct FakeTools {
+    pub context: Context,
+    pub helm: FakeBinary,
+    pub kubectl: FakeBinary,
+    workspace_root_dir: String,
+    _lock: MutexGuard<'static, ()>,
+}
+
+impl FakeTools {
+    /// a context whose helm and kubectl are the given scripts, the context has its own workspace
+    pub fn new(name: &str, helm_script: &str, kubectl_script: &str) -> Self {
+        let lock = TOOLS_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        let workspace_root_dir = format!(
+            "{}/qovery-engine-fake-tools-{}",
+            std::env::temp_dir().to_str().unwrap(),
+            name
+        );
+        let _ = std::fs::remove_dir_all(workspace_root_dir.as_str());
+        std::fs::create_dir_all(workspace_root_dir.as_str()).unwrap();
+
+        let helm = FakeBinary::new(workspace_root_dir.as_str(), "helm", helm_script);
+        let kubectl = FakeBinary::new(workspace_root_dir.as_str(), "kubectl", kubectl_script);
+
+        let context = Context::new(
+            "execution-id".to_string(),
+            workspace_root_dir.clone(),
+            format!("{}/lib", env!("CARGO_MANIFEST_DIR")),
+            false,
+            None,
+            None,
+        )
+        .with_tool_config(ToolConfig {
+            helm: Some(helm.path.clone()),
+            kubectl: Some(kubectl.path.clone()),
+        });
+
+        FakeTools {
+            context,
+            helm,
+            kubectl,
+            workspace_root_dir,
+            _lock: lock,
+        }
+    }
+
+    /// run `f` against a cluster whose kubeconfig is already cached, so it is never downloaded
+    pub fn with_deployment_target<F>(&self, f: F)
+    where
+        F: FnOnce(&DeploymentTarget),
+    {
+        let environment = Environment::new(
+            Kind::Development,
+            "environment-id",
+            "project-id",
+            "owner-id",
+            "organization-id",
+            vec![],
+            vec![],
+        );
+
+        self.with_environment_target(&environment, f)
+    }
+
+    /// same as `with_deployment_target`, the environment of the target is `environment`
+    pub fn with_environment_target<F>(&self, environment: &Environment, f: F)
+    where
+        F: FnOnce(&DeploymentTarget),
+    {
+        let cloud_provider = AWS::new(
+            self.context.clone(),
+            "cloud-provider-id",
+            "organization-id",
+            "cloud-provider",
+            "access-key-id",
+            "secret-access-key",
+            TerraformStateCredentials {
+                access_key_id: "access-key-id".to_string(),
+                secret_access_key: "secret-access-key".to_string(),
+                region: "eu-west-3".to_string(),
+            },
+        );
+        let dns_provider = Route53::new(
+            self.context.clone(),
+            "dns-provider-id",
+            "dns-provider",
+            "example.com",
+            "hosted-zone-id",
+        );
+        let kubernetes = EKS::new(
+            self.context.clone(),
+            "cluster-id",
+            "cluster",
+            "1.16",
+            "eu-west-3",
+            &cloud_provider,
+            &dns_provider,
+            Options::default(),
+            vec![],
+        );
+
+        let kubeconfig =
+            KubeconfigCache::new(self.context.workspace_root_dir(), KUBECONFIG_CACHE_TTL).path("cluster-id");
+        std::fs::create_dir_all(std::path::Path::new(kubeconfig.as_str()).parent().unwrap()).unwrap();
+        std::fs::write(kubeconfig.as_str(), "apiVersion: v1\nkind: Config\n").unwrap();
+
+        f(&DeploymentTarget::SelfHosted(&kubernetes, environment))
+    }
+
+    /// an external service running a single instance of a public image
+    pub fn external_service(&self) -> ExternalService {
+        self.named_external_service("id", "my-job")
+    }
+
+    /// same as `external_service`, with the given id and name
+    pub fn named_external_service(&self, id: &str, name: &str) -> ExternalService {
+        let image = Image {
+            application_id: "id".to_string(),
+            name: "my-job".to_string(),
+            tag: "tag".to_string(),
+            commit_id: "0123456789abcdef".to_string(),
+            registry_name: None,
+            registry_secret: None,
+            registry_url: None,
+            registry_credentials: None,
+            digest: None,
+            architecture_variants: vec![],
+            registry_candidates: vec![],
+        };
+
+        ExternalService::new_with_single_instance(
+            self.context.clone(),
+            id,
+            Action::Create,
+            name,
+            "1".to_string(),
+            256,
+            None,
+            Timeout::Default,
+            image,
+            vec![],
+            vec![],
+            vec![],
+            vec![],
+        )
+    }
+}
+
+impl Drop for FakeTools {
+    fn drop(&mut self) {
+        self.context = self.context.clone().with_tool_config(ToolConfig::default());
+        let _ = std::fs::remove_dir_all(self.workspace_root_dir.as_str());
+    }
+}
@@ -1 +1,3 @@
-
+mod environment;
+mod external_service;
+mod fake_tools;